@@ -1,4 +1,5 @@
 use crate::structures::symbol::*;
+use crate::diagnostic::Span;
 
 // SysY开始
 #[derive(Debug)]
@@ -20,6 +21,8 @@ pub struct FuncDef {
     pub id: String,
     pub params: Option<Vec<FuncFParam>>,
     pub block: Block,
+    // 函数名所在位置
+    pub span: Span,
 }
 
 // 下标
@@ -35,6 +38,8 @@ pub struct FuncFParam {
     pub ty: Type,
     pub id: String,
     pub idx: Vec<Index>,
+    // 参数名所在位置
+    pub span: Span,
 }
 
 // 声明
@@ -57,6 +62,8 @@ pub struct ConstDef {
     pub id: String,
     pub dims: Vec<Exp>,
     pub init: InitVal,
+    // 常量名所在位置
+    pub span: Span,
 }
 
 // 变量声明
@@ -72,6 +79,8 @@ pub struct VarDef {
     pub id: String,
     pub dims: Vec<Exp>,
     pub init: Option<InitVal>,
+    // 变量名所在位置
+    pub span: Span,
 }
 
 // 初始化
@@ -107,8 +116,8 @@ pub enum Stmt {
     Exp(Option<Exp>),
     Block(Block),
     Return(Return),
-    Break,
-    Continue,
+    Break(Span),
+    Continue(Span),
     If{exp: Exp, stmt1: Box<Stmt>, stmt2: Option<Box<Stmt>>},
     While{exp: Exp, stmt: Box<Stmt>},
 }
@@ -124,12 +133,39 @@ pub struct Assign {
 #[derive(Debug)]
 pub struct Return {
     pub val: Option<Exp>,
+    // return关键字所在位置
+    pub span: Span,
 }
 
 // 表达式
 #[derive(Debug)]
 pub struct Exp {
     pub exp: Box<LOrExp>,
+    pub span: Span,
+    // 语义分析得到的类型，常量表达式的is_const为true
+    pub ty: Option<SymbolType>,
+}
+
+impl Exp {
+    /// 语法错误恢复时代替出错的表达式，值为0
+    pub fn error(span: Span) -> Self {
+        let zero = UnaryExp::PrimExp(PrimaryExp::Number(Number::Int(0)));
+        let exp = LOrExp::LAndExp(LAndExp::EqExp(EqExp::RelExp(RelExp::AddExp(AddExp::MulExp(MulExp::UnaryExp(zero))))));
+        Exp { exp: Box::new(exp), span, ty: None }
+    }
+}
+
+// 语义分析时解析到的符号
+#[derive(Debug, Clone)]
+pub struct SymbolRef {
+    // 变量/常量的类型，函数为返回值类型
+    pub ty: SymbolType,
+    // 标量常量的值，函数的签名，其余为Void
+    pub value: SymbolVal,
+    // 定义所在位置，库函数为None
+    pub def: Option<Span>,
+    // 初始化值有错误的常量，使用处不再报错
+    pub poisoned: bool,
 }
 
 // 字面量数值
@@ -144,6 +180,10 @@ pub enum Number {
 pub struct LVal {
     pub id: String,
     pub idx: Vec<Exp>,
+    // 标识符所在位置
+    pub span: Span,
+    // 语义分析解析到的符号
+    pub symbol: Option<SymbolRef>,
 }
 
 // 主表达式
@@ -161,7 +201,7 @@ pub enum UnaryExp {
     Pos(Box<UnaryExp>),
     Neg(Box<UnaryExp>),
     Not(Box<UnaryExp>),
-    Call{id: String, params: Option<Vec<Exp>>},
+    Call{id: String, params: Option<Vec<Exp>>, span: Span, symbol: Option<SymbolRef>},
 }
 
 // 乘除表达式
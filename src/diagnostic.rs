@@ -0,0 +1,315 @@
+use std::fmt::Write;
+use lalrpop_util::ParseError;
+use lalrpop_util::lexer::Token;
+
+/// 源码中的一段区间，lo和hi为字节偏移，左闭右开
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Span {
+    pub lo: usize,
+    pub hi: usize,
+}
+
+impl Span {
+    pub fn new(lo: usize, hi: usize) -> Self {
+        Span { lo, hi }
+    }
+}
+
+/// 诊断信息的级别
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Error,
+    Warning,
+}
+
+impl Level {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Level::Error => "error",
+            Level::Warning => "warning",
+        }
+    }
+}
+
+/// 带源码位置的诊断信息
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub level: Level,
+    pub message: String,
+    pub span: Option<Span>,
+    /// 标注在插入符号后的说明
+    pub label: Option<String>,
+    /// 附加在末尾的提示
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn error(message: impl Into<String>) -> Self {
+        Diagnostic {
+            level: Level::Error,
+            message: message.into(),
+            span: None,
+            label: None,
+            notes: vec!(),
+        }
+    }
+
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.level == Level::Error
+    }
+
+    /// 按rustc的格式渲染诊断信息，例如：
+    /// ```text
+    /// error: 未定义的标识符`a`
+    ///  --> test.sy:3:12
+    ///   |
+    /// 3 |     return a;
+    ///   |            ^ 未定义
+    /// ```
+    pub fn render(&self, source: &SourceFile) -> String {
+        let mut out = String::new();
+        writeln!(out, "{}: {}", self.level.as_str(), self.message).unwrap();
+        match self.span {
+            Some(span) => {
+                let (line, col) = source.line_col(span.lo);
+                let text = source.line_text(line);
+                let gutter = " ".repeat(line.to_string().len());
+                writeln!(out, "{}--> {}:{}:{}", gutter, source.name, line, col).unwrap();
+                writeln!(out, "{} |", gutter).unwrap();
+                writeln!(out, "{} | {}", line, text).unwrap();
+                //区间跨行时只标到行尾
+                let (end_line, end_col) = source.line_col(span.hi);
+                let end_col = if end_line == line { end_col } else { text.chars().count() + 1 };
+                let width = end_col.saturating_sub(col).max(1);
+                //制表符原样保留，保证插入符号与源码对齐
+                let indent: String = text.chars()
+                    .take(col - 1)
+                    .map(|c| if c == '\t' { '\t' } else { ' ' })
+                    .collect();
+                write!(out, "{} | {}{}", gutter, indent, "^".repeat(width)).unwrap();
+                if let Some(label) = &self.label {
+                    write!(out, " {}", label).unwrap();
+                }
+                out.push('\n');
+                for note in self.notes.iter() {
+                    writeln!(out, "{} = note: {}", gutter, note).unwrap();
+                }
+            }
+            None => {
+                writeln!(out, " --> {}", source.name).unwrap();
+                for note in self.notes.iter() {
+                    writeln!(out, "  = note: {}", note).unwrap();
+                }
+            }
+        }
+        out
+    }
+}
+
+/// 被编译的源文件，用于将字节偏移换算成行列号
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    pub name: String,
+    pub text: String,
+    /// 每一行起始处的字节偏移
+    line_starts: Vec<usize>,
+}
+
+impl SourceFile {
+    pub fn new(name: impl Into<String>, text: impl Into<String>) -> Self {
+        let text = text.into();
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(idx, _)| idx + 1))
+            .collect();
+        SourceFile { name: name.into(), text, line_starts }
+    }
+
+    /// 字节偏移对应的行列号，均从1开始，列号按字符计数
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.text.len());
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(line) => line - 1,
+        };
+        let col = self.text[self.line_starts[line]..offset].chars().count() + 1;
+        (line + 1, col)
+    }
+
+    /// 第line行（从1开始）的内容，不含换行符
+    pub fn line_text(&self, line: usize) -> &str {
+        let start = self.line_starts[line - 1];
+        let end = self.line_starts.get(line).copied().unwrap_or(self.text.len());
+        self.text[start..end].trim_end_matches(['\n', '\r'])
+    }
+}
+
+/// 将lalrpop中的终结符名翻译成SysY中的术语
+fn describe_token(token: &str) -> String {
+    match token {
+        r##"r#"[_a-zA-Z][_a-zA-Z0-9]*"#"## => String::from("标识符"),
+        r##"r#"[1-9][0-9]*"#"## | r##"r#"0[0-7]*"#"## | r##"r#"0[xX][0-9a-fA-F]+"#"## => String::from("整数常量"),
+        _ if token.starts_with("r#\"") => String::from("浮点数常量"),
+        _ => match token.strip_prefix('"').and_then(|token| token.strip_suffix('"')) {
+            Some(token) => format!("`{}`", token),
+            None => String::from(token),
+        },
+    }
+}
+
+/// 期望的符号列表，去掉重复的描述
+fn describe_expected(expected: &[String]) -> String {
+    let mut names: Vec<String> = vec!();
+    for name in expected.iter().map(|token| describe_token(token)) {
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    match names.len() {
+        0 => String::from("没有可以接受的符号"),
+        1 => format!("期望{}", names[0]),
+        _ => {
+            let last = names.pop().unwrap();
+            format!("期望{}或{}", names.join("、"), last)
+        }
+    }
+}
+
+/// 实际遇到的符号
+fn describe_found(text: &str) -> String {
+    match text.chars().next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => match text {
+            "int" | "float" | "void" | "const" | "if" | "else" | "while" | "break" | "continue" | "return" => {
+                format!("关键字`{}`", text)
+            }
+            _ => format!("标识符`{}`", text),
+        },
+        Some(c) if c.is_ascii_digit() || c == '.' => format!("常量`{}`", text),
+        _ => format!("符号`{}`", text),
+    }
+}
+
+/// 将lalrpop的语法错误转换为诊断信息
+pub fn parse_error(source: &str, err: ParseError<usize, Token<'_>, &'static str>) -> Diagnostic {
+    match err {
+        ParseError::InvalidToken { location } => {
+            let c = source[location..].chars().next().unwrap_or(' ');
+            Diagnostic::error(format!("无法识别的字符`{}`", c))
+                .with_span(Span::new(location, location + c.len_utf8()))
+        }
+        ParseError::UnrecognizedEof { location, expected } => {
+            Diagnostic::error("源码意外结束")
+                .with_span(Span::new(location, location))
+                .with_label(describe_expected(&expected))
+        }
+        ParseError::UnrecognizedToken { token: (lo, token, hi), expected } => {
+            Diagnostic::error(format!("意外的{}", describe_found(token.1)))
+                .with_span(Span::new(lo, hi))
+                .with_label(describe_expected(&expected))
+        }
+        ParseError::ExtraToken { token: (lo, token, hi) } => {
+            Diagnostic::error(format!("多余的{}", describe_found(token.1)))
+                .with_span(Span::new(lo, hi))
+        }
+        ParseError::User { error } => Diagnostic::error(error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Session;
+
+    /// 编译源码，返回全部诊断信息
+    fn diagnose(source: &str) -> Vec<Diagnostic> {
+        let session = Session::default();
+        let mut ast = session.parse(source).unwrap();
+        session.analyse(&mut ast).unwrap_err()
+    }
+
+    #[test]
+    fn line_col() {
+        let source = SourceFile::new("a.sy", "int a;\r\n\tint 变量 = 1;\n");
+        assert_eq!(source.line_col(0), (1, 1));
+        assert_eq!(source.line_col(5), (1, 6));
+        assert_eq!(source.line_col(8), (2, 1));
+        //列号按字符计数
+        assert_eq!(source.line_col(source.text.find('=').unwrap()), (2, 9));
+        assert_eq!(source.line_col(usize::MAX), (3, 1));
+        assert_eq!(source.line_text(1), "int a;");
+        assert_eq!(source.line_text(2), "\tint 变量 = 1;");
+        assert_eq!(source.line_text(3), "");
+    }
+
+    #[test]
+    fn render_with_span() {
+        let source = SourceFile::new("test.sy", "int main() {\n    return a;\n}\n");
+        let lo = source.text.find("a;").unwrap();
+        let diagnostic = Diagnostic::error("未定义的标识符`a`")
+            .with_span(Span::new(lo, lo + 1))
+            .with_label("未定义")
+            .with_note("提示");
+        assert_eq!(diagnostic.render(&source), "\
+error: 未定义的标识符`a`
+ --> test.sy:2:12
+  |
+2 |     return a;
+  |            ^ 未定义
+  = note: 提示
+");
+    }
+
+    #[test]
+    fn render_tabs_and_multiline_span() {
+        let source = SourceFile::new("t.sy", "\tx = 1 +\n2;\n");
+        let lo = source.text.find('1').unwrap();
+        let hi = source.text.find(';').unwrap();
+        let rendered = Diagnostic::error("e").with_span(Span::new(lo, hi)).render(&source);
+        //跨行的区间只标到行尾，制表符原样保留
+        assert_eq!(rendered.lines().last().unwrap(), "  | \t    ^^^");
+    }
+
+    #[test]
+    fn render_without_span() {
+        let source = SourceFile::new("<stdin>", "");
+        let rendered = Diagnostic::error("程序中没有定义`main`函数").with_note("n").render(&source);
+        assert_eq!(rendered, "error: 程序中没有定义`main`函数\n --> <stdin>\n  = note: n\n");
+    }
+
+    #[test]
+    fn semantic_error_spans() {
+        let source = "int main() {\n    int b = c;\n    return b(1);\n}\n";
+        let diagnostics = diagnose(source);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].message, "未定义的标识符`c`");
+        assert_eq!(diagnostics[0].span, Some(Span::new(source.find("c;").unwrap(), source.find("c;").unwrap() + 1)));
+        assert_eq!(diagnostics[1].message, "`b`不是函数，不能调用");
+        let lo = source.find("b(1)").unwrap();
+        assert_eq!(diagnostics[1].span.map(|span| span.lo), Some(lo));
+    }
+
+    #[test]
+    fn parse_error_describes_tokens() {
+        let source = "int main() { return 0 }";
+        let diagnostics = Session::default().parse(source).unwrap_err();
+        let rendered = diagnostics[0].render(&SourceFile::new("a.sy", source));
+        assert!(rendered.starts_with("error: 意外的符号`}`\n --> a.sy:1:23\n"), "{}", rendered);
+        assert!(rendered.contains("期望"), "{}", rendered);
+        let diagnostics = Session::default().parse("int main() { int a = 1 @ 2; }").unwrap_err();
+        assert_eq!(diagnostics[0].message, "无法识别的字符`@`");
+    }
+}
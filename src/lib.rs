@@ -0,0 +1,20 @@
+pub mod utils;
+pub mod structures;
+pub mod ast;
+pub mod diagnostic;
+pub mod options;
+pub mod semantic;
+pub mod session;
+pub mod llvm_gen;
+pub mod llvm_opt;
+pub mod llvm_exec;
+pub mod riscv_gen;
+pub mod riscv_opt;
+pub mod riscv_exec;
+
+pub use session::Session;
+pub use options::OptLevel;
+
+use lalrpop_util::lalrpop_mod;
+// 语法错误恢复需要向生成的parser传入&mut Vec
+lalrpop_mod!(#[allow(clippy::ptr_arg)] pub parser);
@@ -0,0 +1,157 @@
+use crate::llvm_exec::sylib::SylibMemory;
+use crate::structures::llvm_struct::*;
+use crate::structures::symbol::*;
+use crate::utils::float::parse_float;
+
+/// 低于此地址的访问视为空指针
+const NULL_GUARD: usize = 16;
+/// 栈最多增长到的地址
+const MEMORY_LIMIT: usize = 1 << 30;
+
+/// 解释执行时的值，i1、i8、i32与i64都以符号扩展后的i64保存
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Val {
+    Int(i64),
+    Float(f32),
+    Ptr(usize),
+}
+
+impl Val {
+    pub fn as_int(self) -> i64 {
+        match self {
+            Val::Int(num) => num,
+            Val::Float(num) => num as i64,
+            Val::Ptr(addr) => addr as i64,
+        }
+    }
+
+    pub fn as_float(self) -> f32 {
+        match self {
+            Val::Float(num) => num,
+            other => other.as_int() as f32,
+        }
+    }
+
+    pub fn as_ptr(self) -> usize {
+        match self {
+            Val::Ptr(addr) => addr,
+            other => other.as_int() as usize,
+        }
+    }
+}
+
+/// 类型在内存中占的字节数，指针为8字节
+pub fn size_of(ty: &SymbolType) -> usize {
+    match &ty.width {
+        SymbolWidth::Void => 0,
+        SymbolWidth::Bool | SymbolWidth::I8 => 1,
+        SymbolWidth::I32 | SymbolWidth::Float => 4,
+        SymbolWidth::I64 => 8,
+        SymbolWidth::Arr { dims, .. } if dims.first() == Some(&-1) => 8,
+        SymbolWidth::Arr { tar, dims } => {
+            dims.iter().map(|dim| *dim as usize).product::<usize>() * size_of(tar)
+        },
+    }
+}
+
+/// 平坦的小端字节内存，全局变量在低地址，其后是向上增长的栈
+#[derive(Debug)]
+pub struct Memory {
+    bytes: Vec<u8>,
+    /// 栈顶，alloca从这里分配
+    pub sp: usize,
+}
+
+impl Memory {
+    /// 按顺序为全局变量分配空间并写入初始值，返回各全局变量的地址
+    pub fn new(globals: &[GlobalVar]) -> (Self, Vec<usize>) {
+        let mut mem = Memory { bytes: vec![0; NULL_GUARD], sp: NULL_GUARD };
+        let addrs = globals.iter().map(|var| {
+            let addr = mem.alloc(size_of(&var.var_type)).unwrap();
+            let elem = match &var.var_type.width {
+                SymbolWidth::Arr { tar, .. } => tar.as_ref(),
+                _ => &var.var_type,
+            };
+            let size = size_of(elem);
+            for (i, init) in var.init_values.iter().enumerate() {
+                let val = match elem.width {
+                    SymbolWidth::Float => Val::Float(parse_float(init)),
+                    _ => Val::Int(init.parse().unwrap()),
+                };
+                mem.store(elem, addr + i * size, val).unwrap();
+            }
+            addr
+        }).collect();
+        (mem, addrs)
+    }
+
+    /// 在栈顶分配size字节，按8字节对齐
+    pub fn alloc(&mut self, size: usize) -> Result<usize, String> {
+        let addr = (self.sp + 7) & !7;
+        let end = addr + size;
+        if end > MEMORY_LIMIT {
+            return Err("栈溢出".to_string());
+        }
+        if end > self.bytes.len() {
+            self.bytes.resize(end.max(self.bytes.len() * 2), 0);
+        }
+        self.sp = end;
+        Ok(addr)
+    }
+
+    /// 检查[addr, addr + len)可以访问，返回对应的切片
+    fn range(&mut self, addr: usize, len: usize) -> Result<&mut [u8], String> {
+        if addr < NULL_GUARD {
+            return Err(format!("访问空指针附近的地址{:#x}", addr));
+        }
+        if addr + len > self.sp {
+            return Err(format!("访问越界的地址{:#x}", addr));
+        }
+        Ok(&mut self.bytes[addr..addr + len])
+    }
+
+    pub fn load(&mut self, ty: &SymbolType, addr: usize) -> Result<Val, String> {
+        let size = size_of(ty);
+        let bytes = self.range(addr, size)?;
+        let mut buf = [0; 8];
+        buf[..size].copy_from_slice(bytes);
+        let val = match &ty.width {
+            SymbolWidth::Bool => Val::Int(buf[0] as i64 & 1),
+            SymbolWidth::I8 => Val::Int(buf[0] as i8 as i64),
+            SymbolWidth::I32 => Val::Int(i32::from_le_bytes(buf[..4].try_into().unwrap()) as i64),
+            SymbolWidth::I64 => Val::Int(i64::from_le_bytes(buf)),
+            SymbolWidth::Float => Val::Float(f32::from_le_bytes(buf[..4].try_into().unwrap())),
+            SymbolWidth::Arr { .. } if size == 8 => Val::Ptr(u64::from_le_bytes(buf) as usize),
+            _ => return Err(format!("无法读取{}类型的值", ty.get_typename())),
+        };
+        Ok(val)
+    }
+
+    pub fn store(&mut self, ty: &SymbolType, addr: usize, val: Val) -> Result<(), String> {
+        let size = size_of(ty);
+        let buf = match &ty.width {
+            SymbolWidth::Float => (val.as_float().to_bits() as u64).to_le_bytes(),
+            SymbolWidth::Arr { .. } if size == 8 => (val.as_ptr() as u64).to_le_bytes(),
+            SymbolWidth::Bool | SymbolWidth::I8 | SymbolWidth::I32 | SymbolWidth::I64 => val.as_int().to_le_bytes(),
+            _ => return Err(format!("无法写入{}类型的值", ty.get_typename())),
+        };
+        self.range(addr, size)?.copy_from_slice(&buf[..size]);
+        Ok(())
+    }
+}
+
+impl SylibMemory for Memory {
+    fn load_word(&mut self, addr: usize) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.range(addr, 4)?.try_into().unwrap()))
+    }
+
+    fn store_word(&mut self, addr: usize, word: u32) -> Result<(), String> {
+        self.range(addr, 4)?.copy_from_slice(&word.to_le_bytes());
+        Ok(())
+    }
+
+    fn fill(&mut self, addr: usize, byte: u8, len: usize) -> Result<(), String> {
+        self.range(addr, len)?.fill(byte);
+        Ok(())
+    }
+}
@@ -0,0 +1,458 @@
+pub mod memory;
+pub mod sylib;
+
+use std::collections::HashMap;
+use std::fmt;
+use crate::structures::llvm_struct::*;
+use crate::structures::symbol::*;
+use memory::*;
+use sylib::Runtime;
+
+/// 解释执行的结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecResult {
+    /// main的返回值，作为进程退出码时只保留低8位
+    pub exit_code: i32,
+    pub stdout: Vec<u8>,
+    /// starttime与stoptime的计时结果，格式与sylib相同
+    pub stderr: String,
+    /// 执行过的指令数，不含注释
+    pub steps: u64,
+}
+
+/// 解释执行时的运行错误，记录出错的函数与指令
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecError {
+    pub func: String,
+    /// 出错指令的文本
+    pub instr: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for ExecError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.func)?;
+        if let Some(instr) = &self.instr {
+            write!(f, ", `{}`", instr)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+impl LLVMProgram {
+    /// 解释执行main，input为标准输入的内容，库函数由sylib::Runtime在本地实现
+    pub fn interpret(&self, input: &[u8]) -> Result<ExecResult, ExecError> {
+        let (mem, addrs) = Memory::new(&self.global_var);
+        let globals = self.global_var.iter()
+            .zip(addrs)
+            .map(|(var, addr)| (var.var_name.as_str(), addr))
+            .collect();
+        let funcs = self.func_def.iter()
+            .map(|func| (&func.func_name[1..], func))
+            .collect();
+        let mut interp = Interpreter {
+            funcs,
+            globals,
+            mem,
+            runtime: Runtime::new(input),
+            frames: vec![],
+            steps: 0,
+        };
+        let exit_code = interp.run()?;
+        Ok(ExecResult {
+            exit_code,
+            stderr: interp.runtime.timer_report(),
+            stdout: interp.runtime.stdout,
+            steps: interp.steps,
+        })
+    }
+}
+
+/// 一次函数调用的栈帧
+struct Frame<'a> {
+    func: &'a FuncDef,
+    args: Vec<Val>,
+    /// 各指令的结果，以ValueId为下标
+    values: Vec<Val>,
+    block: BlockId,
+    /// 下一条要执行的一般指令在nor_ins中的序号
+    pos: usize,
+    /// 调用者中接收返回值的call指令
+    ret_to: Option<ValueId>,
+    /// 进入函数时的栈顶，返回时释放此后alloca的空间
+    sp: usize,
+}
+
+/// 用显式的栈帧列表执行，递归再深也不会耗尽宿主的栈
+struct Interpreter<'a> {
+    /// 函数名(不含@)到函数定义
+    funcs: HashMap<&'a str, &'a FuncDef>,
+    /// 全局变量名到地址
+    globals: HashMap<&'a str, usize>,
+    mem: Memory,
+    runtime: Runtime<'a>,
+    frames: Vec<Frame<'a>>,
+    steps: u64,
+}
+
+impl<'a> Interpreter<'a> {
+    fn run(&mut self) -> Result<i32, ExecError> {
+        let main = *self.funcs.get("main").ok_or_else(|| ExecError {
+            func: "@main".to_string(),
+            instr: None,
+            message: "程序中没有main函数".to_string(),
+        })?;
+        self.enter(main, vec![], None).map_err(|msg| error(main, None, msg))?;
+        loop {
+            let frame = self.frames.last_mut().unwrap();
+            let func = frame.func;
+            let block = func.block(frame.block);
+            let id = match block.nor_ins.get(frame.pos) {
+                Some(id) => *id,
+                None => match block.ter_ins {
+                    Some(id) => id,
+                    None => return Err(error(func, None, format!("基本块%{}没有终结指令", block.block_label))),
+                },
+            };
+            frame.pos += 1;
+            match self.step(func, id) {
+                Ok(Some(exit_code)) => return Ok(exit_code),
+                Ok(None) => {},
+                Err(msg) => return Err(error(func, Some(id), msg)),
+            }
+        }
+    }
+
+    /// 执行一条指令，main返回时得到其返回值
+    fn step(&mut self, func: &'a FuncDef, id: ValueId) -> Result<Option<i32>, String> {
+        let instr = func.instr(id);
+        if !matches!(instr, Instruction::Comment(_)) {
+            self.steps += 1;
+        }
+        match instr {
+            Instruction::Comment(_) => {},
+            Instruction::Br(cond, label1, label2) => {
+                let target = match (cond, label2) {
+                    (Some(cond), Some(label2)) if self.operand(cond)?.as_int() & 1 == 0 => *label2,
+                    _ => *label1,
+                };
+                self.jump(target)?;
+            },
+            Instruction::Ret(_, val) => {
+                let val = match val {
+                    Some(val) => Some(self.operand(val)?),
+                    None => None,
+                };
+                let frame = self.frames.pop().unwrap();
+                self.mem.sp = frame.sp;
+                match self.frames.last_mut() {
+                    Some(caller) => {
+                        if let (Some(ret_to), Some(val)) = (frame.ret_to, val) {
+                            caller.values[ret_to.0] = val;
+                        }
+                    },
+                    None => return Ok(Some(val.map_or(0, |val| val.as_int() as i32))),
+                }
+            },
+            Instruction::Call(name, _, params) => {
+                let args = params.iter()
+                    .map(|(param, _)| self.operand(param))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let name = &name[1..];
+                match self.funcs.get(name) {
+                    Some(callee) => self.enter(callee, args, Some(id))?,
+                    None => {
+                        if let Some(val) = self.runtime.call(name, &args, &mut self.mem)? {
+                            self.set(id, val);
+                        }
+                    },
+                }
+            },
+            Instruction::Store { ty, value, ptr, .. } => {
+                let value = self.operand(value)?;
+                let ptr = self.operand(ptr)?.as_ptr();
+                self.mem.store(ty, ptr, value)?;
+            },
+            _ => {
+                let val = self.eval(instr)?;
+                self.set(id, val);
+            },
+        }
+        Ok(None)
+    }
+
+    /// 计算有结果且没有控制流副作用的指令
+    fn eval(&mut self, instr: &Instruction) -> Result<Val, String> {
+        let val = match instr {
+            Instruction::Add(bin_op)
+            | Instruction::Sub(bin_op)
+            | Instruction::Mul(bin_op)
+            | Instruction::Sdiv(bin_op)
+            | Instruction::Srem(bin_op) => {
+                let num1 = self.operand(&bin_op.op1)?.as_int();
+                let num2 = self.operand(&bin_op.op2)?.as_int();
+                let res = match instr {
+                    Instruction::Add(_) => num1.wrapping_add(num2),
+                    Instruction::Sub(_) => num1.wrapping_sub(num2),
+                    Instruction::Mul(_) => num1.wrapping_mul(num2),
+                    _ if num2 == 0 => return Err("除以零".to_string()),
+                    Instruction::Sdiv(_) => num1.wrapping_div(num2),
+                    _ => num1.wrapping_rem(num2),
+                };
+                Val::Int(truncate(&bin_op.op_type, res))
+            },
+            Instruction::Fadd(bin_op)
+            | Instruction::Fsub(bin_op)
+            | Instruction::Fmul(bin_op)
+            | Instruction::Fdiv(bin_op) => {
+                let num1 = self.operand(&bin_op.op1)?.as_float();
+                let num2 = self.operand(&bin_op.op2)?.as_float();
+                Val::Float(match instr {
+                    Instruction::Fadd(_) => num1 + num2,
+                    Instruction::Fsub(_) => num1 - num2,
+                    Instruction::Fmul(_) => num1 * num2,
+                    _ => num1 / num2,
+                })
+            },
+            Instruction::Cmp(cond, bin_op) => {
+                let num1 = self.operand(&bin_op.op1)?.as_int();
+                let num2 = self.operand(&bin_op.op2)?.as_int();
+                let res = match cond.as_str() {
+                    "eq" => num1 == num2,
+                    "ne" => num1 != num2,
+                    "slt" => num1 < num2,
+                    "sgt" => num1 > num2,
+                    "sle" => num1 <= num2,
+                    "sge" => num1 >= num2,
+                    _ => return Err(format!("未知的比较条件`{}`", cond)),
+                };
+                Val::Int(res as i64)
+            },
+            Instruction::Fcmp(cond, bin_op) => {
+                let num1 = self.operand(&bin_op.op1)?.as_float();
+                let num2 = self.operand(&bin_op.op2)?.as_float();
+                // 有序比较，任一操作数为NaN时结果为假
+                let res = match cond.as_str() {
+                    "oeq" => num1 == num2,
+                    "one" => num1.partial_cmp(&num2).map_or(false, |ord| ord.is_ne()),
+                    "olt" => num1 < num2,
+                    "ogt" => num1 > num2,
+                    "ole" => num1 <= num2,
+                    "oge" => num1 >= num2,
+                    _ => return Err(format!("未知的比较条件`{}`", cond)),
+                };
+                Val::Int(res as i64)
+            },
+            Instruction::ZeroExt(cast_op) => {
+                let num = self.operand(&cast_op.val)?.as_int();
+                match cast_op.type_1.width {
+                    SymbolWidth::Bool => Val::Int(num & 1),
+                    SymbolWidth::I8 => Val::Int(num & 0xff),
+                    SymbolWidth::I32 => Val::Int(num & 0xffff_ffff),
+                    _ => Val::Int(num),
+                }
+            },
+            Instruction::I32ToFloat(cast_op) => Val::Float(self.operand(&cast_op.val)?.as_int() as i32 as f32),
+            Instruction::FloatToI32(cast_op) => Val::Int(self.operand(&cast_op.val)?.as_float() as i32 as i64),
+            Instruction::Alloca { ty, .. } => Val::Ptr(self.mem.alloc(size_of(ty))?),
+            Instruction::Load { ty, ptr, .. } => {
+                let ptr = self.operand(ptr)?.as_ptr();
+                self.mem.load(ty, ptr)?
+            },
+            Instruction::GetElemPtr(ty, ptr, idx) => {
+                // 第一个下标跨过整个ty，之后每个下标进入下一维，跨度为剩余各维之积
+                let (elem_size, dims) = match &ty.width {
+                    SymbolWidth::Arr { tar, dims } if dims.first() != Some(&-1) => (size_of(tar), dims.as_slice()),
+                    _ => (size_of(ty), &[][..]),
+                };
+                let mut addr = self.operand(ptr)?.as_ptr() as i64;
+                for (i, index) in idx.iter().enumerate() {
+                    let stride = dims.get(i..).map_or(1, |dims| dims.iter().product::<i32>()) as usize * elem_size;
+                    addr += self.operand(index)?.as_int() * stride as i64;
+                }
+                Val::Ptr(addr as usize)
+            },
+            Instruction::BitCast(_, val, _) => self.operand(val)?,
+            Instruction::Phi(..) => return Err("phi只能位于基本块开头".to_string()),
+            _ => unreachable!(),
+        };
+        Ok(val)
+    }
+
+    /// 调用函数：压入新栈帧并分配其局部变量
+    fn enter(&mut self, func: &'a FuncDef, args: Vec<Val>, ret_to: Option<ValueId>) -> Result<(), String> {
+        self.frames.push(Frame {
+            func,
+            args,
+            values: vec![Val::Int(0); func.values.len()],
+            block: func.entry(),
+            pos: 0,
+            ret_to,
+            sp: self.mem.sp,
+        });
+        for id in func.local_vars.iter() {
+            let val = self.eval(func.instr(*id))?;
+            self.set(*id, val);
+        }
+        Ok(())
+    }
+
+    /// 跳转到target，按来路同时计算其全部phi
+    fn jump(&mut self, target: BlockId) -> Result<(), String> {
+        let frame = self.frames.last().unwrap();
+        let func = frame.func;
+        let pred = frame.block;
+        let block = func.block(target);
+        let mut vals = Vec::with_capacity(block.phi_ins.len());
+        for id in block.phi_ins.iter() {
+            let Instruction::Phi(_, candidates) = func.instr(*id) else {
+                return Err("phi_ins中存在非phi指令".to_string());
+            };
+            let (val, _) = candidates.iter()
+                .find(|(_, from)| *from == pred)
+                .ok_or_else(|| format!("%{}中的phi缺少来自%{}的值", block.block_label, func.block(pred).block_label))?;
+            vals.push(self.operand(val)?);
+        }
+        self.steps += vals.len() as u64;
+        let frame = self.frames.last_mut().unwrap();
+        for (id, val) in block.phi_ins.iter().zip(vals) {
+            frame.values[id.0] = val;
+        }
+        frame.block = target;
+        frame.pos = 0;
+        Ok(())
+    }
+
+    fn set(&mut self, id: ValueId, val: Val) {
+        self.frames.last_mut().unwrap().values[id.0] = val;
+    }
+
+    fn operand(&self, op: &Operand) -> Result<Val, String> {
+        let frame = self.frames.last().unwrap();
+        let val = match op {
+            Operand::ConstInt(num) => Val::Int(*num),
+            Operand::ConstFloat(num) => Val::Float(*num),
+            Operand::Null => Val::Ptr(0),
+            Operand::Global(name) => match self.globals.get(name.as_str()) {
+                Some(addr) => Val::Ptr(*addr),
+                None => return Err(format!("未定义的全局变量`@{}`", name)),
+            },
+            Operand::Arg(idx) => frame.args[*idx],
+            Operand::Value(id) => frame.values[id.0],
+        };
+        Ok(val)
+    }
+}
+
+/// 把整数运算的结果截断到op_type的宽度再符号扩展
+fn truncate(ty: &SymbolType, num: i64) -> i64 {
+    match ty.width {
+        SymbolWidth::Bool => num & 1,
+        SymbolWidth::I8 => num as i8 as i64,
+        SymbolWidth::I32 => num as i32 as i64,
+        _ => num,
+    }
+}
+
+fn error(func: &FuncDef, id: Option<ValueId>, message: String) -> ExecError {
+    ExecError {
+        func: func.func_name.clone(),
+        instr: id.map(|id| func.instr_text(id, &func.value_names())),
+        message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::llvm_gen::read_text::read_llvm;
+
+    fn run(text: &str, input: &str) -> (i32, String) {
+        let result = read_llvm(text).unwrap().interpret(input.as_bytes()).unwrap();
+        (result.exit_code, String::from_utf8(result.stdout).unwrap())
+    }
+
+    #[test]
+    fn phis_read_values_before_the_jump() {
+        // %x与%y每次迭代交换，同一基本块的phi应同时取值，依次读到的%x为1、2、1
+        let text = "\
+define i32 @main() {
+entry:
+  br label %loop
+
+loop:
+  %i = phi i32 [ 0, %entry ], [ %i1, %loop ]
+  %x = phi i32 [ 1, %entry ], [ %y, %loop ]
+  %y = phi i32 [ 2, %entry ], [ %x, %loop ]
+  %acc = phi i32 [ 0, %entry ], [ %acc1, %loop ]
+  %t = mul i32 %acc, 10
+  %acc1 = add i32 %t, %x
+  %i1 = add i32 %i, 1
+  %c = icmp slt i32 %i1, 3
+  br i1 %c, label %loop, label %exit
+
+exit:
+  ret i32 %acc1
+}";
+        assert_eq!(run(text, "").0, 121);
+    }
+
+    #[test]
+    fn multi_dimensional_gep() {
+        let text = "\
+@a = global [2 x [3 x i32]] [[3 x i32] [i32 1, i32 2, i32 3], [3 x i32] [i32 4, i32 5, i32 6]], align 4
+
+declare void @putint(i32 noundef)
+declare void @putch(i32 noundef)
+
+define i32 @main() {
+entry:
+  %row = getelementptr [2 x [3 x i32]], [2 x [3 x i32]]* @a, i32 0, i32 1
+  %p = getelementptr [3 x i32], [3 x i32]* %row, i32 0, i32 2
+  %x = load i32, i32* %p, align 4
+  call void @putint(i32 noundef %x)
+  call void @putch(i32 noundef 32)
+  %first = getelementptr [2 x [3 x i32]], [2 x [3 x i32]]* @a, i32 0, i32 0, i32 0
+  %q = getelementptr i32, i32* %first, i32 4
+  store i32 50, i32* %q, align 4
+  %r = getelementptr [2 x [3 x i32]], [2 x [3 x i32]]* @a, i32 0, i32 1, i32 1
+  %y = load i32, i32* %r, align 4
+  call void @putint(i32 noundef %y)
+  %l = alloca [2 x [2 x i32]], align 4
+  %l11 = getelementptr [2 x [2 x i32]], [2 x [2 x i32]]* %l, i32 0, i32 1, i32 1
+  store i32 7, i32* %l11, align 4
+  %lflat = getelementptr [2 x [2 x i32]], [2 x [2 x i32]]* %l, i32 0, i32 0, i32 3
+  %z = load i32, i32* %lflat, align 4
+  ret i32 %z
+}";
+        assert_eq!(run(text, ""), (7, String::from("6 50")));
+    }
+
+    #[test]
+    fn sylib_io() {
+        let text = "\
+declare i32 @getint()
+declare i32 @getch()
+declare i32 @getarray(i32* noundef)
+declare void @putint(i32 noundef)
+declare void @putch(i32 noundef)
+declare void @putarray(i32 noundef, i32* noundef)
+declare void @putfloat(float noundef)
+
+define i32 @main() {
+entry:
+  %arr = alloca [4 x i32], align 4
+  %x = call i32 @getint()
+  %ch = call i32 @getch()
+  %p = getelementptr [4 x i32], [4 x i32]* %arr, i32 0, i32 0
+  %n = call i32 @getarray(i32* noundef %p)
+  call void @putint(i32 noundef %x)
+  call void @putch(i32 noundef %ch)
+  call void @putarray(i32 noundef %n, i32* noundef %p)
+  call void @putfloat(float noundef 0x3FF8000000000000)
+  %eof = call i32 @getch()
+  ret i32 %eof
+}";
+        // getint跳过前导空白，之后的getch读到紧跟的字符；输入结束后getch返回-1
+        assert_eq!(run(text, "  -12,3 4 -5 6"), (-1, String::from("-12,3: 4 -5 6\n0x1.8p+0")));
+    }
+}
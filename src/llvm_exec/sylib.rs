@@ -0,0 +1,195 @@
+use std::io::Write;
+use std::time::{Duration, Instant};
+use crate::llvm_exec::memory::Val;
+use crate::utils::float::parse_float;
+
+/// 库函数读写数组与memset所需的内存访问，由IR解释器与汇编模拟器各自实现
+pub trait SylibMemory {
+    fn load_word(&mut self, addr: usize) -> Result<u32, String>;
+    fn store_word(&mut self, addr: usize, word: u32) -> Result<(), String>;
+    fn fill(&mut self, addr: usize, byte: u8, len: usize) -> Result<(), String>;
+}
+
+/// sylib运行时库的本地实现，输入输出都在内存中进行
+#[derive(Debug)]
+pub struct Runtime<'a> {
+    input: &'a [u8],
+    pos: usize,
+    pub stdout: Vec<u8>,
+    /// starttime开始计时的时刻
+    start: Option<Instant>,
+    /// 每对starttime与stoptime之间经过的时间
+    timers: Vec<Duration>,
+}
+
+impl<'a> Runtime<'a> {
+    pub fn new(input: &'a [u8]) -> Self {
+        Runtime { input, pos: 0, stdout: vec![], start: None, timers: vec![] }
+    }
+
+    /// 调用名为name的库函数，name不含@，返回值为None表示void
+    pub fn call(&mut self, name: &str, args: &[Val], mem: &mut impl SylibMemory) -> Result<Option<Val>, String> {
+        let res = match name {
+            "getint" => Some(Val::Int(self.read_int() as i64)),
+            "getch" => {
+                let ch = self.input.get(self.pos).map_or(-1, |&ch| ch as i64);
+                self.pos += 1;
+                Some(Val::Int(ch))
+            },
+            "getfloat" => Some(Val::Float(self.read_float())),
+            "getarray" | "getfarray" => {
+                let len = self.read_int();
+                for i in 0..len.max(0) as usize {
+                    let addr = args[0].as_ptr() + i * 4;
+                    if name == "getarray" {
+                        let num = self.read_int();
+                        mem.store_word(addr, num as u32)?;
+                    } else {
+                        let num = self.read_float();
+                        mem.store_word(addr, num.to_bits())?;
+                    }
+                }
+                Some(Val::Int(len as i64))
+            },
+            "putint" => {
+                write!(self.stdout, "{}", args[0].as_int() as i32).unwrap();
+                None
+            },
+            "putch" => {
+                self.stdout.push(args[0].as_int() as u8);
+                None
+            },
+            "putfloat" => {
+                self.stdout.extend(format_hex_float(args[0].as_float() as f64).bytes());
+                None
+            },
+            "putarray" | "putfarray" => {
+                let len = args[0].as_int() as i32;
+                write!(self.stdout, "{}:", len).unwrap();
+                for i in 0..len.max(0) as usize {
+                    let addr = args[1].as_ptr() + i * 4;
+                    if name == "putarray" {
+                        let num = mem.load_word(addr)? as i32;
+                        write!(self.stdout, " {}", num).unwrap();
+                    } else {
+                        let num = f32::from_bits(mem.load_word(addr)?);
+                        write!(self.stdout, " {}", format_hex_float(num as f64)).unwrap();
+                    }
+                }
+                self.stdout.push(b'\n');
+                None
+            },
+            "starttime" => {
+                self.start = Some(Instant::now());
+                None
+            },
+            "stoptime" => {
+                let start = self.start.take().unwrap_or_else(Instant::now);
+                self.timers.push(start.elapsed());
+                None
+            },
+            "llvm.memset.p0i8.i64" => {
+                mem.fill(args[0].as_ptr(), args[1].as_int() as u8, args[2].as_int() as usize)?;
+                None
+            },
+            _ => return Err(format!("未定义的函数`{}`", name)),
+        };
+        Ok(res)
+    }
+
+    /// 按sylib中after_main的格式输出计时结果
+    pub fn timer_report(&self) -> String {
+        let mut report = String::new();
+        let mut total = Duration::ZERO;
+        for (i, time) in self.timers.iter().enumerate() {
+            report += &format!("Timer@{:04}-{:04}: {}\n", i + 1, i + 1, format_duration(*time));
+            total += *time;
+        }
+        report += &format!("TOTAL: {}\n", format_duration(total));
+        report
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.input.get(self.pos).map_or(false, |ch| ch.is_ascii_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    /// 相当于scanf("%d")，读不到数字时返回0
+    fn read_int(&mut self) -> i32 {
+        self.skip_whitespace();
+        let start = self.pos;
+        if matches!(self.input.get(self.pos), Some(b'+' | b'-')) {
+            self.pos += 1;
+        }
+        while self.input.get(self.pos).map_or(false, |ch| ch.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        let text = std::str::from_utf8(&self.input[start..self.pos]).unwrap();
+        text.parse::<i64>().map_or(0, |num| num as i32)
+    }
+
+    /// 相当于scanf("%a")，接受十进制与十六进制浮点数
+    fn read_float(&mut self) -> f32 {
+        self.skip_whitespace();
+        let start = self.pos;
+        if matches!(self.input.get(self.pos), Some(b'+' | b'-')) {
+            self.pos += 1;
+        }
+        let mut prev = 0;
+        while let Some(&ch) = self.input.get(self.pos) {
+            let is_hex = self.input[start..self.pos].iter().any(|ch| *ch == b'x' || *ch == b'X');
+            let exp_sign = (ch == b'+' || ch == b'-')
+                && if is_hex { matches!(prev, b'p' | b'P') } else { matches!(prev, b'e' | b'E') };
+            if !(ch.is_ascii_alphanumeric() || ch == b'.' || exp_sign) {
+                break;
+            }
+            prev = ch;
+            self.pos += 1;
+        }
+        let text = std::str::from_utf8(&self.input[start..self.pos]).unwrap();
+        let (neg, body) = match text.strip_prefix('-') {
+            Some(body) => (true, body),
+            None => (false, text.strip_prefix('+').unwrap_or(text)),
+        };
+        let num = if body.starts_with("0x") || body.starts_with("0X") {
+            if body.contains(['p', 'P']) {
+                parse_float(body)
+            } else {
+                parse_float(&format!("{}p0", body))
+            }
+        } else {
+            body.parse().unwrap_or(0.0)
+        };
+        if neg { -num } else { num }
+    }
+}
+
+/// 相当于printf("%a")
+pub fn format_hex_float(num: f64) -> String {
+    let sign = if num.is_sign_negative() { "-" } else { "" };
+    if num.is_nan() {
+        return format!("{}nan", sign);
+    }
+    if num.is_infinite() {
+        return format!("{}inf", sign);
+    }
+    if num == 0.0 {
+        return format!("{}0x0p+0", sign);
+    }
+    let bits = num.to_bits();
+    let exp = ((bits >> 52) & 0x7ff) as i64 - 1023;
+    let frac = bits & ((1 << 52) - 1);
+    let digits = format!("{:013x}", frac);
+    let digits = digits.trim_end_matches('0');
+    if digits.is_empty() {
+        format!("{}0x1p{:+}", sign, exp)
+    } else {
+        format!("{}0x1.{}p{:+}", sign, digits, exp)
+    }
+}
+
+fn format_duration(time: Duration) -> String {
+    let us = time.as_micros();
+    format!("{}H-{}M-{}S-{}us", us / 3_600_000_000, us / 60_000_000 % 60, us / 1_000_000 % 60, us % 1_000_000)
+}
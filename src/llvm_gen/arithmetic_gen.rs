@@ -104,7 +104,7 @@ impl Generate for LVal {
     type Out = (SymbolType, String);
 
     fn generate(&self, program: &mut LLVMProgram, scopes: &mut Scopes, labels: &mut Labels) -> Self::Out {
-        let val = scopes.get(self.id.as_str()).expect(&format!("Undefined {}", self.id)).clone();
+        let val = scopes.get(self.id.as_str()).expect("标识符已在语义分析中解析").clone();
         if val.ty.is_const {
             (val.ty.clone(), get_symbol_val(&val.value))
         } else {
@@ -320,8 +320,9 @@ impl Generate for UnaryExp {
                 }
                 (SymbolType::new(SymbolWidth::Bool, false), result)
             },
-            UnaryExp::Call{id, params} => {
-                let func = scopes.get_function(id.as_str()).expect(format!("Undefined function {}", id).as_str());
+            UnaryExp::Call{id, params, ..} => {
+                // 函数与参数个数、类型已在语义分析中检查
+                let func = scopes.get_function(id.as_str()).expect("函数已在语义分析中解析").clone();
                 let ret_type = func.ty.clone();
                 if params.is_none() {
                     let res: String;
@@ -344,7 +345,6 @@ impl Generate for UnaryExp {
                 if let SymbolVal::Func(_, param_list) = &func.value {
                     let func_param = param_list.clone();
                     let func_label = func.label.clone();
-                    assert!(func_param.len() == params.len(), "Params length of function call is not correct, found {}, expected {}", params.len(), func_param.len());
 
                     // 计算参数
                     let mut param_info: Vec<(String, SymbolType)> = vec!();
@@ -16,6 +16,9 @@ fn get_position(dims: &Vec<i32>, pos: &Vec<i32>) -> i32 {
     res
 }
 
+// 等待插入的指令<指令类型, 操作数名称, 类型>，GetElemPtr需要插入到其元素的store之前
+type PendingInstr = (InstructionType, Vec<String>, SymbolType);
+
 // 遍历多维数组，使用GetElemPtr或者Store指令初始化，成功返回true
 fn traverse_array(
     program: &mut LLVMProgram,  // 程序
@@ -24,7 +27,7 @@ fn traverse_array(
     label: &String,             // 当前维度的指针标号
     types: &Vec<SymbolType>,    // 数组内类型
     vals: &Vec<String>,         // 值
-    ins: &mut Vec<Instruction>, // 指令
+    ins: &mut Vec<PendingInstr>, // 指令
     pos: &mut Vec<i32>,         // 索引
 ) -> bool {
     // 数组内类型为空或者值为空
@@ -46,7 +49,7 @@ fn traverse_array(
                     let arr_val = vals[elem_pos as usize].to_string();
                     let str_vec = vec![arr_val.as_str(), label.as_str(), "4"];
                     // 生成store指令
-                    ins.push(Instruction::make_instruction(InstructionType::Store, str_vec, ty_vec));
+                    ins.push((InstructionType::Store, str_vec.iter().map(|s| s.to_string()).collect(), ty_vec[0].clone()));
                 } else {
                     flag = false;
                 }
@@ -86,7 +89,7 @@ fn traverse_array(
 
                     if traverse_array(program, labels, ty, &ptr, types, vals, ins, pos) {
                         flag = true;
-                        ins.insert(ins_len, Instruction::make_instruction(InstructionType::GetElemPtr, str_vec, ty_vec));
+                        ins.insert(ins_len, (InstructionType::GetElemPtr, str_vec.iter().map(|s| s.to_string()).collect(), ty_vec[0].clone()));
                     } else {
                         labels.recover_num();
                     }
@@ -132,11 +135,7 @@ pub fn decl_arr(
         // 然后更新作用域
         let str_vec = vec!(label.as_str(), "16");
         let ty_vec = vec!(&ty_arr);
-        let x=program.get_block_label();
-        program.insert_alloc(
-            Instruction::make_instruction(InstructionType::Alloca, str_vec, ty_vec), 
-            &x
-        );
+        program.insert_alloc(str_vec, ty_vec);
         
         // 做类型转换，使用BitCase将label转换为一个数组指针类型
         let res = labels.pop_num_str();
@@ -163,11 +162,11 @@ pub fn decl_arr(
         // 遍历数组
         let tra_flag = traverse_array(program, labels, &ty_arr, &label, types, &val_init, &mut ins, &mut pos);
         if tra_flag {
-            for i in ins.into_iter() {
-                program.insert_instr(i);
+            for (instr_type, str_vec, ty) in ins.into_iter() {
+                program.push_instr(instr_type, str_vec.iter().map(|s| s.as_str()).collect(), vec![&ty]);
             }
         }
     } else {
-        panic!("{} has been declared!", id);
+        unreachable!("{}重复定义，应在语义分析中报错", id);
     }
 }
\ No newline at end of file
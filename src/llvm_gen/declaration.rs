@@ -55,7 +55,7 @@ impl Generate for ConstDecl {
                 let res = type_conver(program, labels, init_val[0].clone(), &init_ty[0], &ty);
                 let symbol_val = make_symbol_val(&ty, &res);
                 if scopes.push(labels, &id, &ty, &symbol_val, None).is_none() {
-                    panic!("{} has been defined", id);
+                    unreachable!("{}重复定义，应在语义分析中报错", id);
                 }
             }
             
@@ -113,11 +113,7 @@ impl Generate for VarDecl {
                     let str_vec = vec!(label.as_str(), "4");
                     let ty_vec = vec!(&ty);
 
-                    let block_label = program.get_block_label();
-                    program.insert_alloc(
-                        Instruction::make_instruction(InstructionType::Alloca, str_vec, ty_vec.clone()),
-                        block_label.as_str()
-                    );
+                    program.insert_alloc(str_vec, ty_vec.clone());
 
                     // 如果初始化
                     if !init_val.is_empty() {
@@ -132,7 +128,7 @@ impl Generate for VarDecl {
                     }
                 }
             } else {
-                panic!("{} has been defined!", id);
+                unreachable!("{}重复定义，应在语义分析中报错", id);
             }
         }
         
@@ -23,7 +23,10 @@ impl Generate for ConstDef {
             
             let dims: Vec<i32> = self.dims
                 .iter()
-                .map(|dim| dim.generate(program, scopes, labels).1.parse().unwrap())
+                .map(|dim| {
+                    let (_, val) = dim.generate(program, scopes, labels);
+                    val.parse().expect("数组维度已在语义分析中检查为整型常量")
+                })
                 .collect::<Vec<i32>>();
             
             let (ty, val) = self.init.generate(program, scopes, labels, &dims);
@@ -40,7 +43,7 @@ impl Generate for VarDef {
         let mut dims: Vec<i32> = vec!();
         for dim in self.dims.iter() {
             let (_, val) = dim.generate(program, scopes, labels);
-            dims.push(val.parse().expect(&format!("{} is not integer", val)));
+            dims.push(val.parse().expect("数组维度已在语义分析中检查为整型常量"));
         }
 
         // 是否初始化
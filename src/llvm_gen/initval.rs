@@ -7,7 +7,7 @@ use crate::llvm_gen::sysy_gen::*;
 // 初始化值
 impl InitVal {
     // 确定对齐的维度
-    fn init_align(dims: &Vec<i32>, mut fill: i32, now_depth: usize) -> usize {
+    pub fn init_align(dims: &Vec<i32>, mut fill: i32, now_depth: usize) -> usize {
         if fill < 1 {
             return now_depth + 1;
         } else {
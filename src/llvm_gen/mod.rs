@@ -5,6 +5,7 @@ pub mod declaration;
 pub mod arithmetic_gen;
 pub mod logic_gen;
 pub mod write_text;
+pub mod read_text;
 pub mod instruction_gen;
 pub mod define;
 pub mod array_declaration;
@@ -15,6 +16,7 @@ use crate::structures::llvm_struct::*;
 use crate::structures::scopes::*;
 use crate::ast::*;
 
+/// 生成llvm，ast需要先通过语义分析
 pub fn generate_llvm(ast: &mut SysY) -> LLVMProgram {
     let mut program = LLVMProgram::new();
     let mut scopes = Scopes::new();
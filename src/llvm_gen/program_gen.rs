@@ -1,6 +1,7 @@
 use crate::structures::llvm_struct::*;
 use crate::structures::symbol::*;
 use crate::structures::scopes::*;
+use crate::utils::check::is_num_label;
 
 impl LLVMProgram {
     fn curr_func(&mut self) -> Option<&mut FuncDef> {
@@ -30,65 +31,53 @@ impl LLVMProgram {
     }
 
     pub fn push_func(&mut self, func_type: &SymbolType, func_name: &str, params: Vec<(String, SymbolType)>) {
-        self.func_def.push(
-            FuncDef {
-                func_type: func_type.clone(),
-                func_name: func_name.to_string(),
-                params: vec![],
-                blocks: vec![],
-                local_vars: vec![]
-            }
-        );
+        self.names = NameTable::default();
+        let params: Vec<Param> = params.into_iter()
+            .map(|(param_name, param_type)| Param { param_name, param_type })
+            .collect();
+        params.iter().enumerate().for_each(|(i, param)| {
+            self.names.values.insert(param.param_name.clone(), Operand::Arg(i));
+        });
+        self.func_def.push(FuncDef::new(func_name, func_type, params));
+
+        let entry = self.make_block_id("_entry");
+        self.curr_func().unwrap().layout.push(entry);
+    }
 
+    /// 函数结束时，若最后的基本块没有终结指令(例如void函数末尾或return之后的空块)，补上返回默认值的ret
+    pub fn end_func(&mut self) {
+        let curr_bb = self.curr_bb();
         let curr_func = self.curr_func().unwrap();
-        
-        for (param_name, param_type) in params.into_iter() {
-            curr_func.params.push(
-                Param {
-                    param_name,
-                    param_type
-                }
-            );
+        if curr_func.block(curr_bb).ter_ins.is_some() {
+            return;
         }
-        
-        curr_func.blocks.push(
-            Block {
-                block_label: "_entry".to_string(),
-                phi_ins: Vec::new(),
-                nor_ins: Vec::new(),
-                ter_ins: None,
-                ins_num: 0,
-                depth: 0,
-            }
-        );
+        let ret_type = curr_func.func_type.clone();
+        let ret_val = match ret_type.width {
+            SymbolWidth::Void => None,
+            SymbolWidth::Float => Some(Operand::ConstFloat(0.0)),
+            _ => Some(Operand::ConstInt(0)),
+        };
+        curr_func.push_value(curr_bb, Instruction::Ret(ret_type, ret_val), None);
     }
 
     pub fn push_bb(&mut self, block_label: &str, scopes: &Scopes) {
-        let curr_func = self.curr_func().unwrap();
-        let curr_bb_mut = curr_func.curr_bb_mut().unwrap();
-        assert!(curr_bb_mut.ter_ins.is_some(), "It's not allowed to push a new basic block before pushing terminator instr.\n");
-        
+        let curr_bb = self.curr_bb();
+        assert!(self.curr_func().unwrap().block(curr_bb).ter_ins.is_some(), "It's not allowed to push a new basic block before pushing terminator instr.\n");
+
         let mut depth = scopes.get_depth();
         if block_label.contains("while_entry") {
             depth += 1;
         }
 
-        curr_func.blocks.push(
-            Block {
-                block_label: block_label.to_string(),
-                phi_ins: Vec::new(),
-                nor_ins: Vec::new(),
-                ter_ins: None,
-                ins_num: curr_func.count_instr(),
-                depth,
-            }
-        );
+        let bb = self.make_block_id(block_label);
+        let curr_func = self.curr_func().unwrap();
+        curr_func.block_mut(bb).depth = depth;
+        curr_func.layout.push(bb);
     }
 
-    pub fn get_block_label(&mut self) -> String {
-        let curr_func = self.curr_func().unwrap();
-        let curr_bb_mut = curr_func.curr_bb_mut().unwrap();
-        String::from(&curr_bb_mut.block_label)
+    /// 当前正在生成的基本块
+    fn curr_bb(&mut self) -> BlockId {
+        *self.curr_func().unwrap().layout.last().unwrap()
     }
 
     pub fn push_comment(&mut self, cotent: &str) {
@@ -96,60 +85,29 @@ impl LLVMProgram {
         self.push_instr(InstructionType::Comment, str_vec, vec!());
     }
 
-    pub fn insert_instr(&mut self, instr: Instruction) {
-        let curr_func = self.curr_func().expect(&format!("Push instr {:?} in void func", instr));
-        let curr_bb_mut = curr_func.curr_bb_mut().expect(&format!("Push instr {:?} in void func", instr));
-        curr_bb_mut.nor_ins.push(instr);
+    /// 将指令加入当前基本块，并登记结果的名称
+    /// 数字标号的结果不保留名称，打印时重新编号
+    pub fn insert_instr(&mut self, instr: Instruction, res: Option<String>) {
+        let curr_bb = self.curr_bb();
+        let name = res.clone().filter(|s| !is_num_label(s));
+        let id = self.curr_func().unwrap().push_value(curr_bb, instr, name);
+        if let Some(res) = res {
+            self.names.values.insert(res, Operand::Value(id));
+        }
     }
 
-    pub fn insert_alloc(&mut self, alloc: Instruction, bb_label: &str) {
-        let curr_func = self.curr_func().expect(&format!("Push instr {:?} in void func", alloc));
-        curr_func.local_vars.push(
-            LocalVar { 
-                ins: alloc, 
-                label: bb_label.to_string() 
-            }
-        );
+    pub fn insert_alloc(&mut self, str_vec: Vec<&str>, ty_vec: Vec<&SymbolType>) {
+        let (alloc, res) = self.make_instruction(InstructionType::Alloca, str_vec, ty_vec);
+        self.insert_instr(alloc, res);
     }
-    
+
     pub fn push_instr(&mut self, instr_type: InstructionType, str_vec: Vec<&str>, types: Vec<&SymbolType>) {
-        let instr = Instruction::make_instruction(instr_type, str_vec, types);
-        self.insert_instr(instr);
+        let (instr, res) = self.make_instruction(instr_type, str_vec, types);
+        self.insert_instr(instr, res);
     }
 
     pub fn push_ter_instr(&mut self, ter_type: InstructionType, str_vec: Vec<&str>, ty_vec: Vec<&SymbolType>) {
-        let curr_func = self.curr_func().unwrap();
-        let curr_bb_mut = curr_func.curr_bb_mut().unwrap();
-        assert!(curr_bb_mut.ter_ins.is_none(), "Already pushed one terminator instr in this basic block before.");
-
-        let ter_ins = Instruction::make_instruction(ter_type, str_vec, ty_vec);
-        curr_bb_mut.ter_ins = Some(ter_ins);
+        let (ter_ins, _) = self.make_instruction(ter_type, str_vec, ty_vec);
+        self.insert_instr(ter_ins, None);
     }
 }
-
-impl FuncDef {
-    pub fn curr_bb(&self) -> Option<&Block> {
-        self.blocks.last()
-    }
-
-    pub fn curr_bb_mut(&mut self) -> Option<&mut Block> {
-        self.blocks.last_mut()
-    }
-    
-    pub fn count_instr(&self) -> usize {
-        let curr_bb = self.curr_bb().unwrap();
-        curr_bb.count_instr()
-    }
-}
-
-impl Block {
-    fn count_self(&self) -> usize {
-        self.phi_ins.len() 
-            + self.nor_ins.len() 
-            + self.ter_ins.is_some() as usize
-    }
-
-    pub fn count_instr(&self) -> usize {
-        self.ins_num + self.count_self()
-    }
-}
\ No newline at end of file
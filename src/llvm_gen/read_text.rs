@@ -0,0 +1,724 @@
+use std::collections::{HashMap, HashSet};
+use crate::diagnostic::{Diagnostic, Span};
+use crate::structures::llvm_struct::*;
+use crate::structures::symbol::*;
+use crate::utils::check::*;
+use crate::utils::float::*;
+
+/// 读入文本形式的llvm，支持write_text输出的子集：全局变量、函数声明、函数定义与全部指令
+/// 只检查语法，IR是否合法由verify检查
+pub fn read_llvm(text: &str) -> Result<LLVMProgram, Diagnostic> {
+    let lines = tokenize(text)?;
+    let mut reader = Reader {
+        program: LLVMProgram::new(),
+        block_refs: HashMap::new(),
+    };
+    let mut idx = 0;
+    while idx < lines.len() {
+        let mut cur = Cursor::new(&lines[idx]);
+        let first = cur.toks[0];
+        idx = match (first.kind, first.text) {
+            (TokKind::Global, _) => {
+                reader.read_global(&mut cur)?;
+                idx + 1
+            },
+            (TokKind::Word, "declare") => {
+                reader.read_decl(&mut cur)?;
+                idx + 1
+            },
+            (TokKind::Word, "define") => reader.read_func(&lines, idx)?,
+            _ => return Err(expected(&first, "全局变量、declare或define")),
+        };
+    }
+    reader.program.names = NameTable::default();
+    Ok(reader.program)
+}
+
+/// 记号的种类
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TokKind {
+    /// 关键字、类型名、字面量与基本块标签
+    Word,
+    /// 局部名称，文本含%
+    Local,
+    /// 全局名称，文本含@
+    Global,
+    Punct,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Token<'a> {
+    kind: TokKind,
+    text: &'a str,
+    span: Span,
+}
+
+/// 一行中的全部记号，以及行尾的位置，用于在行尾报错
+struct Line<'a> {
+    toks: Vec<Token<'a>>,
+    end: usize,
+}
+
+/// 把文本切分为记号，忽略空行与`;`开始的注释
+fn tokenize(text: &str) -> Result<Vec<Line>, Diagnostic> {
+    let is_name = |c: char| c.is_ascii_alphanumeric() || "_.-$".contains(c);
+    let mut lines = vec![];
+    let mut offset = 0;
+    for line_text in text.split_inclusive('\n') {
+        let start = offset;
+        offset += line_text.len();
+        let line_text = line_text.split(';').next().unwrap();
+        let bytes: Vec<(usize, char)> = line_text.char_indices().collect();
+        let mut toks = vec![];
+        let mut i = 0;
+        while i < bytes.len() {
+            let (lo, c) = bytes[i];
+            if c.is_whitespace() {
+                i += 1;
+                continue;
+            }
+            let kind = match c {
+                '%' => TokKind::Local,
+                '@' => TokKind::Global,
+                c if "=,()[]*{}:".contains(c) => TokKind::Punct,
+                c if is_name(c) => TokKind::Word,
+                c => {
+                    let span = Span::new(start + lo, start + lo + c.len_utf8());
+                    return Err(Diagnostic::error(format!("无法识别的字符`{}`", c)).with_span(span));
+                },
+            };
+            let mut j = i + 1;
+            if kind != TokKind::Punct {
+                while j < bytes.len() && is_name(bytes[j].1) {
+                    j += 1;
+                }
+            }
+            let hi = bytes.get(j).map_or(line_text.len(), |(hi, _)| *hi);
+            if kind != TokKind::Word && kind != TokKind::Punct && hi == lo + 1 {
+                let span = Span::new(start + lo, start + hi);
+                return Err(Diagnostic::error("名称不能为空").with_span(span));
+            }
+            toks.push(Token { kind, text: &line_text[lo..hi], span: Span::new(start + lo, start + hi) });
+            i = j;
+        }
+        if !toks.is_empty() {
+            lines.push(Line { toks, end: start + line_text.trim_end().len() });
+        }
+    }
+    Ok(lines)
+}
+
+fn expected(tok: &Token, what: &str) -> Diagnostic {
+    Diagnostic::error(format!("应为{}，实际为`{}`", what, tok.text)).with_span(tok.span)
+}
+
+/// 逐个读取一行中的记号
+struct Cursor<'l, 'a> {
+    toks: &'l [Token<'a>],
+    pos: usize,
+    end: usize,
+}
+
+impl<'l, 'a> Cursor<'l, 'a> {
+    fn new(line: &'l Line<'a>) -> Self {
+        Cursor { toks: &line.toks, pos: 0, end: line.end }
+    }
+
+    fn peek(&self) -> Option<&Token<'a>> {
+        self.toks.get(self.pos)
+    }
+
+    fn next(&mut self, what: &str) -> Result<Token<'a>, Diagnostic> {
+        match self.toks.get(self.pos) {
+            Some(tok) => {
+                self.pos += 1;
+                Ok(*tok)
+            },
+            None => Err(Diagnostic::error(format!("应为{}，但该行已结束", what)).with_span(Span::new(self.end, self.end))),
+        }
+    }
+
+    /// 下一个记号为指定的符号或关键字时读取它
+    fn eat(&mut self, text: &str) -> bool {
+        match self.peek() {
+            Some(tok) if tok.text == text && matches!(tok.kind, TokKind::Punct | TokKind::Word) => {
+                self.pos += 1;
+                true
+            },
+            _ => false,
+        }
+    }
+
+    fn expect(&mut self, text: &str) -> Result<(), Diagnostic> {
+        let tok = self.next(&format!("`{}`", text))?;
+        if tok.text == text && matches!(tok.kind, TokKind::Punct | TokKind::Word) {
+            Ok(())
+        } else {
+            Err(expected(&tok, &format!("`{}`", text)))
+        }
+    }
+
+    fn kind(&mut self, kind: TokKind, what: &str) -> Result<Token<'a>, Diagnostic> {
+        let tok = self.next(what)?;
+        if tok.kind == kind {
+            Ok(tok)
+        } else {
+            Err(expected(&tok, what))
+        }
+    }
+
+    /// 行中不应再有记号
+    fn finish(&self) -> Result<(), Diagnostic> {
+        match self.peek() {
+            Some(tok) => Err(Diagnostic::error(format!("多余的`{}`", tok.text)).with_span(tok.span)),
+            None => Ok(()),
+        }
+    }
+
+    fn read_type(&mut self) -> Result<SymbolType, Diagnostic> {
+        let tok = self.next("类型")?;
+        let width = match (tok.kind, tok.text) {
+            (TokKind::Word, "void") => SymbolWidth::Void,
+            (TokKind::Word, "i1") => SymbolWidth::Bool,
+            (TokKind::Word, "i8") => SymbolWidth::I8,
+            (TokKind::Word, "i32") => SymbolWidth::I32,
+            (TokKind::Word, "i64") => SymbolWidth::I64,
+            (TokKind::Word, "float") => SymbolWidth::Float,
+            (TokKind::Punct, "[") => {
+                let len = self.kind(TokKind::Word, "数组长度")?;
+                let len = len.text.parse::<i32>().ok()
+                    .filter(|len| *len >= 0)
+                    .ok_or_else(|| expected(&len, "数组长度"))?;
+                self.expect("x")?;
+                let elem = self.read_type()?;
+                self.expect("]")?;
+                array_of(len, elem).width
+            },
+            _ => return Err(expected(&tok, "类型")),
+        };
+        let mut ty = SymbolType::new(width, false);
+        while self.eat("*") {
+            ty = pointer_to(&ty);
+        }
+        Ok(ty)
+    }
+
+    /// 读取指针类型，返回其指向的类型
+    fn read_pointer_type(&mut self) -> Result<SymbolType, Diagnostic> {
+        let lo = self.peek().map_or(self.end, |tok| tok.span.lo);
+        let ty = self.read_type()?;
+        match &ty.width {
+            SymbolWidth::Arr { dims, .. } if dims[0] == -1 => Ok(element_of(&ty)),
+            _ => {
+                let hi = self.toks[self.pos - 1].span.hi;
+                Err(Diagnostic::error(format!("应为指针类型，实际为`{}`", ty.get_typename())).with_span(Span::new(lo, hi)))
+            },
+        }
+    }
+
+    /// 可选的`, align n`，缺省为4
+    fn read_align(&mut self) -> Result<String, Diagnostic> {
+        if !self.eat(",") {
+            return Ok(String::from("4"));
+        }
+        self.expect("align")?;
+        let align = self.kind(TokKind::Word, "对齐值")?;
+        if !is_decimal(align.text) {
+            return Err(expected(&align, "对齐值"));
+        }
+        Ok(align.text.to_string())
+    }
+}
+
+/// 以elem为元素、长度为len的数组
+fn array_of(len: i32, elem: SymbolType) -> SymbolType {
+    match elem.width {
+        SymbolWidth::Arr { tar, mut dims } if dims[0] != -1 => {
+            dims.insert(0, len);
+            SymbolType::new(SymbolWidth::Arr { tar, dims }, false)
+        },
+        _ => SymbolType::new(SymbolWidth::Arr { tar: Box::new(elem), dims: vec![len] }, false),
+    }
+}
+
+/// 解析常量字面量，浮点数统一为IEEE754 double的十六进制表示
+fn literal(tok: &Token, ty: &SymbolType) -> Result<Operand, Diagnostic> {
+    let operand = match (tok.text, &ty.width) {
+        ("true", _) => Some(Operand::ConstInt(1)),
+        ("false", _) => Some(Operand::ConstInt(0)),
+        ("null", _) => Some(Operand::Null),
+        (text, SymbolWidth::Float) if is_float_immediate(text) => Some(Operand::ConstFloat(parse_float(text))),
+        (text, SymbolWidth::Float) => text.parse::<f32>().ok().map(Operand::ConstFloat),
+        (text, _) => text.parse::<i64>().ok().map(Operand::ConstInt),
+    };
+    operand.ok_or_else(|| expected(tok, &format!("{}类型的常量", ty.get_typename())))
+}
+
+struct Reader {
+    program: LLVMProgram,
+    /// 当前函数中各基本块第一次被引用的位置，用于报告未定义的基本块
+    block_refs: HashMap<BlockId, Span>,
+}
+
+impl Reader {
+    fn curr_func(&mut self) -> &mut FuncDef {
+        self.program.func_def.last_mut().unwrap()
+    }
+
+    /// `@name = global type init, align n`
+    fn read_global(&mut self, cur: &mut Cursor) -> Result<(), Diagnostic> {
+        let name = cur.kind(TokKind::Global, "全局变量")?;
+        cur.expect("=")?;
+        if !cur.eat("global") {
+            cur.expect("constant")?;
+        }
+        let var_type = cur.read_type()?;
+        let mut init_values = vec![];
+        if !cur.eat("zeroinitializer") {
+            read_const(cur, &var_type, &mut init_values)?;
+        }
+        cur.read_align()?;
+        cur.finish()?;
+        self.program.global_var.push(GlobalVar {
+            var_name: name.text[1..].to_string(),
+            var_type,
+            init_values,
+        });
+        Ok(())
+    }
+
+    /// `declare type @name(type noundef, ...)`
+    fn read_decl(&mut self, cur: &mut Cursor) -> Result<(), Diagnostic> {
+        cur.expect("declare")?;
+        let func_type = cur.read_type()?;
+        let name = cur.kind(TokKind::Global, "函数名")?;
+        cur.expect("(")?;
+        let mut param_types = vec![];
+        while !cur.eat(")") {
+            if !param_types.is_empty() {
+                cur.expect(",")?;
+            }
+            param_types.push(cur.read_type()?);
+            cur.eat("noundef");
+        }
+        cur.finish()?;
+        self.program.func_decl.push(FuncDecl {
+            func_name: name.text[1..].to_string(),
+            func_type,
+            param_types,
+        });
+        Ok(())
+    }
+
+    /// 读入从start行开始的函数定义，返回函数之后的行号
+    fn read_func(&mut self, lines: &[Line], start: usize) -> Result<usize, Diagnostic> {
+        let mut cur = Cursor::new(&lines[start]);
+        cur.expect("define")?;
+        let func_type = cur.read_type()?;
+        let name = cur.kind(TokKind::Global, "函数名")?;
+        cur.expect("(")?;
+        let mut params = vec![];
+        while !cur.eat(")") {
+            if !params.is_empty() {
+                cur.expect(",")?;
+            }
+            let param_type = cur.read_type()?;
+            cur.eat("noundef");
+            let param_name = cur.kind(TokKind::Local, "参数名")?;
+            params.push(Param { param_name: param_name.text.to_string(), param_type });
+        }
+        cur.expect("{")?;
+        cur.finish()?;
+
+        // 函数体到单独一行的`}`为止
+        let end = (start + 1..lines.len())
+            .find(|&idx| lines[idx].toks[0].text == "}")
+            .ok_or_else(|| Diagnostic::error("函数缺少`}`").with_span(name.span))?;
+        Cursor { toks: &lines[end].toks[1..], pos: 0, end: lines[end].end }.finish()?;
+        let body = &lines[start + 1..end];
+
+        self.program.names = NameTable::default();
+        self.block_refs.clear();
+        for (i, param) in params.iter().enumerate() {
+            if self.program.names.values.insert(param.param_name.clone(), Operand::Arg(i)).is_some() {
+                return Err(Diagnostic::error(format!("重复定义的参数`{}`", param.param_name)).with_span(name.span));
+            }
+        }
+        self.program.func_def.push(FuncDef::new(name.text, &func_type, params));
+
+        // 第一遍为每条指令的结果预先分配编号，使操作数可以引用之后定义的值
+        let mut cnt = 0;
+        for line in body.iter() {
+            match line.toks.as_slice() {
+                [label, Token { text: ":", .. }] => {
+                    self.program.make_block_id(label.text);
+                },
+                [res, Token { text: "=", .. }, ..] if res.kind == TokKind::Local => {
+                    let value = Operand::Value(ValueId(cnt));
+                    if self.program.names.values.insert(res.text.to_string(), value).is_some() {
+                        return Err(Diagnostic::error(format!("重复定义的值`{}`", res.text)).with_span(res.span));
+                    }
+                    cnt += 1;
+                },
+                _ => cnt += 1,
+            }
+        }
+
+        // 第二遍按顺序创建基本块与指令
+        let mut curr_bb: Option<BlockId> = None;
+        let mut defined = HashSet::new();
+        for line in body.iter() {
+            let mut cur = Cursor::new(line);
+            if let [label, Token { text: ":", .. }] = line.toks.as_slice() {
+                let bb = self.program.make_block_id(label.text);
+                if !defined.insert(bb) {
+                    return Err(Diagnostic::error(format!("重复定义的基本块`{}`", label.text)).with_span(label.span));
+                }
+                self.curr_func().layout.push(bb);
+                curr_bb = Some(bb);
+                continue;
+            }
+            let first = cur.toks[0];
+            let bb = match curr_bb {
+                Some(bb) if self.curr_func().block(bb).ter_ins.is_none() => bb,
+                Some(_) => return Err(Diagnostic::error("终结指令之后应为新的基本块标签").with_span(first.span)),
+                None => return Err(Diagnostic::error("函数体应以基本块标签开始").with_span(first.span)),
+            };
+            let res = match cur.toks {
+                [res, Token { text: "=", .. }, ..] if res.kind == TokKind::Local => {
+                    cur.pos = 2;
+                    Some(*res)
+                },
+                _ => None,
+            };
+            let instr = self.read_instr(&mut cur)?;
+            cur.finish()?;
+            match (&res, instr.has_result()) {
+                (Some(res), false) => return Err(Diagnostic::error("该指令没有结果").with_span(res.span)),
+                (None, true) => return Err(Diagnostic::error("缺少指令结果的名称").with_span(first.span)),
+                _ => {},
+            }
+            // 数字标号的结果不保留名称，打印时重新编号
+            let name = res.map(|res| res.text.to_string()).filter(|name| !is_num_label(name));
+            self.curr_func().push_value_unlinked(bb, instr, name);
+        }
+
+        if let Some((&id, &span)) = self.block_refs.iter().find(|(id, _)| !defined.contains(id)) {
+            let label = &self.curr_func().block(id).block_label;
+            return Err(Diagnostic::error(format!("未定义的基本块`{}`", label)).with_span(span));
+        }
+        if defined.is_empty() {
+            return Err(Diagnostic::error("函数没有基本块").with_span(name.span));
+        }
+        self.curr_func().link_all_uses();
+        Ok(end + 1)
+    }
+
+    /// `label %name`
+    fn read_label(&mut self, cur: &mut Cursor) -> Result<BlockId, Diagnostic> {
+        cur.expect("label")?;
+        let label = cur.kind(TokKind::Local, "基本块")?;
+        let bb = self.program.make_block_id(&label.text[1..]);
+        self.block_refs.entry(bb).or_insert(label.span);
+        Ok(bb)
+    }
+
+    fn read_operand(&self, cur: &mut Cursor, ty: &SymbolType) -> Result<Operand, Diagnostic> {
+        let tok = cur.next("操作数")?;
+        match tok.kind {
+            TokKind::Local => self.program.names.values.get(tok.text).cloned()
+                .ok_or_else(|| Diagnostic::error(format!("未定义的值`{}`", tok.text)).with_span(tok.span)),
+            TokKind::Global => Ok(Operand::Global(tok.text[1..].to_string())),
+            TokKind::Word => literal(&tok, ty),
+            TokKind::Punct => Err(expected(&tok, "操作数")),
+        }
+    }
+
+    /// `type op1, op2`
+    fn read_bin_op(&self, cur: &mut Cursor) -> Result<BinaryOp, Diagnostic> {
+        let op_type = cur.read_type()?;
+        let op1 = self.read_operand(cur, &op_type)?;
+        cur.expect(",")?;
+        let op2 = self.read_operand(cur, &op_type)?;
+        Ok(BinaryOp { op_type, op1, op2 })
+    }
+
+    /// `type1 val to type2`
+    fn read_cast_op(&self, cur: &mut Cursor) -> Result<CastOp, Diagnostic> {
+        let type_1 = cur.read_type()?;
+        let val = self.read_operand(cur, &type_1)?;
+        cur.expect("to")?;
+        let type_2 = cur.read_type()?;
+        Ok(CastOp { type_1, type_2, val })
+    }
+
+    /// 读入`%res =`之后的指令
+    fn read_instr(&mut self, cur: &mut Cursor) -> Result<Instruction, Diagnostic> {
+        let op = cur.kind(TokKind::Word, "指令")?;
+        let instr = match op.text {
+            "add" | "sub" | "mul" | "sdiv" | "srem" => {
+                // 忽略无符号与有符号溢出标记
+                while cur.eat("nsw") || cur.eat("nuw") {}
+                let bin_op = self.read_bin_op(cur)?;
+                match op.text {
+                    "add" => Instruction::Add(bin_op),
+                    "sub" => Instruction::Sub(bin_op),
+                    "mul" => Instruction::Mul(bin_op),
+                    "sdiv" => Instruction::Sdiv(bin_op),
+                    _ => Instruction::Srem(bin_op),
+                }
+            },
+            "fadd" => Instruction::Fadd(self.read_bin_op(cur)?),
+            "fsub" => Instruction::Fsub(self.read_bin_op(cur)?),
+            "fmul" => Instruction::Fmul(self.read_bin_op(cur)?),
+            "fdiv" => Instruction::Fdiv(self.read_bin_op(cur)?),
+            "icmp" | "fcmp" => {
+                let conds: &[&str] = if op.text == "icmp" {
+                    &["eq", "ne", "slt", "sgt", "sle", "sge"]
+                } else {
+                    &["oeq", "one", "olt", "ogt", "ole", "oge"]
+                };
+                let cond = cur.kind(TokKind::Word, "比较条件")?;
+                if !conds.contains(&cond.text) {
+                    return Err(expected(&cond, &format!("比较条件{}", conds.join("、"))));
+                }
+                let bin_op = self.read_bin_op(cur)?;
+                if op.text == "icmp" {
+                    Instruction::Cmp(cond.text.to_string(), bin_op)
+                } else {
+                    Instruction::Fcmp(cond.text.to_string(), bin_op)
+                }
+            },
+            "zext" => Instruction::ZeroExt(self.read_cast_op(cur)?),
+            "sitofp" => Instruction::I32ToFloat(self.read_cast_op(cur)?),
+            "fptosi" => Instruction::FloatToI32(self.read_cast_op(cur)?),
+            "phi" => {
+                let ty = cur.read_type()?;
+                let mut candidates = vec![];
+                loop {
+                    cur.expect("[")?;
+                    let value = self.read_operand(cur, &ty)?;
+                    cur.expect(",")?;
+                    let label = cur.kind(TokKind::Local, "基本块")?;
+                    let bb = self.program.make_block_id(&label.text[1..]);
+                    self.block_refs.entry(bb).or_insert(label.span);
+                    cur.expect("]")?;
+                    candidates.push((value, bb));
+                    if !cur.eat(",") {
+                        break;
+                    }
+                }
+                Instruction::Phi(ty, candidates)
+            },
+            "alloca" => {
+                let ty = cur.read_type()?;
+                let len = cur.read_align()?;
+                Instruction::Alloca { ty, len }
+            },
+            "store" => {
+                let ty = cur.read_type()?;
+                let value = self.read_operand(cur, &ty)?;
+                cur.expect(",")?;
+                let ptr_type = cur.read_pointer_type()?;
+                let ptr = self.read_operand(cur, &pointer_to(&ptr_type))?;
+                let len = cur.read_align()?;
+                Instruction::Store { ty, value, ptr, len }
+            },
+            "load" => {
+                let ty = cur.read_type()?;
+                cur.expect(",")?;
+                let ptr_type = cur.read_pointer_type()?;
+                let ptr = self.read_operand(cur, &pointer_to(&ptr_type))?;
+                let len = cur.read_align()?;
+                Instruction::Load { ty, ptr, len }
+            },
+            "call" => {
+                let ty = cur.read_type()?;
+                let func = cur.kind(TokKind::Global, "函数名")?;
+                cur.expect("(")?;
+                let mut params = vec![];
+                while !cur.eat(")") {
+                    if !params.is_empty() {
+                        cur.expect(",")?;
+                    }
+                    let param_type = cur.read_type()?;
+                    cur.eat("noundef");
+                    params.push((self.read_operand(cur, &param_type)?, param_type));
+                }
+                Instruction::Call(func.text.to_string(), ty, params)
+            },
+            "getelementptr" => {
+                cur.eat("inbounds");
+                let mut ty = cur.read_type()?;
+                // 指针形参上的GetElemPtr以没有维度的数组表示元素类型
+                if !matches!(ty.width, SymbolWidth::Arr { .. }) {
+                    ty = SymbolType::new(SymbolWidth::Arr { tar: Box::new(ty), dims: vec![] }, false);
+                }
+                cur.expect(",")?;
+                let ptr_type = cur.read_pointer_type()?;
+                let ptr = self.read_operand(cur, &pointer_to(&ptr_type))?;
+                let mut idx = vec![];
+                while cur.eat(",") {
+                    let idx_type = cur.read_type()?;
+                    idx.push(self.read_operand(cur, &idx_type)?);
+                }
+                Instruction::GetElemPtr(ty, ptr, idx)
+            },
+            "bitcast" => {
+                let ty = cur.read_pointer_type()?;
+                let val = self.read_operand(cur, &pointer_to(&ty))?;
+                cur.expect("to")?;
+                let ty2 = cur.read_pointer_type()?;
+                Instruction::BitCast(ty, val, ty2)
+            },
+            "ret" => {
+                let ty = cur.read_type()?;
+                if ty.width == SymbolWidth::Void {
+                    Instruction::Ret(ty, None)
+                } else {
+                    let val = self.read_operand(cur, &ty)?;
+                    Instruction::Ret(ty, Some(val))
+                }
+            },
+            "br" => {
+                if cur.peek().map_or(false, |tok| tok.text == "label") {
+                    Instruction::Br(None, self.read_label(cur)?, None)
+                } else {
+                    let ty = cur.read_type()?;
+                    let cond = self.read_operand(cur, &ty)?;
+                    cur.expect(",")?;
+                    let label1 = self.read_label(cur)?;
+                    cur.expect(",")?;
+                    let label2 = self.read_label(cur)?;
+                    Instruction::Br(Some(cond), label1, Some(label2))
+                }
+            },
+            _ => return Err(Diagnostic::error(format!("未知的指令`{}`", op.text)).with_span(op.span)),
+        };
+        Ok(instr)
+    }
+}
+
+/// 读入类型为ty的常量初始值，数组按行优先展开到values中
+fn read_const(cur: &mut Cursor, ty: &SymbolType, values: &mut Vec<String>) -> Result<(), Diagnostic> {
+    match &ty.width {
+        SymbolWidth::Arr { dims, .. } if dims[0] != -1 => {
+            if cur.eat("zeroinitializer") {
+                let len = dims.iter().product::<i32>() as usize;
+                values.extend(std::iter::repeat(String::from("0")).take(len));
+                return Ok(());
+            }
+            let elem = element_of(ty);
+            cur.expect("[")?;
+            for i in 0..dims[0] {
+                if i != 0 {
+                    cur.expect(",")?;
+                }
+                cur.read_type()?;
+                read_const(cur, &elem, values)?;
+            }
+            cur.expect("]")
+        },
+        _ => {
+            let tok = cur.kind(TokKind::Word, "常量")?;
+            let value = match literal(&tok, ty)? {
+                // 全局变量的浮点初始值以IEEE754 double的十六进制保存
+                Operand::ConstFloat(num) => format_double(num),
+                Operand::ConstInt(num) => num.to_string(),
+                _ => return Err(expected(&tok, "常量")),
+            };
+            values.push(value);
+            Ok(())
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::OptLevel;
+    use crate::session::Session;
+    use crate::structures::writetext_trait::WriteText;
+
+    const SOURCE: &str = "\
+const float PI = 3.14;
+int g = 3;
+int arr[2][3] = {{1, 2}, {3}};
+float f(float x, int a[]) {
+    return x * PI + a[1];
+}
+int main() {
+    int i = 0, s = 0;
+    float b[4] = {};
+    while (i < 10) {
+        if (i % 2 == 0 && i != 4) s = s + arr[1][0];
+        else s = s - g;
+        i = i + 1;
+    }
+    b[1] = f(1.5, arr[0]);
+    putfloat(b[1]);
+    return s;
+}
+";
+
+    fn text(llvm: &LLVMProgram) -> String {
+        let mut output = vec![];
+        llvm.writetext(&mut output);
+        String::from_utf8(output).unwrap()
+    }
+
+    fn strip_comments(text: &str) -> Vec<&str> {
+        text.lines().filter(|line| !line.is_empty() && !line.starts_with(';')).collect()
+    }
+
+    /// 读入出错时的错误信息与出错位置的文本
+    fn error(text: &str) -> (String, String) {
+        let diagnostic = read_llvm(text).err().expect("应当读入失败");
+        let span = diagnostic.span.unwrap();
+        (diagnostic.message, text[span.lo..span.hi].to_string())
+    }
+
+    #[test]
+    fn round_trip() {
+        for opt_level in [OptLevel::O0, OptLevel::O1, OptLevel::O2] {
+            let llvm = Session::new(opt_level).compile_to_llvm(SOURCE).unwrap();
+            let written = text(&llvm);
+            let read = read_llvm(&written).unwrap();
+            assert_eq!(read.verify(), Ok(()), "{:?}", opt_level);
+            // 注释不会被读入，比较时与空行一起去掉
+            assert_eq!(strip_comments(&text(&read)), strip_comments(&written), "{:?}", opt_level);
+        }
+    }
+
+    #[test]
+    fn read_minimal_function() {
+        let llvm = read_llvm("\
+declare void @putint(i32 noundef)
+
+define i32 @main() {
+entry:
+  %x = add i32 1, 2  ; 注释
+  call void @putint(i32 noundef %x)
+  ret i32 %x
+}
+").unwrap();
+        assert_eq!(llvm.func_decl.len(), 1);
+        assert_eq!(llvm.func_def.len(), 1);
+        assert_eq!(llvm.func_def[0].func_name, "@main");
+        assert_eq!(llvm.verify(), Ok(()));
+        assert_eq!(llvm.interpret(b"").unwrap().stdout, b"3");
+    }
+
+    #[test]
+    fn errors() {
+        let func = |body: &str| format!("define i32 @main() {{\nentry:\n{}\n}}\n", body);
+        assert_eq!(error("@g = global i32 3 #"), (String::from("无法识别的字符`#`"), String::from("#")));
+        assert_eq!(error("ret i32 0"), (String::from("应为全局变量、declare或define，实际为`ret`"), String::from("ret")));
+        assert_eq!(error(&func("  %x = frob i32 1, 2\n  ret i32 %x")), (String::from("未知的指令`frob`"), String::from("frob")));
+        assert_eq!(error(&func("  ret i32 %y")), (String::from("未定义的值`%y`"), String::from("%y")));
+        assert_eq!(error(&func("  br label %nowhere")).0, "未定义的基本块`nowhere`");
+        assert_eq!(error(&func("  ret i32 0 0")), (String::from("多余的`0`"), String::from("0")));
+        assert_eq!(error(&func("  %x = add i32 1,")).0, "应为操作数，但该行已结束");
+        assert_eq!(error("define i32 @main() {\nentry:\n  ret i32 0\n").0, "函数缺少`}`");
+    }
+}
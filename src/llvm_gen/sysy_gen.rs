@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 use crate::ast::*;
-use crate::structures::llvm_struct::{LLVMProgram, Instruction, InstructionType};
+use crate::structures::llvm_struct::{LLVMProgram, InstructionType};
 use crate::structures::symbol::*;
 use crate::structures::scopes::*;
 use crate::llvm_gen::type_utils::*;
@@ -26,137 +26,25 @@ impl Generate for SysY {
         scopes: &mut Scopes,
         labels: &mut Labels
     ) -> Self::Out {
+        for (name, ret_ty, param_tys) in sylib_functions() {
+            program.push_func_decl(&ret_ty, name, param_tys.iter().collect());
+            scopes.push(
+                labels,
+                name,
+                &ret_ty,
+                &SymbolVal::Func(ret_ty.clone(), param_tys),
+                None
+            );
+        }
+
         let ty_void = SymbolType::new(SymbolWidth::Void, false);
-        let ty_i32 = SymbolType::new(SymbolWidth::I32, false);
-        let ty_i32_clone = ty_i32.clone();
-        let ty_i32_ptr = SymbolType::new(
-            SymbolWidth::Arr{tar: Box::new(ty_i32_clone),dims: vec!(-1)},
-            false
-        );
         let ty_i8 = SymbolType::new(SymbolWidth::I8, false);
-        let ty_i8_clone = ty_i8.clone();
         let ty_i8_ptr = SymbolType::new(
-            SymbolWidth::Arr{tar: Box::new(ty_i8_clone), dims: vec!(-1)},
+            SymbolWidth::Arr{tar: Box::new(ty_i8.clone()), dims: vec!(-1)},
             false
         );
         let ty_i64 = SymbolType::new(SymbolWidth::I64, false);
         let ty_i1 = SymbolType::new(SymbolWidth::Bool, false);
-        let ty_float = SymbolType::new(SymbolWidth::Float, false);
-        let ty_float_clone = ty_float.clone();
-        let ty_float_ptr = SymbolType::new(
-            SymbolWidth::Arr{tar: Box::new(ty_float_clone),dims: vec!(-1)},
-            false
-        );
-
-        program.push_func_decl(&ty_i32, "getint", vec!());
-        scopes.push(
-            labels,
-            "getint",
-            &ty_i32,
-            &SymbolVal::Func(ty_i32.clone(), vec!()),
-            None
-        );
-
-        program.push_func_decl(&ty_i32, "getch", vec!());
-        scopes.push(
-            labels,
-            "getch",
-            &ty_i32,
-            &SymbolVal::Func(ty_i32.clone(), vec!()),
-            None
-        );
-
-        program.push_func_decl(&ty_i32, "getarray", vec!(&ty_i32_ptr));
-        scopes.push(
-            labels,
-            "getarray",
-            &ty_i32,
-            &SymbolVal::Func(ty_i32.clone(),vec!(ty_i32_ptr.clone())),
-            None
-        );
-
-        program.push_func_decl(&ty_float, "getfloat", vec!());
-        scopes.push(
-            labels,
-            "getfloat",
-            &ty_float,
-            &SymbolVal::Func(ty_float.clone(), vec!()),
-            None
-        );
-
-        program.push_func_decl(&ty_i32, "getfarray", vec!(&ty_float_ptr));
-        scopes.push(
-            labels,
-            "getfarray",
-            &ty_i32,
-            &SymbolVal::Func(ty_i32.clone(), vec!(ty_float_ptr.clone())),
-            None
-        );
-
-        program.push_func_decl(&ty_void, "putint", vec!(&ty_i32));                              
-        scopes.push(
-            labels,
-            "putint",
-            &ty_void,
-            &SymbolVal::Func(ty_void.clone(), vec!(ty_i32.clone())),
-            None
-        );
-
-        program.push_func_decl(&ty_void, "putch", vec!(&ty_i32));                              
-        scopes.push(
-            labels,
-            "putch",
-            &ty_void,
-            &SymbolVal::Func(ty_void.clone(), vec!(ty_i32.clone())),
-            None
-        );
-
-        program.push_func_decl(&ty_void, "putarray", vec!(&ty_i32, &ty_i32_ptr));
-        scopes.push(
-            labels,
-            "putarray",
-            &ty_void,
-            &SymbolVal::Func(ty_void.clone(),
-            vec!(ty_i32.clone(), ty_i32_ptr.clone())),
-            None
-        );
-
-        program.push_func_decl(&ty_void, "putfloat", vec!(&ty_float));                              
-        scopes.push(
-            labels,
-            "putfloat",
-            &ty_void,
-            &SymbolVal::Func(ty_void.clone(), vec!(ty_float.clone())),
-            None
-        );
-
-        program.push_func_decl(&ty_void, "putfarray", vec!(&ty_i32, &ty_float_ptr));
-        scopes.push(
-            labels,
-            "putfarray",
-            &ty_void,
-            &SymbolVal::Func(ty_void.clone(), vec!(ty_i32.clone(), ty_float_ptr.clone())),
-            None
-        );
-
-        program.push_func_decl(&ty_void, "starttime", vec!());
-        scopes.push(
-            labels,
-            "starttime",
-            &ty_void,
-            &SymbolVal::Func(ty_void.clone(), vec!()),
-            None
-        );
-
-        program.push_func_decl(&ty_void, "stoptime", vec!());
-        scopes.push(
-            labels,
-            "stoptime",
-            &ty_void,
-            &SymbolVal::Func(ty_void.clone(), vec!()),
-            None
-        );
-
         program.push_func_decl(
             &ty_void,
             "llvm.memset.p0i8.i64",
@@ -243,10 +131,7 @@ impl Generate for FuncDef {
             ) {
                 let ty_vec = vec!(&i1_ty);
                 let str_vec = vec!(replace_phi.as_str(), "1");
-                program.insert_alloc(
-                    Instruction::make_instruction(InstructionType::Alloca, str_vec, ty_vec),
-                    "_entry"
-                );
+                program.insert_alloc(str_vec, ty_vec);
             } else {
                 panic!("alloc replace_phi failed");
             }
@@ -260,23 +145,21 @@ impl Generate for FuncDef {
                 ) {
                     let str_vec = vec!(label.as_str(), "4");
                     let type_vec = vec!(ty);
-                    program.insert_alloc(
-                        Instruction::make_instruction(InstructionType::Alloca, str_vec, type_vec),
-                        "_entry"
-                    );
+                    program.insert_alloc(str_vec, type_vec);
 
                     let str_vec = vec!(last_label.as_str(), label.as_str(), "4");
                     let type_vec = vec!(ty);
                     program.push_instr(InstructionType::Store, str_vec, type_vec);
                 } else {
-                    panic!("Global or local scope error");
+                    unreachable!("参数{}重复定义，应在语义分析中报错", id);
                 }
             }
             self.block.generate(program, scopes, labels);
+            program.end_func();
             scopes.scope_vec.pop();
             labels.clear();
         } else {
-            panic!("Multi definition of {}", self.id);
+            unreachable!("函数{}重复定义，应在语义分析中报错", self.id);
         }
     }
 }
@@ -307,8 +190,7 @@ impl Generate for FuncFParam {
                 match index {
                     Index::Exp(exp) => {
                         let (_, val) = exp.generate(program, scopes, labels);
-                        let dim: i32 = val.parse().expect(&format!("{} is not integer", val));
-                        dims.push(dim);
+                        dims.push(val.parse().expect("数组维度已在语义分析中检查为整型常量"));
                     },
                     Index::Ptr(dim) => dims.push(dim.clone()),
                 }
@@ -389,7 +271,7 @@ impl Generate for Stmt {
                 program.push_bb(ret_then.as_str(), scopes);
                 
             }
-            Stmt::Break => {
+            Stmt::Break(_) => {
                 if let Some(end) = scopes.get_while_end() {
                     let str_vec = vec!("", end.as_str(), "");
                     let ty_vec = vec!();
@@ -399,10 +281,10 @@ impl Generate for Stmt {
                     program.push_bb(break_then.as_str(), scopes);
                     
                 } else {
-                    panic!("Break appears in non-while scope");
+                    unreachable!("break不在循环中，应在语义分析中报错");
                 }
             },
-            Stmt::Continue => {
+            Stmt::Continue(_) => {
                 if let Some(entry) = scopes.get_while_entry() {
                     let str_vec = vec!("", entry.as_str(), "");
                     let ty_vec = vec!();
@@ -412,7 +294,7 @@ impl Generate for Stmt {
                     program.push_bb(continue_then.as_str(), scopes);
                     
                 } else {
-                    panic!("Continue appears in non-while scope");
+                    unreachable!("continue不在循环中，应在语义分析中报错");
                 }
             },
             Stmt::If{exp, stmt1, stmt2} => {
@@ -3,6 +3,7 @@ use crate::structures::symbol::*;
 use crate::structures::llvm_struct::*;
 use crate::structures::writetext_trait::*;
 use crate::utils::globalvar::*;
+use crate::utils::float::format_double;
 
 impl WriteText for LLVMProgram {
     //递归遍历解析LLVM结构体
@@ -89,40 +90,35 @@ impl WriteText for FuncDef {
         });
 
         write!(output, ") {{\n").unwrap();
-        
-        let blocks = &self.blocks;
 
-        blocks.iter().enumerate().for_each(|(i, block)| {
-            //获取局部变量
-            let localvars = match i {
-                0 => Some(&self.local_vars),
-                _ => None,
-            };
+        let names = self.value_names();
+        let layout = &self.layout;
+
+        layout.iter().enumerate().for_each(|(i, &bb)| {
+            let block = self.block(bb);
+            //局部变量打印在入口块
+            let with_localvars = i == 0;
             match block.ter_ins {
                 //若有终结指令，则调用block的writetext方法
                 Some(_) => {
-                    block.writetext(output, localvars);
-                    if i != blocks.len() - 1 {
+                    block.writetext(output, self, &names, with_localvars);
+                    if i != layout.len() - 1 {
                         write!(output, "\n").unwrap();
                     }
                 },
                 None => {
-                    //如果块中没有终结指令但是有指令，则语法错误
-                    if block.nor_ins.len() != 0 {
-                        panic!();
-                    }
                     // 如果返回语句后为空块，则直接跳过不写入
-                    if block.block_label.contains("ret_then") {                         
+                    if block.block_label.contains("ret_then") && block.nor_ins.is_empty() {
                         return
                     }
                     // 如果没有返回值，则直接 ret void
                     else if self.func_type.width == SymbolWidth::Void {
-                        block.writetext(output, localvars);
+                        block.writetext(output, self, &names, with_localvars);
                         write!(output, "  ret void\n").unwrap();
                     }
                     // 正常的函数
                     else {
-                        block.writetext(output, localvars);
+                        block.writetext(output, self, &names, with_localvars);
                         if self.func_type.width == SymbolWidth::I32 {
                             write!(output, "  ret i32 0\n").unwrap();
                         } else if self.func_type.width == SymbolWidth::Float {
@@ -139,6 +135,48 @@ impl WriteText for FuncDef {
     }
 }
 
+impl FuncDef {
+    /// 打印时各值的名称，以ValueId为下标
+    /// 未命名的值按打印顺序从0开始编号，与LLVM对匿名值的要求一致
+    pub fn value_names(&self) -> Vec<String> {
+        let mut names = vec![String::new(); self.values.len()];
+        let mut cnt = 0;
+        for id in self.all_instrs() {
+            let value = &self.values[id.0];
+            if !value.instr.has_result() {
+                continue;
+            }
+            names[id.0] = match &value.name {
+                Some(name) => name.clone(),
+                None => {
+                    cnt += 1;
+                    format!("%{}", cnt - 1)
+                }
+            };
+        }
+        names
+    }
+
+    /// 操作数的文本
+    pub fn operand_text(&self, op: &Operand, names: &[String]) -> String {
+        match op {
+            Operand::ConstInt(num) => num.to_string(),
+            Operand::ConstFloat(num) => format_double(*num),
+            Operand::Null => String::from("null"),
+            Operand::Global(name) => format!("@{}", name),
+            Operand::Arg(idx) => self.params[*idx].param_name.clone(),
+            Operand::Value(id) => names[id.0].clone(),
+        }
+    }
+
+    /// 单条指令的文本，不含缩进与换行，用于报错
+    pub fn instr_text(&self, id: ValueId, names: &[String]) -> String {
+        let mut output: Vec<u8> = vec![];
+        self.instr(id).writetext(&mut output, self, names, &names[id.0]);
+        String::from_utf8(output).unwrap().trim().to_string()
+    }
+}
+
 impl WriteText for Param {
     fn writetext(&self, output: &mut impl Write) {
         write!(output, "{} noundef {}", self.param_type.get_typename(), self.param_name).unwrap();
@@ -146,114 +184,117 @@ impl WriteText for Param {
 }
 
 impl Block {
-    fn writetext(&self, output: &mut impl Write, localvars: Option<&Vec<LocalVar>>) {
+    fn writetext(&self, output: &mut impl Write, func: &FuncDef, names: &[String], with_localvars: bool) {
         write!(output, "{}:\n", self.block_label).unwrap();
 
         //如果需要在块首部
-        if let Some(instrs) = localvars {
-            for alloc in instrs.iter() {
-                alloc.ins.writetext(output);
+        if with_localvars {
+            for &alloc in func.local_vars.iter() {
+                func.instr(alloc).writetext(output, func, names, &names[alloc.0]);
             }
-            if !instrs.is_empty() {
+            if !func.local_vars.is_empty() {
                 write!(output, "\n").unwrap();
             }
         }
 
-        for instr in self.nor_ins.iter() {
-            instr.writetext(output);
-        }
-        match &self.ter_ins {
-            Some(ter) => ter.writetext(output),
-            None => {},
+        for &instr in self.phi_ins.iter().chain(self.nor_ins.iter()).chain(self.ter_ins.iter()) {
+            func.instr(instr).writetext(output, func, names, &names[instr.0]);
         }
     }
 }
 
-impl WriteText for Instruction {
+impl Instruction {
     //对于各个指令的writetext，具体需要调用每种指令的writetext
-    fn writetext(&self, output: &mut impl Write) {
+    fn writetext(&self, output: &mut impl Write, func: &FuncDef, names: &[String], res: &str) {
+        let text = |op: &Operand| func.operand_text(op, names);
+        let label = |bb: &BlockId| func.block(*bb).block_label.clone();
         //1、对于二元指令，首先输出每种具体的指令头，例如 result = 'op', 然后调用公共的writetext函数
-        fn bin_op_write(output: &mut impl Write, bin_op: &BinaryOp, name: &str) {
-            write!(output, "  {} = {} ", bin_op.res, name).unwrap();
-            bin_op.writetext(output);
+        fn bin_op_write(output: &mut impl Write, bin_op: &BinaryOp, name: &str, res: &str, text: &impl Fn(&Operand) -> String) {
+            write!(output, "  {} = {} ", res, name).unwrap();
+            bin_op.writetext(output, text);
         }
         match self {
             // 1、二元指令
-            Instruction::Add(bin_op) => bin_op_write(output, bin_op, "add"),
-            Instruction::Sub(bin_op) => bin_op_write(output, bin_op, "sub"),
-            Instruction::Mul(bin_op) => bin_op_write(output, bin_op, "mul"),
-            Instruction::Sdiv(bin_op) => bin_op_write(output, bin_op, "sdiv"),
-            Instruction::Srem(bin_op) => bin_op_write(output, bin_op, "srem"),
-            Instruction::Fadd(bin_op) => bin_op_write(output, bin_op, "fadd"),
-            Instruction::Fsub(bin_op) => bin_op_write(output, bin_op, "fsub"),
-            Instruction::Fmul(bin_op) => bin_op_write(output, bin_op, "fmul"),
-            Instruction::Fdiv(bin_op) => bin_op_write(output, bin_op, "fdiv"),
+            Instruction::Add(bin_op) => bin_op_write(output, bin_op, "add", res, &text),
+            Instruction::Sub(bin_op) => bin_op_write(output, bin_op, "sub", res, &text),
+            Instruction::Mul(bin_op) => bin_op_write(output, bin_op, "mul", res, &text),
+            Instruction::Sdiv(bin_op) => bin_op_write(output, bin_op, "sdiv", res, &text),
+            Instruction::Srem(bin_op) => bin_op_write(output, bin_op, "srem", res, &text),
+            Instruction::Fadd(bin_op) => bin_op_write(output, bin_op, "fadd", res, &text),
+            Instruction::Fsub(bin_op) => bin_op_write(output, bin_op, "fsub", res, &text),
+            Instruction::Fmul(bin_op) => bin_op_write(output, bin_op, "fmul", res, &text),
+            Instruction::Fdiv(bin_op) => bin_op_write(output, bin_op, "fdiv", res, &text),
             Instruction::Cmp(cond, bin_op) => {
-                write!(output, "  {} = icmp {} ", bin_op.res, cond).unwrap();
-                bin_op.writetext(output);
+                write!(output, "  {} = icmp {} ", res, cond).unwrap();
+                bin_op.writetext(output, &text);
             },
             Instruction::Fcmp(cond, bin_op) => {
-                write!(output, "  {} = fcmp {} ", bin_op.res, cond).unwrap();
-                bin_op.writetext(output);
+                write!(output, "  {} = fcmp {} ", res, cond).unwrap();
+                bin_op.writetext(output, &text);
             },
             Instruction::ZeroExt(conver_op) => {
-                write!(output, "  {} = zext ", conver_op.res).unwrap();   
-                conver_op.writetext(output);
+                write!(output, "  {} = zext ", res).unwrap();   
+                conver_op.writetext(output, &text);
             },
             Instruction::I32ToFloat(conver_op) => {
-                write!(output, "  {} = sitofp ", conver_op.res).unwrap();   
-                conver_op.writetext(output);
+                write!(output, "  {} = sitofp ", res).unwrap();   
+                conver_op.writetext(output, &text);
             },
             Instruction::FloatToI32(conver_op) => {
-                write!(output, "  {} = fptosi ", conver_op.res).unwrap();   
-                conver_op.writetext(output);
+                write!(output, "  {} = fptosi ", res).unwrap();   
+                conver_op.writetext(output, &text);
             },
-            Instruction::Phi(..) => {
-                todo!()
+            //phi指令格式为 res = phi type [值, %前驱块], ...
+            Instruction::Phi(ty, candidates) => {
+                let candidates = candidates.iter()
+                    .map(|(value, bb)| format!("[ {}, %{} ]", text(value), label(bb)))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                writeln!(output, "  {} = phi {} {}", res, ty.get_typename(), candidates).unwrap();
             },
             //2、alloca指令格式为 res = alloca type, align 对齐值
-            Instruction::Alloca{res, ty, len} => {
+            Instruction::Alloca{ty, len} => {
                 write!(output, "  {} = alloca {}, align {}\n", res, ty.get_typename(), len).unwrap();
             },
             //3、store和load两个访存指令，格式类似
             Instruction::Store{ty, value, ptr, len} => {
                 write!(output, 
                     "  store {} {}, {}* {}, align {}\n", 
-                    ty.get_typename(), value, ty.get_typename(), ptr, len
+                    ty.get_typename(), text(value), ty.get_typename(), text(ptr), len
                 ).unwrap();
             },
-            Instruction::Load{res, ty, ptr, len} => {
+            Instruction::Load{ty, ptr, len} => {
                 write!(
                     output, 
                     "  {} = load {}, {}* {}, align {}\n", 
-                    res, ty.get_typename(), ty.get_typename(), ptr, len
+                    res, ty.get_typename(), ty.get_typename(), text(ptr), len
                 ).unwrap();
             },
             //4、Call指令
-            Instruction::Call(res, label, ty, params) => {
+            Instruction::Call(func_label, ty, params) => {
                 let lf = if res.is_empty() {
                     format!("  ")
                 } else {
                     format!("  {} = ", res)
                 };
-                write!(output, "{}call {} {}(", lf, ty.get_typename(), label).unwrap();
+                write!(output, "{}call {} {}(", lf, ty.get_typename(), func_label).unwrap();
                 for (i, param) in params.iter().enumerate() {
                     if i != 0 {
                         write!(output, ", ").unwrap();
                     }
-                    write!(output, "{} noundef {}", param.1.get_typename(), param.0).unwrap();
+                    write!(output, "{} noundef {}", param.1.get_typename(), text(&param.0)).unwrap();
                 }
                 write!(output, ")\n").unwrap();
             },
             //5、用于访问数组，递归dump
-            Instruction::GetElemPtr(dst, ty, ptr, idx) => {
-                write!(output, "  {} = getelementptr inbounds {}, {}* {}", dst, ty.get_typename(), ty.get_typename(), ptr).unwrap();
-                idx.iter().for_each(|it| write!(output, ", i32 {}", it).unwrap());
+            Instruction::GetElemPtr(ty, ptr, idx) => {
+                write!(output, "  {} = getelementptr inbounds {}, {}* {}", res, ty.get_typename(), ty.get_typename(), text(ptr)).unwrap();
+                idx.iter().for_each(|it| write!(output, ", i32 {}", text(it)).unwrap());
                 write!(output, "\n").unwrap();
             },
             //6、类型转换
-            Instruction::BitCast(res, ty, val, ty2) => {
-                write!(output, "  {} = bitcast {}* {} to {}*\n", res, ty.get_typename(), val, ty2.get_typename()).unwrap();
+            Instruction::BitCast(ty, val, ty2) => {
+                write!(output, "  {} = bitcast {}* {} to {}*\n", res, ty.get_typename(), text(val), ty2.get_typename()).unwrap();
             },
             //7、注释
             Instruction::Comment(content) => {
@@ -262,32 +303,31 @@ impl WriteText for Instruction {
             //8、函数返回
             Instruction::Ret(ty, val) => {
                 match val {
-                    Some(v) => write!(output, "  ret {} {}\n", ty.get_typename(), v).unwrap(),
+                    Some(v) => write!(output, "  ret {} {}\n", ty.get_typename(), text(v)).unwrap(),
                     None => write!(output, "  ret {}\n", ty.get_typename()).unwrap(),
                 }
             },
             //9、分支
             Instruction::Br(cond, label1, label2) => {
                 match label2 {
-                    Some(label) => write!(output, "  br i1 {}, label %{}, label %{}\n", cond.as_ref().unwrap(), label1, label).unwrap(),
-                    None => write!(output, "  br label %{}\n", label1).unwrap(),
+                    Some(label2) => write!(output, "  br i1 {}, label %{}, label %{}\n", text(cond.as_ref().unwrap()), label(label1), label(label2)).unwrap(),
+                    None => write!(output, "  br label %{}\n", label(label1)).unwrap(),
                 }
             },
         }
     }
 }
 
-impl WriteText for BinaryOp {
+impl BinaryOp {
     //二元操作数指令
-    fn writetext(&self, output: &mut impl Write) {
-        write!(output, "{} {}, {}\n", self.op_type.get_typename(), self.op1, self.op2).unwrap();
+    fn writetext(&self, output: &mut impl Write, text: &impl Fn(&Operand) -> String) {
+        write!(output, "{} {}, {}\n", self.op_type.get_typename(), text(&self.op1), text(&self.op2)).unwrap();
     }
 }
 
-impl WriteText for CastOp {
+impl CastOp {
     //类型转换指令
-    fn writetext(&self, output: &mut impl Write) {
-        write!(output, "{} {} to {}\n", self.type_1.get_typename(), self.val, self.type_2.get_typename()).unwrap();
+    fn writetext(&self, output: &mut impl Write, text: &impl Fn(&Operand) -> String) {
+        write!(output, "{} {} to {}\n", self.type_1.get_typename(), text(&self.val), self.type_2.get_typename()).unwrap();
     }
 }
-
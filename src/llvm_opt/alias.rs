@@ -0,0 +1,256 @@
+use std::collections::HashSet;
+use crate::structures::llvm_struct::*;
+use crate::structures::symbol::*;
+
+/// 两个地址之间的别名关系
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AliasResult {
+    /// 一定不指向同一块内存
+    NoAlias,
+    /// 无法确定
+    MayAlias,
+    /// 一定指向同一块内存
+    MustAlias,
+}
+
+/// 地址分解为基址加偏移，偏移为各变量下标乘以字节步长之和，再加上常量字节偏移
+struct Decomposed {
+    base: Operand,
+    /// 变量下标与其字节步长，同一下标只出现一次
+    vars: Vec<(Operand, i64)>,
+    offset: i64,
+}
+
+impl Decomposed {
+    fn add_index(&mut self, func: &FuncDef, idx: &Operand, stride: i64) {
+        let (var, c) = match idx {
+            Operand::ConstInt(c) => (None, *c),
+            Operand::Value(id) => match func.instr(*id) {
+                // 下标为 x + c 时拆出常量，a[i]与a[i + 1]可以区分
+                Instruction::Add(bin_op) => match (&bin_op.op1, &bin_op.op2) {
+                    (Operand::ConstInt(_), Operand::ConstInt(_)) => (Some(idx.clone()), 0),
+                    (x, Operand::ConstInt(c)) | (Operand::ConstInt(c), x) => (Some(x.clone()), *c),
+                    _ => (Some(idx.clone()), 0),
+                },
+                _ => (Some(idx.clone()), 0),
+            },
+            _ => (Some(idx.clone()), 0),
+        };
+        self.offset += c * stride;
+        if let Some(var) = var {
+            match self.vars.iter().position(|(v, _)| *v == var) {
+                Some(pos) => {
+                    self.vars[pos].1 += stride;
+                    if self.vars[pos].1 == 0 {
+                        self.vars.swap_remove(pos);
+                    }
+                },
+                None => self.vars.push((var, stride)),
+            }
+        }
+    }
+
+    /// 变量部分是否相同，相同时两个地址只相差常量偏移
+    fn same_vars(&self, other: &Decomposed) -> bool {
+        self.vars.len() == other.vars.len() && self.vars.iter().all(|var| other.vars.contains(var))
+    }
+}
+
+/// 地址最终指向的对象
+#[derive(Debug, Clone, PartialEq)]
+enum Object {
+    Global(String),
+    Local(ValueId),
+    /// 数组参数，可能指向全局变量或调用者的局部变量
+    Param,
+    Unknown,
+}
+
+impl FuncDef {
+    /// 别名分析：同一基址时比较偏移，否则比较地址可能指向的对象<br>
+    /// load与store都按4字节访问，同一基址、偏移不同的地址不会重叠
+    pub fn alias(&self, ptr1: &Operand, ptr2: &Operand) -> AliasResult {
+        let (addr1, addr2) = (self.decompose(ptr1), self.decompose(ptr2));
+        if addr1.base == addr2.base {
+            if !addr1.same_vars(&addr2) {
+                return AliasResult::MayAlias;
+            }
+            return if addr1.offset == addr2.offset { AliasResult::MustAlias } else { AliasResult::NoAlias };
+        }
+        let (objects1, objects2) = (self.underlying_objects(&addr1.base), self.underlying_objects(&addr2.base));
+        if objects1.iter().all(|obj1| objects2.iter().all(|obj2| self.distinct_objects(obj1, obj2))) {
+            AliasResult::NoAlias
+        } else {
+            AliasResult::MayAlias
+        }
+    }
+
+    pub fn may_alias(&self, ptr1: &Operand, ptr2: &Operand) -> bool {
+        self.alias(ptr1, ptr2) != AliasResult::NoAlias
+    }
+
+    pub fn must_alias(&self, ptr1: &Operand, ptr2: &Operand) -> bool {
+        self.alias(ptr1, ptr2) == AliasResult::MustAlias
+    }
+
+    /// 地址是否可能指向本函数的局部变量
+    pub fn points_to_local(&self, ptr: &Operand) -> bool {
+        self.underlying_objects(ptr).iter().any(|obj| self.is_alloca(obj))
+    }
+
+    /// 地址是否只可能指向本函数的局部变量，这样的读写对调用者不可见
+    pub fn points_only_to_local(&self, ptr: &Operand) -> bool {
+        self.underlying_objects(ptr).iter().all(|obj| self.is_alloca(obj))
+    }
+
+    /// 下标都是常量时，地址的基址与字节偏移
+    pub fn constant_offset(&self, ptr: &Operand) -> Option<(Operand, i64)> {
+        let addr = self.decompose(ptr);
+        addr.vars.is_empty().then_some((addr.base, addr.offset))
+    }
+
+    /// 沿GetElemPtr与BitCast将地址分解为基址与偏移
+    fn decompose(&self, ptr: &Operand) -> Decomposed {
+        let mut addr = Decomposed { base: ptr.clone(), vars: vec![], offset: 0 };
+        while let Operand::Value(id) = addr.base {
+            match self.instr(id) {
+                Instruction::GetElemPtr(ty, base, idx) => {
+                    // 第一个下标以整个类型为步长，之后每个下标去掉一维
+                    let dims: &[i32] = match &ty.width {
+                        SymbolWidth::Arr { dims, .. } => dims,
+                        _ => &[],
+                    };
+                    for (k, op) in idx.iter().enumerate() {
+                        let stride = dims[k.min(dims.len())..].iter().map(|&d| d as i64).product::<i64>() * 4;
+                        addr.add_index(self, op, stride);
+                    }
+                    addr.base = base.clone();
+                },
+                Instruction::BitCast(_, base, _) => addr.base = base.clone(),
+                _ => break,
+            }
+        }
+        addr
+    }
+
+    /// 地址可能的来源，穿过GetElemPtr、BitCast与phi
+    pub fn underlying_objects(&self, ptr: &Operand) -> Vec<Operand> {
+        let mut objects = vec![];
+        let mut visited = HashSet::new();
+        let mut worklist = vec![ptr.clone()];
+        while let Some(ptr) = worklist.pop() {
+            match &ptr {
+                Operand::Value(id) if visited.insert(*id) => match self.instr(*id) {
+                    Instruction::GetElemPtr(_, base, _) | Instruction::BitCast(_, base, _) => worklist.push(base.clone()),
+                    Instruction::Phi(_, candidates) => worklist.extend(candidates.iter().map(|(value, _)| value.clone())),
+                    _ => objects.push(ptr.clone()),
+                },
+                Operand::Value(_) => {},
+                _ => objects.push(ptr.clone()),
+            }
+        }
+        objects
+    }
+
+    fn object_of(&self, op: &Operand) -> Object {
+        match op {
+            Operand::Global(name) => Object::Global(name.clone()),
+            Operand::Value(id) if self.is_alloca(op) => Object::Local(*id),
+            Operand::Arg(idx) => match &self.params[*idx].param_type.width {
+                SymbolWidth::Arr { dims, .. } if dims[0] == -1 => Object::Param,
+                _ => Object::Unknown,
+            },
+            _ => Object::Unknown,
+        }
+    }
+
+    /// 两个对象是否一定不同：不同的全局变量与局部变量互不重叠；
+    /// 数组参数与其他来源的指针只可能指向全局变量或调用者的局部变量，不会指向本函数的局部变量
+    fn distinct_objects(&self, obj1: &Operand, obj2: &Operand) -> bool {
+        match (self.object_of(obj1), self.object_of(obj2)) {
+            (Object::Global(name1), Object::Global(name2)) => name1 != name2,
+            (Object::Local(id1), Object::Local(id2)) => id1 != id2,
+            (Object::Local(_), _) | (_, Object::Local(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::llvm_gen::read_text::read_llvm;
+    use crate::structures::llvm_struct::*;
+    use super::AliasResult::*;
+
+    const INPUT: &str = "\
+@g = global i32 0, align 4
+@h = global i32 0, align 4
+@arr = global [4 x [5 x i32]] zeroinitializer, align 4
+
+define void @f(i32* noundef %a, i32 noundef %i) {
+entry:
+  %l = alloca [4 x i32], align 4
+  %m = alloca i32, align 4
+  %l0 = getelementptr [4 x i32], [4 x i32]* %l, i32 0, i32 0
+  %l1 = getelementptr [4 x i32], [4 x i32]* %l, i32 0, i32 1
+  %l0_1 = getelementptr i32, i32* %l0, i32 1
+  %li = getelementptr [4 x i32], [4 x i32]* %l, i32 0, i32 %i
+  %i1 = add i32 %i, 1
+  %li1 = getelementptr [4 x i32], [4 x i32]* %l, i32 0, i32 %i1
+  %row = getelementptr [4 x [5 x i32]], [4 x [5 x i32]]* @arr, i32 0, i32 1
+  %r1 = getelementptr [4 x [5 x i32]], [4 x [5 x i32]]* @arr, i32 0, i32 1, i32 0
+  %r0 = getelementptr [4 x [5 x i32]], [4 x [5 x i32]]* @arr, i32 0, i32 0, i32 5
+  %ai = getelementptr i32, i32* %a, i32 %i
+  %c = icmp sgt i32 %i, 0
+  br i1 %c, label %then, label %end
+
+then:
+  br label %end
+
+end:
+  %p = phi i32* [ %l0, %then ], [ %ai, %entry ]
+  ret void
+}";
+
+    fn operand(func: &FuncDef, name: &str) -> Operand {
+        if let Some(global) = name.strip_prefix('@') {
+            return Operand::Global(global.to_string());
+        }
+        if let Some(idx) = func.params.iter().position(|param| param.param_name == name) {
+            return Operand::Arg(idx);
+        }
+        let id = func.values.iter().position(|value| value.name.as_deref() == Some(name)).unwrap();
+        Operand::Value(ValueId(id))
+    }
+
+    #[test]
+    fn alias_queries() {
+        let llvm = read_llvm(INPUT).unwrap();
+        let func = &llvm.func_def[0];
+        let alias = |ptr1: &str, ptr2: &str| func.alias(&operand(func, ptr1), &operand(func, ptr2));
+
+        // 同一基址比较字节偏移，数组的第二行开头与第一行越过末尾的位置相同
+        assert_eq!(alias("%l0", "%l1"), NoAlias);
+        assert_eq!(alias("%l1", "%l0_1"), MustAlias);
+        assert_eq!(alias("%li", "%li1"), NoAlias);
+        assert_eq!(alias("%li", "%l1"), MayAlias);
+        assert_eq!(alias("%row", "%r1"), MustAlias);
+        assert_eq!(alias("%r1", "%r0"), MustAlias);
+        // 不同的对象：数组参数不会指向本函数的局部变量
+        assert_eq!(alias("@g", "@h"), NoAlias);
+        assert_eq!(alias("@g", "%l0"), NoAlias);
+        assert_eq!(alias("@g", "%ai"), MayAlias);
+        assert_eq!(alias("%ai", "%l1"), NoAlias);
+        // phi可能指向局部数组或参数
+        assert_eq!(alias("%p", "%m"), NoAlias);
+        assert_eq!(alias("%p", "%l1"), MayAlias);
+        assert_eq!(alias("%p", "@g"), MayAlias);
+
+        let p = operand(func, "%p");
+        assert!(func.points_to_local(&p));
+        assert!(!func.points_only_to_local(&p));
+        assert!(func.points_only_to_local(&operand(func, "%li")));
+        assert_eq!(func.constant_offset(&operand(func, "%r0")), Some((Operand::Global("arr".to_string()), 20)));
+        assert_eq!(func.constant_offset(&operand(func, "%li")), None);
+    }
+}
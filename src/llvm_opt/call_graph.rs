@@ -0,0 +1,215 @@
+use std::collections::{HashMap, HashSet};
+use crate::structures::llvm_struct::*;
+
+/// 调用图：各个有定义的函数直接调用的函数，均带@前缀
+pub struct CallGraph {
+    /// 有定义的函数，与func_def的顺序相同
+    pub funcs: Vec<String>,
+    /// 各个有定义的函数直接调用的有定义的函数，不重复
+    pub callees: HashMap<String, Vec<String>>,
+    /// 各个有定义的函数调用的全部函数，含库函数
+    pub calls: HashMap<String, Vec<String>>,
+}
+
+impl LLVMProgram {
+    pub fn call_graph(&self) -> CallGraph {
+        let defined: HashSet<&String> = self.func_def.iter().map(|func| &func.func_name).collect();
+        let mut callees = HashMap::new();
+        let mut calls = HashMap::new();
+        for func in self.func_def.iter() {
+            let mut called: Vec<String> = vec![];
+            for id in func.all_instrs() {
+                if let Instruction::Call(name, _, _) = func.instr(id) {
+                    if !called.contains(name) {
+                        called.push(name.clone());
+                    }
+                }
+            }
+            callees.insert(func.func_name.clone(), called.iter().filter(|name| defined.contains(name)).cloned().collect());
+            calls.insert(func.func_name.clone(), called);
+        }
+        CallGraph {
+            funcs: self.func_def.iter().map(|func| func.func_name.clone()).collect(),
+            callees,
+            calls,
+        }
+    }
+
+    /// 删除从main出发调用不到的函数，以及不再被调用的库函数声明；没有main时不删除
+    pub fn remove_dead_functions(&mut self) {
+        let graph = self.call_graph();
+        let main = String::from("@main");
+        if !graph.callees.contains_key(&main) {
+            return;
+        }
+        let reachable = graph.reachable_from(&main);
+        self.func_def.retain(|func| reachable.contains(&func.func_name));
+        let called: HashSet<&String> = reachable.iter().flat_map(|name| graph.calls[name].iter()).collect();
+        self.func_decl.retain(|decl| called.contains(&format!("@{}", decl.func_name)));
+    }
+}
+
+impl CallGraph {
+    /// 从root出发沿调用边能到达的有定义的函数，含root本身
+    pub fn reachable_from(&self, root: &str) -> HashSet<String> {
+        let mut reachable = HashSet::new();
+        let mut worklist = vec![root.to_string()];
+        while let Some(name) = worklist.pop() {
+            if reachable.insert(name.clone()) {
+                worklist.extend(self.callees[&name].iter().cloned());
+            }
+        }
+        reachable
+    }
+
+    /// 强连通分量，采用Tarjan算法；被调用者所在的分量排在调用者之前，分量内按func_def的顺序排列
+    pub fn sccs(&self) -> Vec<Vec<String>> {
+        let mut tarjan = Tarjan { graph: self, index: HashMap::new(), low: HashMap::new(), stack: vec![], on_stack: HashSet::new(), sccs: vec![] };
+        for name in self.funcs.iter() {
+            if !tarjan.index.contains_key(name) {
+                tarjan.visit(name);
+            }
+        }
+        let order: HashMap<&String, usize> = self.funcs.iter().enumerate().map(|(i, name)| (name, i)).collect();
+        tarjan.sccs.iter_mut().for_each(|scc| scc.sort_by_key(|name| order[name]));
+        tarjan.sccs
+    }
+
+    /// 递归的函数：所在的强连通分量中有多个函数，或者直接调用自身
+    pub fn recursive(&self) -> HashSet<String> {
+        self.sccs().into_iter()
+            .filter(|scc| scc.len() > 1 || self.callees[&scc[0]].contains(&scc[0]))
+            .flatten()
+            .collect()
+    }
+}
+
+struct Tarjan<'a> {
+    graph: &'a CallGraph,
+    /// 各函数被访问的次序
+    index: HashMap<String, usize>,
+    /// 各函数经过子树中的边能回到的最小次序
+    low: HashMap<String, usize>,
+    stack: Vec<String>,
+    on_stack: HashSet<String>,
+    sccs: Vec<Vec<String>>,
+}
+
+impl Tarjan<'_> {
+    fn visit(&mut self, name: &str) {
+        let index = self.index.len();
+        self.index.insert(name.to_string(), index);
+        self.low.insert(name.to_string(), index);
+        self.stack.push(name.to_string());
+        self.on_stack.insert(name.to_string());
+        for callee in self.graph.callees[name].iter() {
+            if !self.index.contains_key(callee) {
+                self.visit(callee);
+                let low = self.low[name].min(self.low[callee]);
+                self.low.insert(name.to_string(), low);
+            } else if self.on_stack.contains(callee) {
+                let low = self.low[name].min(self.index[callee]);
+                self.low.insert(name.to_string(), low);
+            }
+        }
+        if self.low[name] == index {
+            let mut scc = vec![];
+            loop {
+                let member = self.stack.pop().unwrap();
+                self.on_stack.remove(&member);
+                let done = member == name;
+                scc.push(member);
+                if done {
+                    break;
+                }
+            }
+            self.sccs.push(scc);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use crate::llvm_gen::read_text::read_llvm;
+    use crate::llvm_opt::run_on_text;
+    use crate::structures::llvm_struct::LLVMProgram;
+
+    const INPUT: &str = "\
+declare i32 @getint()
+declare i32 @getch()
+declare void @putint(i32 noundef)
+
+define i32 @main() {
+entry:
+  %a = call i32 @a()
+  %e = call i32 @e(i32 noundef %a)
+  call void @putint(i32 noundef %e)
+  ret i32 0
+}
+
+define i32 @a() {
+entry:
+  %r = call i32 @b()
+  ret i32 %r
+}
+
+define i32 @b() {
+entry:
+  %r = call i32 @d()
+  %c = call i32 @c()
+  ret i32 %r
+}
+
+define i32 @c() {
+entry:
+  %r = call i32 @getint()
+  ret i32 %r
+}
+
+define i32 @d() {
+entry:
+  %r = call i32 @b()
+  ret i32 %r
+}
+
+define i32 @e(i32 noundef %x) {
+entry:
+  %r = call i32 @e(i32 noundef %x)
+  ret i32 %r
+}
+
+define i32 @dead() {
+entry:
+  %r = call i32 @getch()
+  %s = call i32 @c()
+  ret i32 %r
+}";
+
+    fn names(list: &[&str]) -> HashSet<String> {
+        list.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn sccs_and_recursion() {
+        let graph = read_llvm(INPUT).unwrap().call_graph();
+        assert_eq!(graph.callees["@b"], ["@d", "@c"]);
+        assert_eq!(graph.calls["@c"], ["@getint"]);
+        // 被调用者所在的分量在调用者之前，互相递归的b与d在同一分量
+        let sccs = graph.sccs();
+        assert_eq!(sccs, [vec!["@c"], vec!["@b", "@d"], vec!["@a"], vec!["@e"], vec!["@main"], vec!["@dead"]]);
+        assert_eq!(graph.recursive(), names(&["@b", "@d", "@e"]));
+        assert_eq!(graph.reachable_from("@a"), names(&["@a", "@b", "@c", "@d"]));
+    }
+
+    #[test]
+    fn remove_dead_functions() {
+        let output = run_on_text(INPUT, LLVMProgram::remove_dead_functions);
+        // @dead及只被它调用的@getch被删除，@c仍被@b调用
+        assert!(!output.contains("@dead") && !output.contains("@getch"), "{}", output);
+        assert!(output.starts_with("declare i32 @getint()\ndeclare void @putint(i32 noundef)"), "{}", output);
+        let llvm = read_llvm(&output).unwrap();
+        let funcs: Vec<&str> = llvm.func_def.iter().map(|func| func.func_name.as_str()).collect();
+        assert_eq!(funcs, ["@main", "@a", "@b", "@c", "@d", "@e"]);
+    }
+}
@@ -0,0 +1,235 @@
+use crate::structures::llvm_struct::*;
+
+/// 函数的控制流图，以BlockId的下标索引，不在布局中的基本块没有边
+pub struct ControlFlowGraph {
+    /// 入口块
+    pub entry: BlockId,
+    /// 各基本块的后继
+    pub succs: Vec<Vec<BlockId>>,
+    /// 各基本块的前驱
+    pub preds: Vec<Vec<BlockId>>,
+}
+
+/// 支配树及支配边界
+pub struct DominatorTree {
+    /// 入口块
+    pub entry: BlockId,
+    /// 从入口块出发的逆后序，只包含可达块
+    pub rpo: Vec<BlockId>,
+    /// 直接支配者，入口块与不可达块为None
+    pub idom: Vec<Option<BlockId>>,
+    /// 支配树上的子节点
+    pub children: Vec<Vec<BlockId>>,
+    /// 支配边界
+    pub frontier: Vec<Vec<BlockId>>,
+}
+
+impl FuncDef {
+    /// 构建控制流图，保留自环边
+    pub fn build_cfg(&self) -> ControlFlowGraph {
+        let mut succs = vec![vec![]; self.blocks.len()];
+        let mut preds = vec![vec![]; self.blocks.len()];
+        for &bb in self.layout.iter() {
+            for succ in self.successors(bb) {
+                if !succs[bb.0].contains(&succ) {
+                    succs[bb.0].push(succ);
+                    preds[succ.0].push(bb);
+                }
+            }
+        }
+        ControlFlowGraph { entry: self.entry(), succs, preds }
+    }
+}
+
+impl ControlFlowGraph {
+    /// 从入口块出发的逆后序
+    pub fn reverse_post_order(&self) -> Vec<BlockId> {
+        let mut visited = vec![false; self.succs.len()];
+        let mut post_order = Vec::with_capacity(self.succs.len());
+        // 栈中保存<基本块, 下一个要访问的后继的位置>
+        let mut stk = vec![(self.entry, 0)];
+        visited[self.entry.0] = true;
+        while let Some((node, next)) = stk.pop() {
+            if let Some(&succ) = self.succs[node.0].get(next) {
+                stk.push((node, next + 1));
+                if !visited[succ.0] {
+                    visited[succ.0] = true;
+                    stk.push((succ, 0));
+                }
+            } else {
+                post_order.push(node);
+            }
+        }
+        post_order.reverse();
+        post_order
+    }
+
+    /// 计算支配树与支配边界
+    /// 直接支配者采用Cooper-Harvey-Kennedy迭代算法
+    pub fn dominator_tree(&self) -> DominatorTree {
+        let len = self.succs.len();
+        let rpo = self.reverse_post_order();
+        let mut rpo_num = vec![usize::MAX; len];
+        rpo.iter().enumerate().for_each(|(i, b)| rpo_num[b.0] = i);
+
+        let mut idom: Vec<Option<BlockId>> = vec![None; len];
+        idom[self.entry.0] = Some(self.entry);
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &b in rpo.iter().skip(1) {
+                let mut new_idom: Option<BlockId> = None;
+                for &p in self.preds[b.0].iter() {
+                    if idom[p.0].is_none() {
+                        continue;
+                    }
+                    new_idom = match new_idom {
+                        None => Some(p),
+                        Some(cur) => Some(intersect(&idom, &rpo_num, p, cur)),
+                    };
+                }
+                if new_idom.is_some() && idom[b.0] != new_idom {
+                    idom[b.0] = new_idom;
+                    changed = true;
+                }
+            }
+        }
+        // 入口块没有直接支配者
+        idom[self.entry.0] = None;
+
+        let mut children = vec![vec![]; len];
+        for &b in rpo.iter() {
+            if let Some(d) = idom[b.0] {
+                children[d.0].push(b);
+            }
+        }
+
+        // 汇合点的每个前驱沿支配树向上走到汇合点的直接支配者为止，途经的块的支配边界都包含该汇合点
+        let mut frontier = vec![vec![]; len];
+        for &b in rpo.iter() {
+            let preds: Vec<BlockId> = self.preds[b.0].iter().cloned().filter(|p| rpo_num[p.0] != usize::MAX).collect();
+            if preds.len() < 2 {
+                continue;
+            }
+            for p in preds {
+                let mut runner = Some(p);
+                while let Some(r) = runner {
+                    if Some(r) == idom[b.0] {
+                        break;
+                    }
+                    if !frontier[r.0].contains(&b) {
+                        frontier[r.0].push(b);
+                    }
+                    runner = idom[r.0];
+                }
+            }
+        }
+
+        DominatorTree { entry: self.entry, rpo, idom, children, frontier }
+    }
+}
+
+fn intersect(idom: &[Option<BlockId>], rpo_num: &[usize], mut a: BlockId, mut b: BlockId) -> BlockId {
+    while a != b {
+        while rpo_num[a.0] > rpo_num[b.0] {
+            a = idom[a.0].unwrap();
+        }
+        while rpo_num[b.0] > rpo_num[a.0] {
+            b = idom[b.0].unwrap();
+        }
+    }
+    a
+}
+
+impl DominatorTree {
+    /// a是否支配b
+    pub fn dominates(&self, a: BlockId, mut b: BlockId) -> bool {
+        loop {
+            if a == b {
+                return true;
+            }
+            match self.idom[b.0] {
+                Some(d) => b = d,
+                None => return false,
+            }
+        }
+    }
+
+    /// 支配树的先序遍历
+    pub fn pre_order(&self) -> Vec<BlockId> {
+        let mut res = vec![];
+        let mut stk = vec![self.entry];
+        while let Some(b) = stk.pop() {
+            res.push(b);
+            stk.extend(self.children[b.0].iter().rev());
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::llvm_gen::read_text::read_llvm;
+    use crate::structures::llvm_struct::*;
+
+    /// 菱形的分支之后接一个循环，dead不可达
+    const INPUT: &str = "\
+define void @f(i1 noundef %c) {
+entry:
+  br i1 %c, label %a, label %b
+
+a:
+  br label %join
+
+b:
+  br label %join
+
+dead:
+  br label %join
+
+join:
+  br label %loop
+
+loop:
+  br i1 %c, label %body, label %exit
+
+body:
+  br i1 %c, label %loop, label %exit
+
+exit:
+  ret void
+}";
+
+    #[test]
+    fn dominator_tree_and_frontier() {
+        let llvm = read_llvm(INPUT).unwrap();
+        let func = &llvm.func_def[0];
+        let block = |label: &str| func.layout.iter().cloned().find(|&b| func.block(b).block_label == label).unwrap();
+        let labels = |blocks: &[BlockId]| blocks.iter().map(|&b| func.block(b).block_label.clone()).collect::<Vec<_>>();
+        let dom = func.build_cfg().dominator_tree();
+
+        assert_eq!(labels(&dom.rpo), ["entry", "b", "a", "join", "loop", "body", "exit"]);
+        let idom = |label: &str| dom.idom[block(label).0].map(|d| func.block(d).block_label.clone());
+        assert_eq!(idom("entry"), None);
+        assert_eq!(idom("a").as_deref(), Some("entry"));
+        // 不可达的前驱不影响汇合点的支配者
+        assert_eq!(idom("join").as_deref(), Some("entry"));
+        assert_eq!(idom("dead"), None);
+        assert_eq!(idom("body").as_deref(), Some("loop"));
+        assert_eq!(idom("exit").as_deref(), Some("loop"));
+
+        let frontier = |label: &str| labels(&dom.frontier[block(label).0]);
+        assert_eq!(frontier("a"), ["join"]);
+        assert_eq!(frontier("b"), ["join"]);
+        assert!(frontier("entry").is_empty());
+        assert_eq!(frontier("loop"), ["loop"]);
+        let mut body = frontier("body");
+        body.sort();
+        assert_eq!(body, ["exit", "loop"]);
+
+        assert!(dom.dominates(block("join"), block("exit")));
+        assert!(!dom.dominates(block("a"), block("join")));
+        assert!(!dom.dominates(block("body"), block("exit")));
+        assert_eq!(labels(&dom.pre_order()), ["entry", "b", "a", "join", "loop", "body", "exit"]);
+    }
+}
@@ -1,131 +1,88 @@
-use std::collections::{HashMap, HashSet};
-use crate::llvm_opt::flow_graph::{build_map, calc_active};
+use std::collections::HashSet;
+use crate::llvm_opt::purity::*;
 use crate::structures::llvm_struct::*;
-use crate::structures::scopes::*;
-use crate::utils::check::is_num_label;
 
 impl LLVMProgram {
     pub fn eliminate_unused_code(&mut self) {
+        let effects = self.call_effects();
         // 对每个函数进行遍历
         self.func_def.iter_mut().for_each(|func| {
             // 消除不可达代码
-            let active_bb = unreachable_code_eliminate(func);
+            unreachable_code_eliminate(func);
             // 消除死代码
-            let active_labels = dead_code_eliminate(func, &active_bb);
-            // 统计指令数量
-            let instr_cnt = func.count_instr();
-            // 收集活跃指令
-            let mut active_instrs = collect_active_instructions(func, &active_labels);
-            // 收集活跃分配
-            let active_allocs = collect_active_allocations(func, &active_labels, instr_cnt);
-            // 将活跃分配插入活跃指令集合
-            active_allocs.into_iter().for_each(|i| { active_instrs.insert(i); });
-            // 初始化标签映射
-            let (mut labels, mut label_map) = initialize_label_maps();
-            // 重写函数
-            func.rewrite (
-                // 更新标签
-                &mut |s| update_label(&mut labels, &mut label_map, s),
-                // 检查基本块是否活跃
-                &|s| active_bb.contains(s),
-                // 检查指令是否活跃
-                &|i| active_instrs.contains(&(i as usize)),
-            );
+            dead_code_eliminate(func, &effects);
         });
     }
 }
 
-// 收集活跃指令
-fn collect_active_instructions(func: &mut FuncDef, active_labels: &HashSet<String>) -> HashSet<usize> {
-    func.blocks.iter().map(|block| {
-        let mut res: HashSet<usize> = HashSet::new();
-        let mut instr_cnt = block.ins_num.clone();
-        // 遍历phi指令
-        for p in block.phi_ins.iter() {
-            if p.is_active(&active_labels) { res.insert(instr_cnt.clone()); }
-            instr_cnt += 1;
-        }
-        // 遍历普通指令
-        for i in block.nor_ins.iter() {
-            if i.is_active(&active_labels) { res.insert(instr_cnt.clone()); }
-            instr_cnt += 1;
-        }
-        // 如果有终止指令，则插入
-        if block.ter_ins.is_some() { res.insert(instr_cnt.clone()); }
-        res
-    }).fold(HashSet::new(), |mut acc, instrs| {
-        for i in instrs.into_iter() { acc.insert(i); }
-        acc
-    })
-}
-
-// 消除死代码
-pub fn dead_code_eliminate(func: &FuncDef, active_bb: &HashSet<String>) -> HashSet<String> {
-    // 构建指令集合
-    let items: Vec<&Instruction> = func.make_blocks()
-        .iter()
-        .filter(|b| active_bb.contains(&b.block_label))
-        .map(|b| b.make_block_instrs())
-        .flatten()
+// 删除从入口不可达的基本块，以及phi中来自这些块的候选
+pub fn unreachable_code_eliminate(func: &mut FuncDef) {
+    let reachable: HashSet<BlockId> = func.build_cfg()
+        .reverse_post_order()
+        .into_iter()
+        .collect();
+    let removed: HashSet<BlockId> = func.layout.iter()
+        .filter(|bb| !reachable.contains(bb))
+        .cloned()
         .collect();
-    // 构建后继和前驱映射
-    let (succs, mut preds) = build_map(items.clone());
-    // 过滤前驱映射，只保留活跃的
-    preds = preds.into_iter()
-        .filter(|(s, _)| s.contains("@") || s.contains("%"))
+    if removed.is_empty() {
+        return;
+    }
+    let phis: Vec<ValueId> = func.layout.iter()
+        .filter(|bb| reachable.contains(bb))
+        .flat_map(|&bb| func.block(bb).phi_ins.clone())
         .collect();
-    // 计算活跃基本块
-    calc_active(&succs, preds)
+    for phi in phis {
+        if let Instruction::Phi(ty, candidates) = func.instr(phi) {
+            if candidates.iter().any(|(_, pred)| removed.contains(pred)) {
+                let candidates = candidates.iter()
+                    .filter(|(_, pred)| !removed.contains(pred))
+                    .cloned()
+                    .collect();
+                func.set_instr(phi, Instruction::Phi(ty.clone(), candidates));
+            }
+        }
+    }
+    func.remove_blocks(&removed);
 }
 
-// 函数用于消除不可达代码，返回活跃的基本块标签的集合
-pub fn unreachable_code_eliminate(func: &FuncDef) -> HashSet<String> {
-    // 将函数的基本块转换为向量
-    let items: Vec<_> = func.make_blocks();
-    // 构建后继和前驱的映射关系
-    let (succs, mut preds) = build_map(items.clone());
-    // 如果前驱中不包含"_entry"，则将其添加到前驱映射中
-    if !preds.contains_key("_entry") {
-        preds.insert(String::from("_entry"), HashSet::new());
+// 从有副作用的指令出发标记活跃的指令，删除其余指令
+// 只被store过而从未被读取的局部变量，连同对它的store一起删除
+pub fn dead_code_eliminate(func: &mut FuncDef, effects: &CallEffects) {
+    let mut live: HashSet<ValueId> = HashSet::new();
+    let mut worklist: Vec<ValueId> = vec![];
+    for id in func.all_instrs() {
+        let is_root = match func.instr(id) {
+            // 有副作用的调用与终结指令总是执行，不写内存的调用在结果被使用时才活跃
+            Instruction::Call(name, _, _) => !effects.removable(name),
+            Instruction::Ret(_, _) | Instruction::Br(_, _, _) => true,
+            // 写入局部变量的store在该变量活跃时才活跃
+            Instruction::Store { ptr, .. } => !func.is_alloca(ptr),
+            // 由于删除死代码后，代码结构发生变化，注释不再打印
+            _ => false,
+        };
+        if is_root && live.insert(id) {
+            worklist.push(id);
+        }
     }
-    // 在"_entry"的前驱中添加一个空字符串
-    preds.get_mut("_entry")
-        .unwrap()
-        .insert(String::from(""));
-    // 计算活跃的基本块标签
-    calc_active(&succs, preds)
-}
 
-// 函数用于收集活跃的分配指令，返回活跃的指令的索引的集合
-fn collect_active_allocations(func: &mut FuncDef, active_labels: &HashSet<String>, instr_cnt: usize) -> HashSet<usize> {
-    // 遍历函数的局部变量并根据活跃的标签进行过滤
-    func.local_vars.iter().enumerate().filter(|(_, alloc)| {
-        alloc.ins.is_active(&active_labels)
-    }).map(|(i, _)| instr_cnt + i).collect::<HashSet<usize>>()
-}
+    while let Some(id) = worklist.pop() {
+        let mut deps = func.instr(id).used_values();
+        if let Instruction::Alloca { .. } = func.instr(id) {
+            deps.extend(func.values[id.0].users.iter().filter(|&&user| {
+                matches!(func.instr(user), Instruction::Store { ptr: Operand::Value(ptr), .. } if *ptr == id)
+            }));
+        }
+        for dep in deps {
+            if live.insert(dep) {
+                worklist.push(dep);
+            }
+        }
+    }
 
-// 函数用于初始化标签和标签映射
-fn initialize_label_maps() -> (Labels, HashMap<String, String>) {
-    (Labels::new(), HashMap::new())
+    let dead: HashSet<ValueId> = func.all_instrs()
+        .into_iter()
+        .filter(|id| !live.contains(id))
+        .collect();
+    func.erase_values(&dead);
 }
-
-// 函数用于更新标签
-pub fn update_label(labels: &mut Labels, label_map: &mut HashMap<String, String>, old_label: &str) -> String {
-    // 如果旧标签不包含 "%"，则直接返回旧标签
-    if !old_label.contains("%") {
-        return String::from(old_label);
-    }
-    // 如果旧标签不是数字标签，则根据标签映射返回新标签
-    if !is_num_label(old_label) {
-        return label_map.get(old_label)
-            .map_or(String::from(old_label), |x| String::from(x));
-    }
-    // 如果旧标签是数字标签且存在于标签映射中，则返回对应的新标签
-    if let Some(new_label) = label_map.get(old_label) {
-        return new_label.to_string();
-    }
-    // 生成一个新的数字标签，并将旧标签与新标签的映射关系添加到标签映射中
-    let new_label = labels.pop_num_str();
-    label_map.insert(String::from(old_label), new_label.clone());
-    new_label
-}
\ No newline at end of file
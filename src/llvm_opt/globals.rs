@@ -0,0 +1,219 @@
+use std::collections::HashSet;
+use crate::structures::llvm_struct::*;
+use crate::structures::symbol::*;
+use crate::utils::check::is_float_immediate;
+use crate::utils::float::parse_float;
+
+impl LLVMProgram {
+    /// 全局变量优化：从未被写入的全局变量视为常量，对它的读取直接替换为初值，数组要求下标均为常量；
+    /// 只在非递归的main中使用的全局标量改为main的局部变量，由mem2reg提升为SSA值；之后不再使用的全局变量被删除
+    pub fn optimize_globals(&mut self) {
+        let written = self.written_globals();
+        for var in self.global_var.iter().filter(|var| !written.contains(&var.var_name)) {
+            self.func_def.iter_mut().for_each(|func| func.fold_constant_loads(var));
+        }
+        self.localize_main_globals();
+
+        let used: HashSet<String> = self.func_def.iter()
+            .flat_map(|func| func.globals_used())
+            .collect();
+        self.global_var.retain(|var| used.contains(&var.var_name));
+    }
+
+    /// 可能被写入的全局变量：作为store的地址，或作为实参传给可能写内存的函数
+    fn written_globals(&self) -> HashSet<String> {
+        let effects = self.call_effects();
+        let mut written = HashSet::new();
+        for func in self.func_def.iter() {
+            for id in func.all_instrs() {
+                let ptrs: Vec<&Operand> = match func.instr(id) {
+                    Instruction::Store { ptr, .. } => vec![ptr],
+                    Instruction::Call(name, _, params) if effects.may_write(name) => params.iter().map(|(arg, _)| arg).collect(),
+                    _ => continue,
+                };
+                for ptr in ptrs {
+                    for obj in func.underlying_objects(ptr) {
+                        if let Operand::Global(name) = obj {
+                            written.insert(name);
+                        }
+                    }
+                }
+            }
+        }
+        written
+    }
+
+    /// main只执行一次时，只在其中使用的全局标量等价于在入口处以初值初始化的局部变量
+    fn localize_main_globals(&mut self) {
+        let graph = self.call_graph();
+        let main = String::from("@main");
+        if !graph.callees.contains_key(&main) || graph.callees.values().any(|callees| callees.contains(&main)) {
+            return;
+        }
+        let used_elsewhere: HashSet<String> = self.func_def.iter()
+            .filter(|func| func.func_name != main)
+            .flat_map(|func| func.globals_used())
+            .collect();
+        let main_func = self.func_def.iter_mut().find(|func| func.func_name == main).unwrap();
+        let used_in_main = main_func.globals_used();
+        let candidates: Vec<&GlobalVar> = self.global_var.iter()
+            .filter(|var| matches!(var.var_type.width, SymbolWidth::I32 | SymbolWidth::Float))
+            .filter(|var| used_in_main.contains(&var.var_name) && !used_elsewhere.contains(&var.var_name))
+            .collect();
+        if candidates.is_empty() {
+            return;
+        }
+        candidates.into_iter().for_each(|var| main_func.localize_global(var));
+        main_func.mem2reg();
+    }
+}
+
+impl FuncDef {
+    /// 指令中用到的全局变量
+    fn globals_used(&self) -> HashSet<String> {
+        self.all_instrs().into_iter()
+            .flat_map(|id| self.instr(id).operands().into_iter().cloned().collect::<Vec<_>>())
+            .filter_map(|op| match op {
+                Operand::Global(name) => Some(name),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// 将从常量全局变量var中读取固定位置的load替换为初值
+    fn fold_constant_loads(&mut self, var: &GlobalVar) {
+        let global = Operand::Global(var.var_name.clone());
+        for id in self.all_instrs() {
+            let offset = match self.instr(id) {
+                Instruction::Load { ptr, .. } => match self.constant_offset(ptr) {
+                    Some((base, offset)) if base == global => offset,
+                    _ => continue,
+                },
+                _ => continue,
+            };
+            if let Some(value) = initial_value(var, offset) {
+                self.replace_all_uses(id, &value);
+                self.erase_value(id);
+            }
+        }
+    }
+
+    /// 在入口块以初值初始化新的局部变量，并将对全局标量var的读写都改为对它的读写
+    fn localize_global(&mut self, var: &GlobalVar) {
+        let global = Operand::Global(var.var_name.clone());
+        let entry = self.entry();
+        let local = Operand::Value(self.push_value(entry, Instruction::Alloca { ty: var.var_type.clone(), len: "4".to_string() }, None));
+        for id in self.all_instrs() {
+            let mut instr = self.instr(id).clone();
+            let mut replaced = false;
+            for op in instr.operands_mut() {
+                if *op == global {
+                    *op = local.clone();
+                    replaced = true;
+                }
+            }
+            if replaced {
+                self.set_instr(id, instr);
+            }
+        }
+        let init = initial_value(var, 0).unwrap();
+        self.insert_value(entry, 0, Instruction::Store { ty: var.var_type.clone(), value: init, ptr: local, len: "4".to_string() }, None);
+    }
+}
+
+/// 全局变量中字节偏移为offset的元素的初值，越界或不是整数、浮点数时返回None
+fn initial_value(var: &GlobalVar, offset: i64) -> Option<Operand> {
+    let (elem, len) = match &var.var_type.width {
+        SymbolWidth::Arr { tar, dims } => (&tar.width, dims.iter().map(|&d| d as i64).product::<i64>()),
+        width => (width, 1),
+    };
+    if offset < 0 || offset % 4 != 0 || offset / 4 >= len {
+        return None;
+    }
+    // 没有初值时全为0
+    let text = var.init_values.get((offset / 4) as usize).map_or("0", |text| text.as_str());
+    match elem {
+        SymbolWidth::I32 => text.parse::<i64>().ok().map(Operand::ConstInt),
+        SymbolWidth::Float if is_float_immediate(text) => Some(Operand::ConstFloat(parse_float(text))),
+        SymbolWidth::Float => text.parse::<f32>().ok().map(Operand::ConstFloat),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::llvm_opt::run_on_text;
+    use crate::structures::llvm_struct::LLVMProgram;
+
+    const INPUT: &str = "\
+@k = global i32 7, align 4
+@pi = global float 0x400921FB60000000, align 4
+@tbl = global [3 x i32] [i32 1, i32 2, i32 3], align 4
+@cnt = global i32 5, align 4
+@shared = global i32 0, align 4
+@unused = global i32 9, align 4
+
+declare void @putint(i32 noundef)
+
+define void @bump() {
+entry:
+  %s = load i32, i32* @shared, align 4
+  %s1 = add i32 %s, 1
+  store i32 %s1, i32* @shared, align 4
+  ret void
+}
+
+define i32 @main(i32 noundef %i) {
+entry:
+  %k = load i32, i32* @k, align 4
+  %pi = load float, float* @pi, align 4
+  %f = fptosi float %pi to i32
+  %p2 = getelementptr [3 x i32], [3 x i32]* @tbl, i32 0, i32 2
+  %t2 = load i32, i32* %p2, align 4
+  %pi_ = getelementptr [3 x i32], [3 x i32]* @tbl, i32 0, i32 %i
+  %ti = load i32, i32* %pi_, align 4
+  %c = load i32, i32* @cnt, align 4
+  %c1 = add i32 %c, %k
+  store i32 %c1, i32* @cnt, align 4
+  call void @bump()
+  %c2 = load i32, i32* @cnt, align 4
+  %r1 = add i32 %c2, %t2
+  %r2 = add i32 %r1, %ti
+  %r3 = add i32 %r2, %f
+  ret i32 %r3
+}";
+
+    #[test]
+    fn fold_and_localize_globals() {
+        let output = run_on_text(INPUT, LLVMProgram::optimize_globals);
+        // 未被写入的@k、@pi与@tbl的常量下标读取替换为初值，只在main中使用的@cnt改为局部变量，
+        // @bump写入的@shared保留，不再使用的全局变量被删除
+        assert_eq!(output, "\
+@tbl = global[3 x i32]  [i32 1, i32 2, i32 3]
+@shared = global i32 0, align 4
+
+declare void @putint(i32 noundef)
+
+define void @bump() {
+entry:
+  %s = load i32, i32* @shared, align 4
+  %s1 = add i32 %s, 1
+  store i32 %s1, i32* @shared, align 4
+  ret void
+}
+
+define i32 @main(i32 noundef %i) {
+entry:
+  %f = fptosi float 0x400921FB60000000 to i32
+  %p2 = getelementptr inbounds [3 x i32], [3 x i32]* @tbl, i32 0, i32 2
+  %pi_ = getelementptr inbounds [3 x i32], [3 x i32]* @tbl, i32 0, i32 %i
+  %ti = load i32, i32* %pi_, align 4
+  %c1 = add i32 5, 7
+  call void @bump()
+  %r1 = add i32 %c1, 3
+  %r2 = add i32 %r1, %ti
+  %r3 = add i32 %r2, %f
+  ret i32 %r3
+}");
+    }
+}
@@ -0,0 +1,232 @@
+use std::collections::{HashMap, HashSet};
+use crate::llvm_opt::purity::*;
+use crate::structures::llvm_struct::*;
+
+impl LLVMProgram {
+    /// 基于支配树的全局值编号：支配者中已计算过的纯指令、纯函数调用与未被改写的load直接复用其结果
+    pub fn gvn(&mut self) {
+        let effects = self.call_effects();
+        self.func_def.iter_mut().for_each(|func| func.gvn(&effects));
+    }
+}
+
+/// 纯指令的编号：操作码、类型与操作数都相同的指令结果相同
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct ExprKey {
+    opcode: String,
+    ty: String,
+    operands: Vec<Operand>,
+}
+
+/// load的编号：同一地址、同一类型
+type LoadKey = (Operand, String);
+
+impl FuncDef {
+    fn gvn(&mut self, effects: &CallEffects) {
+        let cfg = self.build_cfg();
+        let dom = cfg.dominator_tree();
+        let mut exprs: HashMap<ExprKey, ValueId> = HashMap::new();
+        // 各基本块末尾仍然有效的load，供只有支配者一个前驱的子节点继承
+        let mut block_loads: Vec<Option<HashMap<LoadKey, ValueId>>> = vec![None; self.blocks.len()];
+        // 各基本块加入exprs的编号，离开时删除
+        let mut inserted: Vec<Vec<ExprKey>> = vec![vec![]; self.blocks.len()];
+        let mut dead = HashSet::new();
+
+        // 栈中元素为<基本块, 是否为离开事件>
+        let mut events = vec![(dom.entry, false)];
+        while let Some((b, leaving)) = events.pop() {
+            if leaving {
+                inserted[b.0].iter().for_each(|key| { exprs.remove(key); });
+                continue;
+            }
+            events.push((b, true));
+            dom.children[b.0].iter().rev().for_each(|&c| events.push((c, false)));
+
+            // 只有从直接支配者进入时，内存才与支配者末尾相同，否则其他路径上可能有store
+            let mut loads = match dom.idom[b.0] {
+                Some(idom) if cfg.preds[b.0] == [idom] => block_loads[idom.0].clone().unwrap_or_default(),
+                _ => HashMap::new(),
+            };
+            for id in self.block(b).nor_ins.clone() {
+                match self.instr(id) {
+                    Instruction::Load { ty, ptr, .. } => {
+                        let key = (ptr.clone(), ty.get_typename());
+                        match loads.get(&key) {
+                            Some(&prev) => {
+                                self.replace_all_uses(id, &Operand::Value(prev));
+                                dead.insert(id);
+                            },
+                            None => { loads.insert(key, id); },
+                        }
+                    },
+                    Instruction::Store { ptr, .. } => {
+                        let ptr = ptr.clone();
+                        loads.retain(|(addr, _), _| !self.may_alias(addr, &ptr));
+                    },
+                    Instruction::Call(name, _, _) if effects.may_write(name) => loads.clear(),
+                    instr => {
+                        let key = match expr_key(instr, effects) {
+                            Some(key) => key,
+                            None => continue,
+                        };
+                        match exprs.get(&key) {
+                            Some(&prev) => {
+                                self.replace_all_uses(id, &Operand::Value(prev));
+                                dead.insert(id);
+                            },
+                            None => {
+                                exprs.insert(key.clone(), id);
+                                inserted[b.0].push(key);
+                            },
+                        }
+                    },
+                }
+            }
+            block_loads[b.0] = Some(loads);
+        }
+        self.erase_values(&dead);
+    }
+}
+
+/// 纯指令的编号，其他指令返回None；可交换的运算按固定顺序排列操作数
+fn expr_key(instr: &Instruction, effects: &CallEffects) -> Option<ExprKey> {
+    let (opcode, ty, commutative) = match instr {
+        Instruction::Add(bin_op) => ("add", bin_op.op_type.get_typename(), true),
+        Instruction::Sub(bin_op) => ("sub", bin_op.op_type.get_typename(), false),
+        Instruction::Mul(bin_op) => ("mul", bin_op.op_type.get_typename(), true),
+        Instruction::Sdiv(bin_op) => ("sdiv", bin_op.op_type.get_typename(), false),
+        Instruction::Srem(bin_op) => ("srem", bin_op.op_type.get_typename(), false),
+        Instruction::Fadd(bin_op) => ("fadd", bin_op.op_type.get_typename(), true),
+        Instruction::Fsub(bin_op) => ("fsub", bin_op.op_type.get_typename(), false),
+        Instruction::Fmul(bin_op) => ("fmul", bin_op.op_type.get_typename(), true),
+        Instruction::Fdiv(bin_op) => ("fdiv", bin_op.op_type.get_typename(), false),
+        Instruction::Cmp(cond, bin_op) => {
+            let commutative = cond == "eq" || cond == "ne";
+            return Some(make_key(format!("icmp {}", cond), bin_op.op_type.get_typename(), instr, commutative));
+        },
+        Instruction::Fcmp(cond, bin_op) => {
+            let commutative = cond == "oeq" || cond == "one";
+            return Some(make_key(format!("fcmp {}", cond), bin_op.op_type.get_typename(), instr, commutative));
+        },
+        Instruction::ZeroExt(cast_op) => ("zext", format!("{} {}", cast_op.type_1.get_typename(), cast_op.type_2.get_typename()), false),
+        Instruction::I32ToFloat(cast_op) => ("sitofp", format!("{} {}", cast_op.type_1.get_typename(), cast_op.type_2.get_typename()), false),
+        Instruction::FloatToI32(cast_op) => ("fptosi", format!("{} {}", cast_op.type_1.get_typename(), cast_op.type_2.get_typename()), false),
+        Instruction::GetElemPtr(ty, _, _) => ("getelementptr", ty.get_typename(), false),
+        Instruction::BitCast(ty1, _, ty2) => ("bitcast", format!("{} {}", ty1.get_typename(), ty2.get_typename()), false),
+        Instruction::Call(name, ty, _) if effects.purity(name) == Purity::Pure => {
+            return Some(make_key(format!("call {}", name), ty.get_typename(), instr, false));
+        },
+        _ => return None,
+    };
+    Some(make_key(opcode.to_string(), ty, instr, commutative))
+}
+
+fn make_key(opcode: String, ty: String, instr: &Instruction, commutative: bool) -> ExprKey {
+    let mut operands: Vec<Operand> = instr.operands().into_iter().cloned().collect();
+    if commutative && operand_rank(&operands[1]) < operand_rank(&operands[0]) {
+        operands.swap(0, 1);
+    }
+    ExprKey { opcode, ty, operands }
+}
+
+/// 可交换运算中操作数的排列顺序
+fn operand_rank(op: &Operand) -> (usize, i64, String) {
+    match op {
+        Operand::Value(id) => (0, id.0 as i64, String::new()),
+        Operand::Arg(idx) => (1, *idx as i64, String::new()),
+        Operand::Global(name) => (2, 0, name.clone()),
+        Operand::ConstInt(num) => (3, *num, String::new()),
+        Operand::ConstFloat(num) => (4, num.to_bits() as i64, String::new()),
+        Operand::Null => (5, 0, String::new()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::llvm_opt::run_on_text;
+    use crate::structures::llvm_struct::LLVMProgram;
+
+    const INPUT: &str = "\
+@g = global i32 0, align 4
+
+declare i32 @getint()
+declare void @putint(i32 noundef)
+
+define i32 @sq(i32 noundef %x) {
+entry:
+  %r = mul i32 %x, %x
+  ret i32 %r
+}
+
+define i32 @main() {
+entry:
+  %x = call i32 @getint()
+  %y = call i32 @getint()
+  %a = add i32 %x, %y
+  %s1 = call i32 @sq(i32 noundef %x)
+  %s2 = call i32 @sq(i32 noundef %x)
+  %l1 = load i32, i32* @g, align 4
+  %l2 = load i32, i32* @g, align 4
+  %f1 = sitofp i32 %x to float
+  %p = fmul float %f1, 0x0000000000000000
+  %n = fmul float %f1, 0x8000000000000000
+  %c = icmp slt i32 %x, %y
+  br i1 %c, label %then, label %else
+
+then:
+  %b = add i32 %y, %x
+  %m1 = mul i32 %x, 2
+  store i32 %b, i32* @g, align 4
+  %l3 = load i32, i32* @g, align 4
+  call void @putint(i32 noundef %l3)
+  %l4 = load i32, i32* @g, align 4
+  br label %end
+
+else:
+  %m2 = mul i32 %x, 2
+  br label %end
+
+end:
+  %l5 = load i32, i32* @g, align 4
+  %m3 = mul i32 %x, 2
+  ret i32 %m3
+}";
+
+    /// %b与%a交换操作数后相同；纯函数调用与没有被改写的load被复用，putint不写内存；
+    /// 兄弟块中的%m1、%m2与汇合处的%m3互不支配，不合并；乘以0.0与-0.0的结果不同
+    #[test]
+    fn reuse_dominating_values() {
+        let output = run_on_text(INPUT, LLVMProgram::gvn);
+        let main = &output[output.find("define i32 @main").unwrap()..];
+        assert_eq!(main, "\
+define i32 @main() {
+entry:
+  %x = call i32 @getint()
+  %y = call i32 @getint()
+  %a = add i32 %x, %y
+  %s1 = call i32 @sq(i32 noundef %x)
+  %l1 = load i32, i32* @g, align 4
+  %f1 = sitofp i32 %x to float
+  %p = fmul float %f1, 0x0000000000000000
+  %n = fmul float %f1, 0x8000000000000000
+  %c = icmp slt i32 %x, %y
+  br i1 %c, label %then, label %else
+
+then:
+  %m1 = mul i32 %x, 2
+  store i32 %a, i32* @g, align 4
+  %l3 = load i32, i32* @g, align 4
+  call void @putint(i32 noundef %l3)
+  br label %end
+
+else:
+  %m2 = mul i32 %x, 2
+  br label %end
+
+end:
+  %l5 = load i32, i32* @g, align 4
+  %m3 = mul i32 %x, 2
+  ret i32 %m3
+}");
+    }
+}
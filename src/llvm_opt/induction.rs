@@ -0,0 +1,203 @@
+use crate::llvm_opt::loops::*;
+use crate::structures::llvm_struct::*;
+use crate::structures::symbol::*;
+
+/// 基本归纳变量：循环头中形如 i = phi [init, 预备块], [i + step, 回边起点] 的phi
+#[derive(Debug, Clone)]
+pub struct InductionVar {
+    /// 循环头中的phi
+    pub phi: ValueId,
+    /// 进入循环时的初值
+    pub init: Operand,
+    /// 每次迭代的增量，不为0
+    pub step: i64,
+    /// 回边上传回phi的 i + step
+    pub next: ValueId,
+}
+
+/// 计数循环：只在循环头离开，每次迭代开始时比较归纳变量与循环不变的边界
+#[derive(Debug, Clone)]
+pub struct CountedLoop {
+    /// 控制循环的归纳变量
+    pub iv: InductionVar,
+    /// 继续循环的条件 iv pred bound，递增时为slt或sle，递减时为sgt或sge
+    pub pred: String,
+    /// 循环不变的边界
+    pub bound: Operand,
+    /// 循环头留在循环中的后继
+    pub body: BlockId,
+    /// 循环头离开循环的后继
+    pub exit: BlockId,
+}
+
+impl FuncDef {
+    /// 操作数在循环l中是否不变：常量、参数、全局变量，以及定义在循环外的值
+    pub fn is_invariant(&self, info: &LoopInfo, l: usize, op: &Operand) -> bool {
+        match op {
+            Operand::Value(id) => self.values[id.0].block.map_or(true, |bb| !info.contains(l, bb)),
+            _ => true,
+        }
+    }
+
+    /// 循环l的基本归纳变量，循环需要有预备块且只有一条回边
+    pub fn induction_vars(&self, info: &LoopInfo, l: usize) -> Vec<InductionVar> {
+        let lp = &info.loops[l];
+        let (preheader, latch) = match (lp.preheader, &lp.latches[..]) {
+            (Some(preheader), [latch]) => (preheader, *latch),
+            _ => return vec![],
+        };
+        self.block(lp.header).phi_ins.iter().filter_map(|&phi| {
+            let candidates = match self.instr(phi) {
+                Instruction::Phi(ty, candidates) if ty.width == SymbolWidth::I32 && candidates.len() == 2 => candidates,
+                _ => return None,
+            };
+            let init = candidates.iter().find(|(_, pred)| *pred == preheader)?.0.clone();
+            let next = candidates.iter().find(|(_, pred)| *pred == latch)?.0.as_value()?;
+            let step = match self.instr(next) {
+                Instruction::Add(bin_op) => match (&bin_op.op1, &bin_op.op2) {
+                    (Operand::Value(v), Operand::ConstInt(step)) | (Operand::ConstInt(step), Operand::Value(v)) if *v == phi => *step,
+                    _ => return None,
+                },
+                _ => return None,
+            };
+            (step != 0).then_some(InductionVar { phi, init, step, next })
+        }).collect()
+    }
+
+    /// 识别计数循环：循环头是唯一离开循环的块，其条件为归纳变量与循环不变量的有符号比较，且随迭代趋向于不满足
+    pub fn counted_loop(&self, info: &LoopInfo, l: usize) -> Option<CountedLoop> {
+        let lp = &info.loops[l];
+        if lp.exits.is_empty() || lp.exits.iter().any(|&(from, _)| from != lp.header) {
+            return None;
+        }
+        let (cond, label1, label2) = match self.instr(self.block(lp.header).ter_ins?) {
+            Instruction::Br(Some(Operand::Value(cond)), label1, Some(label2)) => (*cond, *label1, *label2),
+            _ => return None,
+        };
+        let (pred, bin_op) = match self.instr(cond) {
+            Instruction::Cmp(pred, bin_op) if self.values[cond.0].block == Some(lp.header) => (pred.as_str(), bin_op),
+            _ => return None,
+        };
+        let ivs = self.induction_vars(info, l);
+        let find = |op: &Operand| ivs.iter().find(|iv| *op == Operand::Value(iv.phi)).cloned();
+        // 归纳变量放在比较的左边
+        let (iv, bound, pred) = match (find(&bin_op.op1), find(&bin_op.op2)) {
+            (Some(iv), None) => (iv, bin_op.op2.clone(), pred.to_string()),
+            (None, Some(iv)) => (iv, bin_op.op1.clone(), swap_pred(pred)?),
+            _ => return None,
+        };
+        // 条件为真时离开循环的，取反得到继续循环的条件
+        let (body, exit, pred) = if info.contains(l, label1) {
+            (label1, label2, pred)
+        } else {
+            (label2, label1, negate_pred(&pred)?)
+        };
+        let converges = match pred.as_str() {
+            "slt" | "sle" => iv.step > 0,
+            "sgt" | "sge" => iv.step < 0,
+            _ => false,
+        };
+        (converges && self.is_invariant(info, l, &bound)).then_some(CountedLoop { iv, pred, bound, body, exit })
+    }
+}
+
+impl CountedLoop {
+    /// 初值与边界都为常量时的迭代次数，超过limit时返回None
+    pub fn const_trip_count(&self, limit: usize) -> Option<usize> {
+        let (mut i, bound) = match (&self.iv.init, &self.bound) {
+            (Operand::ConstInt(init), Operand::ConstInt(bound)) => (*init as i32, *bound as i32),
+            _ => return None,
+        };
+        let mut count = 0;
+        while compare(&self.pred, i, bound) {
+            if count == limit {
+                return None;
+            }
+            count += 1;
+            i = i.wrapping_add(self.iv.step as i32);
+        }
+        Some(count)
+    }
+}
+
+/// 有符号比较a pred b
+pub fn compare(pred: &str, a: i32, b: i32) -> bool {
+    match pred {
+        "slt" => a < b,
+        "sle" => a <= b,
+        "sgt" => a > b,
+        "sge" => a >= b,
+        "eq" => a == b,
+        "ne" => a != b,
+        _ => unreachable!(),
+    }
+}
+
+/// 交换比较的两个操作数后的条件
+fn swap_pred(pred: &str) -> Option<String> {
+    let swapped = match pred {
+        "slt" => "sgt",
+        "sle" => "sge",
+        "sgt" => "slt",
+        "sge" => "sle",
+        "eq" | "ne" => pred,
+        _ => return None,
+    };
+    Some(swapped.to_string())
+}
+
+/// 条件取反
+fn negate_pred(pred: &str) -> Option<String> {
+    let negated = match pred {
+        "slt" => "sge",
+        "sle" => "sgt",
+        "sgt" => "sle",
+        "sge" => "slt",
+        "eq" => "ne",
+        "ne" => "eq",
+        _ => return None,
+    };
+    Some(negated.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::llvm_gen::read_text::read_llvm;
+    use crate::structures::llvm_struct::*;
+
+    #[test]
+    fn negative_step() {
+        // %i从10每次减2，%j每次加0不是归纳变量；条件为%i大于0时继续，向不满足的方向收敛
+        let input = "\
+define i32 @main() {
+entry:
+  br label %loop
+
+loop:
+  %i = phi i32 [ 10, %entry ], [ %i1, %body ]
+  %j = phi i32 [ 0, %entry ], [ %j1, %body ]
+  %c = icmp sle i32 %i, 0
+  br i1 %c, label %exit, label %body
+
+body:
+  %i1 = add i32 -2, %i
+  %j1 = add i32 %j, 0
+  br label %loop
+
+exit:
+  ret i32 %j
+}";
+        let llvm = read_llvm(input).unwrap();
+        let func = &llvm.func_def[0];
+        let info = func.loop_info();
+        let ivs = func.induction_vars(&info, 0);
+        assert_eq!(ivs.len(), 1);
+        assert_eq!(func.values[ivs[0].phi.0].name.as_deref(), Some("%i"));
+        assert_eq!((ivs[0].init.clone(), ivs[0].step), (Operand::ConstInt(10), -2));
+
+        let counted = func.counted_loop(&info, 0).unwrap();
+        assert_eq!((counted.pred.as_str(), counted.bound.clone()), ("sgt", Operand::ConstInt(0)));
+        assert_eq!(counted.const_trip_count(100), Some(5));
+        assert_eq!(counted.const_trip_count(4), None);
+    }
+}
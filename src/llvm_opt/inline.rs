@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+use crate::structures::llvm_struct::*;
+use crate::structures::scopes::Labels;
+use crate::structures::symbol::*;
+
+/// 只有一个调用点的函数，内联后不再需要保留调用的开销，阈值放宽为原来的倍数
+const SINGLE_CALL_SITE_FACTOR: usize = 4;
+/// 调用者的规模超过此值后不再向其中内联，避免函数过大导致寄存器分配时大量溢出
+const MAX_CALLER_SIZE: usize = 2000;
+
+impl LLVMProgram {
+    /// 函数内联：被调用者先于调用者处理，将规模不超过阈值的非递归函数展开到调用处，阈值为0时不内联
+    pub fn inline(&mut self, threshold: usize) {
+        let graph = self.call_graph();
+        let recursive = graph.recursive();
+        let mut call_sites: HashMap<String, usize> = HashMap::new();
+        for func in self.func_def.iter() {
+            for id in func.all_instrs() {
+                if let Instruction::Call(name, _, _) = func.instr(id) {
+                    *call_sites.entry(name.clone()).or_insert(0) += 1;
+                }
+            }
+        }
+
+        // 被调用者所在的强连通分量排在调用者之前
+        for name in graph.sccs().into_iter().flatten() {
+            let caller = self.func_def.iter().position(|func| func.func_name == name).unwrap();
+            let mut caller_size = self.func_def[caller].inline_size();
+            let mut labels = Labels::new();
+            for call in self.func_def[caller].all_instrs() {
+                let callee = match self.func_def[caller].instr(call) {
+                    Instruction::Call(callee, _, _) if graph.callees.contains_key(callee) && !recursive.contains(callee) => callee.clone(),
+                    _ => continue,
+                };
+                // 被调用者已经处理过，其中可内联的调用都已展开
+                let callee = self.func_def.iter().find(|func| func.func_name == callee).unwrap().clone();
+                let size = callee.inline_size();
+                let limit = match call_sites[&callee.func_name] {
+                    1 => threshold * SINGLE_CALL_SITE_FACTOR,
+                    _ => threshold,
+                };
+                if size > limit || caller_size + size > MAX_CALLER_SIZE || !callee.returns() || callee.has_loop() {
+                    continue;
+                }
+                self.func_def[caller].inline_call(call, &callee, &labels.pop_block("inline"));
+                caller_size += size;
+            }
+        }
+    }
+}
+
+impl FuncDef {
+    /// 内联时衡量函数规模的指令数，不计注释
+    fn inline_size(&self) -> usize {
+        self.all_instrs().into_iter().filter(|&id| !matches!(self.instr(id), Instruction::Comment(_))).count()
+    }
+
+    /// 含有循环的函数，调用的开销相对函数体的执行可以忽略，展开后反而增加调用者中循环的寄存器压力
+    fn has_loop(&self) -> bool {
+        !self.loop_info().loops.is_empty()
+    }
+
+    /// 函数是否会返回，死循环的函数展开后调用处之后的代码不可达，不内联
+    fn returns(&self) -> bool {
+        self.layout_blocks().any(|(_, block)| {
+            block.ter_ins.map_or(false, |ter| matches!(self.instr(ter), Instruction::Ret(_, _)))
+        })
+    }
+
+    /// 将调用指令call展开为callee的函数体，复制的基本块以prefix为前缀重新命名
+    fn inline_call(&mut self, call: ValueId, callee: &FuncDef, prefix: &str) {
+        let bb = self.values[call.0].block.unwrap();
+        let (ret_ty, args) = match self.instr(call) {
+            Instruction::Call(_, ty, params) => (ty.clone(), params.iter().map(|(arg, _)| arg.clone()).collect::<Vec<_>>()),
+            _ => unreachable!(),
+        };
+        let depth = self.block(bb).depth;
+
+        // 调用之后的指令移到新的基本块，作为被调用者各处返回的汇合点
+        let cont = self.add_block(&format!("{}_end", prefix), depth);
+        let pos = self.block(bb).nor_ins.iter().position(|&id| id == call).unwrap();
+        let rest = self.block_mut(bb).nor_ins.split_off(pos + 1);
+        let ter = self.block_mut(bb).ter_ins.take();
+        for &id in rest.iter().chain(ter.iter()) {
+            self.values[id.0].block = Some(cont);
+        }
+        self.block_mut(cont).nor_ins = rest;
+        self.block_mut(cont).ter_ins = ter;
+        self.retarget_phis(cont, bb);
+
+        let mut block_map = vec![None; callee.blocks.len()];
+        let mut new_blocks = vec![];
+        for (b, block) in callee.layout_blocks() {
+            let label = format!("{}_{}", prefix, block.block_label.trim_start_matches('_'));
+            let new = self.add_block(&label, block.depth + depth);
+            block_map[b.0] = Some(new);
+            new_blocks.push(new);
+        }
+        // 复制的指令按顺序追加到存储区，可以预先确定编号，phi中的前向引用也能直接改写
+        let instrs = callee.all_instrs();
+        let base = self.values.len();
+        let mut value_map = vec![None; callee.values.len()];
+        instrs.iter().enumerate().for_each(|(i, id)| value_map[id.0] = Some(ValueId(base + i)));
+        let map_operand = |op: &Operand| match op {
+            Operand::Arg(idx) => args[*idx].clone(),
+            Operand::Value(id) => Operand::Value(value_map[id.0].unwrap()),
+            _ => op.clone(),
+        };
+        let map_block = |b: BlockId| block_map[b.0].unwrap();
+        let mut returns = vec![];
+        let mut ret_blocks = vec![];
+        for &id in instrs.iter() {
+            let mut instr = callee.instr(id).clone();
+            instr.operands_mut().into_iter().for_each(|op| *op = map_operand(op));
+            let new_bb = map_block(callee.values[id.0].block.unwrap());
+            let instr = match instr {
+                Instruction::Phi(ty, candidates) => {
+                    Instruction::Phi(ty, candidates.into_iter().map(|(value, pred)| (value, map_block(pred))).collect())
+                },
+                Instruction::Br(cond, label1, label2) => Instruction::Br(cond, map_block(label1), label2.map(map_block)),
+                Instruction::Ret(_, value) => {
+                    returns.extend(value.map(|value| (value, new_bb)));
+                    ret_blocks.push(new_bb);
+                    Instruction::Br(None, cont, None)
+                },
+                instr => instr,
+            };
+            self.push_value_unlinked(new_bb, instr, None);
+        }
+        (base..self.values.len()).for_each(|id| self.link_uses(ValueId(id)));
+
+        if ret_ty.width != SymbolWidth::Void {
+            let value = match &returns[..] {
+                [(value, _)] => value.clone(),
+                _ => Operand::Value(self.push_value(cont, Instruction::Phi(ret_ty, returns), None)),
+            };
+            self.replace_all_uses(call, &value);
+        }
+        self.erase_value(call);
+        self.push_value(bb, Instruction::Br(None, new_blocks[0], None), None);
+        let pos = self.layout.iter().position(|&b| b == bb).unwrap() + 1;
+        self.layout.splice(pos..pos, new_blocks.iter().cloned().chain(std::iter::once(cont)));
+
+        // 调用处与被调用者的入口、唯一的返回处与汇合块之间都是直线跳转，合并为一个基本块
+        let callee_entry = new_blocks[0];
+        let entry_merged = callee.build_cfg().preds[callee.entry().0].is_empty();
+        if entry_merged {
+            self.merge_into_pred(bb, callee_entry);
+        }
+        if let [ret_block] = ret_blocks[..] {
+            let ret_block = if entry_merged && ret_block == callee_entry { bb } else { ret_block };
+            self.merge_into_pred(ret_block, cont);
+        }
+    }
+
+    /// 将只能从pred无条件跳转到达、没有phi的基本块bb并入pred
+    fn merge_into_pred(&mut self, pred: BlockId, bb: BlockId) {
+        debug_assert!(self.block(bb).phi_ins.is_empty());
+        let br = self.block(pred).ter_ins.unwrap();
+        self.erase_value(br);
+        let nor_ins = std::mem::take(&mut self.block_mut(bb).nor_ins);
+        let ter = self.block_mut(bb).ter_ins.take();
+        for &id in nor_ins.iter().chain(ter.iter()) {
+            self.values[id.0].block = Some(pred);
+        }
+        self.block_mut(pred).nor_ins.extend(nor_ins);
+        self.block_mut(pred).ter_ins = ter;
+        self.retarget_phis(pred, bb);
+        self.layout.retain(|&b| b != bb);
+    }
+
+    /// 基本块bb的终结指令原本属于old，将其后继中来自old的phi候选改为来自bb
+    fn retarget_phis(&mut self, bb: BlockId, old: BlockId) {
+        for succ in self.successors(bb) {
+            for phi in self.block(succ).phi_ins.clone() {
+                if let Instruction::Phi(ty, candidates) = self.instr(phi) {
+                    let candidates = candidates.iter()
+                        .map(|(value, pred)| (value.clone(), if *pred == old { bb } else { *pred }))
+                        .collect();
+                    self.set_instr(phi, Instruction::Phi(ty.clone(), candidates));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::llvm_opt::run_on_text;
+
+    const INPUT: &str = "\
+declare i32 @getint()
+declare void @putint(i32 noundef)
+
+define i32 @inc(i32 noundef %x) {
+entry:
+  %r = add i32 %x, 1
+  ret i32 %r
+}
+
+define i32 @abs(i32 noundef %x) {
+entry:
+  %c = icmp slt i32 %x, 0
+  br i1 %c, label %neg, label %pos
+
+neg:
+  %n = sub i32 0, %x
+  ret i32 %n
+
+pos:
+  ret i32 %x
+}
+
+define i32 @main() {
+entry:
+  %x = call i32 @getint()
+  %a = call i32 @inc(i32 noundef %x)
+  %b = call i32 @abs(i32 noundef %a)
+  %c = icmp sgt i32 %b, 10
+  br i1 %c, label %then, label %end
+
+then:
+  call void @putint(i32 noundef %b)
+  br label %end
+
+end:
+  %v = phi i32 [ 1, %then ], [ 0, %entry ]
+  ret i32 %v
+}";
+
+    #[test]
+    fn merge_straight_line_blocks() {
+        let output = run_on_text(INPUT, |llvm| llvm.inline(50));
+        let main = &output[output.find("define i32 @main").unwrap()..];
+        // @inc展开后没有多余的基本块，@abs的入口并入调用处，两处返回在汇合块中由phi合并
+        assert_eq!(main, "\
+define i32 @main() {
+entry:
+  %x = call i32 @getint()
+  %0 = add i32 %x, 1
+  %1 = icmp slt i32 %0, 0
+  br i1 %1, label %inline_1_neg, label %inline_1_pos
+
+inline_1_neg:
+  %2 = sub i32 0, %0
+  br label %inline_1_end
+
+inline_1_pos:
+  br label %inline_1_end
+
+inline_1_end:
+  %3 = phi i32 [ %2, %inline_1_neg ], [ %0, %inline_1_pos ]
+  %c = icmp sgt i32 %3, 10
+  br i1 %c, label %then, label %end
+
+then:
+  call void @putint(i32 noundef %3)
+  br label %end
+
+end:
+  %v = phi i32 [ 1, %then ], [ 0, %inline_1_end ]
+  ret i32 %v
+}");
+    }
+}
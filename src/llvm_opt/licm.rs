@@ -0,0 +1,300 @@
+use std::collections::HashSet;
+use crate::llvm_opt::loops::*;
+use crate::llvm_opt::purity::*;
+use crate::structures::llvm_struct::*;
+use crate::structures::symbol::*;
+
+impl LLVMProgram {
+    /// 循环不变量外提：将循环中的不变量提到预备块，并把循环中对全局标量的读写换成局部变量，只在出口写回
+    pub fn licm(&mut self) {
+        let defined: HashSet<String> = self.func_def.iter().map(|func| func.func_name.clone()).collect();
+        let effects = self.call_effects();
+        let scalars: HashSet<String> = self.global_var.iter()
+            .filter(|var| !matches!(var.var_type.width, SymbolWidth::Arr { .. }))
+            .map(|var| var.var_name.clone())
+            .collect();
+        for func in self.func_def.iter_mut() {
+            let ctx = LicmContext { defined: &defined, effects: &effects, scalars: &scalars };
+            func.licm(&ctx);
+        }
+    }
+}
+
+/// 外提时需要的程序信息
+struct LicmContext<'a> {
+    /// 有定义的函数，带@前缀
+    defined: &'a HashSet<String>,
+    /// 各函数的副作用
+    effects: &'a CallEffects,
+    /// 全局标量的名称
+    scalars: &'a HashSet<String>,
+}
+
+impl FuncDef {
+    fn licm(&mut self, ctx: &LicmContext) {
+        let mut info = self.insert_preheaders();
+        if info.loops.is_empty() {
+            return;
+        }
+        if self.promote_globals(ctx) {
+            // 替换成局部变量的读写由mem2reg变为phi，拆分出口边后需要重新分析循环
+            self.mem2reg();
+            info = self.loop_info();
+        }
+        for l in info.inner_to_outer() {
+            self.hoist_invariants(&info, l, ctx);
+        }
+    }
+
+    /// 将循环中不变且可以提前执行的指令依次移到预备块
+    fn hoist_invariants(&mut self, info: &LoopInfo, l: usize, ctx: &LicmContext) {
+        let lp = &info.loops[l];
+        let preheader = match lp.preheader {
+            Some(preheader) => preheader,
+            None => return,
+        };
+        let instrs: Vec<ValueId> = lp.blocks.iter().flat_map(|&b| self.block(b).nor_ins.clone()).collect();
+        let stores: Vec<Operand> = instrs.iter().filter_map(|&id| match self.instr(id) {
+            Instruction::Store { ptr, .. } => Some(ptr.clone()),
+            _ => None,
+        }).collect();
+        let writes_by_call = instrs.iter().any(|&id| {
+            matches!(self.instr(id), Instruction::Call(name, _, _) if ctx.effects.may_write(name))
+        });
+
+        // 按逆后序处理，定义在使用之前，一遍即可提出依赖链上的全部不变量
+        for id in instrs {
+            let invariant = self.instr(id).used_values().iter().all(|v| {
+                self.values[v.0].block.map_or(true, |bb| !info.contains(l, bb))
+            });
+            if !invariant {
+                continue;
+            }
+            let hoistable = match self.instr(id) {
+                Instruction::Load { ptr, .. } => {
+                    !writes_by_call
+                        && self.dereferenceable(ptr)
+                        && stores.iter().all(|store| !self.may_alias(store, ptr))
+                },
+                // 循环头中的指令在进入循环时一定执行，其中的纯函数调用可以提前；只读的调用还要求循环中没有写入
+                // 可能不返回的调用提前后会越过循环头中它之前的指令，不能提出
+                Instruction::Call(name, _, _) if self.values[id.0].block == Some(lp.header) && !ctx.effects.may_diverge(name) => match ctx.effects.purity(name) {
+                    Purity::Pure => true,
+                    Purity::ReadOnly => !writes_by_call && stores.is_empty(),
+                    Purity::SideEffecting => false,
+                },
+                instr => is_speculatable(instr),
+            };
+            if hoistable {
+                let bb = self.values[id.0].block.unwrap();
+                self.block_mut(bb).nor_ins.retain(|&v| v != id);
+                self.block_mut(preheader).nor_ins.push(id);
+                self.values[id.0].block = Some(preheader);
+            }
+        }
+    }
+
+    /// 地址是否一定可以访问，即使原本不会执行到也可以提前读取：全局变量、局部变量，以及常量下标不越界的数组元素
+    fn dereferenceable(&self, ptr: &Operand) -> bool {
+        match ptr {
+            Operand::Global(_) => true,
+            Operand::Value(id) => match self.instr(*id) {
+                Instruction::Alloca { .. } => true,
+                Instruction::BitCast(_, base, _) => self.dereferenceable(base),
+                Instruction::GetElemPtr(ty, base, idx) => {
+                    let dims = match &ty.width {
+                        SymbolWidth::Arr { dims, .. } if dims.first() != Some(&-1) => dims.clone(),
+                        _ => vec![],
+                    };
+                    // 第一个下标跨过指针本身，只能为0；之后的下标依次对应各维
+                    let in_bounds = idx.first() == Some(&Operand::ConstInt(0))
+                        && idx.len() <= dims.len() + 1
+                        && idx.iter().skip(1).zip(dims.iter()).all(|(i, &dim)| {
+                            matches!(i, Operand::ConstInt(i) if *i >= 0 && *i < dim as i64)
+                        });
+                    in_bounds && self.dereferenceable(base)
+                },
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// 对循环中读写过的全局标量，在预备块读入局部变量，循环中改为读写局部变量，在各出口写回
+    /// SysY中不能取标量的地址，因此只有直接以全局变量为地址的访问与有定义的函数能读写它
+    fn promote_globals(&mut self, ctx: &LicmContext) -> bool {
+        let mut promoted = false;
+        loop {
+            let info = self.loop_info();
+            // 外层循环优先，提升后内层循环中不再有对该变量的直接访问
+            let candidate = info.loops.iter().find_map(|lp| {
+                self.promotable_global(lp, ctx).map(|name| (lp.clone(), name))
+            });
+            let (lp, name) = match candidate {
+                Some(candidate) => candidate,
+                None => return promoted,
+            };
+            self.promote_global(&info, &lp, &name);
+            promoted = true;
+        }
+    }
+
+    /// 循环中被store过、且没有调用可能读写内存的有定义函数的全局标量
+    fn promotable_global(&self, lp: &Loop, ctx: &LicmContext) -> Option<String> {
+        lp.preheader?;
+        let mut stored = None;
+        for &b in lp.blocks.iter() {
+            for &id in self.block(b).nor_ins.iter() {
+                match self.instr(id) {
+                    Instruction::Call(name, _, _) if ctx.defined.contains(name) && ctx.effects.purity(name) != Purity::Pure => return None,
+                    Instruction::Store { ptr: Operand::Global(name), .. } if ctx.scalars.contains(name) => {
+                        stored.get_or_insert_with(|| name.clone());
+                    },
+                    _ => {},
+                }
+            }
+        }
+        stored
+    }
+
+    fn promote_global(&mut self, info: &LoopInfo, lp: &Loop, name: &str) {
+        let global = Operand::Global(name.to_string());
+        let accesses: Vec<(ValueId, SymbolType)> = lp.blocks.iter()
+            .flat_map(|&b| self.block(b).nor_ins.clone())
+            .filter_map(|id| match self.instr(id) {
+                Instruction::Load { ty, ptr, .. } | Instruction::Store { ty, ptr, .. } if *ptr == global => Some((id, ty.clone())),
+                _ => None,
+            })
+            .collect();
+        let ty = accesses[0].1.clone();
+        let entry = self.entry();
+        let local = Operand::Value(self.push_value(entry, Instruction::Alloca { ty: ty.clone(), len: "4".to_string() }, None));
+
+        let preheader = lp.preheader.unwrap();
+        let init = self.push_value(preheader, Instruction::Load { ty: ty.clone(), ptr: global.clone(), len: "4".to_string() }, None);
+        self.push_value(preheader, Instruction::Store { ty: ty.clone(), value: Operand::Value(init), ptr: local.clone(), len: "4".to_string() }, None);
+
+        for (id, _) in accesses {
+            let mut instr = self.instr(id).clone();
+            match &mut instr {
+                Instruction::Load { ptr, .. } | Instruction::Store { ptr, .. } => *ptr = local.clone(),
+                _ => unreachable!(),
+            }
+            self.set_instr(id, instr);
+        }
+
+        // 出口块的前驱都在循环中时直接在其开头写回，否则拆分出口边
+        let cfg = self.build_cfg();
+        let l = info.loops.iter().position(|other| other.header == lp.header).unwrap();
+        let mut targets: Vec<BlockId> = vec![];
+        for &(from, to) in lp.exits.iter() {
+            let dedicated = cfg.preds[to.0].iter().all(|&pred| info.contains(l, pred));
+            let target = if dedicated { to } else { self.split_edge(from, to) };
+            if !targets.contains(&target) {
+                targets.push(target);
+            }
+        }
+        for target in targets {
+            let value = self.insert_value(target, 0, Instruction::Load { ty: ty.clone(), ptr: local.clone(), len: "4".to_string() }, None);
+            self.insert_value(target, 1, Instruction::Store { ty: ty.clone(), value: Operand::Value(value), ptr: global.clone(), len: "4".to_string() }, None);
+        }
+    }
+}
+
+/// 提前执行也不会出错的纯指令，除数为非零常量的除法与求余才能提前
+fn is_speculatable(instr: &Instruction) -> bool {
+    match instr {
+        Instruction::Sdiv(bin_op) | Instruction::Srem(bin_op) => {
+            matches!(bin_op.op2, Operand::ConstInt(num) if num != 0)
+        },
+        Instruction::Add(_) | Instruction::Sub(_) | Instruction::Mul(_)
+        | Instruction::Fadd(_) | Instruction::Fsub(_) | Instruction::Fmul(_) | Instruction::Fdiv(_)
+        | Instruction::Cmp(_, _) | Instruction::Fcmp(_, _)
+        | Instruction::ZeroExt(_) | Instruction::I32ToFloat(_) | Instruction::FloatToI32(_)
+        | Instruction::GetElemPtr(_, _, _) | Instruction::BitCast(_, _, _) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::llvm_opt::run_on_text;
+    use crate::structures::llvm_struct::LLVMProgram;
+
+    const INPUT: &str = "\
+@g = global i32 0, align 4
+@arr = global [4 x i32] zeroinitializer, align 4
+
+declare void @putint(i32 noundef)
+
+define i32 @sq(i32 noundef %x) {
+entry:
+  %r = mul i32 %x, %x
+  ret i32 %r
+}
+
+define i32 @main(i32 noundef %n, i32 noundef %d) {
+entry:
+  br label %loop
+
+loop:
+  %i = phi i32 [ 0, %entry ], [ %i1, %body ]
+  %s = call i32 @sq(i32 noundef %n)
+  %c = icmp slt i32 %i, %s
+  br i1 %c, label %body, label %exit
+
+body:
+  %a = add i32 %n, 7
+  %q = sdiv i32 %a, 3
+  %v = sdiv i32 %a, %d
+  %p = getelementptr [4 x i32], [4 x i32]* @arr, i32 0, i32 2
+  %e = load i32, i32* %p, align 4
+  %g0 = load i32, i32* @g, align 4
+  %g1 = add i32 %g0, %q
+  %g2 = add i32 %g1, %e
+  store i32 %g2, i32* @g, align 4
+  call void @putint(i32 noundef %v)
+  %i1 = add i32 %i, 1
+  br label %loop
+
+exit:
+  ret i32 0
+}";
+
+    #[test]
+    fn hoist_invariants_and_promote_globals() {
+        let output = run_on_text(INPUT, LLVMProgram::licm);
+        let main = &output[output.find("define i32 @main").unwrap()..];
+        // 循环头中的纯函数调用、常量除数的除法与不越界的数组读取被提出，除数不确定的除法保留；
+        // @g只在出口写回
+        assert_eq!(main, "\
+define i32 @main(i32 noundef %n, i32 noundef %d) {
+entry:
+  %0 = load i32, i32* @g, align 4
+  %s = call i32 @sq(i32 noundef %n)
+  %a = add i32 %n, 7
+  %q = sdiv i32 %a, 3
+  %p = getelementptr inbounds [4 x i32], [4 x i32]* @arr, i32 0, i32 2
+  %e = load i32, i32* %p, align 4
+  br label %loop
+
+loop:
+  %i = phi i32 [ 0, %entry ], [ %i1, %body ]
+  %1 = phi i32 [ %0, %entry ], [ %g2, %body ]
+  %c = icmp slt i32 %i, %s
+  br i1 %c, label %body, label %exit
+
+body:
+  %v = sdiv i32 %a, %d
+  %g1 = add i32 %1, %q
+  %g2 = add i32 %g1, %e
+  call void @putint(i32 noundef %v)
+  %i1 = add i32 %i, 1
+  br label %loop
+
+exit:
+  store i32 %1, i32* @g, align 4
+  ret i32 0
+}");
+    }
+}
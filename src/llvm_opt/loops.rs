@@ -0,0 +1,386 @@
+use std::collections::HashSet;
+use crate::llvm_opt::dominator::*;
+use crate::structures::llvm_struct::*;
+
+/// 自然循环
+#[derive(Debug, Clone)]
+pub struct Loop {
+    /// 循环头，支配循环中的全部基本块
+    pub header: BlockId,
+    /// 回边的起点
+    pub latches: Vec<BlockId>,
+    /// 循环中的全部基本块，包括内层循环的，按逆后序排列，第一个为循环头
+    pub blocks: Vec<BlockId>,
+    /// 直接包含此循环的外层循环
+    pub parent: Option<usize>,
+    /// 直接嵌套在此循环中的内层循环
+    pub children: Vec<usize>,
+    /// 嵌套深度，最外层循环为1
+    pub depth: usize,
+    /// 预备块：循环外唯一的前驱，且只跳转到循环头
+    pub preheader: Option<BlockId>,
+    /// 出口边<循环中的基本块, 循环外的基本块>
+    pub exits: Vec<(BlockId, BlockId)>,
+}
+
+/// 函数中全部自然循环构成的循环嵌套森林
+#[derive(Debug, Clone)]
+pub struct LoopInfo {
+    /// 按循环头的逆后序排列，外层循环在内层循环之前
+    pub loops: Vec<Loop>,
+    /// 各基本块所属的最内层循环，以BlockId的下标索引
+    pub innermost: Vec<Option<usize>>,
+}
+
+impl ControlFlowGraph {
+    /// 由回边找出自然循环，循环头相同的回边合并为一个循环
+    pub fn loop_info(&self, dom: &DominatorTree) -> LoopInfo {
+        let len = self.succs.len();
+        let mut rpo_num = vec![usize::MAX; len];
+        dom.rpo.iter().enumerate().for_each(|(i, b)| rpo_num[b.0] = i);
+
+        // 回边的终点支配起点
+        let mut loops: Vec<Loop> = vec![];
+        for &header in dom.rpo.iter() {
+            let latches: Vec<BlockId> = self.preds[header.0].iter()
+                .cloned()
+                .filter(|&pred| rpo_num[pred.0] != usize::MAX && dom.dominates(header, pred))
+                .collect();
+            if latches.is_empty() {
+                continue;
+            }
+            // 从回边起点沿前驱反向搜索到循环头为止
+            let mut body: HashSet<BlockId> = HashSet::from([header]);
+            let mut worklist = latches.clone();
+            while let Some(b) = worklist.pop() {
+                if body.insert(b) {
+                    worklist.extend(self.preds[b.0].iter().filter(|p| rpo_num[p.0] != usize::MAX));
+                }
+            }
+            let mut blocks: Vec<BlockId> = body.into_iter().collect();
+            blocks.sort_by_key(|b| rpo_num[b.0]);
+            loops.push(Loop {
+                header,
+                latches,
+                blocks,
+                parent: None,
+                children: vec![],
+                depth: 1,
+                preheader: None,
+                exits: vec![],
+            });
+        }
+
+        // 嵌套的循环头不同，内层循环严格小于外层循环，因此包含一个块的最小循环即其最内层循环
+        let mut by_size: Vec<usize> = (0..loops.len()).collect();
+        by_size.sort_by_key(|&l| loops[l].blocks.len());
+        let mut innermost = vec![None; len];
+        for &l in by_size.iter() {
+            for b in loops[l].blocks.iter() {
+                if innermost[b.0].is_none() {
+                    innermost[b.0] = Some(l);
+                }
+            }
+        }
+        // 外层循环的循环头在逆后序中更早，按顺序处理时外层的深度已经确定
+        for l in 0..loops.len() {
+            let parent = by_size.iter()
+                .cloned()
+                .find(|&outer| outer != l && loops[outer].blocks.len() > loops[l].blocks.len() && loops[outer].blocks.contains(&loops[l].header));
+            if let Some(parent) = parent {
+                loops[l].parent = Some(parent);
+                loops[l].depth = loops[parent].depth + 1;
+                loops[parent].children.push(l);
+            }
+        }
+
+        let mut info = LoopInfo { loops, innermost };
+        for l in 0..info.loops.len() {
+            let header = info.loops[l].header;
+            let outside_preds: Vec<BlockId> = self.preds[header.0].iter()
+                .cloned()
+                .filter(|&pred| !info.contains(l, pred))
+                .collect();
+            if let [pred] = outside_preds[..] {
+                if self.succs[pred.0] == [header] {
+                    info.loops[l].preheader = Some(pred);
+                }
+            }
+            let exits: Vec<(BlockId, BlockId)> = info.loops[l].blocks.iter()
+                .flat_map(|&b| self.succs[b.0].iter().map(move |&s| (b, s)))
+                .filter(|&(_, s)| !info.contains(l, s))
+                .collect();
+            info.loops[l].exits = exits;
+        }
+        info
+    }
+}
+
+impl LoopInfo {
+    /// 基本块的循环嵌套深度，不在循环中为0
+    pub fn depth(&self, bb: BlockId) -> usize {
+        self.innermost[bb.0].map_or(0, |l| self.loops[l].depth)
+    }
+
+    /// 循环l是否包含基本块bb，包括bb在其内层循环中的情况
+    pub fn contains(&self, l: usize, bb: BlockId) -> bool {
+        let mut cur = self.innermost.get(bb.0).cloned().flatten();
+        while let Some(inner) = cur {
+            if inner == l {
+                return true;
+            }
+            cur = self.loops[inner].parent;
+        }
+        false
+    }
+
+    /// 内层循环在外层循环之前的顺序
+    pub fn inner_to_outer(&self) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.loops.len()).collect();
+        order.sort_by_key(|&l| std::cmp::Reverse(self.loops[l].depth));
+        order
+    }
+}
+
+impl LLVMProgram {
+    pub fn update_loop_depths(&mut self) {
+        self.func_def.iter_mut().for_each(|func| func.update_loop_depths());
+    }
+}
+
+impl FuncDef {
+    /// 对当前的控制流图进行循环分析
+    pub fn loop_info(&self) -> LoopInfo {
+        let cfg = self.build_cfg();
+        let dom = cfg.dominator_tree();
+        cfg.loop_info(&dom)
+    }
+
+    /// 按当前的控制流图重新计算各块的循环嵌套深度
+    pub fn update_loop_depths(&mut self) {
+        let info = self.loop_info();
+        for b in self.layout.clone() {
+            self.block_mut(b).depth = info.depth(b);
+        }
+    }
+
+    /// 为没有预备块的循环插入预备块，返回插入后的循环分析结果
+    pub fn insert_preheaders(&mut self) -> LoopInfo {
+        let cfg = self.build_cfg();
+        let info = cfg.loop_info(&cfg.dominator_tree());
+        let mut changed = false;
+        for l in info.loops.iter().filter(|l| l.preheader.is_none()) {
+            let outside_preds: Vec<BlockId> = cfg.preds[l.header.0].iter()
+                .cloned()
+                .filter(|&pred| !l.blocks.contains(&pred))
+                .collect();
+            self.insert_preheader(l.header, &outside_preds);
+            changed = true;
+        }
+        if changed { self.loop_info() } else { info }
+    }
+
+    /// 在循环头之前插入预备块，循环外的前驱改为跳转到预备块，循环头的phi中来自这些前驱的候选移入预备块
+    fn insert_preheader(&mut self, header: BlockId, outside_preds: &[BlockId]) -> BlockId {
+        let label = format!("{}_preheader", self.block(header).block_label);
+        let depth = self.block(header).depth;
+        let preheader = self.add_block(&label, depth.saturating_sub(1));
+        let pos = self.layout.iter().position(|&b| b == header).unwrap();
+        self.layout.insert(pos, preheader);
+        for &pred in outside_preds {
+            self.redirect_branch(pred, header, preheader);
+        }
+        for phi in self.block(header).phi_ins.clone() {
+            let (ty, candidates) = match self.instr(phi) {
+                Instruction::Phi(ty, candidates) => (ty.clone(), candidates.clone()),
+                _ => unreachable!(),
+            };
+            let (outside, mut inside): (Vec<_>, Vec<_>) = candidates.into_iter()
+                .partition(|(_, pred)| outside_preds.contains(pred));
+            // 来自循环外的候选都相同时不需要新的phi
+            let value = match &outside[..] {
+                [] => continue,
+                [(value, _), rest @ ..] if rest.iter().all(|(other, _)| other == value) => value.clone(),
+                _ => Operand::Value(self.push_value(preheader, Instruction::Phi(ty.clone(), outside), None)),
+            };
+            inside.push((value, preheader));
+            self.set_instr(phi, Instruction::Phi(ty, inside));
+        }
+        self.push_value(preheader, Instruction::Br(None, header, None), None);
+        preheader
+    }
+
+    /// 拆分控制流边from -> to，在其上插入一个只跳转到to的基本块
+    pub fn split_edge(&mut self, from: BlockId, to: BlockId) -> BlockId {
+        let label = format!("{}_{}", self.block(from).block_label, self.block(to).block_label);
+        let depth = self.block(from).depth;
+        let mid = self.add_block(&label, depth);
+        let pos = self.layout.iter().position(|&b| b == from).unwrap();
+        self.layout.insert(pos + 1, mid);
+        self.redirect_branch(from, to, mid);
+        for phi in self.block(to).phi_ins.clone() {
+            if let Instruction::Phi(ty, candidates) = self.instr(phi) {
+                let candidates = candidates.iter()
+                    .map(|(value, pred)| (value.clone(), if *pred == from { mid } else { *pred }))
+                    .collect();
+                self.set_instr(phi, Instruction::Phi(ty.clone(), candidates));
+            }
+        }
+        self.push_value(mid, Instruction::Br(None, to, None), None);
+        mid
+    }
+
+    /// 将from的终结指令中跳转到old的目标改为new，phi需由调用者维护
+    pub fn redirect_branch(&mut self, from: BlockId, old: BlockId, new: BlockId) {
+        let ter = self.block(from).ter_ins.unwrap();
+        if let Instruction::Br(cond, label1, label2) = self.instr(ter) {
+            let replace = |label: BlockId| if label == old { new } else { label };
+            let br = Instruction::Br(cond.clone(), replace(*label1), label2.map(replace));
+            self.set_instr(ter, br);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::llvm_gen::read_text::read_llvm;
+    use crate::llvm_opt::run_on_text;
+    use super::LoopInfo;
+    use crate::options::{OptLevel, DEFAULT_INLINE_THRESHOLD, DEFAULT_UNROLL_FACTOR};
+    use crate::structures::llvm_struct::*;
+
+    /// 两层嵌套的循环，之后是一个有两个循环外前驱、两个出口的循环
+    const INPUT: &str = "\
+define i32 @main(i32 noundef %n) {
+entry:
+  br label %outer
+
+outer:
+  %i = phi i32 [ 0, %entry ], [ %i1, %outer_latch ]
+  %c1 = icmp slt i32 %i, %n
+  br i1 %c1, label %inner, label %after
+
+inner:
+  %j = phi i32 [ 0, %outer ], [ %j1, %inner_body ]
+  %c2 = icmp slt i32 %j, %i
+  br i1 %c2, label %inner_body, label %outer_latch
+
+inner_body:
+  %j1 = add i32 %j, 1
+  br label %inner
+
+outer_latch:
+  %i1 = add i32 %i, 1
+  br label %outer
+
+after:
+  %c3 = icmp sgt i32 %i, 5
+  br i1 %c3, label %left, label %right
+
+left:
+  br label %loop
+
+right:
+  br label %loop
+
+loop:
+  %k = phi i32 [ 1, %left ], [ 2, %right ], [ %k1, %body ]
+  %c4 = icmp slt i32 %k, 100
+  br i1 %c4, label %body, label %done
+
+body:
+  %k1 = mul i32 %k, 3
+  %c5 = icmp eq i32 %k1, 81
+  br i1 %c5, label %done, label %loop
+
+done:
+  ret i32 %k
+}";
+
+    fn labels(func: &FuncDef, blocks: &[BlockId]) -> Vec<String> {
+        blocks.iter().map(|&b| func.block(b).block_label.clone()).collect()
+    }
+
+    fn block(func: &FuncDef, label: &str) -> BlockId {
+        func.layout.iter().cloned().find(|&b| func.block(b).block_label == label).unwrap()
+    }
+
+    fn find_loop(func: &FuncDef, info: &LoopInfo, header: &str) -> usize {
+        info.loops.iter().position(|l| l.header == block(func, header)).unwrap()
+    }
+
+    #[test]
+    fn natural_loops_and_nesting() {
+        let llvm = read_llvm(INPUT).unwrap();
+        let func = &llvm.func_def[0];
+        let info = func.loop_info();
+        assert_eq!(info.loops.len(), 3);
+
+        let (o, i, k) = (find_loop(func, &info, "outer"), find_loop(func, &info, "inner"), find_loop(func, &info, "loop"));
+        // 外层循环在内层循环之前
+        assert!(o < i);
+        let outer = &info.loops[o];
+        assert_eq!(labels(func, &outer.blocks), ["outer", "inner", "outer_latch", "inner_body"]);
+        assert_eq!(labels(func, &outer.latches), ["outer_latch"]);
+        assert_eq!((outer.parent, outer.children.clone(), outer.depth), (None, vec![i], 1));
+        assert_eq!(outer.preheader, Some(block(func, "entry")));
+        assert_eq!(outer.exits, vec![(block(func, "outer"), block(func, "after"))]);
+
+        let inner = &info.loops[i];
+        assert_eq!(labels(func, &inner.blocks), ["inner", "inner_body"]);
+        assert_eq!((inner.parent, inner.depth), (Some(o), 2));
+        // outer还会跳转到循环外，不是预备块
+        assert_eq!(inner.preheader, None);
+        assert_eq!(inner.exits, vec![(block(func, "inner"), block(func, "outer_latch"))]);
+
+        let other = &info.loops[k];
+        assert_eq!(labels(func, &other.blocks), ["loop", "body"]);
+        assert_eq!(other.preheader, None);
+        assert_eq!(other.exits, vec![(block(func, "loop"), block(func, "done")), (block(func, "body"), block(func, "done"))]);
+
+        assert_eq!(info.depth(block(func, "entry")), 0);
+        assert_eq!(info.depth(block(func, "inner_body")), 2);
+        assert_eq!(info.depth(block(func, "outer_latch")), 1);
+        assert!(info.contains(o, block(func, "inner_body")));
+        assert!(!info.contains(i, block(func, "outer_latch")));
+        let order = info.inner_to_outer();
+        assert!(order.iter().position(|&l| l == i) < order.iter().position(|&l| l == o));
+    }
+
+    #[test]
+    fn insert_preheaders() {
+        let mut llvm = read_llvm(INPUT).unwrap();
+        let func = &mut llvm.func_def[0];
+        let info = func.insert_preheaders();
+        assert!(info.loops.iter().all(|l| l.preheader.is_some()));
+
+        let output = run_on_text(INPUT, |llvm| { llvm.func_def[0].insert_preheaders(); });
+        // 循环外的两个前驱带来的不同初值在预备块中合并
+        assert!(output.contains("\
+loop_preheader:
+  %0 = phi i32 [ 1, %left ], [ 2, %right ]
+  br label %loop
+
+loop:
+  %k = phi i32 [ %k1, %body ], [ %0, %loop_preheader ]"), "{}", output);
+        assert!(output.contains("\
+inner_preheader:
+  br label %inner
+
+inner:
+  %j = phi i32 [ %j1, %inner_body ], [ 0, %inner_preheader ]"), "{}", output);
+    }
+
+    #[test]
+    fn update_depths_after_optimisation() {
+        // 读入的IR中深度都是0，展开与插入预备块也只粗略地设置新块的深度
+        let mut llvm = read_llvm(INPUT).unwrap();
+        llvm.optimise_llvm(OptLevel::O2, DEFAULT_INLINE_THRESHOLD, DEFAULT_UNROLL_FACTOR);
+        let func = &llvm.func_def[0];
+        let info = func.loop_info();
+        assert!(func.layout.iter().any(|&b| info.depth(b) == 2), "{:?}", labels(func, &func.layout));
+        for &b in func.layout.iter() {
+            assert_eq!(func.block(b).depth, info.depth(b), "{}", func.block(b).block_label);
+        }
+    }
+}
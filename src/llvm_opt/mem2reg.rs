@@ -0,0 +1,297 @@
+use std::collections::{HashMap, HashSet};
+use crate::llvm_opt::dominator::*;
+use crate::llvm_opt::eliminate::unreachable_code_eliminate;
+use crate::structures::llvm_struct::*;
+use crate::structures::symbol::*;
+
+impl LLVMProgram {
+    /// 将只被load/store访问的标量alloca提升为SSA值，并在汇合点插入phi
+    pub fn mem2reg(&mut self) {
+        self.func_def.iter_mut().for_each(|func| func.mem2reg());
+    }
+}
+
+/// 可提升的局部变量
+struct PromotedVar {
+    alloca: ValueId,
+    ty: SymbolType,
+}
+
+impl FuncDef {
+    pub(crate) fn mem2reg(&mut self) {
+        unreachable_code_eliminate(self);
+        let vars = self.promotable_vars();
+        if vars.is_empty() {
+            return;
+        }
+        let var_idx: HashMap<ValueId, usize> = vars.iter().enumerate().map(|(i, v)| (v.alloca, i)).collect();
+        let cfg = self.build_cfg();
+        let dom = cfg.dominator_tree();
+
+        // 每个基本块中各变量对应的phi，<变量下标, phi>
+        let phis = self.insert_phis(&vars, &var_idx, &cfg, &dom);
+        let mut dead = self.rename(&vars, &var_idx, &cfg, &dom, &phis);
+        dead.extend(vars.iter().map(|v| v.alloca));
+        self.erase_values(&dead);
+    }
+
+    /// 找出类型为标量或指针，且只作为load/store地址使用的alloca
+    fn promotable_vars(&self) -> Vec<PromotedVar> {
+        self.local_vars.iter().filter_map(|&alloca| {
+            let ty = match self.instr(alloca) {
+                Instruction::Alloca { ty, .. } => ty,
+                _ => return None,
+            };
+            let scalar = match &ty.width {
+                SymbolWidth::I32 | SymbolWidth::Float | SymbolWidth::Bool => true,
+                SymbolWidth::Arr { tar: _, dims } => dims[0] == -1,
+                _ => false,
+            };
+            let only_load_store = self.values[alloca.0].users.iter().all(|&user| match self.instr(user) {
+                Instruction::Load { .. } => true,
+                Instruction::Store { value, .. } => value != &Operand::Value(alloca),
+                _ => false,
+            });
+            if scalar && only_load_store {
+                Some(PromotedVar { alloca, ty: ty.clone() })
+            } else {
+                None
+            }
+        }).collect()
+    }
+
+    /// 若指令是对被提升变量的load或store，返回变量下标
+    fn promoted_access(&self, instr: ValueId, var_idx: &HashMap<ValueId, usize>) -> Option<usize> {
+        match self.instr(instr) {
+            Instruction::Load { ptr: Operand::Value(ptr), .. }
+            | Instruction::Store { ptr: Operand::Value(ptr), .. } => var_idx.get(ptr).cloned(),
+            _ => None,
+        }
+    }
+
+    /// 在迭代支配边界上插入phi，只在变量入口活跃的基本块插入(pruned SSA)
+    fn insert_phis(
+        &mut self,
+        vars: &[PromotedVar],
+        var_idx: &HashMap<ValueId, usize>,
+        cfg: &ControlFlowGraph,
+        dom: &DominatorTree,
+    ) -> Vec<Vec<(usize, ValueId)>> {
+        // 各变量被store的基本块，以及在store之前就被load的基本块
+        let mut def_blocks = vec![HashSet::new(); vars.len()];
+        let mut use_blocks = vec![HashSet::new(); vars.len()];
+        for &bb in self.layout.iter() {
+            let mut defined = HashSet::new();
+            for &instr in self.block(bb).nor_ins.iter() {
+                if let Some(v) = self.promoted_access(instr, var_idx) {
+                    if let Instruction::Store { .. } = self.instr(instr) {
+                        def_blocks[v].insert(bb);
+                        defined.insert(v);
+                    } else if !defined.contains(&v) {
+                        use_blocks[v].insert(bb);
+                    }
+                }
+            }
+        }
+
+        let mut phis = vec![vec![]; self.blocks.len()];
+        for (v, var) in vars.iter().enumerate() {
+            // 变量在入口活跃的基本块
+            let mut live_in = use_blocks[v].clone();
+            let mut worklist: Vec<BlockId> = live_in.iter().cloned().collect();
+            while let Some(b) = worklist.pop() {
+                for &p in cfg.preds[b.0].iter() {
+                    if !def_blocks[v].contains(&p) && live_in.insert(p) {
+                        worklist.push(p);
+                    }
+                }
+            }
+
+            let mut has_phi = HashSet::new();
+            let mut worklist: Vec<BlockId> = def_blocks[v].iter().cloned().collect();
+            while let Some(b) = worklist.pop() {
+                for &df in dom.frontier[b.0].iter() {
+                    if live_in.contains(&df) && has_phi.insert(df) {
+                        let phi = self.push_value(df, Instruction::Phi(var.ty.clone(), vec![]), None);
+                        phis[df.0].push((v, phi));
+                        if !def_blocks[v].contains(&df) {
+                            worklist.push(df);
+                        }
+                    }
+                }
+            }
+        }
+        phis
+    }
+
+    /// 沿支配树先序重命名，将load替换为变量当前的值，返回需要删除的load/store
+    fn rename(
+        &mut self,
+        vars: &[PromotedVar],
+        var_idx: &HashMap<ValueId, usize>,
+        cfg: &ControlFlowGraph,
+        dom: &DominatorTree,
+        phis: &[Vec<(usize, ValueId)>],
+    ) -> HashSet<ValueId> {
+        // 未初始化的变量取对应类型的零值
+        let zeros: Vec<Operand> = vars.iter().map(|v| match &v.ty.width {
+            SymbolWidth::Float => Operand::ConstFloat(0.0),
+            SymbolWidth::Arr { .. } => Operand::Null,
+            _ => Operand::ConstInt(0),
+        }).collect();
+        let mut stacks: Vec<Vec<Operand>> = vec![vec![]; vars.len()];
+        let mut dead = HashSet::new();
+
+        // 栈中元素为<基本块, 是否为离开事件>，离开时弹出该块压入的值
+        let mut pushed: Vec<Vec<usize>> = vec![vec![]; self.blocks.len()];
+        let mut events = vec![(dom.entry, false)];
+        while let Some((b, leaving)) = events.pop() {
+            if leaving {
+                pushed[b.0].iter().for_each(|&v| { stacks[v].pop(); });
+                continue;
+            }
+            events.push((b, true));
+            dom.children[b.0].iter().rev().for_each(|&c| events.push((c, false)));
+
+            for &(v, phi) in phis[b.0].iter() {
+                stacks[v].push(Operand::Value(phi));
+                pushed[b.0].push(v);
+            }
+            for instr in self.block(b).nor_ins.clone() {
+                let v = match self.promoted_access(instr, var_idx) {
+                    Some(v) => v,
+                    None => continue,
+                };
+                // 之前的load已被替换，store的值已经是实际的值
+                match self.instr(instr).clone() {
+                    Instruction::Load { .. } => {
+                        let value = stacks[v].last().unwrap_or(&zeros[v]).clone();
+                        self.replace_all_uses(instr, &value);
+                    },
+                    Instruction::Store { value, .. } => {
+                        stacks[v].push(value);
+                        pushed[b.0].push(v);
+                    },
+                    _ => unreachable!(),
+                }
+                dead.insert(instr);
+            }
+
+            for &s in cfg.succs[b.0].iter() {
+                for &(v, phi) in phis[s.0].iter() {
+                    let value = stacks[v].last().unwrap_or(&zeros[v]).clone();
+                    self.add_phi_incoming(phi, value, b);
+                }
+            }
+        }
+        dead
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::llvm_opt::run_on_text;
+    use crate::structures::llvm_struct::LLVMProgram;
+
+    const INPUT: &str = "\
+declare i32 @getint()
+
+define i32 @diamond(i32 noundef %n) {
+entry:
+  %x = alloca i32, align 4
+  %y = alloca i32, align 4
+  store i32 0, i32* %x, align 4
+  store i32 %n, i32* %y, align 4
+  %c = icmp sgt i32 %n, 0
+  br i1 %c, label %then, label %else
+
+then:
+  store i32 1, i32* %x, align 4
+  br label %end
+
+else:
+  %t = call i32 @getint()
+  store i32 %t, i32* %x, align 4
+  br label %end
+
+end:
+  %v = load i32, i32* %x, align 4
+  %w = load i32, i32* %y, align 4
+  %r = add i32 %v, %w
+  ret i32 %r
+}
+
+define i32 @sum(i32 noundef %n) {
+entry:
+  %s = alloca i32, align 4
+  %i = alloca i32, align 4
+  %tmp = alloca i32, align 4
+  store i32 0, i32* %s, align 4
+  store i32 0, i32* %i, align 4
+  br label %cond
+
+cond:
+  %iv = load i32, i32* %i, align 4
+  %c = icmp slt i32 %iv, %n
+  br i1 %c, label %body, label %exit
+
+body:
+  %sv = load i32, i32* %s, align 4
+  store i32 %sv, i32* %tmp, align 4
+  %tv = load i32, i32* %tmp, align 4
+  %s1 = add i32 %tv, %iv
+  store i32 %s1, i32* %s, align 4
+  %i1 = add i32 %iv, 1
+  store i32 %i1, i32* %i, align 4
+  br label %cond
+
+exit:
+  %r = load i32, i32* %s, align 4
+  ret i32 %r
+}";
+
+    #[test]
+    fn place_phis() {
+        let output = run_on_text(INPUT, LLVMProgram::mem2reg);
+        // 只在一处写入的%y与不跨基本块活跃的%tmp不需要phi
+        assert_eq!(output, "\
+declare i32 @getint()
+
+define i32 @diamond(i32 noundef %n) {
+entry:
+  %c = icmp sgt i32 %n, 0
+  br i1 %c, label %then, label %else
+
+then:
+  br label %end
+
+else:
+  %t = call i32 @getint()
+  br label %end
+
+end:
+  %0 = phi i32 [ %t, %else ], [ 1, %then ]
+  %r = add i32 %0, %n
+  ret i32 %r
+}
+
+define i32 @sum(i32 noundef %n) {
+entry:
+  br label %cond
+
+cond:
+  %0 = phi i32 [ 0, %entry ], [ %s1, %body ]
+  %1 = phi i32 [ 0, %entry ], [ %i1, %body ]
+  %c = icmp slt i32 %1, %n
+  br i1 %c, label %body, label %exit
+
+body:
+  %s1 = add i32 %0, %1
+  %i1 = add i32 %1, 1
+  br label %cond
+
+exit:
+  ret i32 %0
+}");
+    }
+}
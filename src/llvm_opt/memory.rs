@@ -0,0 +1,338 @@
+use std::collections::{HashMap, HashSet};
+use crate::llvm_opt::dominator::*;
+use crate::llvm_opt::purity::*;
+use crate::structures::llvm_struct::*;
+
+impl LLVMProgram {
+    /// 冗余load消除：沿控制流传播各地址中已知的值，所有路径上都已知且相同时，
+    /// load直接使用之前store的值或之前load的结果
+    pub fn redundant_load_eliminate(&mut self) {
+        let effects = self.call_effects();
+        self.func_def.iter_mut().for_each(|func| func.redundant_load_eliminate(&effects));
+    }
+
+    /// 死store消除：删除在所有路径上被再次写入之前都不会被读取的store，
+    /// 以及只被写入、从未被读取的局部变量
+    pub fn dead_store_eliminate(&mut self) {
+        let effects = self.call_effects();
+        self.func_def.iter_mut().for_each(|func| func.dead_store_eliminate(&effects));
+    }
+}
+
+/// 按结构比较的地址：GetElemPtr以<基址, 类型, 下标>表示，不同分支中分别计算的同一地址也相等
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Address {
+    base: Operand,
+    ty: String,
+    indices: Vec<Operand>,
+}
+
+impl Address {
+    /// 地址的计算是否用到了value
+    fn mentions(&self, value: ValueId) -> bool {
+        let value = Operand::Value(value);
+        self.base == value || self.indices.contains(&value)
+    }
+}
+
+/// 各地址中已知的值，以<地址, 类型>为键，值为<用于别名查询的地址操作数, 地址中的值>
+type KnownValues = HashMap<(Address, String), (Operand, Operand)>;
+
+impl FuncDef {
+    fn redundant_load_eliminate(&mut self, effects: &CallEffects) {
+        let cfg = self.build_cfg();
+        let rpo = cfg.reverse_post_order();
+        // 各基本块出口处已知的值，None表示尚未计算，汇合时不参与求交
+        let mut outs: Vec<Option<KnownValues>> = vec![None; self.blocks.len()];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &b in rpo.iter() {
+                let mut known = self.known_at_entry(&cfg, &outs, b);
+                for id in self.block(b).nor_ins.iter() {
+                    self.transfer_known(*id, &mut known, effects);
+                }
+                if outs[b.0].as_ref() != Some(&known) {
+                    outs[b.0] = Some(known);
+                    changed = true;
+                }
+            }
+        }
+
+        let mut dead = HashSet::new();
+        for &b in rpo.iter() {
+            let mut known = self.known_at_entry(&cfg, &outs, b);
+            for id in self.block(b).nor_ins.clone() {
+                if let Instruction::Load { ty, ptr, .. } = self.instr(id) {
+                    if let Some((_, value)) = known.get(&(self.address(ptr), ty.get_typename())).cloned() {
+                        // 已知的值就是不动点中这条load之后的值，替换后无需更新known
+                        self.replace_all_uses(id, &value);
+                        dead.insert(id);
+                        continue;
+                    }
+                }
+                self.transfer_known(id, &mut known, effects);
+            }
+        }
+        self.erase_values(&dead);
+    }
+
+    /// 基本块入口处已知的值：已计算的前驱出口处的交集，值不同的地址不再已知
+    fn known_at_entry(&self, cfg: &ControlFlowGraph, outs: &[Option<KnownValues>], b: BlockId) -> KnownValues {
+        if b == cfg.entry {
+            return HashMap::new();
+        }
+        let mut preds = cfg.preds[b.0].iter().filter_map(|p| outs[p.0].as_ref());
+        let mut known = preds.next().cloned().unwrap_or_default();
+        for out in preds {
+            known.retain(|key, (_, value)| out.get(key).map_or(false, |(_, other)| other == value));
+        }
+        known
+    }
+
+    fn transfer_known(&self, id: ValueId, known: &mut KnownValues, effects: &CallEffects) {
+        match self.instr(id) {
+            Instruction::Load { ty, ptr, .. } => {
+                known.entry((self.address(ptr), ty.get_typename())).or_insert((ptr.clone(), Operand::Value(id)));
+            },
+            Instruction::Store { ty, value, ptr, .. } => {
+                known.retain(|_, (addr, _)| !self.may_alias(addr, ptr));
+                known.insert((self.address(ptr), ty.get_typename()), (ptr.clone(), value.clone()));
+            },
+            Instruction::Call(name, _, _) if effects.may_write(name) => known.clear(),
+            _ => {},
+        }
+    }
+
+    fn dead_store_eliminate(&mut self, effects: &CallEffects) {
+        let cfg = self.build_cfg();
+        let mut post_order = cfg.reverse_post_order();
+        post_order.reverse();
+        // 各基本块入口处在读取之前必然被再次写入的地址及其用于别名查询的操作数，None表示尚未计算，汇合时不参与求交
+        let mut ins: Vec<Option<HashMap<Address, Operand>>> = vec![None; self.blocks.len()];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &b in post_order.iter() {
+                let mut overwritten = self.overwritten_at_exit(&cfg, &ins, b);
+                for id in self.block_instrs(b).into_iter().rev() {
+                    self.transfer_overwritten(id, &mut overwritten, effects);
+                }
+                if ins[b.0].as_ref() != Some(&overwritten) {
+                    ins[b.0] = Some(overwritten);
+                    changed = true;
+                }
+            }
+        }
+
+        let mut dead = HashSet::new();
+        for &b in post_order.iter() {
+            let mut overwritten = self.overwritten_at_exit(&cfg, &ins, b);
+            for id in self.block_instrs(b).into_iter().rev() {
+                if let Instruction::Store { ptr, .. } = self.instr(id) {
+                    if overwritten.contains_key(&self.address(ptr)) {
+                        dead.insert(id);
+                    }
+                }
+                self.transfer_overwritten(id, &mut overwritten, effects);
+            }
+        }
+        self.erase_values(&dead);
+        self.remove_write_only_locals();
+    }
+
+    /// 基本块出口处必然被再次写入的地址：已计算的后继入口处的交集，没有后继时为空
+    fn overwritten_at_exit(&self, cfg: &ControlFlowGraph, ins: &[Option<HashMap<Address, Operand>>], b: BlockId) -> HashMap<Address, Operand> {
+        let mut succs = cfg.succs[b.0].iter().filter_map(|s| ins[s.0].as_ref());
+        let mut overwritten = succs.next().cloned().unwrap_or_default();
+        for set in succs {
+            overwritten.retain(|addr, _| set.contains_key(addr));
+        }
+        overwritten
+    }
+
+    /// 逆序经过一条指令
+    fn transfer_overwritten(&self, id: ValueId, overwritten: &mut HashMap<Address, Operand>, effects: &CallEffects) {
+        // 循环中基址与下标在定义处被重新计算，之后的同名地址不一定是同一块内存
+        overwritten.retain(|addr, _| !addr.mentions(id));
+        match self.instr(id) {
+            Instruction::Store { ptr, .. } => { overwritten.insert(self.address(ptr), ptr.clone()); },
+            Instruction::Load { ptr, .. } => overwritten.retain(|_, addr| !self.may_alias(addr, ptr)),
+            Instruction::Call(name, _, _) if effects.may_read(name) => overwritten.clear(),
+            _ => {},
+        }
+    }
+
+    /// 地址的结构，偏移为0的GetElemPtr与其基址相同
+    fn address(&self, ptr: &Operand) -> Address {
+        let mut ptr = ptr.clone();
+        while let Operand::Value(id) = ptr {
+            match self.instr(id) {
+                Instruction::GetElemPtr(_, base, idx) if idx[..] == [Operand::ConstInt(0)] => ptr = base.clone(),
+                Instruction::GetElemPtr(ty, base, idx) => {
+                    return Address { base: base.clone(), ty: ty.get_typename(), indices: idx.clone() };
+                },
+                _ => break,
+            }
+        }
+        Address { base: ptr, ty: String::new(), indices: vec![] }
+    }
+
+    /// 删除只作为store的地址或memset的目标、从未被读取的局部变量，连同对它的全部写入
+    fn remove_write_only_locals(&mut self) {
+        for alloca in self.local_vars.clone() {
+            let mut derived = vec![alloca];
+            let mut accesses: HashSet<ValueId> = HashSet::from([alloca]);
+            let mut write_only = true;
+            let mut i = 0;
+            while write_only && i < derived.len() {
+                let cur = derived[i];
+                let addr = Operand::Value(cur);
+                i += 1;
+                for &user in self.values[cur.0].users.iter() {
+                    match self.instr(user) {
+                        Instruction::GetElemPtr(_, base, _) | Instruction::BitCast(_, base, _) if *base == addr => derived.push(user),
+                        Instruction::Store { value, ptr, .. } if *ptr == addr && *value != addr => {},
+                        Instruction::Call(name, _, params) if name == "@llvm.memset.p0i8.i64" && params[0].0 == addr => {},
+                        _ => write_only = false,
+                    }
+                    accesses.insert(user);
+                }
+            }
+            if write_only {
+                self.erase_values(&accesses);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::llvm_opt::run_on_text;
+    use crate::structures::llvm_struct::LLVMProgram;
+
+    const LOADS: &str = "\
+@g = global i32 0, align 4
+@arr = global [4 x i32] zeroinitializer, align 4
+
+declare void @putint(i32 noundef)
+
+define i32 @main(i32 noundef %n) {
+entry:
+  store i32 %n, i32* @g, align 4
+  %p = getelementptr [4 x i32], [4 x i32]* @arr, i32 0, i32 1
+  store i32 5, i32* %p, align 4
+  %a = load i32, i32* @g, align 4
+  %b = load i32, i32* %p, align 4
+  call void @putint(i32 noundef %a)
+  %c = load i32, i32* @g, align 4
+  %s = add i32 %b, %c
+  %cond = icmp sgt i32 %n, 0
+  br i1 %cond, label %then, label %else
+
+then:
+  store i32 1, i32* @g, align 4
+  br label %end
+
+else:
+  store i32 2, i32* @g, align 4
+  %q = getelementptr [4 x i32], [4 x i32]* @arr, i32 0, i32 %n
+  store i32 7, i32* %q, align 4
+  br label %end
+
+end:
+  %d = load i32, i32* @g, align 4
+  %e = load i32, i32* %p, align 4
+  %f = load i32, i32* %p, align 4
+  %t = add i32 %d, %e
+  %u = add i32 %t, %f
+  %v = add i32 %u, %s
+  ret i32 %v
+}";
+
+    #[test]
+    fn redundant_loads() {
+        let output = run_on_text(LOADS, LLVMProgram::redundant_load_eliminate);
+        let main = &output[output.find("define i32 @main").unwrap()..];
+        // 两个分支写入的值不同，%q可能与%p相同，汇合后只有对%p的第二次读取冗余
+        assert_eq!(main, "\
+define i32 @main(i32 noundef %n) {
+entry:
+  store i32 %n, i32* @g, align 4
+  %p = getelementptr inbounds [4 x i32], [4 x i32]* @arr, i32 0, i32 1
+  store i32 5, i32* %p, align 4
+  call void @putint(i32 noundef %n)
+  %s = add i32 5, %n
+  %cond = icmp sgt i32 %n, 0
+  br i1 %cond, label %then, label %else
+
+then:
+  store i32 1, i32* @g, align 4
+  br label %end
+
+else:
+  store i32 2, i32* @g, align 4
+  %q = getelementptr inbounds [4 x i32], [4 x i32]* @arr, i32 0, i32 %n
+  store i32 7, i32* %q, align 4
+  br label %end
+
+end:
+  %d = load i32, i32* @g, align 4
+  %e = load i32, i32* %p, align 4
+  %t = add i32 %d, %e
+  %u = add i32 %t, %e
+  %v = add i32 %u, %s
+  ret i32 %v
+}");
+    }
+
+    const STORES: &str = "\
+@g = global i32 0, align 4
+
+declare void @putint(i32 noundef)
+
+define i32 @main(i32 noundef %x) {
+entry:
+  %l = alloca i32, align 4
+  store i32 1, i32* %l, align 4
+  store i32 %x, i32* @g, align 4
+  store i32 2, i32* @g, align 4
+  %v = load i32, i32* @g, align 4
+  store i32 3, i32* @g, align 4
+  call void @putint(i32 noundef %v)
+  store i32 4, i32* @g, align 4
+  %cond = icmp sgt i32 %x, 0
+  br i1 %cond, label %then, label %end
+
+then:
+  store i32 5, i32* @g, align 4
+  br label %end
+
+end:
+  ret i32 0
+}";
+
+    #[test]
+    fn dead_stores() {
+        let output = run_on_text(STORES, LLVMProgram::dead_store_eliminate);
+        let main = &output[output.find("define i32 @main").unwrap()..];
+        // putint不读内存，store 3被覆盖；store 4只在一条路径上被覆盖，保留；只写不读的局部变量被删除
+        assert_eq!(main, "\
+define i32 @main(i32 noundef %x) {
+entry:
+  store i32 2, i32* @g, align 4
+  %v = load i32, i32* @g, align 4
+  call void @putint(i32 noundef %v)
+  store i32 4, i32* @g, align 4
+  %cond = icmp sgt i32 %x, 0
+  br i1 %cond, label %then, label %end
+
+then:
+  store i32 5, i32* @g, align 4
+  br label %end
+
+end:
+  ret i32 0
+}");
+    }
+}
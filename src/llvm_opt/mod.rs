@@ -1,11 +1,79 @@
+pub mod alias;
+pub mod call_graph;
+pub mod dominator;
 pub mod eliminate;
-pub mod flow;
-pub mod flow_graph;
+pub mod globals;
+pub mod gvn;
+pub mod induction;
+pub mod inline;
+pub mod licm;
+pub mod loops;
+pub mod mem2reg;
+pub mod memory;
+pub mod purity;
+pub mod sccp;
+pub mod strength_reduce;
+pub mod tail_recursion;
+pub mod unroll;
+pub mod verify;
 
+use crate::options::OptLevel;
 use crate::structures::llvm_struct::*;
 
 impl LLVMProgram {
-    pub fn optimise_llvm(&mut self) {
-        self.eliminate_unused_code();
+    pub fn optimise_llvm(&mut self, opt_level: OptLevel, inline_threshold: usize, unroll_factor: usize) {
+        self.verify_after("generate_llvm");
+        if opt_level >= OptLevel::O1 {
+            self.run_pass("mem2reg", LLVMProgram::mem2reg);
+            if opt_level >= OptLevel::O2 {
+                // 尾递归改为循环后，函数可能不再递归，可以内联
+                self.run_pass("tail_recursion_eliminate", LLVMProgram::tail_recursion_eliminate);
+                // 内联后实参中的常量可以在被调用者的函数体中继续传播
+                self.run_pass("inline", |llvm| llvm.inline(inline_threshold));
+                // 内联后的函数不再被调用，其中对全局变量的使用不再妨碍全局变量的优化
+                self.run_pass("remove_dead_functions", LLVMProgram::remove_dead_functions);
+                self.run_pass("optimize_globals", LLVMProgram::optimize_globals);
+            }
+            self.run_pass("sccp", LLVMProgram::sccp);
+            if opt_level >= OptLevel::O2 {
+                self.run_pass("gvn", LLVMProgram::gvn);
+                // 值编号合并相同的地址后，同一地址的读写才能互相匹配
+                self.run_pass("redundant_load_eliminate", LLVMProgram::redundant_load_eliminate);
+                self.run_pass("dead_store_eliminate", LLVMProgram::dead_store_eliminate);
+                self.run_pass("licm", LLVMProgram::licm);
+                // 外提到同一预备块的指令可能重复，再做一次值编号合并
+                self.run_pass("gvn", LLVMProgram::gvn);
+                self.run_pass("unroll", |llvm| llvm.unroll(unroll_factor));
+                // 完全展开后归纳变量成为常量，原循环的条件不再满足，再做一次常量传播删除它
+                self.run_pass("sccp", LLVMProgram::sccp);
+                self.run_pass("strength_reduce", LLVMProgram::strength_reduce);
+            }
+            self.run_pass("eliminate_unused_code", LLVMProgram::eliminate_unused_code);
+            // 内联与死代码消除后，部分函数与库函数不再被调用
+            self.run_pass("remove_dead_functions", LLVMProgram::remove_dead_functions);
+        }
+        // 内联、展开与插入预备块都只粗略地设置新块的深度，指令选择前按循环分析重新计算
+        self.run_pass("update_loop_depths", LLVMProgram::update_loop_depths);
     }
-}
\ No newline at end of file
+
+    /// 运行一个pass，debug模式下随后校验IR
+    pub fn run_pass(&mut self, name: &str, pass: impl FnOnce(&mut LLVMProgram)) {
+        pass(self);
+        self.verify_after(name);
+    }
+}
+
+/// pass的测试：读入文本形式的llvm，运行pass并校验后重新输出为文本，去掉首尾的空行
+#[cfg(test)]
+pub(crate) fn run_on_text(text: &str, pass: impl FnOnce(&mut LLVMProgram)) -> String {
+    use crate::llvm_gen::read_text::read_llvm;
+    use crate::structures::writetext_trait::WriteText;
+
+    let mut llvm = read_llvm(text).unwrap();
+    assert_eq!(llvm.verify(), Ok(()), "输入的IR不合法");
+    pass(&mut llvm);
+    assert_eq!(llvm.verify(), Ok(()), "pass之后的IR不合法");
+    let mut output = vec![];
+    llvm.writetext(&mut output);
+    String::from_utf8(output).unwrap().trim().to_string()
+}
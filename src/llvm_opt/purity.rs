@@ -0,0 +1,313 @@
+use std::collections::HashMap;
+use crate::structures::llvm_struct::*;
+
+/// 函数的纯度，只考虑内存与输入输出；是否一定返回由FuncEffects::diverges单独记录
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Purity {
+    /// 不读写内存，也没有输入输出，结果只由参数决定
+    Pure,
+    /// 只读内存，没有写入与输入输出
+    ReadOnly,
+    /// 写内存或有输入输出
+    SideEffecting,
+}
+
+/// 调用一个函数对调用者可见的影响
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FuncEffects {
+    /// 可能读全局变量或参数指向的内存
+    pub reads: bool,
+    /// 可能写全局变量或参数指向的内存
+    pub writes: bool,
+    /// 输入输出、计时等内存之外的副作用
+    pub io: bool,
+    /// 可能不返回：含有循环或递归，删除调用或将其移到其他指令之前会改变程序是否终止或输出；
+    /// 合并参数相同的纯函数调用不受影响
+    pub diverges: bool,
+}
+
+impl FuncEffects {
+    const UNKNOWN: FuncEffects = FuncEffects { reads: true, writes: true, io: true, diverges: true };
+
+    pub fn purity(&self) -> Purity {
+        match self {
+            FuncEffects { writes: false, io: false, reads: false, .. } => Purity::Pure,
+            FuncEffects { writes: false, io: false, reads: true, .. } => Purity::ReadOnly,
+            _ => Purity::SideEffecting,
+        }
+    }
+
+    fn merge(&mut self, other: &FuncEffects) {
+        self.reads |= other.reads;
+        self.writes |= other.writes;
+        self.io |= other.io;
+        self.diverges |= other.diverges;
+    }
+}
+
+/// 程序中各函数的副作用，以带@前缀的函数名为键
+pub struct CallEffects {
+    funcs: HashMap<String, FuncEffects>,
+}
+
+impl CallEffects {
+    /// 被调用函数的副作用，未知的函数按全部副作用处理
+    pub fn of(&self, name: &str) -> FuncEffects {
+        self.funcs.get(name).copied().unwrap_or(FuncEffects::UNKNOWN)
+    }
+
+    pub fn purity(&self, name: &str) -> Purity {
+        self.of(name).purity()
+    }
+
+    pub fn may_read(&self, name: &str) -> bool {
+        self.of(name).reads
+    }
+
+    pub fn may_write(&self, name: &str) -> bool {
+        self.of(name).writes
+    }
+
+    pub fn may_diverge(&self, name: &str) -> bool {
+        self.of(name).diverges
+    }
+
+    /// 结果不被使用时调用可以删除：不写内存、没有输入输出，且一定会返回
+    pub fn removable(&self, name: &str) -> bool {
+        self.purity(name) != Purity::SideEffecting && !self.may_diverge(name)
+    }
+}
+
+/// sylib库函数与memset的副作用：读入输出都有输入输出，读入数组写参数指向的内存，输出数组读参数指向的内存
+fn library_effects() -> Vec<(&'static str, FuncEffects)> {
+    let io = FuncEffects { io: true, ..Default::default() };
+    vec![
+        ("@getint", io),
+        ("@getch", io),
+        ("@getfloat", io),
+        ("@getarray", FuncEffects { writes: true, ..io }),
+        ("@getfarray", FuncEffects { writes: true, ..io }),
+        ("@putint", io),
+        ("@putch", io),
+        ("@putfloat", io),
+        ("@putarray", FuncEffects { reads: true, ..io }),
+        ("@putfarray", FuncEffects { reads: true, ..io }),
+        ("@starttime", io),
+        ("@stoptime", io),
+        ("@llvm.memset.p0i8.i64", FuncEffects { writes: true, ..Default::default() }),
+    ]
+}
+
+impl LLVMProgram {
+    /// 过程间副作用分析：先求各函数自身对非局部内存的读写，再按调用图的强连通分量自底向上合并被调用者的副作用，
+    /// 同一分量中的函数互相递归，副作用相同；递归的函数可能不返回
+    pub fn call_effects(&self) -> CallEffects {
+        let mut funcs: HashMap<String, FuncEffects> = library_effects().into_iter()
+            .map(|(name, effects)| (name.to_string(), effects))
+            .collect();
+        let local: HashMap<&String, FuncEffects> = self.func_def.iter()
+            .map(|func| (&func.func_name, func.local_effects()))
+            .collect();
+        let graph = self.call_graph();
+        let recursive = graph.recursive();
+        for scc in graph.sccs() {
+            let mut effects = FuncEffects::default();
+            for name in scc.iter() {
+                effects.merge(&local[name]);
+                effects.diverges |= recursive.contains(name);
+                // memset的目标在local_effects中按地址区分
+                for callee in graph.calls[name].iter().filter(|&callee| !scc.contains(callee) && callee != "@llvm.memset.p0i8.i64") {
+                    effects.merge(&funcs.get(callee).copied().unwrap_or(FuncEffects::UNKNOWN));
+                }
+            }
+            for name in scc {
+                funcs.insert(name, effects);
+            }
+        }
+        CallEffects { funcs }
+    }
+}
+
+impl FuncDef {
+    /// 函数体中直接的读写与memset，不含其他调用；只访问本函数局部变量的读写与memset对调用者不可见
+    /// 不判断循环能否结束，含有循环即认为可能不返回
+    fn local_effects(&self) -> FuncEffects {
+        let mut effects = FuncEffects { diverges: !self.loop_info().loops.is_empty(), ..Default::default() };
+        for id in self.all_instrs() {
+            match self.instr(id) {
+                Instruction::Load { ptr, .. } if !self.points_only_to_local(ptr) => effects.reads = true,
+                Instruction::Store { ptr, .. } if !self.points_only_to_local(ptr) => effects.writes = true,
+                Instruction::Call(name, _, params) if name == "@llvm.memset.p0i8.i64" && !self.points_only_to_local(&params[0].0) => {
+                    effects.writes = true;
+                },
+                _ => {},
+            }
+        }
+        effects
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::llvm_gen::read_text::read_llvm;
+    use crate::llvm_opt::run_on_text;
+    use crate::structures::llvm_struct::LLVMProgram;
+    use super::*;
+
+    const INPUT: &str = "\
+@g = global i32 0, align 4
+
+declare void @putint(i32 noundef)
+
+define i32 @sq(i32 noundef %x) {
+entry:
+  %r = mul i32 %x, %x
+  ret i32 %r
+}
+
+define i32 @get() {
+entry:
+  %v = load i32, i32* @g, align 4
+  ret i32 %v
+}
+
+define i32 @spin(i32 noundef %x) {
+entry:
+  br label %loop
+
+loop:
+  %i = phi i32 [ %x, %entry ], [ %i1, %loop ]
+  %i1 = add i32 %i, 1
+  %c = icmp ne i32 %i1, 0
+  br i1 %c, label %loop, label %exit
+
+exit:
+  ret i32 %i1
+}
+
+define i32 @even(i32 noundef %x) {
+entry:
+  %r = call i32 @odd(i32 noundef %x)
+  ret i32 %r
+}
+
+define i32 @odd(i32 noundef %x) {
+entry:
+  %r = call i32 @even(i32 noundef %x)
+  ret i32 %r
+}
+
+define i32 @twice(i32 noundef %x) {
+entry:
+  %r = call i32 @spin(i32 noundef %x)
+  ret i32 %r
+}
+
+define i32 @main() {
+entry:
+  %a = call i32 @sq(i32 noundef 3)
+  %b = call i32 @get()
+  %c = call i32 @spin(i32 noundef 1)
+  %d = call i32 @even(i32 noundef 2)
+  %e = call i32 @twice(i32 noundef 1)
+  call void @putint(i32 noundef 0)
+  ret i32 0
+}";
+
+    #[test]
+    fn purity_of_functions() {
+        let effects = read_llvm(INPUT).unwrap().call_effects();
+        assert_eq!(effects.purity("@sq"), Purity::Pure);
+        assert_eq!(effects.purity("@get"), Purity::ReadOnly);
+        // 含有循环、递归或调用了这样的函数，可能不返回，仍是纯函数但不能删除
+        for name in ["@spin", "@even", "@odd", "@twice"] {
+            assert_eq!(effects.of(name), FuncEffects { diverges: true, ..Default::default() }, "{}", name);
+            assert_eq!(effects.purity(name), Purity::Pure, "{}", name);
+            assert!(effects.may_diverge(name) && !effects.removable(name), "{}", name);
+        }
+        assert!(effects.removable("@sq") && effects.removable("@get"));
+        assert_eq!(effects.purity("@putint"), Purity::SideEffecting);
+        assert_eq!(effects.purity("@unknown"), Purity::SideEffecting);
+        assert!(!effects.may_write("@get") && effects.may_read("@get"));
+    }
+
+    #[test]
+    fn keep_calls_that_may_not_return() {
+        let output = run_on_text(INPUT, LLVMProgram::eliminate_unused_code);
+        let main = &output[output.find("define i32 @main").unwrap()..];
+        assert_eq!(main, "\
+define i32 @main() {
+entry:
+  %c = call i32 @spin(i32 noundef 1)
+  %d = call i32 @even(i32 noundef 2)
+  %e = call i32 @twice(i32 noundef 1)
+  call void @putint(i32 noundef 0)
+  ret i32 0
+}");
+    }
+
+    #[test]
+    fn merge_but_do_not_hoist_calls_that_may_not_return() {
+        let input = "\
+declare void @putint(i32 noundef)
+
+define i32 @spin(i32 noundef %x) {
+entry:
+  br label %loop
+
+loop:
+  %i = phi i32 [ %x, %entry ], [ %i1, %loop ]
+  %i1 = add i32 %i, 1
+  %c = icmp ne i32 %i1, 0
+  br i1 %c, label %loop, label %exit
+
+exit:
+  ret i32 %i1
+}
+
+define i32 @main() {
+entry:
+  br label %loop
+
+loop:
+  %i = phi i32 [ 0, %entry ], [ %i1, %body ]
+  call void @putint(i32 noundef %i)
+  %s = call i32 @spin(i32 noundef 1)
+  %t = call i32 @spin(i32 noundef 1)
+  %c = icmp slt i32 %i, %s
+  br i1 %c, label %body, label %exit
+
+body:
+  %i1 = add i32 %i, %t
+  br label %loop
+
+exit:
+  ret i32 0
+}";
+        // 参数相同的两次调用合并；提前到预备块会越过循环头中的putint，保留在原处
+        let output = run_on_text(input, |llvm| {
+            llvm.gvn();
+            llvm.licm();
+        });
+        let main = &output[output.find("define i32 @main").unwrap()..];
+        assert_eq!(main, "\
+define i32 @main() {
+entry:
+  br label %loop
+
+loop:
+  %i = phi i32 [ 0, %entry ], [ %i1, %body ]
+  call void @putint(i32 noundef %i)
+  %s = call i32 @spin(i32 noundef 1)
+  %c = icmp slt i32 %i, %s
+  br i1 %c, label %body, label %exit
+
+body:
+  %i1 = add i32 %i, %s
+  br label %loop
+
+exit:
+  ret i32 0
+}");
+    }
+}
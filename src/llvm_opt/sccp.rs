@@ -0,0 +1,397 @@
+use std::collections::HashSet;
+use crate::llvm_opt::eliminate::unreachable_code_eliminate;
+use crate::structures::llvm_struct::*;
+use crate::structures::symbol::*;
+
+impl LLVMProgram {
+    /// 稀疏条件常量传播：沿可执行的边传播常量，将结果为常量的指令替换为字面量，并删除不会执行的分支
+    pub fn sccp(&mut self) {
+        self.func_def.iter_mut().for_each(|func| func.sccp());
+    }
+}
+
+/// 格上的值，Undef < 常量 < Overdefined
+#[derive(Debug, Clone, Copy)]
+enum Lattice {
+    /// 尚未确定，例如所在的基本块还没有被执行到
+    Undef,
+    Int(i64),
+    Float(f32),
+    /// 运行时才能确定
+    Overdefined,
+}
+
+impl Lattice {
+    fn meet(self, other: Lattice) -> Lattice {
+        match (self, other) {
+            (Lattice::Undef, other) | (other, Lattice::Undef) => other,
+            (Lattice::Int(num1), Lattice::Int(num2)) if num1 == num2 => self,
+            // 按位比较，NaN与自身相同
+            (Lattice::Float(num1), Lattice::Float(num2)) if num1.to_bits() == num2.to_bits() => self,
+            _ => Lattice::Overdefined,
+        }
+    }
+
+    fn same(self, other: Lattice) -> bool {
+        match (self, other) {
+            (Lattice::Undef, Lattice::Undef) | (Lattice::Overdefined, Lattice::Overdefined) => true,
+            (Lattice::Int(num1), Lattice::Int(num2)) => num1 == num2,
+            (Lattice::Float(num1), Lattice::Float(num2)) => num1.to_bits() == num2.to_bits(),
+            _ => false,
+        }
+    }
+
+    fn to_operand(self) -> Option<Operand> {
+        match self {
+            Lattice::Int(num) => Some(Operand::ConstInt(num)),
+            Lattice::Float(num) => Some(Operand::ConstFloat(num)),
+            _ => None,
+        }
+    }
+}
+
+/// 传播过程中的状态
+struct Solver {
+    values: Vec<Lattice>,
+    /// 已知会执行的基本块
+    executable: HashSet<BlockId>,
+    /// 已知会经过的边<前驱, 后继>
+    edges: HashSet<(BlockId, BlockId)>,
+    edge_worklist: Vec<(BlockId, BlockId)>,
+    value_worklist: Vec<ValueId>,
+}
+
+impl FuncDef {
+    fn sccp(&mut self) {
+        let solver = self.solve();
+        self.fold_constants(&solver);
+        self.fold_identities();
+        self.fold_branches(&solver);
+        unreachable_code_eliminate(self);
+    }
+
+    /// 求出各值在格上的不动点
+    fn solve(&self) -> Solver {
+        let mut solver = Solver {
+            values: vec![Lattice::Undef; self.values.len()],
+            executable: HashSet::new(),
+            edges: HashSet::new(),
+            edge_worklist: vec![],
+            value_worklist: vec![],
+        };
+        // 局部变量的地址在运行时才确定
+        for &alloca in self.local_vars.iter() {
+            solver.values[alloca.0] = Lattice::Overdefined;
+        }
+        solver.executable.insert(self.entry());
+        self.block_instrs(self.entry()).into_iter().for_each(|id| self.visit(id, &mut solver));
+
+        loop {
+            if let Some((pred, succ)) = solver.edge_worklist.pop() {
+                if !solver.edges.insert((pred, succ)) {
+                    continue;
+                }
+                if solver.executable.insert(succ) {
+                    // 第一次执行到的基本块，计算其中的全部指令
+                    self.block_instrs(succ).into_iter().for_each(|id| self.visit(id, &mut solver));
+                } else {
+                    // 新的入边只影响phi
+                    self.block(succ).phi_ins.iter().for_each(|&phi| self.visit(phi, &mut solver));
+                }
+            } else if let Some(id) = solver.value_worklist.pop() {
+                for &user in self.values[id.0].users.iter() {
+                    let in_executable = self.values[user.0].block.map_or(false, |bb| solver.executable.contains(&bb));
+                    if in_executable {
+                        self.visit(user, &mut solver);
+                    }
+                }
+            } else {
+                break;
+            }
+        }
+        solver
+    }
+
+    /// 计算一条指令，结果变化时将其使用者加入工作表，终结指令将可能经过的边加入工作表
+    fn visit(&self, id: ValueId, solver: &mut Solver) {
+        let bb = self.values[id.0].block.unwrap();
+        match self.instr(id) {
+            Instruction::Br(cond, label1, label2) => {
+                let cond = cond.as_ref().map_or(Lattice::Overdefined, |cond| self.lattice(cond, solver));
+                let targets = match (cond, label2) {
+                    (_, None) => vec![*label1],
+                    (Lattice::Int(num), Some(label2)) => vec![if num != 0 { *label1 } else { *label2 }],
+                    // 条件未确定时保守地认为两边都会执行，保证删除的分支确实不会执行
+                    (_, Some(label2)) => vec![*label1, *label2],
+                };
+                targets.into_iter().for_each(|succ| solver.edge_worklist.push((bb, succ)));
+            },
+            instr if instr.has_result() => {
+                let res = self.evaluate(bb, instr, solver);
+                let old = solver.values[id.0];
+                let new = old.meet(res);
+                if !new.same(old) {
+                    solver.values[id.0] = new;
+                    solver.value_worklist.push(id);
+                }
+            },
+            _ => {},
+        }
+    }
+
+    fn lattice(&self, op: &Operand, solver: &Solver) -> Lattice {
+        match op {
+            Operand::ConstInt(num) => Lattice::Int(*num),
+            Operand::ConstFloat(num) => Lattice::Float(*num),
+            Operand::Value(id) => solver.values[id.0],
+            _ => Lattice::Overdefined,
+        }
+    }
+
+    /// 由操作数在格上的值计算指令的结果，无法在编译时计算的指令为Overdefined
+    fn evaluate(&self, bb: BlockId, instr: &Instruction, solver: &Solver) -> Lattice {
+        if let Instruction::Phi(_, candidates) = instr {
+            // 只合并来自可执行的边的候选
+            return candidates.iter()
+                .filter(|(_, pred)| solver.edges.contains(&(*pred, bb)))
+                .fold(Lattice::Undef, |res, (value, _)| res.meet(self.lattice(value, solver)));
+        }
+        let operands: Vec<Lattice> = match instr {
+            Instruction::Add(_) | Instruction::Sub(_) | Instruction::Mul(_) | Instruction::Sdiv(_) | Instruction::Srem(_)
+            | Instruction::Fadd(_) | Instruction::Fsub(_) | Instruction::Fmul(_) | Instruction::Fdiv(_)
+            | Instruction::Cmp(_, _) | Instruction::Fcmp(_, _)
+            | Instruction::ZeroExt(_) | Instruction::I32ToFloat(_) | Instruction::FloatToI32(_) => {
+                instr.operands().into_iter().map(|op| self.lattice(op, solver)).collect()
+            },
+            _ => return Lattice::Overdefined,
+        };
+        // 乘以0与对1或-1求余的结果为0，与另一个操作数无关
+        match instr {
+            Instruction::Mul(_) if operands.iter().any(|op| matches!(op, Lattice::Int(0))) => return Lattice::Int(0),
+            Instruction::Srem(_) if matches!(operands[1], Lattice::Int(1) | Lattice::Int(-1)) => return Lattice::Int(0),
+            _ => {},
+        }
+        if operands.iter().any(|op| matches!(op, Lattice::Overdefined)) {
+            return Lattice::Overdefined;
+        }
+        if operands.iter().any(|op| matches!(op, Lattice::Undef)) {
+            return Lattice::Undef;
+        }
+        fold_instr(instr, &operands).unwrap_or(Lattice::Overdefined)
+    }
+
+    /// 将结果为常量的指令的使用替换为字面量，并删除这些指令
+    fn fold_constants(&mut self, solver: &Solver) {
+        let mut dead = HashSet::new();
+        for id in self.all_instrs() {
+            if let Some(value) = solver.values[id.0].to_operand() {
+                self.replace_all_uses(id, &value);
+                dead.insert(id);
+            }
+        }
+        self.erase_values(&dead);
+    }
+
+    /// 常量替换为字面量之后，乘以1与除以1的指令直接使用另一个操作数
+    fn fold_identities(&mut self) {
+        let one = Operand::ConstInt(1);
+        let mut dead = HashSet::new();
+        for id in self.all_instrs() {
+            let value = match self.instr(id) {
+                Instruction::Mul(bin_op) if bin_op.op2 == one => bin_op.op1.clone(),
+                Instruction::Mul(bin_op) if bin_op.op1 == one => bin_op.op2.clone(),
+                Instruction::Sdiv(bin_op) if bin_op.op2 == one => bin_op.op1.clone(),
+                _ => continue,
+            };
+            self.replace_all_uses(id, &value);
+            dead.insert(id);
+        }
+        self.erase_values(&dead);
+    }
+
+    /// 将条件为常量的分支改为无条件跳转，不再跳转到的后继中删除来自本块的phi候选
+    fn fold_branches(&mut self, solver: &Solver) {
+        for bb in self.layout.clone() {
+            let ter = match self.block(bb).ter_ins {
+                Some(ter) if solver.executable.contains(&bb) => ter,
+                _ => continue,
+            };
+            let (label1, label2) = match self.instr(ter) {
+                Instruction::Br(Some(_), label1, Some(label2)) => (*label1, *label2),
+                _ => continue,
+            };
+            let (taken, dead) = match (solver.edges.contains(&(bb, label1)), solver.edges.contains(&(bb, label2))) {
+                (true, false) => (label1, label2),
+                (false, true) => (label2, label1),
+                _ => continue,
+            };
+            self.set_instr(ter, Instruction::Br(None, taken, None));
+            if taken == dead {
+                continue;
+            }
+            for phi in self.block(dead).phi_ins.clone() {
+                if let Instruction::Phi(ty, candidates) = self.instr(phi) {
+                    let candidates = candidates.iter().filter(|(_, pred)| *pred != bb).cloned().collect();
+                    self.set_instr(phi, Instruction::Phi(ty.clone(), candidates));
+                }
+            }
+        }
+    }
+}
+
+/// 按类型截断整数，结果以符号扩展后的i64保存
+fn truncate(ty: &SymbolType, num: i64) -> i64 {
+    match ty.width {
+        SymbolWidth::Bool => num & 1,
+        SymbolWidth::I8 => num as i8 as i64,
+        SymbolWidth::I32 => num as i32 as i64,
+        _ => num,
+    }
+}
+
+/// 对常量操作数求值，运行时行为与目标相关的情况(除以零、溢出的转换)不折叠
+fn fold_instr(instr: &Instruction, operands: &[Lattice]) -> Option<Lattice> {
+    let int = |i: usize| match operands[i] {
+        Lattice::Int(num) => Some(num),
+        _ => None,
+    };
+    let float = |i: usize| match operands[i] {
+        Lattice::Float(num) => Some(num),
+        _ => None,
+    };
+    let res = match instr {
+        Instruction::Add(bin_op) => Lattice::Int(truncate(&bin_op.op_type, int(0)?.wrapping_add(int(1)?))),
+        Instruction::Sub(bin_op) => Lattice::Int(truncate(&bin_op.op_type, int(0)?.wrapping_sub(int(1)?))),
+        Instruction::Mul(bin_op) => Lattice::Int(truncate(&bin_op.op_type, int(0)?.wrapping_mul(int(1)?))),
+        Instruction::Sdiv(bin_op) | Instruction::Srem(bin_op) => {
+            let (num1, num2) = (int(0)?, int(1)?);
+            let min = truncate(&bin_op.op_type, i64::MIN);
+            if num2 == 0 || (num1 == min && num2 == -1) {
+                return None;
+            }
+            match instr {
+                Instruction::Sdiv(_) => Lattice::Int(num1 / num2),
+                _ => Lattice::Int(num1 % num2),
+            }
+        },
+        Instruction::Fadd(_) => Lattice::Float(float(0)? + float(1)?),
+        Instruction::Fsub(_) => Lattice::Float(float(0)? - float(1)?),
+        Instruction::Fmul(_) => Lattice::Float(float(0)? * float(1)?),
+        Instruction::Fdiv(_) => Lattice::Float(float(0)? / float(1)?),
+        Instruction::Cmp(cond, _) => {
+            let (num1, num2) = (int(0)?, int(1)?);
+            let res = match cond.as_str() {
+                "eq" => num1 == num2,
+                "ne" => num1 != num2,
+                "slt" => num1 < num2,
+                "sgt" => num1 > num2,
+                "sle" => num1 <= num2,
+                "sge" => num1 >= num2,
+                _ => return None,
+            };
+            Lattice::Int(res as i64)
+        },
+        Instruction::Fcmp(cond, _) => {
+            let (num1, num2) = (float(0)?, float(1)?);
+            let res = match cond.as_str() {
+                "oeq" => num1 == num2,
+                "one" => num1.partial_cmp(&num2).map_or(false, |ord| ord.is_ne()),
+                "olt" => num1 < num2,
+                "ogt" => num1 > num2,
+                "ole" => num1 <= num2,
+                "oge" => num1 >= num2,
+                _ => return None,
+            };
+            Lattice::Int(res as i64)
+        },
+        Instruction::ZeroExt(cast_op) => {
+            let num = int(0)?;
+            Lattice::Int(match cast_op.type_1.width {
+                SymbolWidth::Bool => num & 1,
+                SymbolWidth::I8 => num & 0xff,
+                SymbolWidth::I32 => num & 0xffff_ffff,
+                _ => num,
+            })
+        },
+        Instruction::I32ToFloat(_) => {
+            // 目标上的转换向零舍入，只折叠能精确表示的整数
+            let num = int(0)? as i32;
+            let res = num as f32;
+            if res as f64 != num as f64 {
+                return None;
+            }
+            Lattice::Float(res)
+        },
+        Instruction::FloatToI32(_) => {
+            let num = float(0)?;
+            if !(num > i32::MIN as f32 - 1.0 && num < i32::MAX as f32) {
+                return None;
+            }
+            Lattice::Int(num as i32 as i64)
+        },
+        _ => return None,
+    };
+    Some(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::llvm_opt::run_on_text;
+    use crate::structures::llvm_struct::LLVMProgram;
+
+    #[test]
+    fn fold_absorbing_and_identity_operands() {
+        let output = run_on_text("\
+declare i32 @getint()
+
+define i32 @main() {
+entry:
+  %x = call i32 @getint()
+  %z = add i32 0, 0
+  %a = mul i32 %z, %x
+  %b = mul i32 %x, 1
+  %c = sdiv i32 %b, 1
+  %d = srem i32 %x, -1
+  %e = add i32 %a, %c
+  %f = add i32 %e, %d
+  ret i32 %f
+}
+", LLVMProgram::sccp);
+        assert_eq!(output, "\
+declare i32 @getint()
+
+define i32 @main() {
+entry:
+  %x = call i32 @getint()
+  %e = add i32 0, %x
+  %f = add i32 %e, 0
+  ret i32 %f
+}");
+    }
+
+    #[test]
+    fn keep_division_by_zero() {
+        // 除以0的行为与目标相关，不折叠；所在分支不执行时整条指令被删除
+        let output = run_on_text("\
+declare i32 @getint()
+
+define i32 @main() {
+entry:
+  %x = call i32 @getint()
+  %z = add i32 0, 0
+  %c = icmp eq i32 %z, 0
+  br i1 %c, label %then, label %else
+
+then:
+  %d = sdiv i32 %x, %z
+  ret i32 %d
+
+else:
+  %e = sdiv i32 10, %z
+  ret i32 %e
+}
+", LLVMProgram::sccp);
+        assert!(output.contains("%d = sdiv i32 %x, 0"), "{}", output);
+        assert!(!output.contains("else"), "{}", output);
+    }
+}
@@ -0,0 +1,249 @@
+use crate::llvm_opt::induction::*;
+use crate::llvm_opt::loops::*;
+use crate::structures::llvm_struct::*;
+use crate::structures::symbol::*;
+
+impl LLVMProgram {
+    /// 归纳变量的强度削弱：最内层循环中最后一个下标为归纳变量(加常量)的数组地址，
+    /// 由每次迭代的下标乘步长改为递增的指针
+    pub fn strength_reduce(&mut self) {
+        self.func_def.iter_mut().for_each(|func| func.strength_reduce());
+    }
+}
+
+/// 同一个数组中随归纳变量移动的一组地址
+struct AddressGroup {
+    /// 第一个地址，确定基址、类型与除最后一个以外的下标
+    first: ValueId,
+    /// 归纳变量在induction_vars中的下标
+    iv: usize,
+    /// 组中的地址与其下标相对归纳变量的偏移
+    members: Vec<(ValueId, i64)>,
+}
+
+impl FuncDef {
+    fn strength_reduce(&mut self) {
+        // 只改动循环中的指令与预备块，不改变控制流，循环分析的结果一直有效
+        let info = self.insert_preheaders();
+        for l in 0..info.loops.len() {
+            // 外层循环的指针在整个内层循环中都要占用寄存器，只处理最内层循环
+            if info.loops[l].children.is_empty() {
+                self.reduce_addresses(&info, l);
+            }
+        }
+    }
+
+    fn reduce_addresses(&mut self, info: &LoopInfo, l: usize) {
+        let lp = &info.loops[l];
+        let ivs = self.induction_vars(info, l);
+        if ivs.is_empty() {
+            return;
+        }
+        let (preheader, latch) = (lp.preheader.unwrap(), lp.latches[0]);
+
+        let mut groups: Vec<AddressGroup> = vec![];
+        for id in lp.blocks.iter().flat_map(|&b| self.block(b).nor_ins.clone()) {
+            let (base, idx) = match self.instr(id) {
+                Instruction::GetElemPtr(_, base, idx) => (base, idx),
+                _ => continue,
+            };
+            let (last, rest) = idx.split_last().unwrap();
+            if !self.is_invariant(info, l, base) || !rest.iter().all(|op| self.is_invariant(info, l, op)) {
+                continue;
+            }
+            let (iv, offset) = match self.iv_offset(&ivs, last) {
+                Some(found) => found,
+                None => continue,
+            };
+            match groups.iter_mut().find(|group| group.iv == iv && self.same_array(group.first, id)) {
+                Some(group) => group.members.push((id, offset)),
+                None => groups.push(AddressGroup { first: id, iv, members: vec![(id, offset)] }),
+            }
+        }
+
+        for group in groups {
+            let iv = &ivs[group.iv];
+            let (ty, base, mut idx) = match self.instr(group.first) {
+                Instruction::GetElemPtr(ty, base, idx) => (ty.clone(), base.clone(), idx.clone()),
+                _ => unreachable!(),
+            };
+            let ptr_type = self.instr(group.first).result_type().unwrap();
+            let elem_type = pointee_array(&ty, idx.len());
+            // 以组中偏移最小的地址为指针，进入循环时为其下标在归纳变量取初值时的值，之后每次迭代前进step个元素
+            // 偏移为负时起点可能在数组之前，inbounds的地址越界后整个指针都无效，不论初值是否为常量都不处理这一组
+            let min = group.members.iter().map(|&(_, offset)| offset).min().unwrap();
+            if min < 0 {
+                continue;
+            }
+            *idx.last_mut().unwrap() = match &iv.init {
+                Operand::ConstInt(init) if init + min < 0 => continue,
+                Operand::ConstInt(init) => Operand::ConstInt(init + min),
+                init if min == 0 => init.clone(),
+                init => {
+                    let op_type = self.instr(iv.phi).result_type().unwrap();
+                    let add = Instruction::Add(BinaryOp { op_type, op1: init.clone(), op2: Operand::ConstInt(min) });
+                    Operand::Value(self.push_value(preheader, add, None))
+                },
+            };
+            let start = self.push_value(preheader, Instruction::GetElemPtr(ty, base, idx), None);
+            let ptr = self.push_value(lp.header, Instruction::Phi(ptr_type, vec![(Operand::Value(start), preheader)]), None);
+            let next = self.push_value(latch, Instruction::GetElemPtr(elem_type.clone(), Operand::Value(ptr), vec![Operand::ConstInt(iv.step)]), None);
+            self.add_phi_incoming(ptr, Operand::Value(next), latch);
+            for (id, offset) in group.members {
+                let offset = offset - min;
+                if offset == 0 {
+                    self.replace_all_uses(id, &Operand::Value(ptr));
+                    self.erase_value(id);
+                } else {
+                    self.set_instr(id, Instruction::GetElemPtr(elem_type.clone(), Operand::Value(ptr), vec![Operand::ConstInt(offset)]));
+                }
+            }
+        }
+    }
+
+    /// 下标为归纳变量或归纳变量加常量时，返回归纳变量的下标与常量
+    fn iv_offset(&self, ivs: &[InductionVar], op: &Operand) -> Option<(usize, i64)> {
+        if let Some(iv) = ivs.iter().position(|iv| *op == Operand::Value(iv.phi)) {
+            return Some((iv, 0));
+        }
+        match self.instr(op.as_value()?) {
+            Instruction::Add(bin_op) => match (&bin_op.op1, &bin_op.op2) {
+                (x, Operand::ConstInt(c)) | (Operand::ConstInt(c), x) => {
+                    let iv = ivs.iter().position(|iv| *x == Operand::Value(iv.phi))?;
+                    Some((iv, *c))
+                },
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// 两个地址的类型、基址与除最后一个以外的下标都相同
+    fn same_array(&self, gep1: ValueId, gep2: ValueId) -> bool {
+        match (self.instr(gep1), self.instr(gep2)) {
+            (Instruction::GetElemPtr(ty1, base1, idx1), Instruction::GetElemPtr(ty2, base2, idx2)) => {
+                ty1.get_typename() == ty2.get_typename()
+                    && base1 == base2
+                    && idx1.len() == idx2.len()
+                    && idx1[..idx1.len() - 1] == idx2[..idx2.len() - 1]
+            },
+            _ => false,
+        }
+    }
+}
+
+/// 有n个下标的GetElemPtr得到的指针所指的类型，标量以没有维度的数组表示，用作指针递增的GetElemPtr的类型
+fn pointee_array(ty: &SymbolType, n: usize) -> SymbolType {
+    let elem = (1..n).fold(ty.clone(), |ty, _| element_of(&ty));
+    match elem.width {
+        SymbolWidth::Arr { .. } => elem,
+        _ => SymbolType::new(SymbolWidth::Arr { tar: Box::new(elem), dims: vec![] }, false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::llvm_opt::run_on_text;
+    use crate::structures::llvm_struct::LLVMProgram;
+
+    const INPUT: &str = "\
+@a = global [16 x i32] zeroinitializer, align 4
+
+declare i32 @getint()
+
+define i32 @main() {
+entry:
+  %n = call i32 @getint()
+  br label %loop
+
+loop:
+  %i = phi i32 [ 1, %entry ], [ %i1, %body ]
+  %c = icmp slt i32 %i, %n
+  br i1 %c, label %body, label %next
+
+body:
+  %p0 = getelementptr [16 x i32], [16 x i32]* @a, i32 0, i32 %i
+  %i1 = add i32 %i, 1
+  %p1 = getelementptr [16 x i32], [16 x i32]* @a, i32 0, i32 %i1
+  %k = add i32 %i, 2
+  %p2 = getelementptr [16 x i32], [16 x i32]* @a, i32 0, i32 %k
+  %x = load i32, i32* %p0, align 4
+  %y = load i32, i32* %p1, align 4
+  %z = add i32 %x, %y
+  store i32 %z, i32* %p2, align 4
+  br label %loop
+
+next:
+  br label %loop2
+
+loop2:
+  %j = phi i32 [ %n, %next ], [ %j1, %body2 ]
+  %c2 = icmp slt i32 %j, 15
+  br i1 %c2, label %body2, label %exit
+
+body2:
+  %j1 = add i32 %j, 1
+  %m = add i32 %j, -1
+  %q0 = getelementptr [16 x i32], [16 x i32]* @a, i32 0, i32 %m
+  %q1 = getelementptr [16 x i32], [16 x i32]* @a, i32 0, i32 %j
+  %w = load i32, i32* %q0, align 4
+  store i32 %w, i32* %q1, align 4
+  br label %loop2
+
+exit:
+  ret i32 0
+}";
+
+    #[test]
+    fn reduce_address_groups() {
+        let output = run_on_text(INPUT, LLVMProgram::strength_reduce);
+        let main = &output[output.find("define i32 @main").unwrap()..];
+        // 第一个循环中偏移为0、1、2的地址共用一个递增的指针；
+        // 第二个循环的初值不是常量，偏移为-1时起点可能越界，保持不变
+        assert_eq!(main, "\
+define i32 @main() {
+entry:
+  %n = call i32 @getint()
+  %0 = getelementptr inbounds [16 x i32], [16 x i32]* @a, i32 0, i32 1
+  br label %loop
+
+loop:
+  %i = phi i32 [ 1, %entry ], [ %i1, %body ]
+  %1 = phi i32* [ %0, %entry ], [ %2, %body ]
+  %c = icmp slt i32 %i, %n
+  br i1 %c, label %body, label %next
+
+body:
+  %i1 = add i32 %i, 1
+  %p1 = getelementptr inbounds i32, i32* %1, i32 1
+  %k = add i32 %i, 2
+  %p2 = getelementptr inbounds i32, i32* %1, i32 2
+  %x = load i32, i32* %1, align 4
+  %y = load i32, i32* %p1, align 4
+  %z = add i32 %x, %y
+  store i32 %z, i32* %p2, align 4
+  %2 = getelementptr inbounds i32, i32* %1, i32 1
+  br label %loop
+
+next:
+  br label %loop2
+
+loop2:
+  %j = phi i32 [ %n, %next ], [ %j1, %body2 ]
+  %c2 = icmp slt i32 %j, 15
+  br i1 %c2, label %body2, label %exit
+
+body2:
+  %j1 = add i32 %j, 1
+  %m = add i32 %j, -1
+  %q0 = getelementptr inbounds [16 x i32], [16 x i32]* @a, i32 0, i32 %m
+  %q1 = getelementptr inbounds [16 x i32], [16 x i32]* @a, i32 0, i32 %j
+  %w = load i32, i32* %q0, align 4
+  store i32 %w, i32* %q1, align 4
+  br label %loop2
+
+exit:
+  ret i32 0
+}");
+    }
+}
@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+use crate::structures::llvm_struct::*;
+use crate::structures::symbol::*;
+
+impl LLVMProgram {
+    /// 尾递归消除：将函数末尾对自身的调用改为跳回函数开头，参数由循环头的phi传递
+    pub fn tail_recursion_eliminate(&mut self) {
+        self.func_def.iter_mut().for_each(|func| func.tail_recursion_eliminate());
+    }
+}
+
+impl FuncDef {
+    fn tail_recursion_eliminate(&mut self) {
+        let tail_calls: Vec<(BlockId, ValueId)> = self.layout.iter()
+            .filter_map(|&bb| self.tail_self_call(bb).map(|call| (bb, call)))
+            .collect();
+        if tail_calls.is_empty() {
+            return;
+        }
+        // 实参指向本函数的局部变量时，跳回开头会与下一次调用共用同一块栈空间
+        if tail_calls.iter().any(|&(_, call)| self.call_args(call).iter().any(|arg| self.points_to_local(arg))) {
+            return;
+        }
+        // 每次递归都原样传递的参数不需要phi
+        let changed: Vec<usize> = (0..self.params.len())
+            .filter(|&idx| tail_calls.iter().any(|&(_, call)| self.call_args(call)[idx] != Operand::Arg(idx)))
+            .collect();
+
+        // 新的入口块只跳转到原入口块，原入口块成为循环头
+        let header = self.entry();
+        let label = self.block(header).block_label.clone();
+        self.block_mut(header).block_label = String::from("tail_recursion");
+        let entry = self.add_block(&label, 0);
+        self.layout.insert(0, entry);
+        for &alloca in self.local_vars.iter() {
+            self.values[alloca.0].block = Some(entry);
+        }
+        self.push_value(entry, Instruction::Br(None, header, None), None);
+
+        let phis: HashMap<usize, ValueId> = changed.iter().map(|&idx| {
+            let ty = self.params[idx].param_type.clone();
+            (idx, self.push_value(header, Instruction::Phi(ty, vec![(Operand::Arg(idx), entry)]), None))
+        }).collect();
+        for id in self.all_instrs() {
+            if phis.values().any(|&phi| phi == id) {
+                continue;
+            }
+            let mut instr = self.instr(id).clone();
+            let mut replaced = false;
+            for op in instr.operands_mut() {
+                if let Operand::Arg(idx) = op {
+                    if let Some(&phi) = phis.get(idx) {
+                        *op = Operand::Value(phi);
+                        replaced = true;
+                    }
+                }
+            }
+            if replaced {
+                self.set_instr(id, instr);
+            }
+        }
+
+        for (bb, call) in tail_calls {
+            let args = self.call_args(call);
+            for &idx in changed.iter() {
+                self.add_phi_incoming(phis[&idx], args[idx].clone(), bb);
+            }
+            let ret = self.block(bb).ter_ins.unwrap();
+            self.erase_value(ret);
+            self.erase_value(call);
+            self.push_value(bb, Instruction::Br(None, header, None), None);
+        }
+    }
+
+    fn call_args(&self, call: ValueId) -> Vec<Operand> {
+        match self.instr(call) {
+            Instruction::Call(_, _, params) => params.iter().map(|(arg, _)| arg.clone()).collect(),
+            _ => unreachable!(),
+        }
+    }
+
+    /// 基本块以对自身的调用结束，且紧接着返回调用的结果时，返回该调用
+    fn tail_self_call(&self, bb: BlockId) -> Option<ValueId> {
+        let block = self.block(bb);
+        let call = *block.nor_ins.last()?;
+        let returns_call = match (self.instr(call), self.instr(block.ter_ins?)) {
+            (Instruction::Call(name, _, _), _) if *name != self.func_name => false,
+            (Instruction::Call(_, ty, _), Instruction::Ret(_, None)) => ty.width == SymbolWidth::Void,
+            (Instruction::Call(_, _, _), Instruction::Ret(_, Some(value))) => *value == Operand::Value(call),
+            _ => false,
+        };
+        returns_call.then_some(call)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::llvm_opt::run_on_text;
+    use crate::structures::llvm_struct::LLVMProgram;
+
+    const INPUT: &str = "\
+declare void @putint(i32 noundef)
+
+define i32 @sum(i32 noundef %n, i32 noundef %acc) {
+entry:
+  %c = icmp sle i32 %n, 0
+  br i1 %c, label %done, label %rec
+
+done:
+  ret i32 %acc
+
+rec:
+  %n1 = sub i32 %n, 1
+  %acc1 = add i32 %acc, %n
+  %r = call i32 @sum(i32 noundef %n1, i32 noundef %acc1)
+  ret i32 %r
+}
+
+define i32 @fact(i32 noundef %n) {
+entry:
+  %c = icmp sle i32 %n, 1
+  br i1 %c, label %done, label %rec
+
+done:
+  ret i32 1
+
+rec:
+  %n1 = sub i32 %n, 1
+  %r = call i32 @fact(i32 noundef %n1)
+  %m = mul i32 %n, %r
+  ret i32 %m
+}
+
+define void @count(i32 noundef %n, i32 noundef %step) {
+entry:
+  %c = icmp sle i32 %n, 0
+  br i1 %c, label %done, label %rec
+
+done:
+  ret void
+
+rec:
+  call void @putint(i32 noundef %n)
+  %n1 = sub i32 %n, %step
+  call void @count(i32 noundef %n1, i32 noundef %step)
+  ret void
+}";
+
+    #[test]
+    fn eliminate_tail_recursion() {
+        let output = run_on_text(INPUT, LLVMProgram::tail_recursion_eliminate);
+        // 累加器形式的@sum与无返回值的@count改为循环，每次原样传递的%step不需要phi；
+        // @fact的递归结果还要参与乘法，不在尾部，保持不变
+        assert_eq!(output, "\
+declare void @putint(i32 noundef)
+
+define i32 @sum(i32 noundef %n, i32 noundef %acc) {
+entry:
+  br label %tail_recursion
+
+tail_recursion:
+  %0 = phi i32 [ %n, %entry ], [ %n1, %rec ]
+  %1 = phi i32 [ %acc, %entry ], [ %acc1, %rec ]
+  %c = icmp sle i32 %0, 0
+  br i1 %c, label %done, label %rec
+
+done:
+  ret i32 %1
+
+rec:
+  %n1 = sub i32 %0, 1
+  %acc1 = add i32 %1, %0
+  br label %tail_recursion
+}
+
+define i32 @fact(i32 noundef %n) {
+entry:
+  %c = icmp sle i32 %n, 1
+  br i1 %c, label %done, label %rec
+
+done:
+  ret i32 1
+
+rec:
+  %n1 = sub i32 %n, 1
+  %r = call i32 @fact(i32 noundef %n1)
+  %m = mul i32 %n, %r
+  ret i32 %m
+}
+
+define void @count(i32 noundef %n, i32 noundef %step) {
+entry:
+  br label %tail_recursion
+
+tail_recursion:
+  %0 = phi i32 [ %n, %entry ], [ %n1, %rec ]
+  %c = icmp sle i32 %0, 0
+  br i1 %c, label %done, label %rec
+
+done:
+  ret void
+
+rec:
+  call void @putint(i32 noundef %0)
+  %n1 = sub i32 %0, %step
+  br label %tail_recursion
+}");
+    }
+}
@@ -0,0 +1,441 @@
+use std::collections::HashMap;
+use crate::llvm_opt::induction::*;
+use crate::llvm_opt::loops::*;
+use crate::structures::llvm_struct::*;
+use crate::structures::scopes::Labels;
+use crate::structures::symbol::*;
+
+/// 完全展开后的指令数上限
+const FULL_UNROLL_SIZE: usize = 128;
+/// 部分展开的循环的指令数上限，展开后约为原来的factor倍
+const PARTIAL_UNROLL_SIZE: usize = 40;
+
+impl LLVMProgram {
+    /// 循环展开：迭代次数为常量且展开后规模不大的最内层计数循环完全展开，
+    /// 其余的按factor倍部分展开，剩余不足factor次的迭代仍由原循环完成；factor不超过1时不展开
+    pub fn unroll(&mut self, factor: usize) {
+        if factor <= 1 {
+            return;
+        }
+        self.func_def.iter_mut().for_each(|func| func.unroll(factor));
+    }
+}
+
+impl FuncDef {
+    fn unroll(&mut self, factor: usize) {
+        let info = self.insert_preheaders();
+        let headers: Vec<BlockId> = info.loops.iter()
+            .filter(|lp| lp.children.is_empty())
+            .map(|lp| lp.header)
+            .collect();
+        let mut labels = Labels::new();
+        for header in headers {
+            // 展开会改变控制流，每次重新分析循环
+            let info = self.loop_info();
+            let l = match info.loops.iter().position(|lp| lp.header == header) {
+                Some(l) => l,
+                None => continue,
+            };
+            let counted = match self.counted_loop(&info, l) {
+                Some(counted) => counted,
+                None => continue,
+            };
+            let lp = &info.loops[l];
+            let size: usize = lp.blocks.iter()
+                .flat_map(|&b| self.block_instrs(b))
+                .filter(|&id| !matches!(self.instr(id), Instruction::Comment(_)))
+                .count();
+            match counted.const_trip_count(FULL_UNROLL_SIZE / size) {
+                // 不会执行的循环由常量传播删除
+                Some(0) => {},
+                Some(trip) => self.unroll_full(lp, &counted, trip, &labels.pop_block("unroll")),
+                // 迭代次数为常量且少于factor时，展开的循环不会执行
+                None if size <= PARTIAL_UNROLL_SIZE && counted.const_trip_count(factor - 1).is_none() => {
+                    self.unroll_partial(lp, &counted, factor, &labels.pop_block("unroll"));
+                },
+                None => {},
+            }
+        }
+    }
+
+    /// 完全展开：依次执行trip次迭代的复制后进入原循环头，此时条件不再满足，原循环由常量传播删除
+    fn unroll_full(&mut self, lp: &Loop, counted: &CountedLoop, trip: usize, prefix: &str) {
+        let preheader = lp.preheader.unwrap();
+        let phis = self.block(lp.header).phi_ins.clone();
+        let mut values: HashMap<ValueId, Operand> = phis.iter().map(|&phi| (phi, self.phi_incoming(phi, preheader))).collect();
+        let mut pos = self.layout.iter().position(|&b| b == lp.header).unwrap();
+        let mut pred = preheader;
+        for j in 0..trip {
+            let (head, latch, next) = self.clone_iteration(lp, counted.body, &values, &format!("{}_{}", prefix, j), pos);
+            self.redirect_branch(pred, lp.header, head);
+            pos += lp.blocks.len();
+            pred = latch;
+            values = next;
+        }
+        for phi in phis {
+            self.replace_phi_incoming(phi, preheader, values[&phi].clone(), pred);
+        }
+    }
+
+    /// 部分展开：在原循环之前插入每次迭代执行factor次循环体的循环，剩余的迭代由原循环完成
+    fn unroll_partial(&mut self, lp: &Loop, counted: &CountedLoop, factor: usize, prefix: &str) {
+        let preheader = lp.preheader.unwrap();
+        let i32_type = SymbolType::new(SymbolWidth::I32, false);
+        // 展开的循环每次迭代开始时 iv + span 仍需满足条件，即iv与bound - span比较
+        let span = counted.iv.step * (factor as i64 - 1);
+        let limit = self.push_value(preheader, Instruction::Sub(BinaryOp {
+            op_type: i32_type.clone(),
+            op1: counted.bound.clone(),
+            op2: Operand::ConstInt(span),
+        }), None);
+        // bound - span溢出时不进入展开的循环
+        let no_overflow = if counted.iv.step > 0 { "slt" } else { "sgt" };
+        let valid = self.push_value(preheader, Instruction::Cmp(no_overflow.to_string(), BinaryOp {
+            op_type: i32_type.clone(),
+            op1: Operand::Value(limit),
+            op2: counted.bound.clone(),
+        }), None);
+
+        let depth = self.block(lp.header).depth;
+        let head = self.add_block(&format!("{}_header", prefix), depth);
+        let exit = self.add_block(&format!("{}_exit", prefix), depth);
+        let ter = self.block(preheader).ter_ins.unwrap();
+        self.set_instr(ter, Instruction::Br(Some(Operand::Value(valid)), head, Some(exit)));
+
+        let phis = self.block(lp.header).phi_ins.clone();
+        let mut head_phis = vec![];
+        let mut values = HashMap::new();
+        for &phi in phis.iter() {
+            let ty = match self.instr(phi) {
+                Instruction::Phi(ty, _) => ty.clone(),
+                _ => unreachable!(),
+            };
+            let init = self.phi_incoming(phi, preheader);
+            let head_phi = self.push_value(head, Instruction::Phi(ty, vec![(init, preheader)]), None);
+            head_phis.push(head_phi);
+            values.insert(phi, Operand::Value(head_phi));
+        }
+        let cond = self.push_value(head, Instruction::Cmp(counted.pred.clone(), BinaryOp {
+            op_type: i32_type,
+            op1: values[&counted.iv.phi].clone(),
+            op2: Operand::Value(limit),
+        }), None);
+
+        let mut pos = self.layout.iter().position(|&b| b == lp.header).unwrap();
+        self.layout.insert(pos, head);
+        pos += 1;
+        let mut first = None;
+        let mut pred = head;
+        for j in 0..factor {
+            let (copy, latch, next) = self.clone_iteration(lp, counted.body, &values, &format!("{}_{}", prefix, j), pos);
+            match first {
+                None => first = Some(copy),
+                Some(_) => self.redirect_branch(pred, lp.header, copy),
+            }
+            pos += lp.blocks.len();
+            pred = latch;
+            values = next;
+        }
+        self.push_value(head, Instruction::Br(Some(Operand::Value(cond)), first.unwrap(), Some(exit)), None);
+        self.redirect_branch(pred, lp.header, head);
+
+        // 原循环从展开的循环结束时的值继续，没有进入展开的循环时从初值开始
+        self.layout.insert(pos, exit);
+        for (&phi, &head_phi) in phis.iter().zip(head_phis.iter()) {
+            self.add_phi_incoming(head_phi, values[&phi].clone(), pred);
+            let (ty, init) = match self.instr(head_phi) {
+                Instruction::Phi(ty, candidates) => (ty.clone(), candidates[0].0.clone()),
+                _ => unreachable!(),
+            };
+            let merged = self.push_value(exit, Instruction::Phi(ty, vec![(init, preheader), (Operand::Value(head_phi), head)]), None);
+            self.replace_phi_incoming(phi, preheader, Operand::Value(merged), exit);
+        }
+        self.push_value(exit, Instruction::Br(None, lp.header, None), None);
+    }
+
+    /// 将循环的一次迭代(循环头中的一般指令与其余各块)复制到以prefix命名的新基本块中，插入到布局的pos处
+    /// values为本次迭代开始时循环头各phi的值；返回复制的入口块、回边起点的复制，以及下一次迭代开始时各phi的值
+    /// 回边起点的复制仍跳转到原循环头，由调用者修改
+    fn clone_iteration(&mut self, lp: &Loop, body: BlockId, values: &HashMap<ValueId, Operand>, prefix: &str, pos: usize) -> (BlockId, BlockId, HashMap<ValueId, Operand>) {
+        let mut block_map = HashMap::new();
+        for (i, &b) in lp.blocks.iter().enumerate() {
+            let label = format!("{}_{}", prefix, self.block(b).block_label.trim_start_matches('_'));
+            let new = self.add_block(&label, self.block(b).depth);
+            block_map.insert(b, new);
+            self.layout.insert(pos + i, new);
+        }
+        let map_block = |b: BlockId| if b == lp.header { b } else { block_map[&b] };
+
+        // 循环中只有回边，按逆后序复制时定义总在使用之前
+        let mut value_map = values.clone();
+        for &b in lp.blocks.iter() {
+            let instrs = if b == lp.header { self.block(b).nor_ins.clone() } else { self.block_instrs(b) };
+            for id in instrs {
+                let mut instr = self.instr(id).clone();
+                for op in instr.operands_mut() {
+                    if let Some(new) = op.as_value().and_then(|v| value_map.get(&v)) {
+                        *op = new.clone();
+                    }
+                }
+                let instr = match instr {
+                    Instruction::Comment(_) => continue,
+                    Instruction::Phi(ty, candidates) => {
+                        Instruction::Phi(ty, candidates.into_iter().map(|(value, pred)| (value, block_map[&pred])).collect())
+                    },
+                    Instruction::Br(cond, label1, label2) => Instruction::Br(cond, map_block(label1), label2.map(map_block)),
+                    instr => self.fold_const_add(instr),
+                };
+                let new = self.push_value(block_map[&b], instr, None);
+                value_map.insert(id, Operand::Value(new));
+            }
+        }
+        let head = block_map[&lp.header];
+        self.push_value(head, Instruction::Br(None, block_map[&body], None), None);
+
+        let latch = lp.latches[0];
+        let next = values.keys().map(|&phi| {
+            let value = self.phi_incoming(phi, latch);
+            let value = value.as_value().and_then(|v| value_map.get(&v)).cloned().unwrap_or(value);
+            (phi, value)
+        }).collect();
+        (head, block_map[&latch], next)
+    }
+
+    /// 将 (x + c1) + c2 合并为 x + (c1 + c2)，展开后各次迭代的归纳变量都直接由循环头的值得到
+    fn fold_const_add(&self, instr: Instruction) -> Instruction {
+        if let Instruction::Add(bin_op) = &instr {
+            if let Some((Operand::Value(inner), c2)) = const_add(&instr) {
+                if let Some((x, c1)) = const_add(self.instr(inner)) {
+                    return Instruction::Add(BinaryOp {
+                        op_type: bin_op.op_type.clone(),
+                        op1: x,
+                        op2: Operand::ConstInt(c1.wrapping_add(c2) as i32 as i64),
+                    });
+                }
+            }
+        }
+        instr
+    }
+
+    /// phi中来自pred的值
+    fn phi_incoming(&self, phi: ValueId, pred: BlockId) -> Operand {
+        match self.instr(phi) {
+            Instruction::Phi(_, candidates) => candidates.iter().find(|(_, p)| *p == pred).unwrap().0.clone(),
+            _ => unreachable!(),
+        }
+    }
+
+    /// 将phi中来自old_pred的候选替换为来自new_pred的value
+    fn replace_phi_incoming(&mut self, phi: ValueId, old_pred: BlockId, value: Operand, new_pred: BlockId) {
+        if let Instruction::Phi(ty, candidates) = self.instr(phi) {
+            let candidates = candidates.iter()
+                .map(|(v, p)| if *p == old_pred { (value.clone(), new_pred) } else { (v.clone(), *p) })
+                .collect();
+            self.set_instr(phi, Instruction::Phi(ty.clone(), candidates));
+        }
+    }
+}
+
+/// 形如 x + c 的整数加法，返回<x, c>
+fn const_add(instr: &Instruction) -> Option<(Operand, i64)> {
+    match instr {
+        Instruction::Add(bin_op) => match (&bin_op.op1, &bin_op.op2) {
+            (Operand::ConstInt(_), Operand::ConstInt(_)) => None,
+            (x, Operand::ConstInt(c)) | (Operand::ConstInt(c), x) => Some((x.clone(), *c)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::llvm_gen::read_text::read_llvm;
+    use crate::llvm_opt::run_on_text;
+
+    /// 从0开始每次加step、小于bound时继续的计数循环，返回累加的和
+    fn counted_loop(init: &str, step: i64, bound: &str) -> String {
+        format!("\
+declare i32 @getint()
+
+define i32 @main() {{
+entry:
+  %n = call i32 @getint()
+  br label %loop
+
+loop:
+  %i = phi i32 [ {}, %entry ], [ %i1, %body ]
+  %s = phi i32 [ 0, %entry ], [ %s1, %body ]
+  %c = icmp slt i32 %i, {}
+  br i1 %c, label %body, label %exit
+
+body:
+  %s1 = add i32 %s, %i
+  %i1 = add i32 %i, {}
+  br label %loop
+
+exit:
+  ret i32 %s
+}}", init, bound, step)
+    }
+
+    #[test]
+    fn full_unroll() {
+        // 3次迭代依次复制，之后进入原循环头时条件不再满足，原循环留给常量传播删除
+        let output = run_on_text(&counted_loop("0", 1, "3"), |llvm| llvm.unroll(4));
+        let main = &output[output.find("define i32 @main").unwrap()..];
+        assert_eq!(main, "\
+define i32 @main() {
+entry:
+  %n = call i32 @getint()
+  br label %unroll_0_0_loop
+
+unroll_0_0_loop:
+  %0 = icmp slt i32 0, 3
+  br label %unroll_0_0_body
+
+unroll_0_0_body:
+  %1 = add i32 0, 0
+  %2 = add i32 0, 1
+  br label %unroll_0_1_loop
+
+unroll_0_1_loop:
+  %3 = icmp slt i32 %2, 3
+  br label %unroll_0_1_body
+
+unroll_0_1_body:
+  %4 = add i32 %1, %2
+  %5 = add i32 %2, 1
+  br label %unroll_0_2_loop
+
+unroll_0_2_loop:
+  %6 = icmp slt i32 %5, 3
+  br label %unroll_0_2_body
+
+unroll_0_2_body:
+  %7 = add i32 %4, %5
+  %8 = add i32 %2, 2
+  br label %loop
+
+loop:
+  %i = phi i32 [ %8, %unroll_0_2_body ], [ %i1, %body ]
+  %s = phi i32 [ %7, %unroll_0_2_body ], [ %s1, %body ]
+  %c = icmp slt i32 %i, 3
+  br i1 %c, label %body, label %exit
+
+body:
+  %s1 = add i32 %s, %i
+  %i1 = add i32 %i, 1
+  br label %loop
+
+exit:
+  ret i32 %s
+}");
+    }
+
+    #[test]
+    fn partial_unroll() {
+        // 展开的循环与i + 1比较%n - 1，剩余的迭代由原循环从展开的循环结束时的值继续
+        let output = run_on_text(&counted_loop("0", 1, "%n"), |llvm| llvm.unroll(2));
+        let main = &output[output.find("define i32 @main").unwrap()..];
+        assert_eq!(main, "\
+define i32 @main() {
+entry:
+  %n = call i32 @getint()
+  %0 = sub i32 %n, 1
+  %1 = icmp slt i32 %0, %n
+  br i1 %1, label %unroll_0_header, label %unroll_0_exit
+
+unroll_0_header:
+  %2 = phi i32 [ 0, %entry ], [ %10, %unroll_0_1_body ]
+  %3 = phi i32 [ 0, %entry ], [ %9, %unroll_0_1_body ]
+  %4 = icmp slt i32 %2, %0
+  br i1 %4, label %unroll_0_0_loop, label %unroll_0_exit
+
+unroll_0_0_loop:
+  %5 = icmp slt i32 %2, %n
+  br label %unroll_0_0_body
+
+unroll_0_0_body:
+  %6 = add i32 %3, %2
+  %7 = add i32 %2, 1
+  br label %unroll_0_1_loop
+
+unroll_0_1_loop:
+  %8 = icmp slt i32 %7, %n
+  br label %unroll_0_1_body
+
+unroll_0_1_body:
+  %9 = add i32 %6, %7
+  %10 = add i32 %2, 2
+  br label %unroll_0_header
+
+unroll_0_exit:
+  %11 = phi i32 [ 0, %entry ], [ %2, %unroll_0_header ]
+  %12 = phi i32 [ 0, %entry ], [ %3, %unroll_0_header ]
+  br label %loop
+
+loop:
+  %i = phi i32 [ %11, %unroll_0_exit ], [ %i1, %body ]
+  %s = phi i32 [ %12, %unroll_0_exit ], [ %s1, %body ]
+  %c = icmp slt i32 %i, %n
+  br i1 %c, label %body, label %exit
+
+body:
+  %s1 = add i32 %s, %i
+  %i1 = add i32 %i, 1
+  br label %loop
+
+exit:
+  ret i32 %s
+}");
+    }
+
+    fn run_unrolled(text: &str, factor: usize, input: &str) -> i32 {
+        let mut llvm = read_llvm(text).unwrap();
+        llvm.unroll(factor);
+        assert_eq!(llvm.verify(), Ok(()));
+        llvm.interpret(input.as_bytes()).unwrap().exit_code
+    }
+
+    #[test]
+    fn remainder_and_overflow_guard() {
+        // 7次迭代中4次由展开的循环完成，剩余3次由原循环完成
+        assert_eq!(run_unrolled(&counted_loop("0", 1, "%n"), 4, "7"), 21);
+        assert_eq!(run_unrolled(&counted_loop("0", 3, "%n"), 4, "10"), 18);
+        // 原循环只执行2次；%n - 3 * 1073741823回绕为3，不检查时展开的循环会执行4次，应跳过它
+        let text = counted_loop("-2147483648", 1073741823, "%n");
+        assert_eq!(run_unrolled(&text, 1, "-1073741824"), 1073741823);
+        assert_eq!(run_unrolled(&text, 4, "-1073741824"), 1073741823);
+    }
+
+    #[test]
+    fn keep_loops_exiting_from_body() {
+        // 循环体中的break使循环头不是唯一的出口
+        let input = "\
+declare i32 @getint()
+
+define i32 @main() {
+entry:
+  %n = call i32 @getint()
+  br label %loop
+
+loop:
+  %i = phi i32 [ 0, %entry ], [ %i1, %latch ]
+  %c = icmp slt i32 %i, %n
+  br i1 %c, label %body, label %exit
+
+body:
+  %b = icmp eq i32 %i, 5
+  br i1 %b, label %exit, label %latch
+
+latch:
+  %i1 = add i32 %i, 1
+  br label %loop
+
+exit:
+  ret i32 %i
+}";
+        assert_eq!(run_on_text(input, |llvm| llvm.unroll(4)), input);
+    }
+}
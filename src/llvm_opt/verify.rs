@@ -0,0 +1,471 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use crate::llvm_opt::dominator::*;
+use crate::structures::llvm_struct::*;
+use crate::structures::symbol::*;
+
+/// IR校验发现的一处错误，记录所在的函数、基本块与指令
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifyError {
+    pub func: String,
+    pub block: Option<String>,
+    /// 出错指令的文本
+    pub instr: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.func)?;
+        if let Some(block) = &self.block {
+            write!(f, ", %{}", block)?;
+        }
+        if let Some(instr) = &self.instr {
+            write!(f, ", `{}`", instr)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+impl LLVMProgram {
+    /// 检查IR是否合法，返回发现的全部错误
+    pub fn verify(&self) -> Result<(), Vec<VerifyError>> {
+        let globals: HashMap<&str, &SymbolType> = self.global_var.iter()
+            .map(|var| (var.var_name.as_str(), &var.var_type))
+            .collect();
+        // 函数名(含@)到形参类型与返回类型
+        let mut signatures: HashMap<String, (Vec<SymbolType>, SymbolType)> = HashMap::new();
+        for decl in self.func_decl.iter() {
+            signatures.insert(format!("@{}", decl.func_name), (decl.param_types.clone(), decl.func_type.clone()));
+        }
+        for def in self.func_def.iter() {
+            let params = def.params.iter().map(|param| param.param_type.clone()).collect();
+            signatures.insert(def.func_name.clone(), (params, def.func_type.clone()));
+        }
+
+        let errors: Vec<VerifyError> = self.func_def.iter()
+            .flat_map(|func| Verifier::new(func, &globals, &signatures).run())
+            .collect();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// 在debug模式下校验IR，出错时指出是哪个pass破坏了哪条指令
+    pub fn verify_after(&self, pass: &str) {
+        if !cfg!(debug_assertions) {
+            return;
+        }
+        if let Err(errors) = self.verify() {
+            let errors: Vec<String> = errors.iter().map(|err| format!("  {}", err)).collect();
+            panic!("IR在`{}`之后校验失败:\n{}", pass, errors.join("\n"));
+        }
+    }
+}
+
+/// 两个类型在IR中是否相同，忽略常量属性
+fn same_type(a: &SymbolType, b: &SymbolType) -> bool {
+    a.get_typename() == b.get_typename()
+}
+
+struct Verifier<'a> {
+    func: &'a FuncDef,
+    globals: &'a HashMap<&'a str, &'a SymbolType>,
+    signatures: &'a HashMap<String, (Vec<SymbolType>, SymbolType)>,
+    names: Vec<String>,
+    cfg: ControlFlowGraph,
+    dom: DominatorTree,
+    /// 各指令的位置<基本块, 块内序号>，局部变量位于入口块最前
+    position: HashMap<ValueId, (BlockId, usize)>,
+    errors: Vec<VerifyError>,
+}
+
+impl<'a> Verifier<'a> {
+    fn new(
+        func: &'a FuncDef,
+        globals: &'a HashMap<&'a str, &'a SymbolType>,
+        signatures: &'a HashMap<String, (Vec<SymbolType>, SymbolType)>,
+    ) -> Self {
+        let cfg = func.build_cfg();
+        let dom = cfg.dominator_tree();
+        Verifier {
+            func,
+            globals,
+            signatures,
+            names: func.value_names(),
+            cfg,
+            dom,
+            position: HashMap::new(),
+            errors: vec![],
+        }
+    }
+
+    fn error(&mut self, bb: Option<BlockId>, instr: Option<ValueId>, message: String) {
+        self.errors.push(VerifyError {
+            func: self.func.func_name.clone(),
+            block: bb.map(|bb| self.func.block(bb).block_label.clone()),
+            instr: instr.map(|id| self.func.instr_text(id, &self.names)),
+            message,
+        });
+    }
+
+    fn run(mut self) -> Vec<VerifyError> {
+        if self.func.layout.is_empty() {
+            self.error(None, None, String::from("函数没有基本块"));
+            return self.errors;
+        }
+        self.check_layout();
+        // 结构有误时位置信息不可靠，不再检查使用关系
+        if self.errors.is_empty() {
+            let instrs: Vec<(BlockId, ValueId)> = self.func.layout.iter()
+                .flat_map(|&bb| self.func.block_instrs(bb).into_iter().map(move |id| (bb, id)))
+                .collect();
+            let entry = self.func.entry();
+            for &alloca in self.func.local_vars.iter() {
+                self.check_instr(entry, alloca);
+            }
+            for (bb, id) in instrs {
+                self.check_instr(bb, id);
+            }
+        }
+        self.errors
+    }
+
+    /// 检查每个基本块的指令分区与终结指令，并记录各指令的位置
+    fn check_layout(&mut self) {
+        let func = self.func;
+        let entry = func.entry();
+        let layout: HashSet<BlockId> = func.layout.iter().cloned().collect();
+        if layout.len() != func.layout.len() {
+            self.error(None, None, String::from("布局中有重复的基本块"));
+        }
+
+        for &alloca in func.local_vars.iter() {
+            if !matches!(func.instr(alloca), Instruction::Alloca { .. }) {
+                self.error(Some(entry), Some(alloca), String::from("局部变量表中只能有alloca"));
+            }
+            if func.values[alloca.0].block != Some(entry) {
+                self.error(Some(entry), Some(alloca), String::from("alloca应属于入口块"));
+            }
+            if self.position.insert(alloca, (entry, 0)).is_some() {
+                self.error(Some(entry), Some(alloca), String::from("指令在函数中出现多次"));
+            }
+        }
+
+        for &bb in func.layout.iter() {
+            let block = func.block(bb);
+            for &phi in block.phi_ins.iter() {
+                if !matches!(func.instr(phi), Instruction::Phi(_, _)) {
+                    self.error(Some(bb), Some(phi), String::from("phi区中只能有phi"));
+                }
+            }
+            for &instr in block.nor_ins.iter() {
+                match func.instr(instr) {
+                    Instruction::Phi(_, _) => self.error(Some(bb), Some(instr), String::from("phi只能位于基本块开头")),
+                    Instruction::Alloca { .. } => self.error(Some(bb), Some(instr), String::from("alloca只能位于局部变量表中")),
+                    Instruction::Br(_, _, _) | Instruction::Ret(_, _) => {
+                        self.error(Some(bb), Some(instr), String::from("终结指令只能位于基本块末尾"))
+                    },
+                    _ => {},
+                }
+            }
+            match block.ter_ins {
+                Some(ter) => {
+                    if !matches!(func.instr(ter), Instruction::Br(_, _, _) | Instruction::Ret(_, _)) {
+                        self.error(Some(bb), Some(ter), String::from("基本块末尾不是终结指令"));
+                    }
+                    for succ in func.instr(ter).successors() {
+                        if !layout.contains(&succ) {
+                            let label = func.block(succ).block_label.clone();
+                            self.error(Some(bb), Some(ter), format!("跳转目标%{}不在函数中", label));
+                        }
+                    }
+                },
+                None => self.error(Some(bb), None, String::from("基本块没有终结指令")),
+            }
+
+            for (i, id) in func.block_instrs(bb).into_iter().enumerate() {
+                if func.values[id.0].block != Some(bb) {
+                    self.error(Some(bb), Some(id), String::from("指令记录的所属基本块与实际不符"));
+                }
+                // 局部变量占据入口块的0号位置
+                if self.position.insert(id, (bb, i + 1)).is_some() {
+                    self.error(Some(bb), Some(id), String::from("指令在函数中出现多次"));
+                }
+            }
+        }
+    }
+
+    /// 检查一条指令的操作数、类型与phi的前驱
+    fn check_instr(&mut self, bb: BlockId, id: ValueId) {
+        let func = self.func;
+        let instr = func.instr(id);
+
+        // 操作数必须已定义，且定义支配使用
+        match instr {
+            Instruction::Phi(_, candidates) => {
+                for (value, pred) in candidates.iter() {
+                    // 来自前驱的值只需在前驱的末尾可用
+                    self.check_operand(bb, id, value, (*pred, usize::MAX));
+                }
+            },
+            _ => {
+                let pos = self.position[&id];
+                for op in instr.operands() {
+                    self.check_operand(bb, id, op, pos);
+                }
+            },
+        }
+
+        match instr {
+            Instruction::Add(bin_op)
+            | Instruction::Sub(bin_op)
+            | Instruction::Mul(bin_op)
+            | Instruction::Sdiv(bin_op)
+            | Instruction::Srem(bin_op)
+            | Instruction::Fadd(bin_op)
+            | Instruction::Fsub(bin_op)
+            | Instruction::Fmul(bin_op)
+            | Instruction::Fdiv(bin_op)
+            | Instruction::Cmp(_, bin_op)
+            | Instruction::Fcmp(_, bin_op) => {
+                self.check_type(bb, id, &bin_op.op1, &bin_op.op_type);
+                self.check_type(bb, id, &bin_op.op2, &bin_op.op_type);
+            },
+            Instruction::ZeroExt(cast_op)
+            | Instruction::I32ToFloat(cast_op)
+            | Instruction::FloatToI32(cast_op) => {
+                self.check_type(bb, id, &cast_op.val, &cast_op.type_1);
+            },
+            Instruction::Phi(ty, candidates) => {
+                for (value, _) in candidates.iter() {
+                    self.check_type(bb, id, value, ty);
+                }
+                self.check_phi_preds(bb, id, candidates);
+            },
+            Instruction::Store { ty, value, ptr, .. } => {
+                self.check_type(bb, id, value, ty);
+                self.check_type(bb, id, ptr, &pointer_to(ty));
+            },
+            Instruction::Load { ty, ptr, .. } => {
+                self.check_type(bb, id, ptr, &pointer_to(ty));
+            },
+            Instruction::GetElemPtr(ty, ptr, _) => {
+                self.check_type(bb, id, ptr, &pointer_to(ty));
+            },
+            Instruction::BitCast(ty, ptr, _) => {
+                self.check_type(bb, id, ptr, &pointer_to(ty));
+            },
+            Instruction::Call(label, ty, params) => {
+                match self.signatures.get(label) {
+                    Some((param_types, ret_type)) => {
+                        if !same_type(ret_type, ty) {
+                            self.error(Some(bb), Some(id), format!("{}的返回类型为{}", label, ret_type.get_typename()));
+                        }
+                        if param_types.len() != params.len() {
+                            self.error(Some(bb), Some(id), format!("{}需要{}个参数", label, param_types.len()));
+                        }
+                        for ((value, value_ty), param_ty) in params.iter().zip(param_types.iter()) {
+                            if !same_type(value_ty, param_ty) {
+                                self.error(Some(bb), Some(id), format!("参数类型应为{}", param_ty.get_typename()));
+                            }
+                            self.check_type(bb, id, value, value_ty);
+                        }
+                    },
+                    None => self.error(Some(bb), Some(id), format!("调用了未声明的函数{}", label)),
+                }
+            },
+            Instruction::Ret(ty, value) => {
+                if !same_type(ty, &func.func_type) {
+                    self.error(Some(bb), Some(id), format!("函数的返回类型为{}", func.func_type.get_typename()));
+                }
+                match value {
+                    Some(value) => self.check_type(bb, id, value, ty),
+                    None if ty.width != SymbolWidth::Void => self.error(Some(bb), Some(id), String::from("缺少返回值")),
+                    None => {},
+                }
+            },
+            Instruction::Br(Some(cond), _, _) => {
+                self.check_type(bb, id, cond, &SymbolType::new(SymbolWidth::Bool, false));
+            },
+            _ => {},
+        }
+    }
+
+    /// 检查操作数已定义且其定义支配pos处的使用
+    fn check_operand(&mut self, bb: BlockId, id: ValueId, op: &Operand, pos: (BlockId, usize)) {
+        let func = self.func;
+        match op {
+            Operand::Arg(idx) if *idx >= func.params.len() => {
+                self.error(Some(bb), Some(id), format!("函数没有第{}个参数", idx));
+            },
+            Operand::Global(name) if !self.globals.contains_key(name.as_str()) => {
+                self.error(Some(bb), Some(id), format!("未定义的全局变量@{}", name));
+            },
+            Operand::Value(v) => {
+                if v.0 >= func.values.len() || !self.position.contains_key(v) {
+                    self.error(Some(bb), Some(id), format!("使用了已删除或未放置的值{:?}", v));
+                    return;
+                }
+                if !func.instr(*v).has_result() {
+                    let text = func.instr_text(*v, &self.names);
+                    self.error(Some(bb), Some(id), format!("使用了没有结果的指令`{}`", text));
+                    return;
+                }
+                // 不可达的使用处不要求支配关系
+                let (use_bb, use_idx) = pos;
+                if self.dom.rpo.contains(&use_bb) {
+                    let (def_bb, def_idx) = self.position[v];
+                    let dominates = if def_bb == use_bb {
+                        def_idx < use_idx
+                    } else {
+                        self.dom.dominates(def_bb, use_bb)
+                    };
+                    if !dominates {
+                        self.error(Some(bb), Some(id), format!("{}的定义不支配此处的使用", self.names[v.0]));
+                    }
+                }
+                if !func.values[v.0].users.contains(&id) {
+                    self.error(Some(bb), Some(id), format!("{}的使用者中缺少此指令", self.names[v.0]));
+                }
+            },
+            _ => {},
+        }
+    }
+
+    /// 检查操作数的类型，常量的类型由使用处决定，不做检查
+    fn check_type(&mut self, bb: BlockId, id: ValueId, op: &Operand, expected: &SymbolType) {
+        let actual = match op {
+            Operand::Global(name) => self.globals.get(name.as_str()).map(|ty| pointer_to(ty)),
+            _ => self.func.operand_type(op),
+        };
+        if let Some(actual) = actual {
+            if !same_type(&actual, expected) {
+                let text = self.func.operand_text(op, &self.names);
+                self.error(Some(bb), Some(id), format!("{}的类型为{}，应为{}", text, actual.get_typename(), expected.get_typename()));
+            }
+        }
+    }
+
+    /// phi的候选块与基本块的前驱一一对应
+    fn check_phi_preds(&mut self, bb: BlockId, id: ValueId, candidates: &[(Operand, BlockId)]) {
+        let mut incoming: Vec<BlockId> = candidates.iter().map(|(_, pred)| *pred).collect();
+        let mut preds = self.cfg.preds[bb.0].clone();
+        incoming.sort();
+        preds.sort();
+        if incoming != preds {
+            let labels = |blocks: &[BlockId]| blocks.iter()
+                .map(|b| format!("%{}", self.func.block(*b).block_label))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let message = format!("phi的候选块[{}]与前驱[{}]不一致", labels(&incoming), labels(&preds));
+            self.error(Some(bb), Some(id), message);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::llvm_gen::read_text::read_llvm;
+    use crate::structures::llvm_struct::*;
+    use crate::structures::symbol::*;
+
+    const INPUT: &str = "\
+@g = global i32 0, align 4
+
+define i32 @f(i32 noundef %n) {
+entry:
+  %c = icmp sgt i32 %n, 0
+  br i1 %c, label %then, label %else
+
+then:
+  %a = add i32 %n, 1
+  br label %end
+
+else:
+  %b = sub i32 %n, 1
+  br label %end
+
+end:
+  %r = phi i32 [ %a, %then ], [ %b, %else ]
+  store i32 %r, i32* @g, align 4
+  ret i32 %r
+}";
+
+    /// 读入INPUT，经过modify修改后校验，返回全部错误的文本
+    fn errors(input: &str, modify: impl FnOnce(&mut FuncDef)) -> Vec<String> {
+        let mut llvm = read_llvm(input).unwrap();
+        assert_eq!(llvm.verify(), Ok(()));
+        modify(&mut llvm.func_def[0]);
+        llvm.verify().unwrap_err().iter().map(|err| err.to_string()).collect()
+    }
+
+    fn block(func: &FuncDef, label: &str) -> BlockId {
+        func.layout.iter().cloned().find(|&b| func.block(b).block_label == label).unwrap()
+    }
+
+    #[test]
+    fn missing_terminator() {
+        let errors = errors(INPUT, |func| {
+            let ter = func.block(block(func, "then")).ter_ins.unwrap();
+            func.erase_value(ter);
+        });
+        assert_eq!(errors, ["@f, %then: 基本块没有终结指令"]);
+    }
+
+    #[test]
+    fn use_not_dominated() {
+        let errors = errors(INPUT, |func| {
+            let b = func.block(block(func, "else")).nor_ins[0];
+            let a = func.block(block(func, "then")).nor_ins[0];
+            let mut instr = func.instr(b).clone();
+            if let Instruction::Sub(bin_op) = &mut instr {
+                bin_op.op1 = Operand::Value(a);
+            }
+            func.set_instr(b, instr);
+        });
+        assert_eq!(errors, ["@f, %else, `%b = sub i32 %a, 1`: %a的定义不支配此处的使用"]);
+    }
+
+    #[test]
+    fn phi_incoming_mismatch() {
+        let errors = errors(INPUT, |func| {
+            let phi = func.block(block(func, "end")).phi_ins[0];
+            let mut instr = func.instr(phi).clone();
+            if let Instruction::Phi(_, candidates) = &mut instr {
+                candidates.pop();
+            }
+            func.set_instr(phi, instr);
+        });
+        assert_eq!(errors, ["@f, %end, `%r = phi i32 [ %a, %then ]`: phi的候选块[%then]与前驱[%then, %else]不一致"]);
+    }
+
+    #[test]
+    fn mismatched_memory_type() {
+        let errors = errors(INPUT, |func| {
+            let store = func.block(block(func, "end")).nor_ins[0];
+            let mut instr = func.instr(store).clone();
+            if let Instruction::Store { ty, .. } = &mut instr {
+                *ty = SymbolType::new(SymbolWidth::Float, false);
+            }
+            func.set_instr(store, instr);
+        });
+        assert_eq!(errors, [
+            "@f, %end, `store float %r, float* @g, align 4`: %r的类型为i32，应为float",
+            "@f, %end, `store float %r, float* @g, align 4`: @g的类型为i32*，应为float*",
+        ]);
+    }
+
+    #[test]
+    fn branch_outside_layout() {
+        let errors = errors(INPUT, |func| {
+            let orphan = func.add_block("orphan", 0);
+            let then = block(func, "then");
+            let ter = func.block(then).ter_ins.unwrap();
+            func.set_instr(ter, Instruction::Br(None, orphan, None));
+        });
+        assert_eq!(errors, ["@f, %then, `br label %orphan`: 跳转目标%orphan不在函数中"]);
+    }
+}
@@ -1,46 +1,108 @@
-mod utils;
-mod structures;
-mod ast;
-mod llvm_gen;
-mod llvm_opt;
-mod riscv_gen;
-mod riscv_opt;
-
 use std::fs;
 use std::env::args;
-use std::fs::read_to_string;
-use llvm_gen::generate_llvm;
-use riscv_gen::generate_asm;
-use crate::structures::writetext_trait::*;
+use std::io::{self, Read, Write};
+use std::process::exit;
+use compiler::Session;
+use compiler::diagnostic::{Diagnostic, SourceFile};
+use compiler::options::*;
 
-use lalrpop_util::lalrpop_mod;
-lalrpop_mod!(parser);
+/// 打印错误信息并以非零状态码退出
+fn fatal(msg: impl std::fmt::Display) -> ! {
+    eprintln!("error: {}", msg);
+    exit(1);
+}
 
-fn main() {
-    let mut args = args();
-    //跳过第一个参数
-    args.next();
-    //获取待编译的文件名
-    let file_name = args.next().unwrap();
-    //用lalrpop解析得到ast
-    let mut ast = parser::SysYParser::new().parse(&read_to_string(&file_name).unwrap()).unwrap();
-    //生成llvm
-    let mut llvm = generate_llvm(&mut ast);
-    llvm.optimise_llvm();
-    let filename_without_suffix = file_name.split(".").collect::<Vec<_>>()[0].to_string();
-    //编译选项，可选-llvm和-S
-    match args.next().unwrap().as_str() {
-        "-llvm" => {
-            let mut llvm_file = fs::File::create(filename_without_suffix + ".ll").unwrap();
-            llvm.writetext(&mut llvm_file);
-        }
-        "-S" => {
-            let mut asm = generate_asm(&llvm);
-            asm.optimise_riscv();
+/// 打印全部诊断信息并以非零状态码退出
+fn abort(source: &SourceFile, diagnostics: &[Diagnostic]) -> ! {
+    for diagnostic in diagnostics.iter() {
+        eprintln!("{}", diagnostic.render(source));
+    }
+    let errors = diagnostics.iter().filter(|diagnostic| diagnostic.is_error()).count();
+    eprintln!("error: 由于{}个错误，编译终止", errors);
+    exit(1);
+}
+
+/// 将产物写到文件，未指定文件时写到标准输出
+fn emit_to(options: &Options, emit: Emit, write: impl FnOnce(&mut dyn Write)) {
+    match options.output_path(emit) {
+        Some(path) => {
+            //例如输入foo.ll且输出llvm时，默认的输出文件就是输入文件
+            if options.input.as_ref() == Some(&path) {
+                fatal(format!("输出文件`{}`会覆盖输入文件，请用-o指定其它文件", path.display()));
+            }
+            let file = fs::File::create(&path)
+                .unwrap_or_else(|err| fatal(format!("无法创建`{}`: {}", path.display(), err)));
+            let mut file = io::BufWriter::new(file);
+            write(&mut file);
+            file.flush().unwrap_or_else(|err| fatal(format!("无法写入`{}`: {}", path.display(), err)));
+        }
+        None => {
+            let mut stdout = io::stdout().lock();
+            write(&mut stdout);
+            stdout.flush().unwrap_or_else(|err| fatal(format!("无法写入标准输出: {}", err)));
+        }
+    }
+}
 
-            let mut asm_file = fs::File::create(filename_without_suffix + ".s").unwrap();
-            asm.writetext(&mut asm_file);
+fn main() {
+    //跳过第一个参数，解析编译选项
+    let options = match Options::parse(args().skip(1)) {
+        Ok(Command::Compile(options)) => options,
+        Ok(Command::Help) => {
+            print!("{}", USAGE);
+            return;
+        }
+        Err(msg) => {
+            eprintln!("error: {}\n", msg);
+            eprint!("{}", USAGE);
+            exit(2);
+        }
+    };
+    //读取源码，未指定文件时从标准输入读取
+    let source = match &options.input {
+        Some(path) => fs::read_to_string(path)
+            .unwrap_or_else(|err| fatal(format!("无法读取`{}`: {}", path.display(), err))),
+        None => {
+            let mut source = String::new();
+            io::stdin().read_to_string(&mut source)
+                .unwrap_or_else(|err| fatal(format!("无法读取标准输入: {}", err)));
+            source
+        }
+    };
+    let name = options.input.as_ref()
+        .map_or(String::from("<stdin>"), |path| path.display().to_string());
+    let source = SourceFile::new(name, source);
+    let session = Session {
+        opt_level: options.opt_level,
+        inline_threshold: options.inline_threshold,
+        unroll_factor: options.unroll_factor,
+    };
+    let mut llvm = if options.input_is_llvm() {
+        //输入已经是llvm，跳过前端
+        if options.emits.contains(&Emit::Ast) {
+            fatal("输入为llvm时无法输出ast");
         }
-        _ => panic!()
+        session.read_llvm(&source.text).unwrap_or_else(|diagnostics| abort(&source, &diagnostics))
+    } else {
+        //用lalrpop解析得到ast
+        let mut ast = session.parse(&source.text).unwrap_or_else(|diagnostics| abort(&source, &diagnostics));
+        if options.emits.contains(&Emit::Ast) {
+            emit_to(&options, Emit::Ast, |output| writeln!(output, "{:#?}", ast).unwrap());
+        }
+        if !options.emits.iter().any(|emit| *emit != Emit::Ast) {
+            return;
+        }
+        //生成llvm
+        session.lower(&mut ast).unwrap_or_else(|diagnostics| abort(&source, &diagnostics))
+    };
+    session.optimise_llvm(&mut llvm);
+    if options.emits.contains(&Emit::Llvm) {
+        emit_to(&options, Emit::Llvm, |mut output| session.emit(&llvm, &mut output));
+    }
+    //生成汇编
+    if options.emits.contains(&Emit::Asm) {
+        let mut asm = session.select(&llvm);
+        session.optimise_riscv(&mut asm);
+        emit_to(&options, Emit::Asm, |mut output| session.emit(&asm, &mut output));
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,243 @@
+use std::path::PathBuf;
+
+/// 优化等级
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum OptLevel {
+    /// 不做任何优化
+    O0,
+    /// 只做llvm的死代码消除与汇编的冗余mv消除
+    O1,
+    /// 开启全部优化
+    O2,
+}
+
+/// 默认的内联阈值，规模不超过此值的函数在调用处展开
+pub const DEFAULT_INLINE_THRESHOLD: usize = 50;
+
+/// 默认的循环部分展开倍数
+pub const DEFAULT_UNROLL_FACTOR: usize = 4;
+
+/// 输出的产物种类
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Emit {
+    Ast,
+    Llvm,
+    Asm,
+}
+
+impl Emit {
+    /// 产物对应的文件后缀
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Emit::Ast => "ast",
+            Emit::Llvm => "ll",
+            Emit::Asm => "s",
+        }
+    }
+}
+
+/// 命令行选项
+#[derive(Debug)]
+pub struct Options {
+    /// 输入文件，None表示从标准输入读取
+    pub input: Option<PathBuf>,
+    pub output: Option<PathBuf>,
+    pub emits: Vec<Emit>,
+    pub opt_level: OptLevel,
+    pub inline_threshold: usize,
+    pub unroll_factor: usize,
+}
+
+/// 命令行解析的结果
+pub enum Command {
+    Compile(Options),
+    Help,
+}
+
+pub const USAGE: &str = "\
+用法: compiler [选项] [<输入文件>]
+
+输入文件省略或为`-`时从标准输入读取源码。
+后缀为.ll的输入文件按llvm读入，跳过前端直接优化并生成汇编。
+
+选项:
+  -o <文件>                  输出文件，`-`表示标准输出
+  --emit=<种类>[,<种类>...]  输出的产物，可选ast、llvm、asm，默认asm
+  -S                         等价于--emit=asm
+  -llvm                      等价于--emit=llvm
+  -O0, -O1, -O2              优化等级，默认-O2
+  --inline-threshold=<n>     -O2下内联的函数规模上限(指令数)，默认50，0表示不内联
+  --unroll-factor=<n>        -O2下循环部分展开的倍数，默认4，0或1表示不展开
+  -h, --help                 打印本帮助信息
+";
+
+fn parse_emit(kind: &str) -> Result<Emit, String> {
+    match kind {
+        "ast" => Ok(Emit::Ast),
+        "llvm" | "llvm-ir" | "ll" => Ok(Emit::Llvm),
+        "asm" | "s" => Ok(Emit::Asm),
+        _ => Err(format!("未知的产物种类`{}`", kind)),
+    }
+}
+
+fn push_emit(emits: &mut Vec<Emit>, emit: Emit) {
+    if !emits.contains(&emit) {
+        emits.push(emit);
+    }
+}
+
+impl Options {
+    /// 解析命令行参数（不含程序名）
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Command, String> {
+        let mut args = args.into_iter();
+        let mut positional = Vec::new();
+        let mut output = None;
+        let mut emits = Vec::new();
+        let mut opt_level = OptLevel::O2;
+        let mut inline_threshold = DEFAULT_INLINE_THRESHOLD;
+        let mut unroll_factor = DEFAULT_UNROLL_FACTOR;
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(Command::Help),
+                "-o" => {
+                    let file = args.next().ok_or("`-o`后缺少输出文件名")?;
+                    output = Some(PathBuf::from(file));
+                }
+                "-S" => push_emit(&mut emits, Emit::Asm),
+                "-llvm" => push_emit(&mut emits, Emit::Llvm),
+                "-O0" => opt_level = OptLevel::O0,
+                "-O1" => opt_level = OptLevel::O1,
+                "-O2" | "-O" => opt_level = OptLevel::O2,
+                "-" => positional.push(arg),
+                _ => {
+                    if let Some(kinds) = arg.strip_prefix("--emit=") {
+                        for kind in kinds.split(',').filter(|kind| !kind.is_empty()) {
+                            push_emit(&mut emits, parse_emit(kind)?);
+                        }
+                    } else if let Some(threshold) = arg.strip_prefix("--inline-threshold=") {
+                        inline_threshold = threshold.parse()
+                            .map_err(|_| format!("内联阈值`{}`不是非负整数", threshold))?;
+                    } else if let Some(factor) = arg.strip_prefix("--unroll-factor=") {
+                        unroll_factor = factor.parse()
+                            .map_err(|_| format!("展开倍数`{}`不是非负整数", factor))?;
+                    } else if let Some(file) = arg.strip_prefix("-o") {
+                        output = Some(PathBuf::from(file));
+                    } else if arg.starts_with('-') {
+                        return Err(format!("未知的选项`{}`", arg));
+                    } else {
+                        positional.push(arg);
+                    }
+                }
+            }
+        }
+        //兼容旧的用法：compiler test.c -S test.s，第二个位置参数视为输出文件
+        let mut positional = positional.into_iter();
+        let input = positional.next().filter(|file| file != "-").map(PathBuf::from);
+        if let Some(file) = positional.next() {
+            if output.is_some() {
+                return Err(format!("多余的参数`{}`", file));
+            }
+            output = Some(PathBuf::from(file));
+        }
+        if let Some(file) = positional.next() {
+            return Err(format!("多余的参数`{}`", file));
+        }
+        if emits.is_empty() {
+            emits.push(Emit::Asm);
+        }
+        Ok(Command::Compile(Options { input, output, emits, opt_level, inline_threshold, unroll_factor }))
+    }
+
+    /// 输入是否为文本形式的llvm
+    pub fn input_is_llvm(&self) -> bool {
+        self.input.as_ref().map_or(false, |file| file.extension().map_or(false, |ext| ext == "ll"))
+    }
+
+    /// 计算某种产物的输出路径，None表示写到标准输出
+    pub fn output_path(&self, emit: Emit) -> Option<PathBuf> {
+        match &self.output {
+            Some(file) if file.as_os_str() == "-" => None,
+            //只有一种产物时，-o即为输出文件
+            Some(file) if self.emits.len() == 1 => Some(file.clone()),
+            //多种产物时，-o作为文件名主干，替换后缀
+            Some(file) => Some(file.with_extension(emit.extension())),
+            //未指定-o时，在输入文件旁生成同名文件；从标准输入读取时写到标准输出
+            //with_extension只替换最后一个后缀，路径中其它位置的`.`保持不变
+            None => self.input.as_ref().map(|file| file.with_extension(emit.extension())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        match Options::parse(args.iter().map(|arg| arg.to_string()))? {
+            Command::Compile(options) => Ok(options),
+            Command::Help => Err(String::from("help")),
+        }
+    }
+
+    #[test]
+    fn defaults() {
+        let options = parse(&["test.sy"]).unwrap();
+        assert_eq!(options.input, Some(PathBuf::from("test.sy")));
+        assert_eq!(options.output, None);
+        assert_eq!(options.emits, vec![Emit::Asm]);
+        assert_eq!(options.opt_level, OptLevel::O2);
+        assert_eq!(options.inline_threshold, DEFAULT_INLINE_THRESHOLD);
+        assert_eq!(options.unroll_factor, DEFAULT_UNROLL_FACTOR);
+        assert_eq!(options.output_path(Emit::Asm), Some(PathBuf::from("test.s")));
+    }
+
+    #[test]
+    fn flags() {
+        let options = parse(&["-O1", "--emit=llvm,asm,llvm", "-S", "-o", "out", "--inline-threshold=0", "--unroll-factor=8", "a.sy"]).unwrap();
+        assert_eq!(options.opt_level, OptLevel::O1);
+        assert_eq!(options.emits, vec![Emit::Llvm, Emit::Asm]);
+        assert_eq!(options.inline_threshold, 0);
+        assert_eq!(options.unroll_factor, 8);
+        //多种产物时-o作为文件名主干
+        assert_eq!(options.output_path(Emit::Llvm), Some(PathBuf::from("out.ll")));
+        assert_eq!(options.output_path(Emit::Asm), Some(PathBuf::from("out.s")));
+        assert_eq!(parse(&["-O0", "-O", "a.sy"]).unwrap().opt_level, OptLevel::O2);
+        assert_eq!(parse(&["-oout.s", "a.sy"]).unwrap().output, Some(PathBuf::from("out.s")));
+        assert!(matches!(Options::parse(vec![String::from("--help")]), Ok(Command::Help)));
+    }
+
+    #[test]
+    fn stdin_and_stdout() {
+        let options = parse(&["-", "-o", "-"]).unwrap();
+        assert_eq!(options.input, None);
+        assert_eq!(options.output_path(Emit::Asm), None);
+        //从标准输入读取且未指定-o时写到标准输出
+        let options = parse(&["-llvm"]).unwrap();
+        assert_eq!(options.input, None);
+        assert_eq!(options.output_path(Emit::Llvm), None);
+        //-o -时多种产物都写到标准输出
+        let options = parse(&["a.sy", "--emit=llvm,asm", "-o", "-"]).unwrap();
+        assert_eq!(options.output_path(Emit::Llvm), None);
+        assert_eq!(options.output_path(Emit::Asm), None);
+    }
+
+    #[test]
+    fn legacy_positional_output() {
+        let options = parse(&["test.c", "-S", "test.s", "-O2"]).unwrap();
+        assert_eq!(options.input, Some(PathBuf::from("test.c")));
+        assert_eq!(options.output_path(Emit::Asm), Some(PathBuf::from("test.s")));
+        assert!(!options.input_is_llvm());
+        assert!(parse(&["test.ll"]).unwrap().input_is_llvm());
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(parse(&["-o"]).unwrap_err(), "`-o`后缺少输出文件名");
+        assert_eq!(parse(&["--emit=obj"]).unwrap_err(), "未知的产物种类`obj`");
+        assert_eq!(parse(&["--inline-threshold=-1"]).unwrap_err(), "内联阈值`-1`不是非负整数");
+        assert_eq!(parse(&["--unroll-factor=x"]).unwrap_err(), "展开倍数`x`不是非负整数");
+        assert_eq!(parse(&["-x"]).unwrap_err(), "未知的选项`-x`");
+        assert_eq!(parse(&["a.sy", "b.s", "c"]).unwrap_err(), "多余的参数`c`");
+        assert_eq!(parse(&["a.sy", "-o", "x.s", "b.s"]).unwrap_err(), "多余的参数`b.s`");
+    }
+}
@@ -0,0 +1,287 @@
+use std::collections::HashMap;
+use crate::riscv_exec::SimError;
+use crate::riscv_exec::memory::parse_imm;
+use crate::structures::riscv_struct::*;
+use crate::structures::symbol::*;
+
+/// 整数寄存器按编号排列，浮点寄存器接在其后，编号加32
+const INT_REGS: [&str; 32] = [
+    "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "s0", "s1", "a0", "a1", "a2", "a3", "a4", "a5",
+    "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11", "t3", "t4", "t5", "t6",
+];
+const FLOAT_REGS: [&str; 32] = [
+    "ft0", "ft1", "ft2", "ft3", "ft4", "ft5", "ft6", "ft7", "fs0", "fs1", "fa0", "fa1", "fa2", "fa3", "fa4", "fa5",
+    "fa6", "fa7", "fs2", "fs3", "fs4", "fs5", "fs6", "fs7", "fs8", "fs9", "fs10", "fs11", "ft8", "ft9", "ft10", "ft11",
+];
+
+pub const RA: usize = 1;
+pub const SP: usize = 2;
+pub const A0: usize = 10;
+pub const FA0: usize = 32 + 10;
+
+/// 寄存器的编号
+pub fn reg_index(name: &str) -> Option<usize> {
+    if name == "fp" {
+        return Some(8);
+    }
+    INT_REGS.iter().position(|reg| *reg == name)
+        .or_else(|| FLOAT_REGS.iter().position(|reg| *reg == name).map(|idx| idx + 32))
+}
+
+/// 调用者保存的寄存器，库函数返回后其值不可信
+pub fn caller_saved() -> impl Iterator<Item = usize> {
+    ["t0", "t1", "t2", "t3", "t4", "t5", "t6", "a1", "a2", "a3", "a4", "a5", "a6", "a7"].into_iter()
+        .chain(["ft0", "ft1", "ft2", "ft3", "ft4", "ft5", "ft6", "ft7", "ft8", "ft9", "ft10", "ft11"])
+        .chain(["fa1", "fa2", "fa3", "fa4", "fa5", "fa6", "fa7"])
+        .map(|name| reg_index(name).unwrap())
+}
+
+/// 寄存器或立即数
+#[derive(Debug, Clone, Copy)]
+pub enum Src {
+    Reg(usize),
+    Imm(i64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AluOp {
+    Add,
+    Sub,
+    Mul,
+    Mulw,
+    Div,
+    Divw,
+    Rem,
+    Remw,
+    Xor,
+    Sll,
+    Srl,
+    Sra,
+    Slt,
+    Sgt,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FpuOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Lt,
+    Le,
+    Eq,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cond {
+    Eq,
+    Ne,
+    Lt,
+    Ge,
+    Gt,
+    Le,
+}
+
+/// 调用的目标
+#[derive(Debug, Clone)]
+pub enum Callee {
+    /// 汇编中定义的函数，值为其第一条指令的位置
+    Func(usize),
+    /// sylib中的库函数，记录各参数是否为浮点与返回值类型
+    Lib { name: String, float_params: Vec<bool>, ret: SymbolWidth },
+    /// memset已在指令选择时展开为一串store，只留下不输出的call作为标记
+    Memset,
+}
+
+/// 译码后的指令，寄存器与标签都已解析为编号
+#[derive(Debug, Clone)]
+pub enum Op {
+    Li(usize, i64),
+    Mv(usize, Src),
+    Alu(AluOp, usize, Src, Src),
+    Seqz(usize, usize),
+    Snez(usize, usize),
+    /// fmv.d，在浮点寄存器间复制
+    FmvD(usize, usize),
+    /// fmv.w.x，整数寄存器的低32位移入浮点寄存器
+    FmvWX(usize, usize),
+    /// fmv.x.w，浮点寄存器的低32位符号扩展后移入整数寄存器
+    FmvXW(usize, usize),
+    Fpu(FpuOp, usize, usize, usize),
+    /// fcvt.s.w，向零舍入
+    FcvtSW(usize, usize),
+    /// fcvt.w.s，向零舍入
+    FcvtWS(usize, usize),
+    Load { width: usize, dst: usize, base: usize, offset: i64 },
+    Store { width: usize, src: usize, base: usize, offset: i64 },
+    Branch(Cond, usize, usize, usize),
+    Jump(usize),
+    Call(Callee),
+    /// 尾调用，不改写ra，被调用者直接返回到调用者的调用者
+    Tail(Callee),
+    Ret,
+}
+
+/// 把RiscV的全部函数排成一条指令流
+pub struct Program<'a> {
+    pub ops: Vec<Op>,
+    /// 每条指令对应的函数标签与原始指令，用于报错
+    pub origins: Vec<(&'a str, &'a AsmInstruction)>,
+    /// 函数标签到第一条指令的位置
+    pub funcs: HashMap<&'a str, usize>,
+}
+
+impl<'a> Program<'a> {
+    pub fn decode(asm: &'a RiscV, data_labels: &HashMap<String, usize>) -> Result<Self, SimError> {
+        // 先确定全部函数与基本块的位置，跳转可以指向后面的标签
+        let mut funcs = HashMap::new();
+        let mut labels: HashMap<&str, usize> = HashMap::new();
+        let mut origins = vec![];
+        for func in asm.text.funcs.iter() {
+            funcs.insert(func.label.as_str(), origins.len());
+            for block in func.blocks.iter() {
+                labels.insert(block.label.as_str(), origins.len());
+                origins.extend(block.instrs.iter().map(|instr| (func.label.as_str(), instr)));
+            }
+        }
+        let decoder = Decoder { labels: &labels, funcs: &funcs, data_labels };
+        let ops = origins.iter()
+            .map(|(func, instr)| decoder.decode(instr).map_err(|msg| SimError::new(func, Some(instr), msg)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Program { ops, origins, funcs })
+    }
+}
+
+struct Decoder<'a, 'b> {
+    labels: &'b HashMap<&'a str, usize>,
+    funcs: &'b HashMap<&'a str, usize>,
+    data_labels: &'b HashMap<String, usize>,
+}
+
+impl<'a, 'b> Decoder<'a, 'b> {
+    fn reg(&self, name: &str) -> Result<usize, String> {
+        reg_index(name).ok_or_else(|| format!("未知的寄存器`{}`", name))
+    }
+
+    fn src(&self, text: &str) -> Result<Src, String> {
+        match reg_index(text) {
+            Some(reg) => Ok(Src::Reg(reg)),
+            None => parse_imm(text).map(Src::Imm).ok_or_else(|| format!("无法解析的操作数`{}`", text)),
+        }
+    }
+
+    fn imm(&self, text: &str) -> Result<i64, String> {
+        parse_imm(text).ok_or_else(|| format!("无法解析的立即数`{}`", text))
+    }
+
+    fn label(&self, name: &str) -> Result<usize, String> {
+        self.labels.get(name).cloned().ok_or_else(|| format!("未定义的标签`{}`", name))
+    }
+
+    fn alu(&self, op: AluOp, tri: &TriInstr) -> Result<Op, String> {
+        Ok(Op::Alu(op, self.reg(&tri.dst)?, self.src(&tri.op1)?, self.src(&tri.op2)?))
+    }
+
+    fn fpu(&self, op: FpuOp, tri: &TriInstr) -> Result<Op, String> {
+        Ok(Op::Fpu(op, self.reg(&tri.dst)?, self.reg(&tri.op1)?, self.reg(&tri.op2)?))
+    }
+
+    /// 按write_text中的输出方式译码，例如Mul的width为4时输出mulw
+    fn decode(&self, instr: &AsmInstruction) -> Result<Op, String> {
+        let op = match instr {
+            AsmInstruction::Li(bin) => Op::Li(self.reg(&bin.dst)?, self.imm(&bin.src)?),
+            AsmInstruction::La(bin) => {
+                let addr = self.data_labels.get(&bin.src).ok_or_else(|| format!("未定义的全局变量`{}`", bin.src))?;
+                Op::Li(self.reg(&bin.dst)?, *addr as i64)
+            },
+            AsmInstruction::Mv(bin) => Op::Mv(self.reg(&bin.dst)?, self.src(&bin.src)?),
+            AsmInstruction::Fmv(bin, dst, src) => {
+                let (dst_reg, src_reg) = (self.reg(&bin.dst)?, self.reg(&bin.src)?);
+                match (dst, src) {
+                    _ if dst == src => Op::FmvD(dst_reg, src_reg),
+                    (SymbolWidth::Float, _) => Op::FmvWX(dst_reg, src_reg),
+                    _ => Op::FmvXW(dst_reg, src_reg),
+                }
+            },
+            AsmInstruction::Add(tri) | AsmInstruction::Addi(tri) => self.alu(AluOp::Add, tri)?,
+            AsmInstruction::Sub(tri) => self.alu(AluOp::Sub, tri)?,
+            AsmInstruction::Mul(tri) if tri.width == Some(4) => self.alu(AluOp::Mulw, tri)?,
+            AsmInstruction::Mul(tri) => self.alu(AluOp::Mul, tri)?,
+            AsmInstruction::Div(tri) if tri.width == Some(4) => self.alu(AluOp::Divw, tri)?,
+            AsmInstruction::Div(tri) => self.alu(AluOp::Div, tri)?,
+            AsmInstruction::Rem(tri) if tri.width == Some(4) => self.alu(AluOp::Remw, tri)?,
+            AsmInstruction::Rem(tri) => self.alu(AluOp::Rem, tri)?,
+            AsmInstruction::Xori(tri) => self.alu(AluOp::Xor, tri)?,
+            AsmInstruction::Slli(tri) => self.alu(AluOp::Sll, tri)?,
+            AsmInstruction::Srli(tri) => self.alu(AluOp::Srl, tri)?,
+            AsmInstruction::Srai(tri) => self.alu(AluOp::Sra, tri)?,
+            AsmInstruction::Slt(tri) | AsmInstruction::Slti(tri) => self.alu(AluOp::Slt, tri)?,
+            AsmInstruction::Sgt(tri) => self.alu(AluOp::Sgt, tri)?,
+            AsmInstruction::Seqz(bin) => Op::Seqz(self.reg(&bin.dst)?, self.reg(&bin.src)?),
+            AsmInstruction::Snez(bin) => Op::Snez(self.reg(&bin.dst)?, self.reg(&bin.src)?),
+            AsmInstruction::Fadd(tri) => self.fpu(FpuOp::Add, tri)?,
+            AsmInstruction::Fsub(tri) => self.fpu(FpuOp::Sub, tri)?,
+            AsmInstruction::Fmul(tri) => self.fpu(FpuOp::Mul, tri)?,
+            AsmInstruction::Fdiv(tri) => self.fpu(FpuOp::Div, tri)?,
+            AsmInstruction::Flt(tri) => self.fpu(FpuOp::Lt, tri)?,
+            AsmInstruction::Fle(tri) => self.fpu(FpuOp::Le, tri)?,
+            AsmInstruction::Feq(tri) => self.fpu(FpuOp::Eq, tri)?,
+            AsmInstruction::Fcvt(bin, dst, _) => {
+                let (dst_reg, src_reg) = (self.reg(&bin.dst)?, self.reg(&bin.src)?);
+                if dst == &SymbolWidth::Float {
+                    Op::FcvtSW(dst_reg, src_reg)
+                } else {
+                    Op::FcvtWS(dst_reg, src_reg)
+                }
+            },
+            AsmInstruction::Load(mem, _) => Op::Load {
+                width: mem.width as usize,
+                dst: self.reg(&mem.val)?,
+                base: self.reg(&mem.base)?,
+                offset: self.imm(&mem.offset)?,
+            },
+            AsmInstruction::Store(mem, _) => Op::Store {
+                width: mem.width as usize,
+                src: self.reg(&mem.val)?,
+                base: self.reg(&mem.base)?,
+                offset: self.imm(&mem.offset)?,
+            },
+            AsmInstruction::Branch(cond_tri) => {
+                let cond = match cond_tri.cond.as_str() {
+                    "eq" => Cond::Eq,
+                    "ne" => Cond::Ne,
+                    "lt" => Cond::Lt,
+                    "ge" => Cond::Ge,
+                    "gt" => Cond::Gt,
+                    "le" => Cond::Le,
+                    cond => return Err(format!("未知的分支条件`{}`", cond)),
+                };
+                let tri = &cond_tri.tri;
+                Op::Branch(cond, self.reg(&tri.dst)?, self.reg(&tri.op1)?, self.label(&tri.op2)?)
+            },
+            AsmInstruction::Jump(label) => Op::Jump(self.label(label)?),
+            AsmInstruction::Ret() => Op::Ret,
+            AsmInstruction::Call(_, name, _, _) => Op::Call(self.callee(name)?),
+            AsmInstruction::Tail(name) => Op::Tail(self.callee(name)?),
+        };
+        Ok(op)
+    }
+
+    fn callee(&self, name: &str) -> Result<Callee, String> {
+        let callee = match self.funcs.get(name) {
+            Some(pos) => Callee::Func(*pos),
+            None if name == "memset" => Callee::Memset,
+            None => {
+                let (_, ret, params) = sylib_functions().into_iter()
+                    .find(|(func, _, _)| *func == name)
+                    .ok_or_else(|| format!("未定义的函数`{}`", name))?;
+                Callee::Lib {
+                    name: name.to_string(),
+                    float_params: params.iter().map(|ty| ty.width == SymbolWidth::Float).collect(),
+                    ret: ret.width,
+                }
+            },
+        };
+        Ok(callee)
+    }
+}
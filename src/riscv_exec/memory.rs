@@ -0,0 +1,103 @@
+use crate::llvm_exec::sylib::SylibMemory;
+use crate::structures::riscv_struct::*;
+use crate::structures::symbol::*;
+
+/// 数据段的起始地址，低于此地址的访问视为空指针
+pub const DATA_BASE: usize = 0x10000;
+/// 栈的大小，栈顶位于内存末尾
+pub const STACK_SIZE: usize = 128 << 20;
+
+/// 平坦的小端字节内存，依次为空指针保护区、数据段与栈
+#[derive(Debug)]
+pub struct Memory {
+    bytes: Vec<u8>,
+}
+
+impl Memory {
+    /// 按.align 2依次排布数据段中的全局变量，返回内存与各标签的地址
+    pub fn new(data: &DataSection) -> Result<(Self, Vec<(String, usize)>), String> {
+        let mut bytes = vec![0; DATA_BASE];
+        let mut labels = vec![];
+        for item in data.datas.iter() {
+            let addr = (bytes.len() + 3) & !3;
+            let size = match &item.ty.width {
+                SymbolWidth::Arr { dims, .. } => dims.iter().product::<i32>() as usize * NORMAL_WIDTH as usize,
+                _ => NORMAL_WIDTH as usize,
+            };
+            bytes.resize(addr + size, 0);
+            for (i, value) in item.init_vals.iter().enumerate() {
+                let word = parse_imm(value).ok_or_else(|| format!("无法解析`{}`的初始值`{}`", item.label, value))?;
+                let offset = addr + i * NORMAL_WIDTH as usize;
+                bytes[offset..offset + 4].copy_from_slice(&(word as u32).to_le_bytes());
+            }
+            labels.push((item.label.clone(), addr));
+        }
+        let stack_base = (bytes.len() + 15) & !15;
+        bytes.resize(stack_base + STACK_SIZE, 0);
+        Ok((Memory { bytes }, labels))
+    }
+
+    /// 栈顶地址
+    pub fn stack_top(&self) -> usize {
+        self.bytes.len()
+    }
+
+    fn range(&mut self, addr: u64, len: usize) -> Result<&mut [u8], String> {
+        let addr = addr as usize;
+        if addr < DATA_BASE {
+            return Err(format!("访问空指针附近的地址{:#x}", addr));
+        }
+        if addr.checked_add(len).map_or(true, |end| end > self.bytes.len()) {
+            return Err(format!("访问越界的地址{:#x}", addr));
+        }
+        Ok(&mut self.bytes[addr..addr + len])
+    }
+
+    /// 读取width字节并符号扩展
+    pub fn load(&mut self, addr: u64, width: usize) -> Result<u64, String> {
+        let bytes = self.range(addr, width)?;
+        let mut buf = [0; 8];
+        buf[..width].copy_from_slice(bytes);
+        let val = match width {
+            1 => buf[0] as i8 as i64,
+            2 => i16::from_le_bytes([buf[0], buf[1]]) as i64,
+            4 => i32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as i64,
+            _ => i64::from_le_bytes(buf),
+        };
+        Ok(val as u64)
+    }
+
+    /// 写入val的低width字节
+    pub fn store(&mut self, addr: u64, width: usize, val: u64) -> Result<(), String> {
+        self.range(addr, width)?.copy_from_slice(&val.to_le_bytes()[..width]);
+        Ok(())
+    }
+}
+
+impl SylibMemory for Memory {
+    fn load_word(&mut self, addr: usize) -> Result<u32, String> {
+        Ok(self.load(addr as u64, 4)? as u32)
+    }
+
+    fn store_word(&mut self, addr: usize, word: u32) -> Result<(), String> {
+        self.store(addr as u64, 4, word as u64)
+    }
+
+    fn fill(&mut self, addr: usize, byte: u8, len: usize) -> Result<(), String> {
+        self.range(addr as u64, len)?.fill(byte);
+        Ok(())
+    }
+}
+
+/// 解析汇编中的立即数，包括十进制与0x/0X开头的十六进制
+pub fn parse_imm(text: &str) -> Option<i64> {
+    let (neg, body) = match text.strip_prefix('-') {
+        Some(body) => (true, body),
+        None => (false, text),
+    };
+    let num = match body.strip_prefix("0x").or_else(|| body.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok()? as i64,
+        None => body.parse().ok()?,
+    };
+    Some(if neg { num.wrapping_neg() } else { num })
+}
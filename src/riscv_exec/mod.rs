@@ -0,0 +1,440 @@
+pub mod decode;
+pub mod memory;
+
+use std::collections::HashMap;
+use std::fmt;
+use crate::llvm_exec::memory::Val;
+use crate::llvm_exec::sylib::Runtime;
+use crate::structures::riscv_struct::*;
+use crate::structures::symbol::*;
+use decode::*;
+use memory::*;
+
+/// main返回到此地址时结束模拟
+const EXIT_ADDR: u64 = 0xdead_0000;
+/// 库函数返回后写入调用者保存寄存器的值，用来暴露寄存器分配错误
+const CLOBBER: u64 = 0x5a5a_5a5a_5a5a_5a5a;
+
+/// 模拟执行的结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimResult {
+    /// main的返回值，作为进程退出码时只保留低8位
+    pub exit_code: i32,
+    pub stdout: Vec<u8>,
+    /// starttime与stoptime的计时结果，格式与sylib相同
+    pub stderr: String,
+    /// 执行过的指令数，库函数的调用只算一条
+    pub instructions: u64,
+    /// 按cycles_of估算的周期数
+    pub cycles: u64,
+}
+
+/// 模拟执行时的错误，记录出错的函数与指令
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimError {
+    pub func: String,
+    /// 出错指令的文本
+    pub instr: Option<String>,
+    pub message: String,
+}
+
+impl SimError {
+    pub fn new(func: &str, instr: Option<&AsmInstruction>, message: String) -> Self {
+        SimError {
+            func: func.to_string(),
+            instr: instr.map(|instr| instr.instr_text()),
+            message,
+        }
+    }
+}
+
+impl fmt::Display for SimError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.func)?;
+        if let Some(instr) = &self.instr {
+            write!(f, ", `{}`", instr)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+impl RiscV {
+    /// 在RV64IMF模拟器上执行main，input为标准输入的内容，库函数由sylib::Runtime在本地实现
+    pub fn simulate(&self, input: &[u8]) -> Result<SimResult, SimError> {
+        let (mem, labels) = Memory::new(&self.data).map_err(|msg| SimError::new("<data>", None, msg))?;
+        let labels: HashMap<String, usize> = labels.into_iter().collect();
+        let program = Program::decode(self, &labels)?;
+        let main = *program.funcs.get("main").ok_or_else(|| SimError::new("main", None, "程序中没有main函数".to_string()))?;
+
+        let mut sim = Simulator {
+            regs: [0; 64],
+            pc: main,
+            mem,
+            runtime: Runtime::new(input),
+            instructions: 0,
+            cycles: 0,
+        };
+        sim.regs[SP] = sim.mem.stack_top() as u64;
+        sim.regs[RA] = EXIT_ADDR;
+        let exit_code = sim.run(&program).map_err(|msg| {
+            let (func, instr) = program.origins[sim.pc];
+            SimError::new(func, Some(instr), msg)
+        })?;
+        Ok(SimResult {
+            exit_code,
+            stderr: sim.runtime.timer_report(),
+            stdout: sim.runtime.stdout,
+            instructions: sim.instructions,
+            cycles: sim.cycles,
+        })
+    }
+}
+
+/// 粗略的顺序单发射流水线：乘除、访存与浮点运算有额外延迟，跳转需要清空流水线
+pub fn cycles_of(op: &Op, taken: bool) -> u64 {
+    match op {
+        Op::Alu(AluOp::Mul | AluOp::Mulw, ..) => 3,
+        Op::Alu(AluOp::Div | AluOp::Divw | AluOp::Rem | AluOp::Remw, ..) => 20,
+        Op::Load { .. } => 3,
+        Op::Fpu(FpuOp::Div, ..) => 10,
+        Op::Fpu(FpuOp::Add | FpuOp::Sub | FpuOp::Mul, ..) => 4,
+        Op::FcvtSW(..) | Op::FcvtWS(..) => 4,
+        Op::Branch(..) if taken => 3,
+        Op::Jump(_) | Op::Call(_) | Op::Tail(_) | Op::Ret => 3,
+        _ => 1,
+    }
+}
+
+struct Simulator<'a> {
+    /// 整数寄存器在前，浮点寄存器在后，浮点数按NaN-boxing存在低32位
+    regs: [u64; 64],
+    pc: usize,
+    mem: Memory,
+    runtime: Runtime<'a>,
+    instructions: u64,
+    cycles: u64,
+}
+
+impl<'a> Simulator<'a> {
+    /// 执行到main返回，得到其返回值
+    fn run(&mut self, program: &Program) -> Result<i32, String> {
+        loop {
+            let op = program.ops.get(self.pc).ok_or_else(|| format!("跳转到非法地址{:#x}", self.pc))?;
+            let mut next = self.pc + 1;
+            let mut taken = false;
+            match op {
+                Op::Li(dst, imm) => self.set(*dst, *imm as u64),
+                Op::Mv(dst, src) => self.set(*dst, self.src(*src)),
+                Op::Alu(alu_op, dst, op1, op2) => {
+                    let val = alu(*alu_op, self.src(*op1) as i64, self.src(*op2) as i64);
+                    self.set(*dst, val as u64);
+                },
+                Op::Seqz(dst, src) => self.set(*dst, (self.regs[*src] == 0) as u64),
+                Op::Snez(dst, src) => self.set(*dst, (self.regs[*src] != 0) as u64),
+                Op::FmvD(dst, src) => self.regs[*dst] = self.regs[*src],
+                Op::FmvWX(dst, src) => self.set_float_bits(*dst, self.regs[*src] as u32),
+                Op::FmvXW(dst, src) => self.set(*dst, self.regs[*src] as u32 as i32 as i64 as u64),
+                Op::Fpu(fpu_op, dst, op1, op2) => {
+                    let (num1, num2) = (self.float(*op1), self.float(*op2));
+                    match fpu_op {
+                        FpuOp::Add => self.set_float(*dst, num1 + num2),
+                        FpuOp::Sub => self.set_float(*dst, num1 - num2),
+                        FpuOp::Mul => self.set_float(*dst, num1 * num2),
+                        FpuOp::Div => self.set_float(*dst, num1 / num2),
+                        FpuOp::Lt => self.set(*dst, (num1 < num2) as u64),
+                        FpuOp::Le => self.set(*dst, (num1 <= num2) as u64),
+                        FpuOp::Eq => self.set(*dst, (num1 == num2) as u64),
+                    }
+                },
+                Op::FcvtSW(dst, src) => self.set_float(*dst, int_to_float_rtz(self.regs[*src] as i32)),
+                Op::FcvtWS(dst, src) => {
+                    // 超出范围时饱和，NaN按规范转为最大值
+                    let num = self.float(*src);
+                    let res = if num.is_nan() { i32::MAX } else { num as i32 };
+                    self.set(*dst, res as i64 as u64);
+                },
+                Op::Load { width, dst, base, offset } => {
+                    let addr = self.regs[*base].wrapping_add(*offset as u64);
+                    let val = self.mem.load(addr, *width)?;
+                    if *dst >= 32 && *width == 4 {
+                        self.set_float_bits(*dst, val as u32);
+                    } else {
+                        self.set(*dst, val);
+                    }
+                },
+                Op::Store { width, src, base, offset } => {
+                    let addr = self.regs[*base].wrapping_add(*offset as u64);
+                    self.mem.store(addr, *width, self.regs[*src])?;
+                },
+                Op::Branch(cond, op1, op2, target) => {
+                    let (num1, num2) = (self.regs[*op1] as i64, self.regs[*op2] as i64);
+                    taken = match cond {
+                        Cond::Eq => num1 == num2,
+                        Cond::Ne => num1 != num2,
+                        Cond::Lt => num1 < num2,
+                        Cond::Ge => num1 >= num2,
+                        Cond::Gt => num1 > num2,
+                        Cond::Le => num1 <= num2,
+                    };
+                    if taken {
+                        next = *target;
+                    }
+                },
+                Op::Jump(target) => next = *target,
+                Op::Call(Callee::Func(target)) => {
+                    self.regs[RA] = next as u64;
+                    next = *target;
+                },
+                Op::Call(Callee::Lib { name, float_params, ret }) => self.call_lib(name, float_params, ret)?,
+                Op::Call(Callee::Memset) => {},
+                Op::Tail(Callee::Func(target)) => next = *target,
+                Op::Tail(Callee::Lib { name, float_params, ret }) => {
+                    // 库函数执行完后直接返回到ra
+                    self.call_lib(name, float_params, ret)?;
+                    let ra = self.regs[RA];
+                    if ra == EXIT_ADDR {
+                        self.count(op, false);
+                        return Ok(self.regs[A0] as i32);
+                    }
+                    next = ra as usize;
+                },
+                Op::Tail(Callee::Memset) => return Err(String::from("memset不能作为尾调用")),
+                Op::Ret => {
+                    let ra = self.regs[RA];
+                    if ra == EXIT_ADDR {
+                        self.count(op, false);
+                        return Ok(self.regs[A0] as i32);
+                    }
+                    next = ra as usize;
+                },
+            }
+            // 展开后的memset不输出，不计入指令数
+            if !matches!(op, Op::Call(Callee::Memset)) {
+                self.count(op, taken);
+            }
+            self.pc = next;
+        }
+    }
+
+    fn count(&mut self, op: &Op, taken: bool) {
+        self.instructions += 1;
+        self.cycles += cycles_of(op, taken);
+    }
+
+    /// 按调用约定取出参数调用库函数，随后破坏调用者保存的寄存器
+    fn call_lib(&mut self, name: &str, float_params: &[bool], ret: &SymbolWidth) -> Result<(), String> {
+        let (mut int_arg, mut float_arg) = (A0, FA0);
+        let args: Vec<Val> = float_params.iter().map(|is_float| {
+            if *is_float {
+                float_arg += 1;
+                Val::Float(self.float(float_arg - 1))
+            } else {
+                int_arg += 1;
+                Val::Int(self.regs[int_arg - 1] as i64)
+            }
+        }).collect();
+        let res = self.runtime.call(name, &args, &mut self.mem)?;
+        for reg in caller_saved() {
+            self.regs[reg] = CLOBBER;
+        }
+        match (res, ret) {
+            (Some(val), SymbolWidth::Float) => self.set_float(FA0, val.as_float()),
+            (Some(val), _) => self.set(A0, val.as_int() as u64),
+            (None, _) => {
+                self.regs[A0] = CLOBBER;
+                self.regs[FA0] = CLOBBER;
+            },
+        }
+        Ok(())
+    }
+
+    fn set(&mut self, reg: usize, val: u64) {
+        if reg != 0 {
+            self.regs[reg] = val;
+        }
+    }
+
+    fn src(&self, src: Src) -> u64 {
+        match src {
+            Src::Reg(reg) => self.regs[reg],
+            Src::Imm(imm) => imm as u64,
+        }
+    }
+
+    fn float(&self, reg: usize) -> f32 {
+        f32::from_bits(self.regs[reg] as u32)
+    }
+
+    fn set_float(&mut self, reg: usize, num: f32) {
+        self.set_float_bits(reg, num.to_bits());
+    }
+
+    fn set_float_bits(&mut self, reg: usize, bits: u32) {
+        self.regs[reg] = 0xffff_ffff_0000_0000 | bits as u64;
+    }
+}
+
+fn alu(op: AluOp, num1: i64, num2: i64) -> i64 {
+    let (word1, word2) = (num1 as i32, num2 as i32);
+    match op {
+        AluOp::Add => num1.wrapping_add(num2),
+        AluOp::Sub => num1.wrapping_sub(num2),
+        AluOp::Mul => num1.wrapping_mul(num2),
+        AluOp::Mulw => word1.wrapping_mul(word2) as i64,
+        // 除以零时商为-1，余数为被除数
+        AluOp::Div if num2 == 0 => -1,
+        AluOp::Div => num1.wrapping_div(num2),
+        AluOp::Divw if word2 == 0 => -1,
+        AluOp::Divw => word1.wrapping_div(word2) as i64,
+        AluOp::Rem if num2 == 0 => num1,
+        AluOp::Rem => num1.wrapping_rem(num2),
+        AluOp::Remw if word2 == 0 => word1 as i64,
+        AluOp::Remw => word1.wrapping_rem(word2) as i64,
+        AluOp::Xor => num1 ^ num2,
+        AluOp::Sll => num1 << (num2 & 63),
+        AluOp::Srl => ((num1 as u64) >> (num2 & 63)) as i64,
+        AluOp::Sra => num1 >> (num2 & 63),
+        AluOp::Slt => (num1 < num2) as i64,
+        AluOp::Sgt => (num1 > num2) as i64,
+    }
+}
+
+/// fcvt.s.w的rtz舍入：先按就近舍入转换，超过原值时再向零退一位
+fn int_to_float_rtz(num: i32) -> f32 {
+    let res = num as f32;
+    if (res as f64).abs() > (num as f64).abs() {
+        f32::from_bits(res.to_bits() - 1)
+    } else {
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use AsmInstructionType::*;
+
+    fn instr(ty: AsmInstructionType, operands: &[&str], width: Option<isize>) -> AsmInstruction {
+        AsmInstruction::make_instr(ty, operands.to_vec(), width, vec!())
+    }
+
+    /// 由若干基本块组成main并执行
+    fn run(blocks: Vec<(&str, Vec<AsmInstruction>)>) -> SimResult {
+        let mut func = AsmFunc::new("main", SymbolWidth::I32);
+        for (label, instrs) in blocks {
+            let mut block = AsmBlock::new(label, 0, 0);
+            block.instrs = instrs;
+            func.blocks.push(block);
+        }
+        let mut asm = RiscV::new();
+        asm.text.funcs.push(func);
+        asm.simulate(b"").unwrap()
+    }
+
+    /// 计算op1 op op2并返回结果
+    fn eval(ty: AsmInstructionType, width: Option<isize>, op1: i64, op2: i64) -> i64 {
+        let res = run(vec!(("main", vec!(
+            instr(Li, &["t0", &op1.to_string()], None),
+            instr(Li, &["t1", &op2.to_string()], None),
+            instr(ty, &["a0", "t0", "t1"], width),
+            // 退出码只有低32位，结果须是低32位的符号扩展
+            instr(Srai, &["t2", "a0", "32"], None),
+            instr(Srai, &["t3", "a0", "31"], None),
+            instr(Branch, &["eq", "t2", "t3", ".Lret"], None),
+            instr(Li, &["a0", "12345"], None),
+            instr(Ret, &[], None),
+        )), (".Lret", vec!(instr(Ret, &[], None)))));
+        assert_ne!(res.exit_code, 12345, "结果不是32位数的符号扩展");
+        res.exit_code as i64
+    }
+
+    #[test]
+    fn divide_negative_numbers() {
+        // 商向零取整，余数与被除数同号
+        assert_eq!(eval(Div, Some(4), -7, 2), -3);
+        assert_eq!(eval(Div, Some(4), 7, -2), -3);
+        assert_eq!(eval(Div, Some(4), -7, -2), 3);
+        assert_eq!(eval(Rem, Some(4), -7, 2), -1);
+        assert_eq!(eval(Rem, Some(4), 7, -2), 1);
+        assert_eq!(eval(Rem, Some(4), -7, -2), -1);
+        // 溢出时商为被除数，余数为0
+        assert_eq!(eval(Div, Some(4), i32::MIN as i64, -1), i32::MIN as i64);
+        assert_eq!(eval(Rem, Some(4), i32::MIN as i64, -1), 0);
+        // 除以零时商为-1，余数为被除数
+        assert_eq!(eval(Div, Some(4), -7, 0), -1);
+        assert_eq!(eval(Rem, Some(4), -7, 0), -7);
+    }
+
+    #[test]
+    fn divw_uses_low_words() {
+        // divw只看低32位，4294967292的低32位是-4
+        assert_eq!(eval(Div, Some(4), 4294967292, 2), -2);
+        assert_eq!(eval(Div, None, 4294967292, 4), 1073741823);
+    }
+
+    /// 用sb/sh/sw写入val，再读回并右移32位，返回(低32位, 高32位)
+    fn store_and_load(width: isize, val: i64) -> (i32, i32) {
+        let high = run(vec!(("main", vec!(
+            instr(Addi, &["sp", "sp", "-16"], None),
+            instr(Li, &["t0", "-1"], None),
+            instr(Store, &["t0", "sp", "8"], Some(8)),
+            instr(Li, &["t0", &val.to_string()], None),
+            instr(Store, &["t0", "sp", "8"], Some(width)),
+            instr(Load, &["t1", "sp", "8"], Some(width)),
+            instr(Srai, &["a0", "t1", "32"], None),
+            instr(Addi, &["sp", "sp", "16"], None),
+            instr(Ret, &[], None),
+        ))));
+        let low = run(vec!(("main", vec!(
+            instr(Addi, &["sp", "sp", "-16"], None),
+            instr(Li, &["t0", &val.to_string()], None),
+            instr(Store, &["t0", "sp", "8"], Some(width)),
+            instr(Load, &["a0", "sp", "8"], Some(width)),
+            instr(Addi, &["sp", "sp", "16"], None),
+            instr(Ret, &[], None),
+        ))));
+        (low.exit_code, high.exit_code)
+    }
+
+    #[test]
+    fn loads_sign_extend() {
+        assert_eq!(store_and_load(1, 0xff), (-1, -1));
+        assert_eq!(store_and_load(1, 0x17f), (127, 0));
+        assert_eq!(store_and_load(2, 0x8000), (-32768, -1));
+        assert_eq!(store_and_load(2, 0x7fff), (32767, 0));
+        assert_eq!(store_and_load(4, 0x8000_0000), (i32::MIN, -1));
+        assert_eq!(store_and_load(4, 0x1_7fff_ffff), (i32::MAX, 0));
+        assert_eq!(store_and_load(8, -1), (-1, -1));
+    }
+
+    #[test]
+    fn branches_to_labels() {
+        // 向后跳转构成循环，向前跳转跳过中间的块
+        let res = run(vec!(
+            ("main", vec!(
+                instr(Li, &["a0", "0"], None),
+                instr(Li, &["t0", "10"], None),
+            )),
+            (".Lloop", vec!(
+                instr(Add, &["a0", "a0", "t0"], None),
+                instr(Addi, &["t0", "t0", "-1"], None),
+                instr(Branch, &["gt", "t0", "zero", ".Lloop"], None),
+                instr(Li, &["t1", "-1"], None),
+                // 比较按有符号数进行
+                instr(Branch, &["lt", "t1", "zero", ".Lend"], None),
+            )),
+            (".Lskipped", vec!(
+                instr(Li, &["a0", "-100"], None),
+            )),
+            (".Lend", vec!(
+                instr(Branch, &["ge", "t1", "zero", ".Lskipped"], None),
+                instr(Ret, &[], None),
+            )),
+        ));
+        assert_eq!(res.exit_code, 55);
+        // 2 + 循环10次 * 3 + 2 + 2
+        assert_eq!(res.instructions, 36);
+        // 循环跳转9次，跳到.Lend一次，其余分支不跳转
+        assert_eq!(res.cycles, 36 + 10 * 2 + 2);
+    }
+}
@@ -1,4 +1,4 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use crate::utils::check::*;
 use crate::utils::float::*;
 use crate::structures::llvm_struct::*;
@@ -13,88 +13,272 @@ impl LLVMProgram {
     }
 }
 
+/// 消除phi时插入的复制<目的, 源, 类型>
+pub type PhiCopy = (String, String, SymbolWidth);
+
+/// 指令选择时函数级别的信息
+pub struct SelectContext {
+    /// 函数标签
+    pub func_label: String,
+    /// 函数参数对应的虚拟寄存器，以参数下标索引
+    pub param_regs: Vec<String>,
+    /// 控制流边<前驱块, 后继块>上为消除phi需要插入的并行复制
+    pub phi_copies: HashMap<(BlockId, BlockId), Vec<PhiCopy>>,
+}
+
+/// 指针操作数所在的位置
+enum PtrLoc {
+    /// 保存在虚拟寄存器中
+    Reg,
+    /// 栈上的局部变量
+    Stack,
+    /// 全局变量，不含@前缀
+    Global(String),
+}
+
 impl FuncDef {
     pub fn asm_select(&self, asm: &mut RiscV) {
         if let Some(func) = asm.text.funcs.iter_mut().find(|func| func.label == self.func_name.replace("@", "")) {
             let mut select_cnt = 0;
-            self.blocks.iter().enumerate().for_each(|(idx, block)| {
-                if idx < self.blocks.len() - 1 {
-                    block.select_asm(Some(&self.blocks[idx+1].block_label),
-                        &self.func_name.replace("@", ""), 
-                        &mut select_cnt, 
-                        func, 
-                        &asm.data.labels);
-                } else {
-                    block.select_asm( None, 
-                        &self.func_name.replace("@", ""), 
-                        &mut select_cnt, 
-                        func, 
-                        &asm.data.labels);
+            let ctx = self.make_select_context(&mut select_cnt, func);
+            // 不开优化时会留下return之后不可达的块，寄存器分配只遍历从入口可达的块，因此不为它们生成代码
+            let reachable: HashSet<BlockId> = self.build_cfg().reverse_post_order().into_iter().collect();
+            let layout: Vec<BlockId> = self.layout.iter().cloned().filter(|bb| reachable.contains(bb)).collect();
+            layout.iter().enumerate().for_each(|(idx, &bb)| {
+                let block = self.block(bb);
+                push_block(&format!("{}.{}", ctx.func_label, block.block_label), block.depth, func);
+                if idx == 0 {
+                    self.select_params(&ctx, func);
                 }
+                let next_block = layout.get(idx+1).cloned();
+                self.select_block(bb, next_block, &mut select_cnt, func, &ctx);
             });
         }
     }
-}
 
-impl Block {
-    pub fn select_asm(&self, next_block: Option<&str>, func_label: &str, select_cnt: &mut usize, func: &mut AsmFunc, global_vars: &HashSet<String>) {
-        let this_label: String = func_label.to_string()+"."+&self.block_label;
-        push_block(&this_label, self.depth, func);
-        self.nor_ins.iter().for_each(|instr| instr.select_asm(select_cnt, func, global_vars));
-        if let Some(ter) = &self.ter_ins {
-            if let Instruction::Br(cond, label1, label2) = ter {
+    /// 为函数参数分配虚拟寄存器，并收集消除phi所需的复制
+    fn make_select_context(&self, select_cnt: &mut usize, func: &mut AsmFunc) -> SelectContext {
+        let mut ctx = SelectContext {
+            func_label: self.func_name.replace("@", ""),
+            param_regs: self.params.iter().map(|param| {
+                let width = match &param.param_type.width {
+                    SymbolWidth::Arr{..} => SymbolWidth::I64,
+                    width => width.clone(),
+                };
+                pop_temp_label(select_cnt, &width, func)
+            }).collect(),
+            phi_copies: HashMap::new(),
+        };
+
+        for (bb, block) in self.layout_blocks() {
+            for &phi in block.phi_ins.iter() {
+                if let Instruction::Phi(ty, candidates) = self.instr(phi) {
+                    let width = match &ty.width {
+                        SymbolWidth::Arr{..} => SymbolWidth::I64,
+                        width => width.clone(),
+                    };
+                    let res = ctx.value_reg(phi);
+                    insert_label_type(&res, &width, func);
+                    for (value, pred) in candidates.iter() {
+                        let value = ctx.asm_operand(self, value);
+                        ctx.phi_copies.entry((*pred, bb))
+                            .or_insert_with(Vec::new)
+                            .push((res.clone(), value, width.clone()));
+                    }
+                }
+            }
+        }
+        ctx
+    }
+
+    /// 在入口处将参数寄存器或栈上的参数复制到虚拟寄存器
+    fn select_params(&self, ctx: &SelectContext, func: &mut AsmFunc) {
+        for (i, param) in self.params.iter().enumerate() {
+            let dst = &ctx.param_regs[i];
+            let idx = func.params[&param.param_name];
+            match &param.param_type.width {
+                SymbolWidth::Float => {
+                    if idx < FLOAT_FUNC_ARG.len() {
+                        gen_instr(AsmInstructionType::Fmv, vec!(dst, FLOAT_FUNC_ARG[idx]), None, vec!(SymbolWidth::Float, SymbolWidth::Float), func);
+                    } else {
+                        gen_instr(AsmInstructionType::Load, vec!(dst, "sp", &param.param_name, "f"), Some(NORMAL_WIDTH), vec![], func);
+                    }
+                },
+                width => {
+                    let width_num = match width {
+                        SymbolWidth::Arr{..} => PTR_WIDTH,
+                        _ => NORMAL_WIDTH,
+                    };
+                    if idx < FUNC_ARG.len() {
+                        gen_instr(AsmInstructionType::Mv, vec!(dst, FUNC_ARG[idx]), None, vec![], func);
+                    } else {
+                        gen_instr(AsmInstructionType::Load, vec!(dst, "sp", &param.param_name), Some(width_num), vec![], func);
+                    }
+                },
+            }
+        }
+    }
+
+    fn select_block(&self, bb: BlockId, next_block: Option<BlockId>, select_cnt: &mut usize, func: &mut AsmFunc, ctx: &SelectContext) {
+        let func_label = ctx.func_label.as_str();
+        let block = self.block(bb);
+        block.nor_ins.iter().for_each(|&instr| {
+            // 没有结果的指令(例如返回void的调用)目的寄存器为空
+            let res = if self.instr(instr).has_result() { ctx.value_reg(instr) } else { String::new() };
+            self.instr(instr).select_asm(&res, self, ctx, select_cnt, func);
+        });
+        let no_copies = vec![];
+        let phi_copies = |succ: BlockId| {
+            ctx.phi_copies.get(&(bb, succ)).unwrap_or(&no_copies)
+        };
+        let block_label = |bb: BlockId| self.block(bb).block_label.as_str();
+        if let Some(ter) = block.ter_ins {
+            if let Instruction::Br(cond, label1, label2) = self.instr(ter) {
+                let (label1, label2) = (*label1, *label2);
                 if let (Some(cond), Some(label2)) = (cond, label2) {
-                    if let Some(next_block) = next_block {
-                        if next_block == label1 || next_block == label2 {
-                            let final_label1 = String::from(func_label)+"."+label1;
-                            let final_label2 = String::from(func_label)+"."+label2;
-                            let cond_val: String;
-                            if is_immediate(cond) {
-                                cond_val = pop_temp_label(select_cnt, &SymbolWidth::I32, func);
-                                gen_instr(AsmInstructionType::Li, vec!(&cond_val, cond), None, vec![], func);
-                            } else {
-                                cond_val = String::from(cond);
-                            }
-                            if next_block == label1 {
-                                gen_instr(AsmInstructionType::Branch, vec!("eq", &cond_val, "zero", &final_label2), None, vec![], func);
-                                push_successor(&final_label1, func);
-                                push_successor(&final_label2, func);
-                            } else {
-                                gen_instr(AsmInstructionType::Branch, vec!("ne", &cond_val, "zero", &final_label1), None, vec![], func);
-                                push_successor(&final_label2, func);
-                                push_successor(&final_label1, func);
-                            }
+                    let final_label1 = String::from(func_label)+"."+block_label(label1);
+                    let final_label2 = String::from(func_label)+"."+block_label(label2);
+                    let cond_val: String;
+                    if cond.is_const() {
+                        cond_val = pop_temp_label(select_cnt, &SymbolWidth::I32, func);
+                        gen_instr(AsmInstructionType::Li, vec!(&cond_val, &ctx.asm_operand(self, cond)), None, vec![], func);
+                    } else {
+                        cond_val = ctx.asm_operand(self, cond);
+                    }
+                    let copies1 = phi_copies(label1);
+                    let copies2 = phi_copies(label2);
+                    if copies1.is_empty() && copies2.is_empty() {
+                        if next_block == Some(label1) {
+                            gen_instr(AsmInstructionType::Branch, vec!("eq", &cond_val, "zero", &final_label2), None, vec![], func);
+                            push_successor(&final_label1, func);
+                            push_successor(&final_label2, func);
                         } else {
-                            todo!();
+                            gen_instr(AsmInstructionType::Branch, vec!("ne", &cond_val, "zero", &final_label1), None, vec![], func);
+                            // 两个后继都不紧跟在当前块之后(例如优化后的死循环位于函数末尾)时，再跳转到假分支
+                            if next_block != Some(label2) {
+                                gen_instr(AsmInstructionType::Jump, vec!(&final_label2), None, vec![], func);
+                            }
+                            push_successor(&final_label2, func);
+                            push_successor(&final_label1, func);
                         }
                     } else {
-                        panic!();
+                        // 关键边需要拆分：在当前块之后插入边块来放置复制指令
+                        let edge_label1 = format!("{}.{}.{}", func_label, block.block_label, block_label(label1));
+                        let edge_label2 = format!("{}.{}.{}", func_label, block.block_label, block_label(label2));
+                        let target1 = if copies1.is_empty() { &final_label1 } else { &edge_label1 };
+                        let target2 = if copies2.is_empty() { &final_label2 } else { &edge_label2 };
+                        if copies1.is_empty() {
+                            gen_instr(AsmInstructionType::Branch, vec!("ne", &cond_val, "zero", target1), None, vec![], func);
+                        } else {
+                            gen_instr(AsmInstructionType::Branch, vec!("eq", &cond_val, "zero", target2), None, vec![], func);
+                        }
+                        push_successor(target1, func);
+                        push_successor(target2, func);
+                        if !copies1.is_empty() {
+                            push_block(&edge_label1, self.block(bb).depth, func);
+                            gen_parallel_copy(copies1, select_cnt, func);
+                            if !copies2.is_empty() || next_block != Some(label1) {
+                                gen_instr(AsmInstructionType::Jump, vec!(&final_label1), None, vec![], func);
+                            }
+                            push_successor(&final_label1, func);
+                        }
+                        if !copies2.is_empty() {
+                            push_block(&edge_label2, self.block(bb).depth, func);
+                            gen_parallel_copy(copies2, select_cnt, func);
+                            if next_block != Some(label2) {
+                                gen_instr(AsmInstructionType::Jump, vec!(&final_label2), None, vec![], func);
+                            }
+                            push_successor(&final_label2, func);
+                        }
                     }
                 } else {
-                    if let Some(next_block) = next_block {
-                        let final_label1 = String::from(func_label)+"."+&label1;
-                        if next_block != label1 {
-                            gen_instr(AsmInstructionType::Jump, vec!(&final_label1), None, vec![], func)
-                        }
-                        push_successor(&final_label1, func);
-                    } else {
-                        panic!();
+                    let final_label1 = String::from(func_label)+"."+block_label(label1);
+                    gen_parallel_copy(phi_copies(label1), select_cnt, func);
+                    if next_block != Some(label1) {
+                        gen_instr(AsmInstructionType::Jump, vec!(&final_label1), None, vec![], func)
                     }
+                    push_successor(&final_label1, func);
                 }
             } else {
-                ter.select_asm(select_cnt, func, global_vars);
+                self.instr(ter).select_asm("", self, ctx, select_cnt, func);
+                func.blocks.last_mut().unwrap().tail_call = self.is_tail_call(bb);
             }
         } else if next_block.is_none() {
             gen_instr(AsmInstructionType::Ret, vec![], None, vec![], func);
         }
     }
+
+    /// 指针操作数所在的位置
+    fn ptr_loc(&self, ptr: &Operand) -> PtrLoc {
+        match ptr {
+            Operand::Global(name) => PtrLoc::Global(name.clone()),
+            ptr if self.is_alloca(ptr) => PtrLoc::Stack,
+            _ => PtrLoc::Reg,
+        }
+    }
+}
+
+impl SelectContext {
+    /// 指令结果对应的虚拟寄存器
+    pub fn value_reg(&self, id: ValueId) -> String {
+        format!("%{}", id.0)
+    }
+
+    /// 操作数在汇编中的表示：立即数、虚拟寄存器、栈上变量的标签或带@的全局变量名
+    pub fn asm_operand(&self, def: &FuncDef, op: &Operand) -> String {
+        match op {
+            Operand::ConstInt(num) => num.to_string(),
+            Operand::ConstFloat(num) => format_double(*num),
+            Operand::Null => String::from("0"),
+            Operand::Global(name) => format!("@{}", name),
+            Operand::Arg(idx) => self.param_regs[*idx].clone(),
+            Operand::Value(id) if def.is_alloca(op) => def.slot_label(*id),
+            Operand::Value(id) => self.value_reg(*id),
+        }
+    }
 }
 
 impl Instruction {
-    pub fn select_asm(&self, select_cnt: &mut usize, func: &mut AsmFunc, global_vars: &HashSet<String>) {
+    /// 整型操作数全为立即数时(例如mem2reg将常量传播到了操作数中)，先把立即数加载到寄存器
+    fn materialize_imm(&self, ops: &mut [String], select_cnt: &mut usize, func: &mut AsmFunc) {
+        match self {
+            Instruction::Add(bin_op)
+            | Instruction::Sub(bin_op)
+            | Instruction::Mul(bin_op)
+            | Instruction::Sdiv(bin_op)
+            | Instruction::Srem(bin_op)
+            | Instruction::Cmp(_, bin_op) => {
+                if bin_op.op1.is_const() && bin_op.op2.is_const() {
+                    let li_dst = pop_temp_label(select_cnt, &SymbolWidth::I32, func);
+                    gen_instr(AsmInstructionType::Li, vec!(&li_dst, &ops[0]), None, vec![], func);
+                    ops[0] = li_dst;
+                }
+            },
+            Instruction::ZeroExt(cast_op) | Instruction::I32ToFloat(cast_op) => {
+                if cast_op.val.is_const() {
+                    let li_dst = pop_temp_label(select_cnt, &SymbolWidth::I32, func);
+                    gen_instr(AsmInstructionType::Li, vec!(&li_dst, &ops[0]), None, vec![], func);
+                    ops[0] = li_dst;
+                }
+            },
+            Instruction::FloatToI32(cast_op) => {
+                if cast_op.val.is_const() {
+                    ops[0] = load_float_imm(select_cnt, &ops[0], func);
+                }
+            },
+            _ => {},
+        }
+    }
+
+    /// res为指令结果对应的虚拟寄存器，操作数按operands()的顺序转换为汇编中的表示
+    pub fn select_asm(&self, res: &str, def: &FuncDef, ctx: &SelectContext, select_cnt: &mut usize, func: &mut AsmFunc) {
+        let mut ops: Vec<String> = self.operands().into_iter().map(|op| ctx.asm_operand(def, op)).collect();
+        self.materialize_imm(&mut ops, select_cnt, func);
+        let res = &String::from(res);
         match self {
             //LLVM IR: Add指令
-            Instruction::Add(BinaryOp{res, op_type, op1, op2}) => {
+            Instruction::Add(BinaryOp{op_type, ..}) => {
+                let (op1, op2) = (&ops[0], &ops[1]);
                 //将该指令的目的寄存器与类型绑定
                 insert_label_type(&res, &op_type.width, func);
                 if is_immediate(op1) {
@@ -117,7 +301,8 @@ impl Instruction {
                     gen_instr(AsmInstructionType::Add, vec![res, op1, op2], None, vec![], func);
                 }
             },
-            Instruction::Sub(BinaryOp{res, op_type, op1, op2}) => {
+            Instruction::Sub(BinaryOp{op_type, ..}) => {
+                let (op1, op2) = (&ops[0], &ops[1]);
                 insert_label_type(res, &op_type.width, func);
                 if is_immediate(op2) {
                     //求出op2的相反数
@@ -140,7 +325,8 @@ impl Instruction {
             },
             //这里完成了优化：强度削弱；对有一个立即数是2的幂次的立即数替换为移位指令。
             //能完成这个优化的前提是，常量折叠已做完。
-            Instruction::Mul(BinaryOp{res, op_type, op1, op2}) => {
+            Instruction::Mul(BinaryOp{op_type, ..}) => {
+                let (op1, op2) = (&ops[0], &ops[1]);
                 insert_label_type(res, &op_type.width, func);
                 if is_immediate(op1) { //如果op1是立即数
                     if op1.contains("-") {//如果是负数
@@ -199,7 +385,8 @@ impl Instruction {
                     gen_instr(AsmInstructionType::Mul, vec![res, op1, op2], Some(NORMAL_WIDTH), vec![], func);
                 }
             },
-            Instruction::Sdiv(BinaryOp{res, op_type, op1, op2}) => {
+            Instruction::Sdiv(BinaryOp{op_type, ..}) => {
+                let (op1, op2) = (&ops[0], &ops[1]);
                 insert_label_type(res, &op_type.width, func);
                 if is_immediate(op1) {//第一个op是立即数，只能用除法
                     let li_dst = pop_temp_label(select_cnt, &SymbolWidth::I32, func);
@@ -210,16 +397,17 @@ impl Instruction {
                     if &op2[0..1] == "-" {//如果是负数
                         let op2_positive = op2.replace("-", "");//转成正数
                         match is_poweroftwo(&op2_positive) {//如果是2的幂次
-                            Some(pow) => {
+                            //除以1或-1时移位量为64，不能用移位实现
+                            Some(pow) if pow > 0 => {
                                 let temp_reg = pop_temp_label(select_cnt, &SymbolWidth::I32, func);
                                 gen_instr(AsmInstructionType::Srai, vec![&temp_reg, op1, "63"], None, vec![], func);//算术右移，得到64个1或0
                                 gen_instr(AsmInstructionType::Srli, vec![&temp_reg, &temp_reg, &(64-pow).to_string()], None, vec![], func);//逻辑右移，负数会得到pow个1，正数不变
-                                gen_instr(AsmInstructionType::Add, vec![op1, &temp_reg, op1], None, vec![], func);
-                                gen_instr(AsmInstructionType::Srai, vec![res, op1, &pow.to_string()], None, vec![], func);
+                                gen_instr(AsmInstructionType::Add, vec![&temp_reg, &temp_reg, op1], None, vec![], func);
+                                gen_instr(AsmInstructionType::Srai, vec![res, &temp_reg, &pow.to_string()], None, vec![], func);
                                 gen_instr(AsmInstructionType::Sub, vec![res, "zero", res], None, vec![], func);
 
                             },
-                            None => {
+                            _ => {
                                 let li_dst = pop_temp_label(select_cnt, &SymbolWidth::I32, func);
                                 gen_instr(AsmInstructionType::Li, vec![&li_dst, op2], None, vec![], func);
                                 gen_instr(AsmInstructionType::Div, vec![res, op1, &li_dst], Some(NORMAL_WIDTH), vec![], func);
@@ -227,15 +415,15 @@ impl Instruction {
                         }
                     } else { //就是正数
                         match is_poweroftwo(op2) {
-                            Some(pow) => {
+                            Some(pow) if pow > 0 => {
                                 let temp_reg = pop_temp_label(select_cnt, &SymbolWidth::I32, func);
                                 gen_instr(AsmInstructionType::Srai, vec![&temp_reg, op1, "63"], None, vec![], func);//算术右移，得到64个1或0
                                 gen_instr(AsmInstructionType::Srli, vec![&temp_reg, &temp_reg, &(64-pow).to_string()], None, vec![], func);//逻辑右移，负数会得到pow个1，正数不变
-                                gen_instr(AsmInstructionType::Add, vec![op1, &temp_reg, op1], None, vec![], func);
-                                gen_instr(AsmInstructionType::Srai, vec![res, op1, &pow.to_string()], None, vec![], func);
+                                gen_instr(AsmInstructionType::Add, vec![&temp_reg, &temp_reg, op1], None, vec![], func);
+                                gen_instr(AsmInstructionType::Srai, vec![res, &temp_reg, &pow.to_string()], None, vec![], func);
 
                             },
-                            None => {
+                            _ => {
                                 let li_dst = pop_temp_label(select_cnt, &SymbolWidth::I32, func);
                                 gen_instr(AsmInstructionType::Li, vec![&li_dst, op2], None, vec![], func);
                                 gen_instr(AsmInstructionType::Div, vec![res, op1, &li_dst], Some(NORMAL_WIDTH), vec![], func);
@@ -246,7 +434,8 @@ impl Instruction {
                     gen_instr(AsmInstructionType::Div, vec![res, op1, op2], Some(NORMAL_WIDTH), vec![], func)
                 }
             },
-            Instruction::Srem(BinaryOp{res, op_type, op1, op2}) => {
+            Instruction::Srem(BinaryOp{op_type, ..}) => {
+                let (op1, op2) = (&ops[0], &ops[1]);
                 insert_label_type(res, &op_type.width, func);
                 if is_immediate(op1) || is_immediate(op2) {
                     let li_dst: String;
@@ -263,7 +452,8 @@ impl Instruction {
                     gen_instr(AsmInstructionType::Rem, vec!(res, op1, op2), Some(NORMAL_WIDTH), vec![], func);
                 }
             },
-            Instruction::Cmp(cond, BinaryOp{res, op_type: _, op1, op2}) => {
+            Instruction::Cmp(cond, BinaryOp{..}) => {
+                let (op1, op2) = (&ops[0], &ops[1]);
                 insert_label_type(res, &SymbolWidth::I32, func);
                 let op1_is_imm = is_immediate(op1);
                 let op2_is_imm = is_immediate(op2);
@@ -372,7 +562,7 @@ impl Instruction {
                             if op1_is_imm {
                                 let li_dst = pop_temp_label(select_cnt, &SymbolWidth::I32, func);
                                 gen_instr(AsmInstructionType::Li, vec!(&li_dst, op1), None, vec![], func);
-                                gen_instr(AsmInstructionType::Slt, vec!(res, &li_dst, op1), None, vec![], func);
+                                gen_instr(AsmInstructionType::Slt, vec!(res, &li_dst, op2), None, vec![], func);
                             } else {
                                 if inside_imm_range(op2) {
                                     gen_instr(AsmInstructionType::Slti, vec!(res, op1, op2), None, vec![], func);
@@ -454,31 +644,36 @@ impl Instruction {
                     _ => panic!("Do not support other Icmp condition."),
                 }
             },
-            Instruction::Fadd(BinaryOp{res, op_type: _, op1, op2}) => {
+            Instruction::Fadd(BinaryOp{..}) => {
+                let (op1, op2) = (&ops[0], &ops[1]);
                 insert_label_type(res, &SymbolWidth::Float, func);
                 let op1_final = check_float_op(select_cnt, op1, func);
                 let op2_final = check_float_op(select_cnt, op2, func);
                 gen_instr(AsmInstructionType::Fadd, vec!(res, &op1_final, &op2_final), None, vec![], func);
             },
-            Instruction::Fsub(BinaryOp{res, op_type: _, op1, op2}) => {
+            Instruction::Fsub(BinaryOp{..}) => {
+                let (op1, op2) = (&ops[0], &ops[1]);
                 insert_label_type(res, &SymbolWidth::Float, func);
                 let op1_final = check_float_op(select_cnt, op1, func);
                 let op2_final = check_float_op(select_cnt, op2, func);
                 gen_instr(AsmInstructionType::Fsub, vec!(res, &op1_final, &op2_final), None, vec![], func);
             },
-            Instruction::Fmul(BinaryOp{res, op_type: _, op1, op2}) => {
+            Instruction::Fmul(BinaryOp{..}) => {
+                let (op1, op2) = (&ops[0], &ops[1]);
                 insert_label_type(res, &SymbolWidth::Float, func);
                 let op1_final = check_float_op(select_cnt, op1, func);
                 let op2_final = check_float_op(select_cnt, op2, func);
                 gen_instr(AsmInstructionType::Fmul, vec!(res, &op1_final, &op2_final), None, vec![], func);
             },
-            Instruction::Fdiv(BinaryOp{res, op_type: _, op1, op2}) => {
+            Instruction::Fdiv(BinaryOp{..}) => {
+                let (op1, op2) = (&ops[0], &ops[1]);
                 insert_label_type(res, &SymbolWidth::Float, func);
                 let op1_final = check_float_op(select_cnt, op1, func);
                 let op2_final = check_float_op( select_cnt, op2, func);
                 gen_instr(AsmInstructionType::Fdiv, vec!(res, &op1_final, &op2_final), None, vec![], func);
             },
-            Instruction::Fcmp(cond, BinaryOp{res, op_type: _, op1, op2}) => {
+            Instruction::Fcmp(cond, BinaryOp{..}) => {
+                let (op1, op2) = (&ops[0], &ops[1]);
                 insert_label_type(res, &SymbolWidth::I32, func);
                 let op1_final = check_float_op( select_cnt, op1, func);
                 let op2_final = check_float_op(select_cnt, op2, func);
@@ -507,46 +702,9 @@ impl Instruction {
                     _ => panic!("Do not support other Fcmp condition."),
                 }
             },
-            Instruction::Store{ty, value, ptr, len: _} => {
+            Instruction::Store{ty, ptr: ptr_op, ..} => {
+                let (value, ptr) = (&ops[0], &ops[1]);
                 insert_label_type(ptr, &SymbolWidth::I64, func);
-                // 如果value为函数参数
-                if let Some(idx) = func.params.get(value) { 
-                    let idx = *idx;
-                    if ty.width == SymbolWidth::Float {
-                        //如果参数够参数寄存器的数量，则不需要用栈
-                        if idx < FLOAT_FUNC_ARG.len() {
-                            gen_instr(AsmInstructionType::Store, vec!(FLOAT_FUNC_ARG[idx], "sp", ptr, "f"), Some(NORMAL_WIDTH), vec![], func);
-                        }
-                        //参数过多，需要用栈
-                        else {
-                            //从栈里面pop一个位置
-                            let load_dst = pop_temp_label(select_cnt, &ty.width, func);
-                            gen_instr(AsmInstructionType::Load, vec!(&load_dst, "sp", value, "f"), Some(NORMAL_WIDTH), vec![], func);
-                            gen_instr(AsmInstructionType::Store, vec!(&load_dst, "sp", ptr, "f"), Some(NORMAL_WIDTH), vec![], func);
-                        }
-                    } else {
-                        let width_num = match &ty.width {
-                            SymbolWidth::Arr{tar: _, dims} => {
-                                //如果是指针
-                                if dims[0] == -1 {
-                                    Some(PTR_WIDTH)
-                                } else {
-                                    Some(NORMAL_WIDTH)
-                                }
-                            },
-                            _ => Some(NORMAL_WIDTH)
-                        };
-                        if idx < FUNC_ARG.len() {
-                            gen_instr(AsmInstructionType::Store, vec!(FUNC_ARG[idx], "sp", ptr), width_num, vec![], func);
-                        } else {
-                            let load_dst = pop_temp_label(select_cnt, &ty.width, func);
-                            gen_instr(AsmInstructionType::Load, vec!(&load_dst, "sp", value), width_num, vec![], func);
-                            gen_instr(AsmInstructionType::Store, vec!(&load_dst, "sp", ptr), width_num, vec![], func);
-                        }
-                    }
-                    return;
-                }
-
                 let final_value: String;
                 //如果是立即数，对浮点数需要先在data段声明再移动，整数则直接移动
                 if is_immediate(&value) {
@@ -564,24 +722,32 @@ impl Instruction {
                     SymbolWidth::Float => "f",
                     _ => ""
                 };
+                //指针需要存储8个字节
+                let width_num = match &ty.width {
+                    SymbolWidth::Arr{tar: _, dims} if dims[0] == -1 => Some(PTR_WIDTH),
+                    _ => Some(NORMAL_WIDTH)
+                };
 
-                let inside_stack = func.stack.pushed.contains(ptr);
-                let pure_ptr = &ptr[1..];
-                //如果是llvm的临时标号，其实就是store分配出来的那些临时标号，比如%c_0，直接store即可。
-                if is_num_label(&ptr) {
-                    gen_instr(AsmInstructionType::Store, vec!(&final_value, ptr, "0", prefix), Some(NORMAL_WIDTH), vec![], func);
-                } else if inside_stack {
+                match def.ptr_loc(ptr_op) {
+                    //如果是llvm的临时标号，其实就是store分配出来的那些临时标号，比如%c_0，直接store即可。
+                    PtrLoc::Reg => {
+                        gen_instr(AsmInstructionType::Store, vec!(&final_value, ptr, "0", prefix), width_num, vec![], func);
+                    },
                     // 如果在函数的栈里面，则需要通过栈偏移来store，具体值需要到分配完栈大小才能确定
-                    gen_instr(AsmInstructionType::Store, vec!(&final_value, "sp", ptr, prefix), Some(NORMAL_WIDTH), vec![], func);
-                } else if global_vars.contains(pure_ptr) {
+                    PtrLoc::Stack => {
+                        gen_instr(AsmInstructionType::Store, vec!(&final_value, "sp", ptr, prefix), width_num, vec![], func);
+                    },
                     //如果是全局变量，比如说预先声明的一个int A。则需要用La指令来移动。
                     //先加载到临时寄存器，再移动到目标地址
-                    let store_addr = pop_temp_label(select_cnt, &SymbolWidth::I64, func);
-                    gen_instr(AsmInstructionType::La, vec!(&store_addr, pure_ptr), None, vec![], func);
-                    gen_instr(AsmInstructionType::Store, vec!(&final_value, &store_addr, "0", prefix), Some(NORMAL_WIDTH), vec![], func);
+                    PtrLoc::Global(pure_ptr) => {
+                        let store_addr = pop_temp_label(select_cnt, &SymbolWidth::I64, func);
+                        gen_instr(AsmInstructionType::La, vec!(&store_addr, &pure_ptr), None, vec![], func);
+                        gen_instr(AsmInstructionType::Store, vec!(&final_value, &store_addr, "0", prefix), Some(NORMAL_WIDTH), vec![], func);
+                    },
                 }
             },
-            Instruction::Load{res, ty, ptr, len: _} => {
+            Instruction::Load{ty, ptr: ptr_op, ..} => {
+                let ptr = &ops[0];
                 let mut res_width = ty.width.clone();
                 let prefix = match &ty.width {
                     SymbolWidth::Float => "f",
@@ -606,21 +772,23 @@ impl Instruction {
 
                 
                 let load_res = pop_temp_label(select_cnt, &ty.width, func);
-                let inside_stack = func.stack.pushed.contains(ptr);
-                let pure_ptr = &ptr[1..];
                 //和store指令的步骤一样
-                if is_num_label(ptr) {
+                match def.ptr_loc(ptr_op) {
                     //如果是llvm的临时标号，其实就是store分配出来的那些临时标号，比如%c_0，直接load即可。
-                    gen_instr(AsmInstructionType::Load, vec!(&load_res, ptr, "0", prefix), width_num, vec![], func);
-                } else if inside_stack {
+                    PtrLoc::Reg => {
+                        gen_instr(AsmInstructionType::Load, vec!(&load_res, ptr, "0", prefix), width_num, vec![], func);
+                    },
                     // 如果在函数的栈里面，则需要通过栈偏移来load，具体值需要到分配完栈大小才能确定
-                    gen_instr(AsmInstructionType::Load, vec!(&load_res, "sp", ptr, prefix), width_num, vec![], func);
-                } else if global_vars.contains(pure_ptr) {
+                    PtrLoc::Stack => {
+                        gen_instr(AsmInstructionType::Load, vec!(&load_res, "sp", ptr, prefix), width_num, vec![], func);
+                    },
                     //如果是全局变量，比如说预先声明的一个int A。则需要用La指令来移动。
                     //先加载到临时寄存器，再store到目标地址
-                    let load_addr = pop_temp_label(select_cnt, &SymbolWidth::I64, func);
-                    gen_instr(AsmInstructionType::La, vec!(&load_addr, pure_ptr), None, vec![], func);
-                    gen_instr(AsmInstructionType::Load, vec!(&load_res, &load_addr, "0", prefix), width_num, vec![], func);
+                    PtrLoc::Global(pure_ptr) => {
+                        let load_addr = pop_temp_label(select_cnt, &SymbolWidth::I64, func);
+                        gen_instr(AsmInstructionType::La, vec!(&load_addr, &pure_ptr), None, vec![], func);
+                        gen_instr(AsmInstructionType::Load, vec!(&load_res, &load_addr, "0", prefix), width_num, vec![], func);
+                    },
                 }
                 if prefix == "f" {
                 //是float，用fmv
@@ -631,8 +799,8 @@ impl Instruction {
                 }
                 
             },
-            Instruction::Ret(ret_type, ret_val) => {
-                 if let Some(ret_val) = ret_val {
+            Instruction::Ret(ret_type, _) => {
+                 if let Some(ret_val) = ops.first() {
                     match ret_type.width {
                         SymbolWidth::I32 => {
                             if is_immediate(ret_val) {
@@ -645,9 +813,8 @@ impl Instruction {
                             if is_immediate(ret_val) {
                                 let imm = double_to_float(ret_val);
                                 let imm_reg = pop_temp_label(select_cnt, &SymbolWidth::I32, func);
-                                let dst_reg = pop_temp_label(select_cnt, &SymbolWidth::Float, func);
                                 gen_instr(AsmInstructionType::Li, vec!(&imm_reg, &imm), None, vec![], func);
-                                gen_instr(AsmInstructionType::Fmv, vec!(&dst_reg, &imm_reg), None, vec!(SymbolWidth::Float, SymbolWidth::I32), func);
+                                gen_instr(AsmInstructionType::Fmv, vec!(FLOAT_RETURN[0], &imm_reg), None, vec!(SymbolWidth::Float, SymbolWidth::I32), func);
                             } else {
                                 gen_instr(AsmInstructionType::Fmv, vec!(FLOAT_RETURN[0], &ret_val), None, vec!(SymbolWidth::Float, SymbolWidth::Float), func);
                             }
@@ -657,48 +824,58 @@ impl Instruction {
                 }
                 gen_instr(AsmInstructionType::Ret, vec![], None, vec![], func); 
             },
-            Instruction::ZeroExt(CastOp{res, type_1: _, val, type_2: _}) => {
+            Instruction::ZeroExt(_) => {
+                let val = &ops[0];
                 insert_label_type(res, &SymbolWidth::I32, func);
                 gen_instr(AsmInstructionType::Mv, vec!(res, val), None, vec![], func);
             },
-            Instruction::I32ToFloat(CastOp{res, type_1, val, type_2}) | Instruction::FloatToI32(CastOp{res, type_1, val, type_2})=> {
+            Instruction::I32ToFloat(CastOp{type_1, type_2, ..}) | Instruction::FloatToI32(CastOp{type_1, type_2, ..})=> {
+                let val = &ops[0];
                 insert_label_type(res, &type_2.width, func);
                 gen_instr(AsmInstructionType::Fcvt, vec!(res, val), None, vec!(type_2.width.clone(), type_1.width.clone()), func);
             },
-            Instruction::BitCast(res, _, ptr, _) => {
+            Instruction::BitCast(_, ptr_op, _) => {
+                let ptr = &ops[0];
                 insert_label_type(res, &SymbolWidth::I64, func);
-                let inside_stack = func.stack.pushed.contains(ptr);
-                if inside_stack { // 在栈内
-                    let ptr_pos = format!("#{}", ptr);
-                    gen_instr(AsmInstructionType::Addi, vec!(res, "sp", &ptr_pos), None, vec![], func);
-                } else {
-                    if global_vars.contains(ptr) {
-                        gen_instr(AsmInstructionType::La, vec!(res, ptr), None, vec![], func);
-                    } else {
+                match def.ptr_loc(ptr_op) {
+                    PtrLoc::Stack => { // 在栈内
+                        let ptr_pos = format!("#{}", ptr);
+                        gen_instr(AsmInstructionType::Addi, vec!(res, "sp", &ptr_pos), None, vec![], func);
+                    },
+                    PtrLoc::Global(pure_ptr) => {
+                        gen_instr(AsmInstructionType::La, vec!(res, &pure_ptr), None, vec![], func);
+                    },
+                    PtrLoc::Reg => {
                         gen_instr(AsmInstructionType::Mv, vec!(res, ptr), None, vec![], func);
-                    }
+                    },
                 }
             },
-            Instruction::GetElemPtr(dst, SymbolType{width: SymbolWidth::Arr{tar: _, dims}, is_const: _}, ptr, idx) => {
+            Instruction::GetElemPtr(SymbolType{width: SymbolWidth::Arr{tar: _, dims}, is_const: _}, ptr_op, _) => {
+                let (dst, ptr, idx) = (res, &ops[0], &ops[1..]);
                 //把dst和I64类型做map映射
                 insert_label_type(dst, &SymbolWidth::I64, func);
 
-                let inside_stack = func.stack.pushed.contains(ptr);
+                let ptr_loc = def.ptr_loc(ptr_op);
                 let start_addr: String;
 
                 //接下来的一个大if，目的是设置开始的地址
                 //如果是临时标号
-                if is_num_label(ptr) {
+                if let PtrLoc::Reg = ptr_loc {
                     start_addr = String::from(ptr);
                     if idx.len() == 1 {
                         if idx[0] != "0" {
                             let size = dims.iter().map(|i| *i as usize).product::<usize>() * 4;
                             if is_immediate(&idx[0]) {
-	                            let this_idx = idx[0].parse::<usize>().unwrap() * size;
+	                            //指针递增时下标可能为负
+	                            let this_idx = idx[0].parse::<i64>().unwrap() * size as i64;
 	                            let this_idx = format!("{}", this_idx);
-	                            let li_res = pop_temp_label(select_cnt, &SymbolWidth::I64, func);
-	                            gen_instr(AsmInstructionType::Li, vec!(&li_res, &this_idx), None, vec![], func);
-	                            gen_instr(AsmInstructionType::Add, vec!(dst, &start_addr, &li_res), None, vec![], func);
+	                            if inside_imm_range(&this_idx) {
+	                                gen_instr(AsmInstructionType::Addi, vec!(dst, &start_addr, &this_idx), None, vec![], func);
+	                            } else {
+	                                let li_res = pop_temp_label(select_cnt, &SymbolWidth::I64, func);
+	                                gen_instr(AsmInstructionType::Li, vec!(&li_res, &this_idx), None, vec![], func);
+	                                gen_instr(AsmInstructionType::Add, vec!(dst, &start_addr, &li_res), None, vec![], func);
+	                            }
 	                        } else {
 	                            
 	                            let size = format!("{}", size);
@@ -723,7 +900,7 @@ impl Instruction {
                     }
                 } else {
                     //设置为栈指针（"sp"）加上ptr的偏移量
-                    if inside_stack { // 在栈内
+                    if let PtrLoc::Stack = ptr_loc { // 在栈内
                         let ptr_pos = format!("#{}", ptr);
                         if idx.len() == 1 {
                             gen_instr(AsmInstructionType::Addi, vec!(dst, "sp", &ptr_pos), None, vec![], func);
@@ -733,14 +910,13 @@ impl Instruction {
                         gen_instr(AsmInstructionType::Addi, vec!(&start_addr, "sp", &ptr_pos), None, vec![], func);
                     } else {
                         //如果是全局变量的标签，直接La
-                        let pure_ptr = &ptr[1..];
-                        if global_vars.contains(pure_ptr) {
+                        if let PtrLoc::Global(pure_ptr) = ptr_loc {
                             if idx.len() == 1 {
-                                gen_instr(AsmInstructionType::La, vec!(dst, pure_ptr), None, vec![], func);
+                                gen_instr(AsmInstructionType::La, vec!(dst, &pure_ptr), None, vec![], func);
                                 return;
                             }
                             start_addr = pop_temp_label(select_cnt, &SymbolWidth::I64, func);
-                            gen_instr(AsmInstructionType::La, vec!(&start_addr, pure_ptr), None, vec![], func);
+                            gen_instr(AsmInstructionType::La, vec!(&start_addr, &pure_ptr), None, vec![], func);
                         } else {
                             panic!("Undefined pointer {}", ptr);
                         }
@@ -808,10 +984,10 @@ impl Instruction {
                 }
             },
             Instruction::Comment(_) => {}, // 跳过注释
-            Instruction::Call(res, label, ty, params) => {
+            Instruction::Call(label, ty, params) => {
                 if &label[1..] == "llvm.memset.p0i8.i64" {
-                    let ptr = &params[0].0;
-                    let size_byte: usize = (&params[2].0).parse().unwrap();
+                    let ptr = &ops[0];
+                    let size_byte: usize = ops[2].parse().unwrap();
                     let mut filled_size: usize = 0;
                     let mut inner_filled_size: usize = 0;
                     let imm_reg = pop_temp_label(select_cnt, &SymbolWidth::I64, func);
@@ -855,7 +1031,7 @@ impl Instruction {
                 mark_call(func);
                 insert_label_type(res, &ty.width, func);
                 let mut str_vec:Vec<&str> = vec!(res, &label[1..]);
-                let mut new_params:Vec<&str> = params.iter().map(|(s, _)| s.as_str()).collect();
+                let mut new_params:Vec<&str> = ops.iter().map(|s| s.as_str()).collect();
                 str_vec.append(&mut new_params);
                 let mut ty_vec = vec!(ty.width.clone());
                 let mut param_ty = params.iter().map(|(_, t)| t.width.clone()).collect::<Vec<_>>();
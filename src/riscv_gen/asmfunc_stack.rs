@@ -83,11 +83,16 @@ impl AsmFunc {
             // 从后向前遍历所有的指令
             for cnt in (0..len).rev() {
                 // 根据指令类型进行匹配
+                let is_load = matches!(block.instrs[cnt], AsmInstruction::Load(..));
                 match block.instrs.get_mut(cnt).unwrap() {
                     AsmInstruction::Store(MemInstr{width: _, val, base, offset}, _)
                     | AsmInstruction::Load(MemInstr{width: _, val, base, offset}, _) => {
-                        // 查找一个不等于 val 的保留寄存器
-                        let preserved_reg = PRESERVED.iter().map(|r| *r).find(|r| *r != val.as_str()).unwrap();
+                        // 加载到整数寄存器时直接用目标寄存器计算地址，避免覆盖已加载到另一个保留寄存器的溢出值
+                        // 其余情况查找一个不等于 val 的保留寄存器
+                        let preserved_reg = match PRESERVED.iter().copied().find(|r| *r == val.as_str()) {
+                            Some(reg) if is_load => reg,
+                            _ => PRESERVED.iter().map(|r| *r).find(|r| *r != val.as_str()).unwrap(),
+                        };
                         if base == "sp" {
                             // 获取栈偏移并转换为字符串
                             let stack_pos =  self.stack.get_position(offset.as_str()).to_string();
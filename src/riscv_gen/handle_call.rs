@@ -321,7 +321,7 @@ impl AsmBlock {
                 context.stack.push_normal(stored_pos.as_str(), 8);
                 self.instrs.insert(position, AsmInstruction::make_instr(
                     AsmInstructionType::Load,
-                    vec!(FUNC_ARG[context.int_cnt], stored_pos.as_str()),
+                    vec!(FUNC_ARG[context.int_cnt], "sp", stored_pos.as_str()),
                     Some(PTR_WIDTH),
                     vec!()
                 ));
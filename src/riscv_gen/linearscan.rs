@@ -161,11 +161,11 @@ impl Interval {
         }
     }
 
+    // 物理寄存器被定义，闭合由push_right打开的区间；若没有打开的区间，则只占据定义处
     pub fn push_left(&mut self, left: usize) {
-        if !self.intervals.is_empty() {
-            self.intervals.last_mut().unwrap().left = left;
-        } else {
-            self.set_first_left(left);
+        match self.intervals.last_mut() {
+            Some(last) if last.left == 0 && last.right >= left => last.left = left,
+            _ => self.intervals.push(IntervalRange { left, right: left }),
         }
     }
 
@@ -186,8 +186,21 @@ impl Interval {
         self.intervals.push(push_interval);
     }
     
+    // 排序并合并重叠的区间，保证最后一个区间的右端点最大
     pub fn sort_interval(&mut self) {
         self.intervals.sort_by(|a, b| a.cmp(b));
+        let mut merged: Vec<IntervalRange> = Vec::with_capacity(self.intervals.len());
+        for range in self.intervals.drain(..) {
+            match merged.last_mut() {
+                Some(last) if last.judge_overlap(&range) => last.right = std::cmp::max(last.right, range.right),
+                _ => merged.push(range),
+            }
+        }
+        self.intervals = merged;
+    }
+
+    pub fn is_overlap(&self, other: &Self) -> bool {
+        self.intervals.iter().any(|a| other.intervals.iter().any(|b| a.judge_overlap(b)))
     }
 
     pub fn is_inactive(&self, now: &Self) -> bool {
@@ -263,20 +276,29 @@ impl LiveInterval<'_> {
     }
 
     // 计算全局（块间）的虚拟/物理寄存器的活跃区间
+    // 存在循环时，变量沿回边活跃，需要迭代到不动点
     pub fn cul_global_liveinterval(&mut self) {
-        for &idx in self.depth_first_order.iter().rev() {
-            let block = self.target.blocks.get(idx).unwrap();
-            let mut live_out = BTreeSet::new();             //当前块的活跃出口变量
-            
-            for sur in &block.successor {
-                let sur_id = self.block_idx.get(sur.as_str()).unwrap();
-                let sur_info = self.block_info.get(sur_id).unwrap();
-                live_out.extend(&sur_info.live_in);
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &idx in self.depth_first_order.iter().rev() {
+                let block = self.target.blocks.get(idx).unwrap();
+                let mut live_out = BTreeSet::new();             //当前块的活跃出口变量
+
+                for sur in &block.successor {
+                    let sur_id = self.block_idx.get(sur.as_str()).unwrap();
+                    let sur_info = self.block_info.get(sur_id).unwrap();
+                    live_out.extend(&sur_info.live_in);
+                }
+                let info = self.block_info.get_mut(&idx).unwrap();
+                // 计算出口和入口的活跃变量
+                let live_in = &(&live_out - &info.live_kill) | &info.live_gen;
+                if live_in != info.live_in || live_out != info.live_out {
+                    changed = true;
+                }
+                info.live_out = live_out;
+                info.live_in = live_in;
             }
-            let info = self.block_info.get_mut(&idx).unwrap();
-            // 计算出口和入口的活跃变量
-            info.live_out = live_out;
-            info.live_in = &(&info.live_out - &info.live_kill) | &info.live_gen;
         }
     }
 
@@ -354,6 +376,7 @@ pub struct LinearScan {
     pub activenodes: Vec<ActiveNode>,
     pub inactivemap: HashMap<String, &'static str>,
     pub spilled: HashSet<String>,
+    pub phy_interval: HashMap<&'static str, Interval>,
 }
 
 impl Ord for ActiveNode {
@@ -430,6 +453,7 @@ impl LinearScan {
         // 找到满足条件的节点中，活跃区间最大的节点
         if let Some((max_idx, max_interval)) = self.activenodes.iter().enumerate().filter(
             |(_, node)| Self::regtype_filter(node.phy.as_ref().unwrap(), &regty)
+                && self.phy_interval.get(node.phy.unwrap()).map_or(true, |phy| !phy.is_overlap(&interval))
         ).max_by(
             |(_, node0), (_, node1)| node0.cmp(node1)
         ).map(
@@ -458,7 +482,8 @@ impl LinearScan {
             reg_res: RegisterResource::new(), 
             activenodes: vec!(), 
             inactivemap: HashMap::new(), 
-            spilled: HashSet::new()
+            spilled: HashSet::new(),
+            phy_interval: HashMap::new(),
         }
     }
     
@@ -471,7 +496,11 @@ impl LinearScan {
         liveinterval.cul_local_liveinterval();
         liveinterval.cul_global_liveinterval();
         liveinterval.build_liveinterval();
-        let (vir_interval, _) = liveinterval.dump_intervals();
+        let (vir_interval, phy_interval) = liveinterval.dump_intervals();
+        // 物理寄存器(如函数入口处的参数寄存器)活跃时不能分配给与之冲突的虚拟寄存器
+        self.phy_interval = phy_interval.into_iter().filter_map(
+            |(phy, interval)| ALL_REGS.get(phy).map(|phy| (*phy, interval))
+        ).collect();
 
         // 将虚拟寄存器和活跃区间存入self.var_interval
         self.var_interval = vir_interval.into_iter().map(
@@ -506,7 +535,11 @@ impl LinearScan {
                 // 根据活跃间隔判断该寄存器是否是需要保存的
                 func.is_reg_saved(&interval, vir.as_str())
             );
-            let reg = self.reg_res.get_register(&regty, |reg| Self::regtype_filter(reg, &regty));
+            let phy_interval = &self.phy_interval;
+            let reg = self.reg_res.get_register(&regty, |reg| {
+                Self::regtype_filter(reg, &regty)
+                    && phy_interval.get(reg).map_or(true, |phy| !phy.is_overlap(&interval))
+            });
             if let Some(phy) = reg {
                 self.activenodes.push(ActiveNode::new(vir, interval, phy));
             } else {
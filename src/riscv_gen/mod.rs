@@ -15,12 +15,14 @@ pub mod restore_registers;
 pub mod register_alloc;
 pub mod register_type;
 pub mod handle_call;
+pub mod tail_call;
 
 use linearscan::*;
+use crate::options::OptLevel;
 use crate::structures::llvm_struct::*;
 use crate::structures::riscv_struct::*;
 
-pub fn generate_asm(program: &LLVMProgram) -> RiscV {
+pub fn generate_asm(program: &LLVMProgram, opt_level: OptLevel) -> RiscV {
     let mut asm = RiscV::new();
     //使用LLVM IR来进行数据段的构造，代码段的构造以及指令选择
     program.push_datasection(&mut asm);
@@ -36,6 +38,10 @@ pub fn generate_asm(program: &LLVMProgram) -> RiscV {
     asm.deterministic_stack();
     //分配和释放栈空间
     asm.stack_alloc_free();
+    //直接返回调用结果的块改为拆除栈帧后尾调用，需要在栈上的地址映射之前进行
+    if opt_level >= OptLevel::O1 {
+        asm.tail_calls();
+    }
     asm.map_stack_address();
     asm
 }
@@ -14,22 +14,28 @@ impl FuncDef {
             let stack = &mut func.stack;
             let label_type = &mut func.label_type;
             //局部变量全都存入栈中
-            self.local_vars.iter().for_each(|local_var|{
-                if let Instruction::Alloca{res, ty, len: _} = &local_var.ins {
-                    label_type.insert(res.to_string(), ty.width.clone());
+            self.local_vars.iter().for_each(|&local_var|{
+                if let Instruction::Alloca{ty, len: _} = self.instr(local_var) {
+                    let res = self.slot_label(local_var);
+                    label_type.insert(res.clone(), ty.width.clone());
                     if let SymbolWidth::Arr{tar:_, dims} = &ty.width {
                         //如果是指针，把长度设为8
                         if dims[0] == -1 {
-                            stack.push_normal(res, 8);
+                            stack.push_normal(&res, 8);
                         } else {
                             let len = dims.iter().fold(4, |acc, x| acc * x);
-                            stack.push_normal(res, len as isize);
+                            stack.push_normal(&res, len as isize);
                         }
                     } else {
-                        stack.push_normal(res,  4);
+                        stack.push_normal(&res,  4);
                     }
                 }
             });
         }
     }
+
+    /// 局部变量在栈上的标签，未命名的局部变量以编号命名
+    pub fn slot_label(&self, id: ValueId) -> String {
+        self.values[id.0].name.clone().unwrap_or_else(|| format!("%slot.{}", id.0))
+    }
 }
\ No newline at end of file
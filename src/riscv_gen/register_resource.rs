@@ -193,6 +193,7 @@ impl AsmInstruction {
             }
             AsmInstruction::Jump(_) => (None, vec!()),
             AsmInstruction::Ret() => (None, vec!()),
+            AsmInstruction::Tail(_) => (None, vec!()),
             AsmInstruction::Call(ret, _, params, _) => (Some(ret.as_str()), params.iter().map(|s| s.as_str()).collect()),
         }
     }
@@ -105,7 +105,7 @@ impl AsmInstruction {
             AsmInstruction::Xori(tri) | AsmInstruction::Slt(tri) | AsmInstruction::Slti(tri) |
             AsmInstruction::Flt(tri) | AsmInstruction::Fle(tri) | AsmInstruction::Feq(tri) |
             AsmInstruction::Fadd(tri) | AsmInstruction::Fsub(tri) | AsmInstruction::Fmul(tri) | AsmInstruction::Fdiv(tri) |
-            AsmInstruction::Sgt(tri) | AsmInstruction::Branch(CondTriInstr{cond: _, tri}) => {
+            AsmInstruction::Sgt(tri) => {
                 match tri {
                     TriInstr{width: _, dst, op1, op2} => {
                         if filter_type(ty) {
@@ -114,6 +114,12 @@ impl AsmInstruction {
                     }
                 }
             },
+            // 分支指令的dst为比较的寄存器，是输入而不是输出
+            AsmInstruction::Branch(CondTriInstr{cond: _, tri: TriInstr{width: _, dst, op1, op2}}) => {
+                if filter_type(ty) {
+                    map_labels(None, vec!(dst, op1, op2));
+                }
+            },
             AsmInstruction::Store(mem, _) => {
                 match mem {
                     MemInstr{width: _, val, base, offset} => {
@@ -147,3 +153,27 @@ impl AsmInstruction {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spilled_branch_operand_is_loaded_before_branch() {
+        let mut func = AsmFunc::new("f", SymbolWidth::Void);
+        let mut block = AsmBlock::new("entry", 0, 0);
+        block.instrs.push(AsmInstruction::make_instr(AsmInstructionType::Branch, vec!("ne", "%cond", "zero", ".L1"), None, vec!()));
+        func.blocks.push(block);
+        func.label_type.insert(String::from("%cond"), SymbolWidth::I32);
+
+        func.rewrite_spilled(&HashSet::from([String::from("%cond")]));
+
+        let instrs = &func.blocks[0].instrs;
+        assert_eq!(instrs.len(), 2);
+        assert!(matches!(instrs[0], AsmInstruction::Load(..)));
+        match &instrs[1] {
+            AsmInstruction::Branch(CondTriInstr{cond: _, tri}) => assert_ne!(tri.dst, "%cond"),
+            instr => panic!("expected branch, found {:?}", instr),
+        }
+    }
+}
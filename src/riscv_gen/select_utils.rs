@@ -3,6 +3,7 @@ use crate::utils::float::*;
 use crate::utils::check::*;
 use crate::structures::riscv_struct::*;
 use crate::structures::symbol::*;
+use crate::riscv_gen::asm_select::PhiCopy;
 
 /// 向代码块列表中添加一个新的代码块，使用给定的代码块标签和深度
 pub fn push_block(block_label: &str, depth: usize, func: &mut AsmFunc) {
@@ -49,6 +50,42 @@ pub fn check_float_op(select_cnt: &mut usize, op: &str, func: &mut AsmFunc) -> S
     }
 }
 
+/// 生成一条复制指令，源操作数可以是立即数
+pub fn gen_copy(dst: &str, src: &str, width: &SymbolWidth, select_cnt: &mut usize, func: &mut AsmFunc) {
+    if *width == SymbolWidth::Float {
+        let src = check_float_op(select_cnt, src, func);
+        gen_instr(AsmInstructionType::Fmv, vec!(dst, &src), None, vec!(SymbolWidth::Float, SymbolWidth::Float), func);
+    } else if is_immediate(src) {
+        gen_instr(AsmInstructionType::Li, vec!(dst, src), None, vec![], func);
+    } else {
+        gen_instr(AsmInstructionType::Mv, vec!(dst, src), None, vec![], func);
+    }
+}
+
+/// 将并行复制<目的, 源, 类型>展开为顺序的复制指令<br>
+/// 每次选出目的不再被其他复制读取的复制先执行，若只剩下环，则用临时寄存器保存其中一个目的的旧值来打破环
+pub fn gen_parallel_copy(copies: &[PhiCopy], select_cnt: &mut usize, func: &mut AsmFunc) {
+    let mut pending: Vec<PhiCopy> = copies.iter()
+        .filter(|(dst, src, _)| dst != src)
+        .cloned()
+        .collect();
+    while !pending.is_empty() {
+        let ready = pending.iter().position(|(dst, _, _)| pending.iter().all(|(_, src, _)| src != dst));
+        match ready {
+            Some(idx) => {
+                let (dst, src, width) = pending.remove(idx);
+                gen_copy(&dst, &src, &width, select_cnt, func);
+            },
+            None => {
+                let (dst, _, width) = pending[0].clone();
+                let temp = pop_temp_label(select_cnt, &width, func);
+                gen_copy(&temp, &dst, &width, select_cnt, func);
+                pending.iter_mut().filter(|(_, src, _)| *src == dst).for_each(|(_, src, _)| *src = temp.clone());
+            },
+        }
+    }
+}
+
 pub fn pop_temp_label(cnt: &mut usize, ty: &SymbolWidth, func: &mut AsmFunc) -> String {
     let res = format!("%temp.{}", cnt);
     insert_label_type(&res, ty, func);
@@ -118,6 +155,7 @@ impl AsmInstruction {
             AsmInstructionType::Branch => AsmInstruction::Branch(CondTriInstr::new(str_vec[0], None, str_vec[1], str_vec[2], str_vec[3])),
             AsmInstructionType::Jump => AsmInstruction::Jump(String::from(str_vec[0])),
             AsmInstructionType::Ret => AsmInstruction::Ret(),
+            AsmInstructionType::Tail => AsmInstruction::Tail(String::from(str_vec[0])),
             AsmInstructionType::Call => {
                 AsmInstruction::Call(
                     String::from(str_vec[0]),
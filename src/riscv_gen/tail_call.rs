@@ -0,0 +1,183 @@
+use std::collections::BTreeSet;
+use crate::structures::llvm_struct::*;
+use crate::structures::riscv_struct::*;
+use crate::structures::riscv_regs::*;
+use crate::structures::symbol::*;
+
+impl FuncDef {
+    /// 基本块以调用结束且紧接着返回调用的结果时，可以改为尾调用<br>
+    /// 参数必须都能放进参数寄存器，且不能指向本函数的栈帧，因为跳转前栈帧已经释放
+    pub fn is_tail_call(&self, bb: BlockId) -> bool {
+        let block = self.block(bb);
+        let (call, ter) = match (block.nor_ins.last(), block.ter_ins) {
+            (Some(&call), Some(ter)) => (call, ter),
+            _ => return false,
+        };
+        let params = match self.instr(call) {
+            Instruction::Call(name, _, params) if name != "@llvm.memset.p0i8.i64" => params,
+            _ => return false,
+        };
+        let returns_call = match self.instr(ter) {
+            Instruction::Ret(_, None) => true,
+            Instruction::Ret(_, Some(value)) => *value == Operand::Value(call),
+            _ => false,
+        };
+        let float_cnt = params.iter().filter(|(_, ty)| ty.width == SymbolWidth::Float).count();
+        returns_call
+            && float_cnt <= FLOAT_FUNC_ARG.len()
+            && params.len() - float_cnt <= FUNC_ARG.len()
+            && params.iter().all(|(arg, _)| !self.points_to_local(arg))
+    }
+}
+
+impl RiscV {
+    pub fn tail_calls(&mut self) {
+        self.text.funcs.iter_mut().for_each(|func| func.tail_calls());
+    }
+}
+
+impl AsmFunc {
+    /// 将标记为尾调用的块改写为：参数装载、恢复保存的寄存器、释放栈空间，最后tail跳转到被调用函数<br>
+    /// 原先调用之后取回返回值、再放入返回值寄存器的指令都不再需要
+    pub fn tail_calls(&mut self) {
+        for block in self.blocks.iter_mut().filter(|block| block.tail_call) {
+            let ret = block.instrs.len() - 1;
+            let call = block.instrs.iter().rposition(|instr| matches!(instr, AsmInstruction::Call(..))).unwrap();
+            let func_name = match &block.instrs[call] {
+                AsmInstruction::Call(_, func_name, _, _) => func_name.clone(),
+                _ => unreachable!(),
+            };
+            let start = epilogue_start(&block.instrs, &self.used_saved);
+            assert!(start > call, "{}: 尾调用块的出口没有在调用之后", block.label);
+            let epilogue: Vec<AsmInstruction> = block.instrs.drain(start..ret).collect();
+            block.instrs.truncate(call);
+            block.instrs.extend(epilogue);
+            block.instrs.push(AsmInstruction::make_instr(AsmInstructionType::Tail, vec!(&func_name), None, vec!()));
+        }
+    }
+}
+
+/// 以ret结束的块中，出口处恢复寄存器与释放栈空间的指令的起始位置<br>
+/// restore_registers在ret之前插入以寄存器名占位偏移的ld，stack_alloc_free随后在ret之前插入释放栈空间的指令
+fn epilogue_start(instrs: &[AsmInstruction], used_saved: &BTreeSet<&'static str>) -> usize {
+    let is_sp_adjust = |tri: &TriInstr| tri.dst == "sp" && tri.op1 == "sp";
+    let ret = instrs.len() - 1;
+    // 栈帧超出12位立即数的范围时先将大小装入临时寄存器
+    let mut start = match &instrs[..ret] {
+        [.., AsmInstruction::Li(li), AsmInstruction::Add(add)] if is_sp_adjust(add) && add.op2 == li.dst => ret - 2,
+        [.., AsmInstruction::Addi(addi)] if is_sp_adjust(addi) => ret - 1,
+        _ => ret,
+    };
+    let mut restored = BTreeSet::new();
+    while let Some(AsmInstruction::Load(mem, _)) = start.checked_sub(1).map(|idx| &instrs[idx]) {
+        let saved = used_saved.iter().find(|reg| **reg == mem.val);
+        match saved {
+            Some(reg) if mem.base == "sp" && mem.offset == mem.val && restored.insert(*reg) => start -= 1,
+            _ => break,
+        }
+    }
+    assert_eq!(restored.len(), used_saved.len(), "出口处恢复的寄存器与保存的不一致");
+    start
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llvm_gen::read_text::read_llvm;
+
+    #[test]
+    fn tail_call_blocks() {
+        let input = "\
+define i32 @g(i32 noundef %x) {
+entry:
+  ret i32 %x
+}
+
+define void @v(i32* noundef %p) {
+entry:
+  ret void
+}
+
+define i32 @f(i32 noundef %n, i32 noundef %acc) {
+entry:
+  %c = icmp sgt i32 %n, 0
+  br i1 %c, label %acc_call, label %not_tail
+
+acc_call:
+  %acc1 = add i32 %acc, %n
+  %r = call i32 @g(i32 noundef %acc1)
+  ret i32 %r
+
+not_tail:
+  %r1 = call i32 @g(i32 noundef %n)
+  %m = add i32 %r1, 1
+  ret i32 %m
+}
+
+define void @w(i32* noundef %p) {
+entry:
+  %a = alloca i32, align 4
+  %c = icmp eq i32* %p, null
+  br i1 %c, label %void_call, label %local_arg
+
+void_call:
+  call void @v(i32* noundef %p)
+  ret void
+
+local_arg:
+  call void @v(i32* noundef %a)
+  ret void
+}";
+        let llvm = read_llvm(input).unwrap();
+        let tail_calls: Vec<(String, bool)> = llvm.func_def.iter()
+            .flat_map(|func| func.layout.iter().map(move |&bb| (func.block(bb).block_label.clone(), func.is_tail_call(bb))))
+            .filter(|(label, _)| label != "entry")
+            .collect();
+        // 调用结果还要参与运算的不是尾调用，参数指向本函数栈帧的也不能改为尾调用
+        assert_eq!(tail_calls, [
+            (String::from("acc_call"), true),
+            (String::from("not_tail"), false),
+            (String::from("void_call"), true),
+            (String::from("local_arg"), false),
+        ]);
+    }
+
+    fn instr(ty: AsmInstructionType, operands: &[&str], width: Option<isize>) -> AsmInstruction {
+        AsmInstruction::make_instr(ty, operands.to_vec(), width, vec!())
+    }
+
+    #[test]
+    fn keep_epilogue_before_tail() {
+        use AsmInstructionType::*;
+        let mut func = AsmFunc::new("f", SymbolWidth::I32);
+        func.used_saved.extend(["s1", "s2"]);
+        let mut block = AsmBlock::new("f.acc_call", 0, 0);
+        block.tail_call = true;
+        block.instrs = vec!(
+            instr(Mv, &["a0", "s1"], None),
+            instr(Call, &["", "g", "a0"], None),
+            instr(Mv, &["a5", "a0"], None),
+            instr(Mv, &["a0", "a5"], None),
+            instr(Load, &["s2", "sp", "s2"], Some(8)),
+            instr(Load, &["s1", "sp", "s1"], Some(8)),
+            // 栈帧较大时释放栈空间需要两条指令
+            instr(Li, &["t0", "4096"], None),
+            instr(Add, &["sp", "sp", "t0"], None),
+            instr(Ret, &[], None),
+        );
+        func.blocks.push(block);
+
+        func.tail_calls();
+
+        let texts: Vec<String> = func.blocks[0].instrs.iter().map(|instr| instr.instr_text()).collect();
+        let expected: Vec<String> = vec!(
+            instr(Mv, &["a0", "s1"], None),
+            instr(Load, &["s2", "sp", "s2"], Some(8)),
+            instr(Load, &["s1", "sp", "s1"], Some(8)),
+            instr(Li, &["t0", "4096"], None),
+            instr(Add, &["sp", "sp", "t0"], None),
+            instr(Tail, &["g"], None),
+        ).iter().map(|instr| instr.instr_text()).collect();
+        assert_eq!(texts, expected);
+    }
+}
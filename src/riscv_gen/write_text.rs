@@ -84,6 +84,13 @@ impl AsmBlock {
 }
 
 impl AsmInstruction {
+    /// 单条指令的文本，不含缩进与换行，用于报错
+    pub fn instr_text(&self) -> String {
+        let mut text = vec![];
+        self.writetext(&mut text);
+        String::from_utf8(text).unwrap().trim().replace('\t', " ")
+    }
+
     fn writetext(&self, output: &mut impl io::Write) {
         match self {
             AsmInstruction::Li(bin) => {
@@ -237,6 +244,9 @@ impl AsmInstruction {
                     write!(output, "\tcall\t{}\n",func_name).unwrap();
                 }
             },
+            AsmInstruction::Tail(func_name) => {
+                writeln!(output, "\ttail\t{}", func_name).unwrap();
+            },
         }
     }
 }
@@ -1,10 +1,15 @@
 pub mod remove_ld;
 pub mod remove_mv;
+use crate::options::OptLevel;
 use crate::structures::riscv_struct::*;
 
 impl RiscV {
-    pub fn optimise_riscv(&mut self) {
-        self.remove_ld();
-        self.remove_mv();
+    pub fn optimise_riscv(&mut self, opt_level: OptLevel) {
+        if opt_level >= OptLevel::O2 {
+            self.remove_ld();
+        }
+        if opt_level >= OptLevel::O1 {
+            self.remove_mv();
+        }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,130 @@
+use crate::ast::Number;
+use crate::structures::symbol::*;
+use crate::utils::float::{format_double, parse_float};
+
+/// 编译期常量的值，与llvm_gen中常量折叠的语义保持一致
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConstVal {
+    Int(i32),
+    Float(f32),
+}
+
+impl ConstVal {
+    pub fn from_number(num: &Number) -> Self {
+        match num {
+            Number::Int(num) => ConstVal::Int(*num),
+            Number::Float(num) => ConstVal::Float(parse_float(num)),
+        }
+    }
+
+    /// 从符号表中常量的值恢复
+    pub fn from_symbol_val(val: &SymbolVal) -> Option<Self> {
+        match val {
+            SymbolVal::I32(val) => val.parse().ok().map(ConstVal::Int),
+            SymbolVal::Float(val) => Some(ConstVal::Float(parse_float(val))),
+            _ => None,
+        }
+    }
+
+    /// 转换为符号表中的表示，浮点数使用IEEE754 double的十六进制形式
+    pub fn to_symbol_val(self) -> SymbolVal {
+        match self {
+            ConstVal::Int(num) => SymbolVal::I32(num.to_string()),
+            ConstVal::Float(num) => SymbolVal::Float(format_double(num)),
+        }
+    }
+
+    /// 按声明的类型做隐式类型转换
+    pub fn convert_to(self, ty: &SymbolType) -> Self {
+        match (self, &ty.width) {
+            (ConstVal::Int(num), SymbolWidth::Float) => ConstVal::Float(num as f32),
+            (ConstVal::Float(num), SymbolWidth::I32) => ConstVal::Int(num as i32),
+            _ => self,
+        }
+    }
+
+    pub fn as_float(self) -> f32 {
+        match self {
+            ConstVal::Int(num) => num as f32,
+            ConstVal::Float(num) => num,
+        }
+    }
+
+    pub fn is_zero(self) -> bool {
+        match self {
+            ConstVal::Int(num) => num == 0,
+            ConstVal::Float(num) => num == 0.0,
+        }
+    }
+
+    /// 算术运算，整数除零时返回错误信息
+    pub fn arithmetic(self, other: Self, op: &str) -> Result<Self, &'static str> {
+        match (self, other) {
+            (ConstVal::Int(num1), ConstVal::Int(num2)) => {
+                if (op == "/" || op == "%") && num2 == 0 {
+                    return Err("常量表达式中出现除以零");
+                }
+                Ok(ConstVal::Int(match op {
+                    "+" => num1.wrapping_add(num2),
+                    "-" => num1.wrapping_sub(num2),
+                    "*" => num1.wrapping_mul(num2),
+                    "/" => num1.wrapping_div(num2),
+                    _ => num1.wrapping_rem(num2),
+                }))
+            }
+            _ => {
+                let num1 = self.as_float();
+                let num2 = other.as_float();
+                Ok(ConstVal::Float(match op {
+                    "+" => num1 + num2,
+                    "-" => num1 - num2,
+                    "*" => num1 * num2,
+                    "/" => num1 / num2,
+                    _ => num1 % num2,
+                }))
+            }
+        }
+    }
+
+    /// 比较与逻辑运算，结果为0或1
+    pub fn logic(self, other: Self, op: &str) -> Self {
+        let res = match (self, other) {
+            (ConstVal::Int(num1), ConstVal::Int(num2)) => match op {
+                "==" => num1 == num2,
+                "!=" => num1 != num2,
+                "<" => num1 < num2,
+                ">" => num1 > num2,
+                "<=" => num1 <= num2,
+                ">=" => num1 >= num2,
+                "&&" => num1 != 0 && num2 != 0,
+                _ => num1 != 0 || num2 != 0,
+            },
+            _ => {
+                let num1 = self.as_float();
+                let num2 = other.as_float();
+                match op {
+                    "==" => num1 == num2,
+                    "!=" => num1 != num2,
+                    "<" => num1 < num2,
+                    ">" => num1 > num2,
+                    "<=" => num1 <= num2,
+                    ">=" => num1 >= num2,
+                    "&&" => num1 != 0.0 && num2 != 0.0,
+                    _ => num1 != 0.0 || num2 != 0.0,
+                }
+            }
+        };
+        ConstVal::Int(res as i32)
+    }
+
+    pub fn negate(self) -> Self {
+        match self {
+            ConstVal::Int(num) => ConstVal::Int(num.wrapping_neg()),
+            ConstVal::Float(num) => ConstVal::Float(-num),
+        }
+    }
+
+    pub fn logical_not(self) -> Self {
+        ConstVal::Int(self.is_zero() as i32)
+    }
+}
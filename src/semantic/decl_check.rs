@@ -0,0 +1,173 @@
+use crate::ast::*;
+use crate::diagnostic::Span;
+use crate::structures::symbol::*;
+use crate::semantic::Analyse;
+use crate::semantic::env::Env;
+use crate::semantic::const_eval::ConstVal;
+use crate::semantic::exp_check::*;
+
+/// 计算数组各维的长度，维度必须是正整数常量，出错时以1代替
+pub fn const_dims<'a>(env: &mut Env, dims: impl Iterator<Item = &'a mut Exp>) -> Vec<i32> {
+    dims.map(|dim| {
+        let info = dim.analyse(env);
+        match info.val {
+            Some(ConstVal::Int(len)) if len > 0 => len,
+            Some(ConstVal::Int(_)) => {
+                env.error(Some(dim.span), "数组维度必须大于零");
+                1
+            }
+            _ => {
+                if !info.poisoned {
+                    env.error(Some(dim.span), "数组维度必须是整型常量表达式");
+                }
+                1
+            }
+        }
+    }).collect()
+}
+
+/// 检查数组元素或标量的初始化表达式，返回转换为ty后的常量值
+fn check_init_exp(env: &mut Env, exp: &mut Exp, ty: &SymbolType, need_const: bool) -> Option<ConstVal> {
+    let info = exp.analyse(env);
+    if !check_value(env, &info) {
+        return None;
+    }
+    if need_const && info.val.is_none() {
+        env.error(Some(exp.span), "初始化值必须是常量表达式");
+    }
+    info.val.map(|val| val.convert_to(ty))
+}
+
+/// 检查数组的初始化列表，嵌套的规则与InitVal::generate中的填充方式一致，返回这一层占用的元素个数
+fn check_init_list(
+    env: &mut Env,
+    items: &mut [InitVal],
+    ty: &SymbolType,
+    dims: &Vec<i32>,
+    depth: usize,
+    need_const: bool,
+    span: Span,
+) -> i32 {
+    let sub_size: i32 = dims.iter().skip(depth).product();
+    let mut fill = 0;
+    for item in items.iter_mut() {
+        if fill >= sub_size {
+            env.error(Some(span), "初始化值的个数超过了数组的大小");
+            return sub_size;
+        }
+        match item {
+            InitVal::Exp(exp) => {
+                check_init_exp(env, exp, ty, need_const);
+                fill += 1;
+            }
+            InitVal::Arr(arr) => {
+                // 嵌套的初始化列表之前已经初始化的部分必须和最低维对齐
+                if fill % dims.last().unwrap() != 0 {
+                    env.error(Some(span), "嵌套的初始化列表没有与数组的维度对齐");
+                    return sub_size;
+                }
+                let next_depth = InitVal::init_align(dims, fill, depth);
+                fill += check_init_list(env, arr, ty, dims, next_depth, need_const, span);
+            }
+        }
+    }
+    sub_size
+}
+
+/// 检查一个定义的初始化值，标量返回其常量值
+fn check_init(
+    env: &mut Env,
+    init: &mut InitVal,
+    ty: &SymbolType,
+    dims: &Vec<i32>,
+    need_const: bool,
+    span: Span,
+) -> Option<ConstVal> {
+    match init {
+        InitVal::Exp(exp) if dims.is_empty() => check_init_exp(env, exp, ty, need_const),
+        InitVal::Exp(exp) => {
+            env.error(Some(exp.span), "数组必须使用初始化列表进行初始化");
+            None
+        }
+        InitVal::Arr(_) if dims.is_empty() => {
+            env.error(Some(span), "标量不能使用初始化列表进行初始化");
+            None
+        }
+        InitVal::Arr(items) => {
+            check_init_list(env, items, ty, dims, 0, need_const, span);
+            None
+        }
+    }
+}
+
+/// 定义的类型，数组的const标记在数组类型上，元素类型保持非const
+fn def_type(ty: &SymbolType, dims: Vec<i32>, is_const: bool) -> SymbolType {
+    if dims.is_empty() {
+        SymbolType::new(ty.width.clone(), is_const)
+    } else {
+        SymbolType::new(
+            SymbolWidth::Arr{tar: Box::new(SymbolType::new(ty.width.clone(), false)), dims},
+            is_const
+        )
+    }
+}
+
+impl Analyse for Decl {
+    type Out = ();
+
+    fn analyse(&mut self, env: &mut Env) -> Self::Out {
+        match self {
+            Decl::ConstDecl(decl) => decl.analyse(env),
+            Decl::VarDecl(decl) => decl.analyse(env),
+        }
+    }
+}
+
+/// 常量声明，初始化值必须全部是常量
+impl Analyse for ConstDecl {
+    type Out = ();
+
+    fn analyse(&mut self, env: &mut Env) -> Self::Out {
+        let ty = self.ty.ty.clone();
+        for def in self.defs.iter_mut() {
+            if ty.width == SymbolWidth::Void {
+                env.error(Some(def.span), format!("常量`{}`不能声明为void类型", def.id));
+                continue;
+            }
+            let dims = const_dims(env, def.dims.iter_mut());
+            let val = check_init(env, &mut def.init, &ty, &dims, true, def.span);
+            let poisoned = dims.is_empty() && val.is_none();
+            let value = if dims.is_empty() {
+                // 出错时以0代替，并标记常量有错误，避免使用处重复报错
+                val.unwrap_or(ConstVal::Int(0).convert_to(&ty)).to_symbol_val()
+            } else {
+                SymbolVal::Void
+            };
+            env.define(&def.id, def.span, def_type(&ty, dims, true), value);
+            if poisoned {
+                env.poison(&def.id, def.span);
+            }
+        }
+    }
+}
+
+/// 变量声明，全局变量的初始化值必须是常量
+impl Analyse for VarDecl {
+    type Out = ();
+
+    fn analyse(&mut self, env: &mut Env) -> Self::Out {
+        let ty = self.ty.ty.clone();
+        let need_const = env.is_global();
+        for def in self.defs.iter_mut() {
+            if ty.width == SymbolWidth::Void {
+                env.error(Some(def.span), format!("变量`{}`不能声明为void类型", def.id));
+                continue;
+            }
+            let dims = const_dims(env, def.dims.iter_mut());
+            if let Some(init) = &mut def.init {
+                check_init(env, init, &ty, &dims, need_const, def.span);
+            }
+            env.define(&def.id, def.span, def_type(&ty, dims, false), SymbolVal::Void);
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+use crate::ast::SymbolRef;
+use crate::diagnostic::{Diagnostic, Span};
+use crate::structures::symbol::*;
+
+/// 语义分析的环境：作用域栈、当前函数和循环嵌套层数，以及收集到的错误
+pub struct Env {
+    scopes: Vec<HashMap<String, SymbolRef>>,
+    // 当前所在函数的返回值类型，全局作用域为None
+    pub func_type: Option<SymbolType>,
+    // 当前所在循环的嵌套层数
+    pub loop_depth: usize,
+    // 正在分析的表达式位置，用于没有自身位置的子表达式报错
+    exp_spans: Vec<Span>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Default for Env {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Env {
+    /// 创建全局作用域，并加入sylib的库函数
+    pub fn new() -> Self {
+        let mut env = Env {
+            scopes: vec!(HashMap::new()),
+            func_type: None,
+            loop_depth: 0,
+            exp_spans: vec!(),
+            diagnostics: vec!(),
+        };
+        for (name, ret_ty, param_tys) in sylib_functions() {
+            env.scopes[0].insert(String::from(name), SymbolRef {
+                ty: ret_ty.clone(),
+                value: SymbolVal::Func(ret_ty, param_tys),
+                def: None,
+                poisoned: false,
+            });
+        }
+        env
+    }
+
+    pub fn is_global(&self) -> bool {
+        self.scopes.len() == 1
+    }
+
+    pub fn push_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    pub fn pop_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    /// 在当前作用域中定义符号，重复定义时报错并保留原来的定义
+    pub fn define(&mut self, ident: &str, span: Span, ty: SymbolType, value: SymbolVal) {
+        let curr_scope = self.scopes.last_mut().unwrap();
+        if let Some(prev) = curr_scope.get(ident) {
+            let mut diagnostic = Diagnostic::error(format!("重复定义`{}`", ident))
+                .with_span(span)
+                .with_label("在当前作用域中已经定义过");
+            if prev.def.is_none() {
+                diagnostic = diagnostic.with_note(format!("`{}`是sylib中的库函数", ident));
+            }
+            self.diagnostics.push(diagnostic);
+            return;
+        }
+        curr_scope.insert(String::from(ident), SymbolRef { ty, value, def: Some(span), poisoned: false });
+    }
+
+    /// 将当前作用域中在span处定义的符号标记为有错误，重复定义而没有加入作用域时不做处理
+    pub fn poison(&mut self, ident: &str, span: Span) {
+        if let Some(symbol) = self.scopes.last_mut().unwrap().get_mut(ident) {
+            if symbol.def == Some(span) {
+                symbol.poisoned = true;
+            }
+        }
+    }
+
+    /// 由内向外查找符号
+    pub fn lookup(&self, ident: &str) -> Option<&SymbolRef> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(ident))
+    }
+
+    pub fn enter_exp(&mut self, span: Span) {
+        self.exp_spans.push(span);
+    }
+
+    pub fn exit_exp(&mut self) {
+        self.exp_spans.pop();
+    }
+
+    /// 记录一个错误，span为None时使用所在表达式的位置
+    pub fn error(&mut self, span: Option<Span>, message: impl Into<String>) {
+        let mut diagnostic = Diagnostic::error(message);
+        if let Some(span) = span.or_else(|| self.exp_spans.last().copied()) {
+            diagnostic = diagnostic.with_span(span);
+        }
+        self.diagnostics.push(diagnostic);
+    }
+}
@@ -0,0 +1,426 @@
+use std::cmp::Ordering;
+use crate::ast::*;
+use crate::diagnostic::Span;
+use crate::structures::symbol::*;
+use crate::semantic::Analyse;
+use crate::semantic::env::Env;
+use crate::semantic::const_eval::ConstVal;
+
+/// 表达式的分析结果
+#[derive(Debug, Clone)]
+pub struct ExpInfo {
+    // 表达式的类型，编译期常量的is_const为true
+    pub ty: SymbolType,
+    // 编译期常量的值
+    pub val: Option<ConstVal>,
+    // 表达式所在位置，字面量没有记录位置
+    pub span: Option<Span>,
+    // 表达式中已经报过错，不再重复检查
+    pub poisoned: bool,
+}
+
+impl ExpInfo {
+    fn new(width: SymbolWidth, val: Option<ConstVal>, span: Option<Span>) -> Self {
+        ExpInfo {
+            ty: SymbolType::new(width, val.is_some()),
+            val,
+            span,
+            poisoned: false,
+        }
+    }
+
+    fn poisoned(span: Option<Span>) -> Self {
+        ExpInfo {
+            ty: SymbolType::new(SymbolWidth::I32, false),
+            val: None,
+            span,
+            poisoned: true,
+        }
+    }
+
+    pub fn is_int(&self) -> bool {
+        matches!(self.ty.width, SymbolWidth::Bool | SymbolWidth::I32)
+    }
+}
+
+/// 合并两个子表达式的位置
+fn merge_span(span1: Option<Span>, span2: Option<Span>) -> Option<Span> {
+    match (span1, span2) {
+        (Some(span1), Some(span2)) => Some(Span::new(span1.lo, span2.hi)),
+        _ => span1.or(span2),
+    }
+}
+
+/// 按SysY的写法打印类型，用于错误信息
+pub fn type_name(ty: &SymbolType) -> String {
+    match &ty.width {
+        SymbolWidth::Void => String::from("void"),
+        SymbolWidth::Float => String::from("float"),
+        SymbolWidth::Arr{tar, dims} => {
+            let mut name = type_name(tar);
+            for dim in dims.iter() {
+                if *dim < 0 {
+                    name += "[]";
+                } else {
+                    name += &format!("[{}]", dim);
+                }
+            }
+            name
+        }
+        _ => String::from("int"),
+    }
+}
+
+/// 检查表达式能否作为数值使用，不能时报错
+pub fn check_value(env: &mut Env, info: &ExpInfo) -> bool {
+    if info.poisoned {
+        return false;
+    }
+    match info.ty.width {
+        SymbolWidth::Void => {
+            env.error(info.span, "void函数没有返回值，不能作为数值使用");
+            false
+        }
+        SymbolWidth::Arr{..} => {
+            env.error(info.span, format!("`{}`类型的数组不能作为数值使用", type_name(&info.ty)));
+            false
+        }
+        _ => true,
+    }
+}
+
+/// 算术运算的类型检查与常量计算
+fn arithmetic(env: &mut Env, info1: ExpInfo, info2: ExpInfo, op: &str) -> ExpInfo {
+    let span = merge_span(info1.span, info2.span);
+    let valid1 = check_value(env, &info1);
+    let valid2 = check_value(env, &info2);
+    if !valid1 || !valid2 {
+        return ExpInfo::poisoned(span);
+    }
+    if op == "%" && !(info1.is_int() && info2.is_int()) {
+        env.error(span, "`%`的操作数必须是整数");
+        return ExpInfo::poisoned(span);
+    }
+    let width = if info1.is_int() && info2.is_int() {
+        SymbolWidth::I32
+    } else {
+        SymbolWidth::Float
+    };
+    let val = match (info1.val, info2.val) {
+        (Some(val1), Some(val2)) => match val1.arithmetic(val2, op) {
+            Ok(val) => Some(val),
+            Err(msg) => {
+                env.error(span, msg);
+                return ExpInfo::poisoned(span);
+            }
+        },
+        _ => None,
+    };
+    ExpInfo::new(width, val, span)
+}
+
+/// 比较与逻辑运算的类型检查与常量计算
+fn logic(env: &mut Env, info1: ExpInfo, info2: ExpInfo, op: &str) -> ExpInfo {
+    let span = merge_span(info1.span, info2.span);
+    let valid1 = check_value(env, &info1);
+    let valid2 = check_value(env, &info2);
+    if !valid1 || !valid2 {
+        return ExpInfo::poisoned(span);
+    }
+    let val = match (info1.val, info2.val) {
+        (Some(val1), Some(val2)) => Some(val1.logic(val2, op)),
+        _ => None,
+    };
+    ExpInfo::new(SymbolWidth::Bool, val, span)
+}
+
+/// 检查实参能否传给类型为param的形参
+fn check_argument(env: &mut Env, func: &str, cnt: usize, param: &SymbolType, arg: &ExpInfo) {
+    if arg.poisoned {
+        return;
+    }
+    if !matches!(param.width, SymbolWidth::Arr{..}) {
+        check_value(env, arg);
+        return;
+    }
+    let compatible = match (&param.width, &arg.ty.width) {
+        (SymbolWidth::Arr{tar: tar1, dims: dims1}, SymbolWidth::Arr{tar: tar2, dims: dims2}) => {
+            // 最高维退化为指针，只比较其余维度
+            tar1.width == tar2.width
+                && dims1.len() == dims2.len()
+                && dims1.iter().zip(dims2.iter()).skip(1).all(|(dim1, dim2)| dim1 == dim2)
+        }
+        _ => false,
+    };
+    if !compatible {
+        env.error(arg.span, format!(
+            "函数`{}`的第{}个参数类型不匹配：期望`{}`，实际为`{}`",
+            func, cnt + 1, type_name(param), type_name(&arg.ty)
+        ));
+    }
+}
+
+/// 表达式，记录类型到ast上
+impl Analyse for Exp {
+    type Out = ExpInfo;
+
+    fn analyse(&mut self, env: &mut Env) -> Self::Out {
+        env.enter_exp(self.span);
+        let mut info = self.exp.analyse(env);
+        env.exit_exp();
+        info.span = Some(self.span);
+        self.ty = Some(info.ty.clone());
+        info
+    }
+}
+
+impl Analyse for LOrExp {
+    type Out = ExpInfo;
+
+    fn analyse(&mut self, env: &mut Env) -> Self::Out {
+        match self {
+            LOrExp::LAndExp(exp) => exp.analyse(env),
+            LOrExp::Or(exp1, exp2) => {
+                let info1 = exp1.analyse(env);
+                let info2 = exp2.analyse(env);
+                logic(env, info1, info2, "||")
+            }
+        }
+    }
+}
+
+impl Analyse for LAndExp {
+    type Out = ExpInfo;
+
+    fn analyse(&mut self, env: &mut Env) -> Self::Out {
+        match self {
+            LAndExp::EqExp(exp) => exp.analyse(env),
+            LAndExp::And(exp1, exp2) => {
+                let info1 = exp1.analyse(env);
+                let info2 = exp2.analyse(env);
+                logic(env, info1, info2, "&&")
+            }
+        }
+    }
+}
+
+impl Analyse for EqExp {
+    type Out = ExpInfo;
+
+    fn analyse(&mut self, env: &mut Env) -> Self::Out {
+        let (body, op) = match self {
+            EqExp::RelExp(exp) => return exp.analyse(env),
+            EqExp::EQ(body) => (body, "=="),
+            EqExp::NE(body) => (body, "!="),
+        };
+        let info1 = body.exp1.analyse(env);
+        let info2 = body.exp2.analyse(env);
+        logic(env, info1, info2, op)
+    }
+}
+
+impl Analyse for RelExp {
+    type Out = ExpInfo;
+
+    fn analyse(&mut self, env: &mut Env) -> Self::Out {
+        let (body, op) = match self {
+            RelExp::AddExp(exp) => return exp.analyse(env),
+            RelExp::Lt(body) => (body, "<"),
+            RelExp::Gt(body) => (body, ">"),
+            RelExp::Le(body) => (body, "<="),
+            RelExp::Ge(body) => (body, ">="),
+        };
+        let info1 = body.exp1.analyse(env);
+        let info2 = body.exp2.analyse(env);
+        logic(env, info1, info2, op)
+    }
+}
+
+impl Analyse for AddExp {
+    type Out = ExpInfo;
+
+    fn analyse(&mut self, env: &mut Env) -> Self::Out {
+        let (body, op) = match self {
+            AddExp::MulExp(exp) => return exp.analyse(env),
+            AddExp::Add(body) => (body, "+"),
+            AddExp::Sub(body) => (body, "-"),
+        };
+        let info1 = body.exp1.analyse(env);
+        let info2 = body.exp2.analyse(env);
+        arithmetic(env, info1, info2, op)
+    }
+}
+
+impl Analyse for MulExp {
+    type Out = ExpInfo;
+
+    fn analyse(&mut self, env: &mut Env) -> Self::Out {
+        let (body, op) = match self {
+            MulExp::UnaryExp(exp) => return exp.analyse(env),
+            MulExp::Mul(body) => (body, "*"),
+            MulExp::Div(body) => (body, "/"),
+            MulExp::Mod(body) => (body, "%"),
+        };
+        let info1 = body.exp1.analyse(env);
+        let info2 = body.exp2.analyse(env);
+        arithmetic(env, info1, info2, op)
+    }
+}
+
+/// 一元运算与函数调用
+impl Analyse for UnaryExp {
+    type Out = ExpInfo;
+
+    fn analyse(&mut self, env: &mut Env) -> Self::Out {
+        match self {
+            UnaryExp::PrimExp(exp) => exp.analyse(env),
+            UnaryExp::Pos(exp) => {
+                let info = exp.analyse(env);
+                if !check_value(env, &info) {
+                    return ExpInfo::poisoned(info.span);
+                }
+                info
+            }
+            UnaryExp::Neg(exp) => {
+                let info = exp.analyse(env);
+                if !check_value(env, &info) {
+                    return ExpInfo::poisoned(info.span);
+                }
+                let width = if info.is_int() { SymbolWidth::I32 } else { SymbolWidth::Float };
+                ExpInfo::new(width, info.val.map(ConstVal::negate), info.span)
+            }
+            UnaryExp::Not(exp) => {
+                let info = exp.analyse(env);
+                if !check_value(env, &info) {
+                    return ExpInfo::poisoned(info.span);
+                }
+                ExpInfo::new(SymbolWidth::Bool, info.val.map(ConstVal::logical_not), info.span)
+            }
+            UnaryExp::Call{id, params, span, symbol} => {
+                let args: Vec<ExpInfo> = params
+                    .iter_mut()
+                    .flatten()
+                    .map(|param| param.analyse(env))
+                    .collect();
+                let func = match env.lookup(id) {
+                    Some(func) => func.clone(),
+                    None => {
+                        env.error(Some(*span), format!("未定义的函数`{}`", id));
+                        return ExpInfo::poisoned(Some(*span));
+                    }
+                };
+                let param_tys = match &func.value {
+                    SymbolVal::Func(_, param_tys) => param_tys.clone(),
+                    _ => {
+                        env.error(Some(*span), format!("`{}`不是函数，不能调用", id));
+                        return ExpInfo::poisoned(Some(*span));
+                    }
+                };
+                if args.len() != param_tys.len() {
+                    env.error(Some(*span), format!(
+                        "函数`{}`需要{}个参数，但提供了{}个",
+                        id, param_tys.len(), args.len()
+                    ));
+                } else {
+                    for (cnt, (param, arg)) in param_tys.iter().zip(args.iter()).enumerate() {
+                        check_argument(env, id, cnt, param, arg);
+                    }
+                }
+                let info = ExpInfo::new(func.ty.width.clone(), None, Some(*span));
+                *symbol = Some(func);
+                info
+            }
+        }
+    }
+}
+
+impl Analyse for PrimaryExp {
+    type Out = ExpInfo;
+
+    fn analyse(&mut self, env: &mut Env) -> Self::Out {
+        match self {
+            PrimaryExp::Exp(exp) => exp.analyse(env),
+            PrimaryExp::Number(num) => {
+                let val = ConstVal::from_number(num);
+                let width = match val {
+                    ConstVal::Int(_) => SymbolWidth::I32,
+                    ConstVal::Float(_) => SymbolWidth::Float,
+                };
+                ExpInfo::new(width, Some(val), None)
+            }
+            PrimaryExp::LVal(lval) => lval.analyse(env),
+        }
+    }
+}
+
+/// 左值，解析到符号并根据下标个数确定类型
+impl Analyse for LVal {
+    type Out = ExpInfo;
+
+    fn analyse(&mut self, env: &mut Env) -> Self::Out {
+        let span = Some(self.span);
+        let mut idx_valid = true;
+        for idx in self.idx.iter_mut() {
+            let info = idx.analyse(env);
+            if !check_value(env, &info) {
+                idx_valid = false;
+            } else if !info.is_int() {
+                env.error(info.span, "数组下标必须是整数");
+                idx_valid = false;
+            }
+        }
+
+        let symbol = match env.lookup(&self.id) {
+            Some(symbol) => symbol.clone(),
+            None => {
+                env.error(span, format!("未定义的标识符`{}`", self.id));
+                return ExpInfo::poisoned(span);
+            }
+        };
+        if let SymbolVal::Func(_, _) = symbol.value {
+            env.error(span, format!("`{}`是函数，不能作为变量使用", self.id));
+            return ExpInfo::poisoned(span);
+        }
+        if symbol.poisoned {
+            self.symbol = Some(symbol);
+            return ExpInfo::poisoned(span);
+        }
+        let info = match &symbol.ty.width {
+            SymbolWidth::Arr{tar, dims} => {
+                match self.idx.len().cmp(&dims.len()) {
+                    Ordering::Greater => {
+                        env.error(span, format!(
+                            "`{}`是{}维数组，不能使用{}个下标",
+                            self.id, dims.len(), self.idx.len()
+                        ));
+                        ExpInfo::poisoned(span)
+                    }
+                    Ordering::Equal => ExpInfo::new(tar.width.clone(), None, span),
+                    Ordering::Less => {
+                        let left_dims = dims[self.idx.len()..].to_vec();
+                        ExpInfo::new(SymbolWidth::Arr{tar: tar.clone(), dims: left_dims}, None, span)
+                    }
+                }
+            }
+            width => {
+                if !self.idx.is_empty() {
+                    env.error(span, format!("`{}`不是数组，不能使用下标", self.id));
+                    ExpInfo::poisoned(span)
+                } else {
+                    let val = if symbol.ty.is_const {
+                        ConstVal::from_symbol_val(&symbol.value)
+                    } else {
+                        None
+                    };
+                    ExpInfo::new(width.clone(), val, span)
+                }
+            }
+        };
+        self.symbol = Some(symbol);
+        if idx_valid {
+            info
+        } else {
+            ExpInfo::poisoned(span)
+        }
+    }
+}
@@ -0,0 +1,85 @@
+pub mod env;
+pub mod const_eval;
+pub mod exp_check;
+pub mod decl_check;
+pub mod stmt_check;
+
+use crate::ast::SysY;
+use crate::diagnostic::Diagnostic;
+use env::Env;
+
+/// 语义分析，检查ast并把类型和解析到的符号记录在ast上
+pub trait Analyse {
+    type Out;
+    fn analyse(&mut self, env: &mut Env) -> Self::Out;
+}
+
+/// 对整个程序做语义分析，通过后llvm_gen可以不再检查错误
+pub fn analyse(ast: &mut SysY) -> Result<(), Vec<Diagnostic>> {
+    let mut env = Env::new();
+    ast.analyse(&mut env);
+    if env.diagnostics.is_empty() {
+        Ok(())
+    } else {
+        Err(env.diagnostics)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Session;
+
+    /// 语义分析得到的全部错误信息
+    fn errors(source: &str) -> Vec<String> {
+        let session = Session::default();
+        let mut ast = session.parse(source).unwrap();
+        match session.analyse(&mut ast) {
+            Ok(()) => vec![],
+            Err(diagnostics) => diagnostics.into_iter().map(|diagnostic| diagnostic.message).collect(),
+        }
+    }
+
+    #[test]
+    fn valid_program() {
+        assert_eq!(errors("const int N = 2 * 3; int a[N][N - 1]; int main() { return a[1][2] + N; }"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn poisoned_const_is_not_reported_again() {
+        let source = "\
+const int N = 1 / 0;
+const int M = N + 1;
+int a[N];
+int main() {
+    int b[M][2];
+    int x = y;
+    const int K = x;
+    int c[K] = {N};
+    return a[0] + b[0][0] + c[0];
+}
+";
+        assert_eq!(errors(source), vec![
+            String::from("常量表达式中出现除以零"),
+            String::from("未定义的标识符`y`"),
+            String::from("初始化值必须是常量表达式"),
+        ]);
+    }
+
+    #[test]
+    fn redefined_const_keeps_original_value() {
+        let source = "const int N = 2; const int N = 1 / 0; int a[N]; int main() { return 0; }";
+        assert_eq!(errors(source), vec![
+            String::from("常量表达式中出现除以零"),
+            String::from("重复定义`N`"),
+        ]);
+    }
+
+    #[test]
+    fn invalid_dimension() {
+        assert_eq!(errors("int a[0]; int b[-1]; int main() { int n = 2; int c[n]; return 0; }"), vec![
+            String::from("数组维度必须大于零"),
+            String::from("数组维度必须大于零"),
+            String::from("数组维度必须是整型常量表达式"),
+        ]);
+    }
+}
@@ -0,0 +1,193 @@
+use crate::ast::*;
+use crate::structures::symbol::*;
+use crate::semantic::Analyse;
+use crate::semantic::env::Env;
+use crate::semantic::exp_check::*;
+use crate::semantic::decl_check::const_dims;
+
+/// 依次分析全部的函数定义和全局声明，最后检查main函数
+impl Analyse for SysY {
+    type Out = ();
+
+    fn analyse(&mut self, env: &mut Env) -> Self::Out {
+        for unit in self.units.iter_mut() {
+            match unit {
+                CompUnit::FuncDef(func_def) => func_def.analyse(env),
+                CompUnit::Decl(decl) => decl.analyse(env),
+            }
+        }
+        let has_main = matches!(
+            env.lookup("main"),
+            Some(SymbolRef { value: SymbolVal::Func(_, _), def: Some(_), .. })
+        );
+        if !has_main {
+            env.error(None, "程序中没有定义`main`函数");
+        }
+    }
+}
+
+/// 函数参数的类型，数组参数的最高维为-1
+impl Analyse for FuncFParam {
+    type Out = SymbolType;
+
+    fn analyse(&mut self, env: &mut Env) -> Self::Out {
+        let mut ty = self.ty.ty.clone();
+        if ty.width == SymbolWidth::Void {
+            env.error(Some(self.span), format!("参数`{}`不能声明为void类型", self.id));
+            ty.width = SymbolWidth::I32;
+        }
+        if self.idx.is_empty() {
+            return ty;
+        }
+        let mut dims = vec!();
+        for index in self.idx.iter_mut() {
+            match index {
+                Index::Ptr(dim) => dims.push(*dim),
+                Index::Exp(exp) => dims.extend(const_dims(env, std::iter::once(exp))),
+            }
+        }
+        SymbolType::new(SymbolWidth::Arr{tar: Box::new(ty), dims}, false)
+    }
+}
+
+/// 先定义函数本身以支持递归，参数与函数体最外层的声明位于同一作用域
+impl Analyse for FuncDef {
+    type Out = ();
+
+    fn analyse(&mut self, env: &mut Env) -> Self::Out {
+        let func_type = self.func_type.ty.clone();
+        let mut param_tys = vec!();
+        if let Some(params) = &mut self.params {
+            for param in params.iter_mut() {
+                param_tys.push(param.analyse(env));
+            }
+        }
+        env.define(&self.id, self.span, func_type.clone(), SymbolVal::Func(func_type.clone(), param_tys.clone()));
+
+        env.push_scope();
+        env.func_type = Some(func_type);
+        if let Some(params) = &self.params {
+            for (param, ty) in params.iter().zip(param_tys.into_iter()) {
+                env.define(&param.id, param.span, ty, SymbolVal::Void);
+            }
+        }
+        for item in self.block.items.iter_mut() {
+            item.analyse(env);
+        }
+        env.func_type = None;
+        env.pop_scope();
+    }
+}
+
+impl Analyse for Block {
+    type Out = ();
+
+    fn analyse(&mut self, env: &mut Env) -> Self::Out {
+        env.push_scope();
+        for item in self.items.iter_mut() {
+            item.analyse(env);
+        }
+        env.pop_scope();
+    }
+}
+
+impl Analyse for BlockItem {
+    type Out = ();
+
+    fn analyse(&mut self, env: &mut Env) -> Self::Out {
+        match self {
+            BlockItem::Decl(decl) => decl.analyse(env),
+            BlockItem::Stmt(stmt) => stmt.analyse(env),
+        }
+    }
+}
+
+/// 条件表达式必须是标量
+fn check_cond(env: &mut Env, exp: &mut Exp) {
+    let info = exp.analyse(env);
+    check_value(env, &info);
+}
+
+impl Analyse for Stmt {
+    type Out = ();
+
+    fn analyse(&mut self, env: &mut Env) -> Self::Out {
+        match self {
+            Stmt::Assign(assign) => assign.analyse(env),
+            Stmt::Exp(exp) => {
+                // 表达式语句的值被丢弃，允许调用void函数
+                if let Some(exp) = exp {
+                    exp.analyse(env);
+                }
+            }
+            Stmt::Block(block) => block.analyse(env),
+            Stmt::Return(ret) => ret.analyse(env),
+            Stmt::Break(span) => {
+                if env.loop_depth == 0 {
+                    env.error(Some(*span), "`break`只能出现在循环中");
+                }
+            }
+            Stmt::Continue(span) => {
+                if env.loop_depth == 0 {
+                    env.error(Some(*span), "`continue`只能出现在循环中");
+                }
+            }
+            Stmt::If{exp, stmt1, stmt2} => {
+                check_cond(env, exp);
+                stmt1.analyse(env);
+                if let Some(stmt2) = stmt2 {
+                    stmt2.analyse(env);
+                }
+            }
+            Stmt::While{exp, stmt} => {
+                check_cond(env, exp);
+                env.loop_depth += 1;
+                stmt.analyse(env);
+                env.loop_depth -= 1;
+            }
+        }
+    }
+}
+
+/// 赋值语句的左值必须是非const的标量
+impl Analyse for Assign {
+    type Out = ();
+
+    fn analyse(&mut self, env: &mut Env) -> Self::Out {
+        let info = self.val.analyse(env);
+        if !info.poisoned {
+            let is_const = self.val.symbol.as_ref().map_or(false, |symbol| symbol.ty.is_const);
+            if is_const {
+                env.error(Some(self.val.span), format!("不能给常量`{}`赋值", self.val.id));
+            } else if let SymbolWidth::Arr{..} = info.ty.width {
+                env.error(Some(self.val.span), format!("不能给`{}`类型的数组整体赋值", type_name(&info.ty)));
+            }
+        }
+        let info = self.exp.analyse(env);
+        check_value(env, &info);
+    }
+}
+
+/// void函数不能返回值，其余函数必须返回值
+impl Analyse for Return {
+    type Out = ();
+
+    fn analyse(&mut self, env: &mut Env) -> Self::Out {
+        let func_type = env.func_type.clone().unwrap();
+        match &mut self.val {
+            Some(exp) => {
+                let info = exp.analyse(env);
+                if func_type.width == SymbolWidth::Void {
+                    env.error(Some(exp.span), "void函数不能返回值");
+                } else {
+                    check_value(env, &info);
+                }
+            }
+            None => {
+                if func_type.width != SymbolWidth::Void {
+                    env.error(Some(self.span), format!("函数需要返回`{}`类型的值", type_name(&func_type)));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use std::io::Write;
+use crate::ast::SysY;
+use crate::diagnostic::{self, Diagnostic};
+use crate::llvm_gen::generate_llvm;
+use crate::llvm_gen::read_text::read_llvm;
+use crate::options::{OptLevel, DEFAULT_INLINE_THRESHOLD, DEFAULT_UNROLL_FACTOR};
+use crate::parser::SysYParser;
+use crate::riscv_gen::generate_asm;
+use crate::semantic;
+use crate::structures::llvm_struct::LLVMProgram;
+use crate::structures::riscv_struct::RiscV;
+use crate::structures::writetext_trait::WriteText;
+
+/// 一次编译会话，按阶段调用：
+/// parse得到ast，analyse做语义分析，lower得到llvm，optimise_llvm，select得到汇编，optimise_riscv，最后emit
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub opt_level: OptLevel,
+    /// -O2下内联的函数规模上限
+    pub inline_threshold: usize,
+    /// -O2下循环部分展开的倍数
+    pub unroll_factor: usize,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Session::new(OptLevel::O2)
+    }
+}
+
+impl Session {
+    pub fn new(opt_level: OptLevel) -> Self {
+        Session { opt_level, inline_threshold: DEFAULT_INLINE_THRESHOLD, unroll_factor: DEFAULT_UNROLL_FACTOR }
+    }
+
+    /// 用lalrpop解析源码得到ast，出错后会尝试恢复，一次报告全部语法错误
+    pub fn parse(&self, source: &str) -> Result<SysY, Vec<Diagnostic>> {
+        let mut errors = vec!();
+        let result = SysYParser::new().parse(&mut errors, source);
+        let mut diagnostics: Vec<Diagnostic> = errors
+            .into_iter()
+            .map(|recovery| diagnostic::parse_error(source, recovery.error))
+            .collect();
+        match result {
+            Ok(ast) if diagnostics.is_empty() => Ok(ast),
+            Ok(_) => Err(diagnostics),
+            Err(err) => {
+                diagnostics.push(diagnostic::parse_error(source, err));
+                Err(diagnostics)
+            }
+        }
+    }
+
+    /// 语义分析，检查错误并把类型和符号记录到ast上
+    pub fn analyse(&self, ast: &mut SysY) -> Result<(), Vec<Diagnostic>> {
+        semantic::analyse(ast)
+    }
+
+    /// 先做语义分析，通过后将ast翻译为llvm
+    pub fn lower(&self, ast: &mut SysY) -> Result<LLVMProgram, Vec<Diagnostic>> {
+        self.analyse(ast)?;
+        Ok(generate_llvm(ast))
+    }
+
+    /// 读入文本形式的llvm并校验，用于直接从.ll编译
+    pub fn read_llvm(&self, text: &str) -> Result<LLVMProgram, Vec<Diagnostic>> {
+        let llvm = read_llvm(text).map_err(|diagnostic| vec!(diagnostic))?;
+        llvm.verify().map_err(|errors| {
+            errors.iter().map(|err| Diagnostic::error(format!("非法的IR: {}", err))).collect::<Vec<_>>()
+        })?;
+        Ok(llvm)
+    }
+
+    /// 按优化等级运行llvm上的优化
+    pub fn optimise_llvm(&self, llvm: &mut LLVMProgram) {
+        llvm.optimise_llvm(self.opt_level, self.inline_threshold, self.unroll_factor);
+    }
+
+    /// 指令选择与寄存器分配，得到汇编
+    pub fn select(&self, llvm: &LLVMProgram) -> RiscV {
+        generate_asm(llvm, self.opt_level)
+    }
+
+    /// 按优化等级运行汇编上的优化
+    pub fn optimise_riscv(&self, asm: &mut RiscV) {
+        asm.optimise_riscv(self.opt_level);
+    }
+
+    /// 将llvm或汇编以文本形式写到任意输出
+    pub fn emit(&self, item: &impl WriteText, output: &mut impl Write) {
+        item.writetext(output);
+    }
+
+    /// 从源码一直编译到优化后的llvm
+    pub fn compile_to_llvm(&self, source: &str) -> Result<LLVMProgram, Vec<Diagnostic>> {
+        let mut ast = self.parse(source)?;
+        let mut llvm = self.lower(&mut ast)?;
+        self.optimise_llvm(&mut llvm);
+        Ok(llvm)
+    }
+
+    /// 从源码一直编译到优化后的汇编
+    pub fn compile_to_asm(&self, source: &str) -> Result<RiscV, Vec<Diagnostic>> {
+        let llvm = self.compile_to_llvm(source)?;
+        let mut asm = self.select(&llvm);
+        self.optimise_riscv(&mut asm);
+        Ok(asm)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 语法错误的信息与出错位置所在的行
+    fn parse_errors(source: &str) -> Vec<(String, usize)> {
+        let diagnostics = Session::default().parse(source).unwrap_err();
+        diagnostics.iter()
+            .map(|diagnostic| {
+                let lo = diagnostic.span.unwrap().lo;
+                (diagnostic.message.clone(), source[..lo].matches('\n').count() + 1)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn recover_at_semicolon() {
+        let source = "int main() {\n    int a = ;\n    a = 1 +;\n    return a;\n}\n";
+        assert_eq!(parse_errors(source), vec![
+            (String::from("意外的符号`;`"), 2),
+            (String::from("意外的符号`;`"), 3),
+        ]);
+    }
+
+    #[test]
+    fn recover_in_condition() {
+        let source = "int main() {\n    int a = 1;\n    if (a + ) a = 2;\n    while (a 1) a = a - 1;\n    return a;\n}\n";
+        assert_eq!(parse_errors(source), vec![
+            (String::from("意外的符号`)`"), 3),
+            (String::from("意外的常量`1`"), 4),
+        ]);
+    }
+
+    #[test]
+    fn recover_from_missing_paren() {
+        let source = "\
+int main() {
+    int a = 1;
+    if (a < 2 {
+        a = 3;
+    } else {
+        a = 4;
+    }
+    while (a > 0 {
+        a = a - 1;
+    }
+    if (a {
+        a = 2;
+    }
+    return a;
+}
+";
+        assert_eq!(parse_errors(source), vec![
+            (String::from("意外的符号`{`"), 3),
+            (String::from("意外的符号`{`"), 8),
+            (String::from("意外的符号`{`"), 11),
+        ]);
+    }
+
+    #[test]
+    fn valid_source_parses() {
+        let source = "int main() { int a = 1; if (a) { a = 2; } else if (a < 3) a = 4; while (a) a = a - 1; return a; }";
+        assert!(Session::default().parse(source).is_ok());
+    }
+}
@@ -0,0 +1,242 @@
+use std::collections::HashSet;
+use crate::structures::llvm_struct::*;
+use crate::structures::symbol::*;
+
+/// 指令存储区与def-use链的维护
+/// 所有对指令操作数的修改都应通过这里的方法进行，以保证users与指令内容一致
+impl FuncDef {
+    pub fn new(func_name: &str, func_type: &SymbolType, params: Vec<Param>) -> Self {
+        Self {
+            func_name: func_name.to_string(),
+            func_type: func_type.clone(),
+            params,
+            blocks: vec![],
+            layout: vec![],
+            values: vec![],
+            local_vars: vec![],
+        }
+    }
+
+    /// 入口块
+    pub fn entry(&self) -> BlockId {
+        self.layout[0]
+    }
+
+    pub fn block(&self, id: BlockId) -> &Block {
+        &self.blocks[id.0]
+    }
+
+    pub fn block_mut(&mut self, id: BlockId) -> &mut Block {
+        &mut self.blocks[id.0]
+    }
+
+    pub fn instr(&self, id: ValueId) -> &Instruction {
+        &self.values[id.0].instr
+    }
+
+    /// 按布局顺序遍历基本块
+    pub fn layout_blocks(&self) -> impl Iterator<Item = (BlockId, &Block)> {
+        self.layout.iter().map(move |&id| (id, &self.blocks[id.0]))
+    }
+
+    /// 基本块中的全部指令，依次为phi、一般指令与终结指令
+    pub fn block_instrs(&self, id: BlockId) -> Vec<ValueId> {
+        let block = self.block(id);
+        block.phi_ins.iter()
+            .chain(block.nor_ins.iter())
+            .chain(block.ter_ins.iter())
+            .cloned()
+            .collect()
+    }
+
+    /// 按布局顺序遍历函数中的全部指令，局部变量在最前
+    pub fn all_instrs(&self) -> Vec<ValueId> {
+        let mut res = self.local_vars.clone();
+        self.layout.iter().for_each(|&b| res.extend(self.block_instrs(b)));
+        res
+    }
+
+    /// 终结指令跳转到的基本块
+    pub fn successors(&self, id: BlockId) -> Vec<BlockId> {
+        self.block(id).ter_ins.map_or(vec![], |ter| self.instr(ter).successors())
+    }
+
+    /// 新建一个不在布局中的空基本块
+    pub fn add_block(&mut self, label: &str, depth: usize) -> BlockId {
+        self.blocks.push(Block {
+            block_label: label.to_string(),
+            phi_ins: vec![],
+            nor_ins: vec![],
+            ter_ins: None,
+            depth,
+        });
+        BlockId(self.blocks.len() - 1)
+    }
+
+    /// 在存储区中创建指令并登记其使用的值，指令尚不属于任何基本块
+    pub fn create_value(&mut self, instr: Instruction, name: Option<String>) -> ValueId {
+        let id = ValueId(self.values.len());
+        self.values.push(ValueData { instr, name, block: None, users: vec![] });
+        self.link_uses(id);
+        id
+    }
+
+    /// 创建指令并追加到基本块中：phi放在phi_ins，跳转与返回作为终结指令，alloca放入局部变量
+    pub fn push_value(&mut self, bb: BlockId, instr: Instruction, name: Option<String>) -> ValueId {
+        let id = self.push_value_unlinked(bb, instr, name);
+        self.link_uses(id);
+        id
+    }
+
+    /// 同push_value，但不登记使用的值，操作数可以引用尚未创建的值(例如读入IR时的前向引用)
+    /// 全部指令创建完后需调用link_all_uses
+    pub fn push_value_unlinked(&mut self, bb: BlockId, instr: Instruction, name: Option<String>) -> ValueId {
+        let id = ValueId(self.values.len());
+        self.values.push(ValueData { instr, name, block: Some(bb), users: vec![] });
+        match self.instr(id) {
+            Instruction::Phi(_, _) => self.block_mut(bb).phi_ins.push(id),
+            Instruction::Alloca { .. } => {
+                // 局部变量属于入口块
+                self.values[id.0].block = Some(self.entry());
+                self.local_vars.push(id);
+            },
+            Instruction::Br(_, _, _) | Instruction::Ret(_, _) => {
+                assert!(self.block(bb).ter_ins.is_none(), "Already pushed one terminator instr in this basic block before.");
+                self.block_mut(bb).ter_ins = Some(id);
+            },
+            _ => self.block_mut(bb).nor_ins.push(id),
+        }
+        id
+    }
+
+    /// 创建一般指令并插入到基本块nor_ins的pos处
+    pub fn insert_value(&mut self, bb: BlockId, pos: usize, instr: Instruction, name: Option<String>) -> ValueId {
+        let id = self.create_value(instr, name);
+        self.values[id.0].block = Some(bb);
+        self.block_mut(bb).nor_ins.insert(pos, id);
+        id
+    }
+
+    /// 替换指令的内容，并更新def-use链
+    pub fn set_instr(&mut self, id: ValueId, instr: Instruction) {
+        self.unlink_uses(id);
+        self.values[id.0].instr = instr;
+        self.link_uses(id);
+    }
+
+    /// 为用push_value_unlinked创建的全部指令登记使用的值
+    pub fn link_all_uses(&mut self) {
+        for id in self.all_instrs() {
+            self.link_uses(id);
+        }
+    }
+
+    /// 为phi增加一个来自pred的候选
+    pub fn add_phi_incoming(&mut self, phi: ValueId, value: Operand, pred: BlockId) {
+        if let Operand::Value(v) = &value {
+            self.values[v.0].users.push(phi);
+        }
+        match &mut self.values[phi.0].instr {
+            Instruction::Phi(_, candidates) => candidates.push((value, pred)),
+            instr => panic!("{:?} is not a phi", instr),
+        }
+    }
+
+    /// 将所有对old的使用替换为new
+    pub fn replace_all_uses(&mut self, old: ValueId, new: &Operand) {
+        if new == &Operand::Value(old) {
+            return;
+        }
+        let mut users = std::mem::take(&mut self.values[old.0].users);
+        users.sort();
+        users.dedup();
+        for user in users {
+            let mut replaced = 0;
+            for op in self.values[user.0].instr.operands_mut() {
+                if op == &Operand::Value(old) {
+                    *op = new.clone();
+                    replaced += 1;
+                }
+            }
+            if let Operand::Value(v) = new {
+                (0..replaced).for_each(|_| self.values[v.0].users.push(user));
+            }
+        }
+    }
+
+    /// 从基本块中删除指令，并解除其对操作数的使用
+    /// 被删除的指令不应再有使用者
+    pub fn erase_value(&mut self, id: ValueId) {
+        debug_assert!(self.values[id.0].users.is_empty(), "erase value {:?} which is still used", id);
+        if let Some(bb) = self.values[id.0].block.take() {
+            let block = self.block_mut(bb);
+            block.phi_ins.retain(|&v| v != id);
+            block.nor_ins.retain(|&v| v != id);
+            if block.ter_ins == Some(id) {
+                block.ter_ins = None;
+            }
+            self.local_vars.retain(|&v| v != id);
+        }
+        self.unlink_uses(id);
+    }
+
+    /// 批量删除指令，指令之间可以互相使用
+    pub fn erase_values(&mut self, dead: &HashSet<ValueId>) {
+        if dead.is_empty() {
+            return;
+        }
+        for block in self.blocks.iter_mut() {
+            block.phi_ins.retain(|v| !dead.contains(v));
+            block.nor_ins.retain(|v| !dead.contains(v));
+            if block.ter_ins.map_or(false, |v| dead.contains(&v)) {
+                block.ter_ins = None;
+            }
+        }
+        self.local_vars.retain(|v| !dead.contains(v));
+        for &id in dead.iter() {
+            self.values[id.0].block = None;
+            self.unlink_uses(id);
+        }
+    }
+
+    /// 将基本块从布局中移除，并删除其中的全部指令
+    pub fn remove_blocks(&mut self, removed: &HashSet<BlockId>) {
+        let dead: HashSet<ValueId> = removed.iter().flat_map(|&b| self.block_instrs(b)).collect();
+        self.erase_values(&dead);
+        self.layout.retain(|b| !removed.contains(b));
+    }
+
+    /// 登记指令对其操作数的使用，用于push_value_unlinked创建的单条指令
+    pub fn link_uses(&mut self, id: ValueId) {
+        for v in self.values[id.0].instr.used_values() {
+            self.values[v.0].users.push(id);
+        }
+    }
+
+    /// 解除指令对其操作数的使用
+    fn unlink_uses(&mut self, id: ValueId) {
+        for v in self.values[id.0].instr.used_values() {
+            let users = &mut self.values[v.0].users;
+            if let Some(pos) = users.iter().position(|&u| u == id) {
+                users.swap_remove(pos);
+            }
+        }
+    }
+
+    /// 是否为alloca指令的结果，即栈上的局部变量
+    pub fn is_alloca(&self, op: &Operand) -> bool {
+        match op {
+            Operand::Value(id) => matches!(self.instr(*id), Instruction::Alloca { .. }),
+            _ => false,
+        }
+    }
+
+    /// 操作数的类型，全局变量与常量的类型由使用处决定，返回None
+    pub fn operand_type(&self, op: &Operand) -> Option<SymbolType> {
+        match op {
+            Operand::Arg(idx) => Some(self.params[*idx].param_type.clone()),
+            Operand::Value(id) => self.instr(*id).result_type(),
+            _ => None,
+        }
+    }
+}
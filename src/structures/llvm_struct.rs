@@ -1,4 +1,5 @@
-use std::collections::HashSet;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
 use crate::structures::symbol::*;
 
 /*
@@ -48,6 +49,8 @@ use crate::structures::symbol::*;
 |  func_type: SymbolType |
 |  params: Vector       |
 |  blocks: Vector       |
+|  layout: Vector       |
+|  values: Vector       |
 |  local_vars: Vector   |
 +-----------------------+
             ^
@@ -69,17 +72,18 @@ use crate::structures::symbol::*;
 |  phi_ins: Vector      |
 |  nor_ins: Vector      |
 |  ter_ins: Option      |
-|  ins_num: Integer     |
 |  depth: Integer       |
 +-----------------------+
             ^
             |
             |
 +-----------------------+
-|      LocalVar          |
+|      ValueData         |
 +-----------------------+
-|  ins: Instruction    |
-|  label: String       |
+|  instr: Instruction  |
+|  name: Option        |
+|  block: Option       |
+|  users: Vector       |
 +-----------------------+
             ^
             |
@@ -118,10 +122,9 @@ use crate::structures::symbol::*;
 +-----------------------+
 |       BinaryOp         |
 +-----------------------+
-|  res: String         |
 |  op_type: SymbolType |
-|  op1: String         |
-|  op2: String         |
+|  op1: Operand        |
+|  op2: Operand        |
 +-----------------------+
             ^
             |
@@ -129,10 +132,9 @@ use crate::structures::symbol::*;
 +-----------------------+
 |         CastOp          |
 +-----------------------+
-|  res: String         |
 |  type_1: SymbolType  |
 |  type_2: SymbolType  |
-|  val: String         |
+|  val: Operand        |
 +-----------------------+
 
  */
@@ -146,6 +148,17 @@ pub struct LLVMProgram {
     pub func_decl: Vec<FuncDecl>,
     /// 函数定义列表
     pub func_def: Vec<FuncDef>,
+    /// 生成当前函数时名称到值与基本块的映射
+    pub names: NameTable,
+}
+
+/// 生成或读入IR时，名称到值与基本块的映射
+#[derive(Debug, Default)]
+pub struct NameTable {
+    /// 局部名称，例如%3、%a_0，到操作数的映射
+    pub values: HashMap<String, Operand>,
+    /// 基本块标签到基本块的映射
+    pub blocks: HashMap<String, BlockId>,
 }
 
 /// 全局变量
@@ -171,7 +184,7 @@ pub struct FuncDecl {
 }
 
 /// 函数定义
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct FuncDef {
     /// 函数名称
     pub func_name: String,
@@ -179,14 +192,18 @@ pub struct FuncDef {
     pub func_type: SymbolType,
     /// 参数列表
     pub params: Vec<Param>,
-    /// 基本块列表
+    /// 基本块存储区，以BlockId为下标
     pub blocks: Vec<Block>,
-    /// 局部变量列表
-    pub local_vars: Vec<LocalVar>,
+    /// 基本块的排列顺序，第一个为入口块
+    pub layout: Vec<BlockId>,
+    /// 指令存储区，以ValueId为下标，删除的指令仍占位
+    pub values: Vec<ValueData>,
+    /// 局部变量(alloca指令)列表，打印在入口块的开头
+    pub local_vars: Vec<ValueId>,
 }
 
 /// 参数
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Param {
     /// 参数名称
     pub param_name: String,
@@ -195,31 +212,88 @@ pub struct Param {
 }
 
 /// 基本块
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Block {
     /// 基本块标签
     pub block_label: String,
     /// phi指令列表
-    pub phi_ins: Vec<Instruction>,
+    pub phi_ins: Vec<ValueId>,
     /// 一般指令列表
-    pub nor_ins: Vec<Instruction>,
+    pub nor_ins: Vec<ValueId>,
     /// 终结指令
-    pub ter_ins: Option<Instruction>,
-    /// 此基本块之前的所有基本块的指令数量
-    pub ins_num: usize,
-    /// 此基本块的循环嵌套深度
+    pub ter_ins: Option<ValueId>,
+    /// 循环嵌套深度，决定指令选择时的溢出权重；生成时按语法得到，optimise_llvm最后按循环分析重新计算
     pub depth: usize,
 }
 
-/// 局部变量
-#[derive(Debug)]
-pub struct LocalVar {
+/// 函数内指令的编号，指令的结果也以此编号引用
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ValueId(pub usize);
+
+/// 函数内基本块的编号
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BlockId(pub usize);
+
+/// 指令存储区中的一项
+#[derive(Debug, Clone)]
+pub struct ValueData {
     /// 指令
-    pub ins: Instruction,
-    /// 标签
-    pub label: String,
+    pub instr: Instruction,
+    /// 结果的名称，例如局部变量%a_0，为None时打印时按顺序编号
+    pub name: Option<String>,
+    /// 所在的基本块，已删除的指令为None
+    pub block: Option<BlockId>,
+    /// 使用此结果的指令，同一条指令多次使用时重复出现
+    pub users: Vec<ValueId>,
 }
 
+/// 操作数
+#[derive(Debug, Clone)]
+pub enum Operand {
+    /// 整型常量，包括i1、i32与i64
+    ConstInt(i64),
+    /// 浮点常量
+    ConstFloat(f32),
+    /// 空指针
+    Null,
+    /// 全局变量或函数，不含@前缀
+    Global(String),
+    /// 函数参数，值为参数的下标
+    Arg(usize),
+    /// 指令的结果
+    Value(ValueId),
+}
+
+/// 浮点常量按位比较，与Hash一致：0.0与-0.0不同，NaN与自身相同
+impl PartialEq for Operand {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Operand::ConstInt(num1), Operand::ConstInt(num2)) => num1 == num2,
+            (Operand::ConstFloat(num1), Operand::ConstFloat(num2)) => num1.to_bits() == num2.to_bits(),
+            (Operand::Null, Operand::Null) => true,
+            (Operand::Global(name1), Operand::Global(name2)) => name1 == name2,
+            (Operand::Arg(idx1), Operand::Arg(idx2)) => idx1 == idx2,
+            (Operand::Value(id1), Operand::Value(id2)) => id1 == id2,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Operand {}
+
+impl Hash for Operand {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Operand::ConstInt(num) => num.hash(state),
+            Operand::ConstFloat(num) => num.to_bits().hash(state),
+            Operand::Null => {},
+            Operand::Global(name) => name.hash(state),
+            Operand::Arg(idx) => idx.hash(state),
+            Operand::Value(id) => id.hash(state),
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub enum Instruction {
@@ -257,34 +331,34 @@ pub enum Instruction {
     /// 浮点数比较指令
     Fcmp(String, BinaryOp),
     // Phi
-    /// Phi指令
-    Phi(String, SymbolType, Vec<(String, String)>),
+    /// Phi指令，候选为<值, 前驱块>
+    Phi(SymbolType, Vec<(Operand, BlockId)>),
     // 变量
     /// 分配内存指令
-    Alloca { res: String, ty: SymbolType, len: String },
+    Alloca { ty: SymbolType, len: String },
     /// 存储指令
-    Store { ty: SymbolType, value: String, ptr: String, len: String },
+    Store { ty: SymbolType, value: Operand, ptr: Operand, len: String },
     /// 加载指令
-    Load { res: String, ty: SymbolType, ptr: String, len: String },
+    Load { ty: SymbolType, ptr: Operand, len: String },
     // 函数
-    /// 函数调用指令
-    Call(String, String, SymbolType, Vec<(String, SymbolType)>),
+    /// 函数调用指令，.0为带@的函数名
+    Call(String, SymbolType, Vec<(Operand, SymbolType)>),
     // 数组
     /// 获取数组元素指针指令
-    GetElemPtr(String, SymbolType, String, Vec<String>),
+    GetElemPtr(SymbolType, Operand, Vec<Operand>),
     /// 位转换指令
-    BitCast(String, SymbolType, String, SymbolType),
+    BitCast(SymbolType, Operand, SymbolType),
     // 注释
     /// 注释指令
     Comment(String),
     // 终结指令
     /// 返回指令
-    Ret(SymbolType, Option<String>),
+    Ret(SymbolType, Option<Operand>),
     /// 分支指令
     /// .0表示条件 可以没有条件
     /// .1表示如果为真，跳转到的分支
     /// .2表示如果为假，跳转到的分支，可以没有该分支
-    Br(Option<String>, String, Option<String>),
+    Br(Option<Operand>, BlockId, Option<BlockId>),
 }
 
 #[derive(Debug, PartialEq)]
@@ -317,29 +391,25 @@ pub enum InstructionType {
 
 
 /// 二元操作
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Clone)]
 pub struct BinaryOp {
-    /// 结果
-    pub res: String,
     /// 操作类型
     pub op_type: SymbolType,
     /// 操作数1
-    pub op1: String,
+    pub op1: Operand,
     /// 操作数2
-    pub op2: String,
+    pub op2: Operand,
 }
 
 /// 转换操作
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Clone)]
 pub struct CastOp {
-    /// 结果
-    pub res: String,
     /// 类型1
     pub type_1: SymbolType,
     /// 类型2
     pub type_2: SymbolType,
     /// 值
-    pub val: String,
+    pub val: Operand,
 }
 
 impl LLVMProgram {
@@ -349,225 +419,174 @@ impl LLVMProgram {
     }
 }
 
-impl BinaryOp {
-    /// 创建一个新的二元操作
-    pub fn new() -> Self {
-        Self::default()
+impl Operand {
+    /// 是否为整型或浮点常量
+    pub fn is_const(&self) -> bool {
+        matches!(self, Operand::ConstInt(_) | Operand::ConstFloat(_))
     }
-}
 
-impl CastOp {
-    /// 创建一个新的转换操作
-    pub fn new() -> Self {
-        Self::default()
+    /// 若为指令的结果，取出其编号
+    pub fn as_value(&self) -> Option<ValueId> {
+        match self {
+            Operand::Value(id) => Some(*id),
+            _ => None,
+        }
     }
 }
 
-/// for eliminate
+/// 取出指令的操作数
 impl Instruction {
-    #[allow(unused)]
-    pub fn is_active(&self, active_labels: &HashSet<String>) -> bool {
+    pub fn operands(&self) -> Vec<&Operand> {
         match self {
-            Instruction::Add(bin_op) => bin_op.is_active(active_labels),
-            Instruction::Sub(bin_op) => bin_op.is_active(active_labels),
-            Instruction::Mul(bin_op) => bin_op.is_active(active_labels),
-            Instruction::Sdiv(bin_op) => bin_op.is_active(active_labels),
-            Instruction::Srem(bin_op) => bin_op.is_active(active_labels),
-            Instruction::Fadd(bin_op) => bin_op.is_active(active_labels),
-            Instruction::Fsub(bin_op) => bin_op.is_active(active_labels),
-            Instruction::Fmul(bin_op) => bin_op.is_active(active_labels),
-            Instruction::Fdiv(bin_op) => bin_op.is_active(active_labels),
-            Instruction::Cmp(_, bin_op) => bin_op.is_active(active_labels),
-            Instruction::Fcmp(_, bin_op) => bin_op.is_active(active_labels),
-            Instruction::ZeroExt(conver_op) => conver_op.is_active(active_labels),
-            Instruction::I32ToFloat(conver_op) => conver_op.is_active(active_labels),
-            Instruction::FloatToI32(conver_op) => conver_op.is_active(active_labels),
-            Instruction::Phi(res, _, _) => active_labels.contains(res.as_str()),
-            Instruction::Alloca { res, ty: _, len: _ } => active_labels.contains(res.as_str()),
-            Instruction::Store { ty: _, value: _, ptr, len: _ } => active_labels.contains(ptr.as_str()),
-            Instruction::Load { res, ty: _, ptr: _, len: _ } => active_labels.contains(res.as_str()),
-            Instruction::Call(_, _, _, _) => true, // Call指令总是执行
-            Instruction::GetElemPtr(_, _, _, _) => true, // GetElemPtr总是执行
-            Instruction::BitCast(res, _, _, _) => active_labels.contains(res.as_str()),
-            // 由于删除死代码后，代码结构发生变化，注释不再打印
-            Instruction::Comment(_) => false,
-            // 终结指令总是执行
-            Instruction::Ret(_, _) => true,
-            Instruction::Br(_, _, _) => true,
-        } // match
-    } // fn
-} // impl
-
-impl BinaryOp {
-    #[allow(unused)]
-    fn is_active(&self, active_labels: &HashSet<String>) -> bool {
-        active_labels.contains(self.res.as_str())
+            Instruction::Add(bin_op)
+            | Instruction::Sub(bin_op)
+            | Instruction::Mul(bin_op)
+            | Instruction::Sdiv(bin_op)
+            | Instruction::Srem(bin_op)
+            | Instruction::Fadd(bin_op)
+            | Instruction::Fsub(bin_op)
+            | Instruction::Fmul(bin_op)
+            | Instruction::Fdiv(bin_op)
+            | Instruction::Cmp(_, bin_op)
+            | Instruction::Fcmp(_, bin_op) => vec![&bin_op.op1, &bin_op.op2],
+            Instruction::ZeroExt(cast_op)
+            | Instruction::I32ToFloat(cast_op)
+            | Instruction::FloatToI32(cast_op) => vec![&cast_op.val],
+            Instruction::Phi(_, candidates) => candidates.iter().map(|(v, _)| v).collect(),
+            Instruction::Alloca { .. } | Instruction::Comment(_) => vec![],
+            Instruction::Store { value, ptr, .. } => vec![value, ptr],
+            Instruction::Load { ptr, .. } => vec![ptr],
+            Instruction::Call(_, _, params) => params.iter().map(|(v, _)| v).collect(),
+            Instruction::GetElemPtr(_, ptr, idx) => std::iter::once(ptr).chain(idx.iter()).collect(),
+            Instruction::BitCast(_, val, _) => vec![val],
+            Instruction::Ret(_, val) => val.iter().collect(),
+            Instruction::Br(cond, _, _) => cond.iter().collect(),
+        }
     }
-}
 
-impl CastOp {
-    #[allow(unused)]
-    fn is_active(&self, active_labels: &HashSet<String>) -> bool {
-        active_labels.contains(self.res.as_str())
+    pub fn operands_mut(&mut self) -> Vec<&mut Operand> {
+        match self {
+            Instruction::Add(bin_op)
+            | Instruction::Sub(bin_op)
+            | Instruction::Mul(bin_op)
+            | Instruction::Sdiv(bin_op)
+            | Instruction::Srem(bin_op)
+            | Instruction::Fadd(bin_op)
+            | Instruction::Fsub(bin_op)
+            | Instruction::Fmul(bin_op)
+            | Instruction::Fdiv(bin_op)
+            | Instruction::Cmp(_, bin_op)
+            | Instruction::Fcmp(_, bin_op) => vec![&mut bin_op.op1, &mut bin_op.op2],
+            Instruction::ZeroExt(cast_op)
+            | Instruction::I32ToFloat(cast_op)
+            | Instruction::FloatToI32(cast_op) => vec![&mut cast_op.val],
+            Instruction::Phi(_, candidates) => candidates.iter_mut().map(|(v, _)| v).collect(),
+            Instruction::Alloca { .. } | Instruction::Comment(_) => vec![],
+            Instruction::Store { value, ptr, .. } => vec![value, ptr],
+            Instruction::Load { ptr, .. } => vec![ptr],
+            Instruction::Call(_, _, params) => params.iter_mut().map(|(v, _)| v).collect(),
+            Instruction::GetElemPtr(_, ptr, idx) => std::iter::once(ptr).chain(idx.iter_mut()).collect(),
+            Instruction::BitCast(_, val, _) => vec![val],
+            Instruction::Ret(_, val) => val.iter_mut().collect(),
+            Instruction::Br(cond, _, _) => cond.iter_mut().collect(),
+        }
     }
-}
 
-///fetch
+    /// 操作数中引用的指令结果
+    pub fn used_values(&self) -> Vec<ValueId> {
+        self.operands().into_iter().filter_map(|op| op.as_value()).collect()
+    }
 
-impl Instruction {
-    pub fn fetch_info(&self) -> (InstructionType, Vec<&str>, Vec<&SymbolType>) {
-        match &self {
-            Instruction::Add(bin_op) => {
-                let str_ty = bin_op.fetch_info();
-                (InstructionType::Add, str_ty.0, str_ty.1)
-            }
-            Instruction::Sub(bin_op) => {
-                let str_ty = bin_op.fetch_info();
-                (InstructionType::Sub, str_ty.0, str_ty.1)
-            }
-            Instruction::Mul(bin_op) => {
-                let str_ty = bin_op.fetch_info();
-                (InstructionType::Mul, str_ty.0, str_ty.1)
-            }
-            Instruction::Sdiv(bin_op) => {
-                let str_ty = bin_op.fetch_info();
-                (InstructionType::Sdiv, str_ty.0, str_ty.1)
-            }
-            Instruction::Srem(bin_op) => {
-                let str_ty = bin_op.fetch_info();
-                (InstructionType::Srem, str_ty.0, str_ty.1)
-            }
-            Instruction::Fadd(bin_op) => {
-                let str_ty = bin_op.fetch_info();
-                (InstructionType::Fadd, str_ty.0, str_ty.1)
-            }
-            Instruction::Fsub(bin_op) => {
-                let str_ty = bin_op.fetch_info();
-                (InstructionType::Fsub, str_ty.0, str_ty.1)
-            }
-            Instruction::Fmul(bin_op) => {
-                let str_ty = bin_op.fetch_info();
-                (InstructionType::Fmul, str_ty.0, str_ty.1)
-            }
-            Instruction::Fdiv(bin_op) => {
-                let str_ty = bin_op.fetch_info();
-                (InstructionType::Fdiv, str_ty.0, str_ty.1)
-            }
-            Instruction::ZeroExt(conver_op) => {
-                let str_ty = conver_op.fetch_info();
-                (InstructionType::ZeroExt, str_ty.0, str_ty.1)
-            }
-            Instruction::I32ToFloat(conver_op) => {
-                let str_ty = conver_op.fetch_info();
-                (InstructionType::I32ToFloat, str_ty.0, str_ty.1)
-            }
-            Instruction::FloatToI32(conver_op) => {
-                let str_ty = conver_op.fetch_info();
-                (InstructionType::FloatToI32, str_ty.0, str_ty.1)
-            }
-            Instruction::Cmp(cond, bin_op) => {
-                let (mut str_vec, ty_vec) = bin_op.fetch_info();
-                str_vec.insert(0, cond.as_str());
-                (InstructionType::Cmp, str_vec, ty_vec)
-            }
-            Instruction::Fcmp(cond, bin_op) => {
-                let (mut str_vec, ty_vec) = bin_op.fetch_info();
-                str_vec.insert(0, cond.as_str());
-                (InstructionType::Fcmp, str_vec, ty_vec)
-            }
-            Instruction::Phi(res, ty, candidates) => {
-                let mut str_vec = vec![res.as_str()];
-                for (v, b) in candidates.iter() {
-                    str_vec.push(v.as_str());
-                    str_vec.push(b.as_str());
-                }
-                (InstructionType::Phi, str_vec, vec![&ty])
-            }
-            Instruction::Alloca { res, ty, len } => (
-                InstructionType::Alloca,
-                vec![res.as_str(), len.as_str()],
-                vec![&ty],
-            ),
-            Instruction::Store {
-                ty,
-                value,
-                ptr,
-                len,
-            } => (
-                InstructionType::Store,
-                vec![value.as_str(), ptr.as_str(), len.as_str()],
-                vec![&ty],
-            ),
-            Instruction::Load {
-                res,
-                ty,
-                ptr,
-                len,
-            } => (
-                InstructionType::Load,
-                vec![res.as_str(), ptr.as_str(), len.as_str()],
-                vec![&ty],
-            ),
-            Instruction::Call(res, label, ty, params) => {
-                let mut str_vec = vec![res.as_str(), label.as_str()];
-                for (v, _) in params.iter() {
-                    str_vec.push(v.as_str());
-                }
-                let mut ty_vec = vec![ty];
-                for (_, t) in params.iter() {
-                    ty_vec.push(t);
-                }
-                (InstructionType::Call, str_vec, ty_vec)
-            }
-            Instruction::GetElemPtr(dst, ty, ptr, idx) => {
-                let mut str_vec = vec![dst.as_str(), ptr.as_str()];
-                for i in idx.iter() {
-                    str_vec.push(i.as_str());
-                }
-                (InstructionType::GetElemPtr, str_vec, vec![&ty])
-            }
-            Instruction::BitCast(res, ty, val, ty2) => (
-                InstructionType::BitCast,
-                vec![res.as_str(), val.as_str()],
-                vec![&ty, &ty2],
-            ),
-            Instruction::Comment(content) => (InstructionType::Comment, vec![content.as_str()], vec![]),
-            Instruction::Ret(ty, val) => {
-                if let Some(v) = val {
-                    (InstructionType::Ret, vec![v.as_str()], vec![&ty])
-                } else {
-                    (InstructionType::Ret, vec![], vec![&ty])
-                }
-            }
-            Instruction::Br(cond, label1, label2) => {
-                if let (Some(c), Some(l2)) = (cond, label2) {
-                    (
-                        InstructionType::Br,
-                        vec![c.as_str(), label1.as_str(), l2.as_str()],
-                        vec![],
-                    )
-                } else {
-                    (InstructionType::Br, vec!["", label1.as_str(), ""], vec![])
-                }
-            }
+    /// 指令是否产生结果
+    pub fn has_result(&self) -> bool {
+        match self {
+            Instruction::Store { .. }
+            | Instruction::Comment(_)
+            | Instruction::Ret(_, _)
+            | Instruction::Br(_, _, _) => false,
+            Instruction::Call(_, ty, _) => ty.width != SymbolWidth::Void,
+            _ => true,
         }
     }
+
+    /// 结果的类型，指针以dims[0]为-1的数组表示
+    pub fn result_type(&self) -> Option<SymbolType> {
+        let ty = match self {
+            Instruction::Add(bin_op)
+            | Instruction::Sub(bin_op)
+            | Instruction::Mul(bin_op)
+            | Instruction::Sdiv(bin_op)
+            | Instruction::Srem(bin_op)
+            | Instruction::Fadd(bin_op)
+            | Instruction::Fsub(bin_op)
+            | Instruction::Fmul(bin_op)
+            | Instruction::Fdiv(bin_op) => bin_op.op_type.clone(),
+            Instruction::Cmp(_, _) | Instruction::Fcmp(_, _) => SymbolType::new(SymbolWidth::Bool, false),
+            Instruction::ZeroExt(cast_op)
+            | Instruction::I32ToFloat(cast_op)
+            | Instruction::FloatToI32(cast_op) => cast_op.type_2.clone(),
+            Instruction::Phi(ty, _) | Instruction::Load { ty, .. } => ty.clone(),
+            Instruction::Alloca { ty, .. } => pointer_to(ty),
+            Instruction::Call(_, ty, _) if ty.width != SymbolWidth::Void => ty.clone(),
+            Instruction::GetElemPtr(ty, _, idx) => {
+                // 第一个下标跨过指针本身，之后每个下标去掉一维
+                let elem = idx.iter().skip(1).fold(ty.clone(), |ty, _| element_of(&ty));
+                pointer_to(&elem)
+            },
+            Instruction::BitCast(_, _, ty) => pointer_to(ty),
+            _ => return None,
+        };
+        Some(ty)
+    }
+
+    /// 终结指令跳转到的基本块
+    pub fn successors(&self) -> Vec<BlockId> {
+        match self {
+            Instruction::Br(_, label1, Some(label2)) => vec![*label1, *label2],
+            Instruction::Br(_, label1, None) => vec![*label1],
+            _ => vec![],
+        }
+    }
+}
+
+/// 指向ty的指针类型
+pub fn pointer_to(ty: &SymbolType) -> SymbolType {
+    match &ty.width {
+        // 没有维度的数组即为其元素类型，例如指针形参上的GetElemPtr
+        SymbolWidth::Arr { tar, dims } if dims.is_empty() => pointer_to(tar),
+        SymbolWidth::Arr { tar, dims } if dims[0] != -1 => {
+            let mut dims = dims.clone();
+            dims.insert(0, -1);
+            SymbolType::new(SymbolWidth::Arr { tar: tar.clone(), dims }, false)
+        },
+        _ => SymbolType::new(SymbolWidth::Arr { tar: Box::new(ty.clone()), dims: vec![-1] }, false),
+    }
 }
 
-impl BinaryOp {
-    fn fetch_info(&self) -> (Vec<&str>, Vec<&SymbolType>) {
-        (
-            vec![self.res.as_str(), self.op1.as_str(), self.op2.as_str()],
-            vec![&self.op_type],
-        )
+/// 数组去掉最高一维后的类型
+pub fn element_of(ty: &SymbolType) -> SymbolType {
+    match &ty.width {
+        SymbolWidth::Arr { tar, dims } if dims.len() > 1 => {
+            SymbolType::new(SymbolWidth::Arr { tar: tar.clone(), dims: dims[1..].to_vec() }, false)
+        },
+        SymbolWidth::Arr { tar, .. } => tar.as_ref().clone(),
+        _ => ty.clone(),
     }
 }
 
-impl CastOp {
-    fn fetch_info(&self) -> (Vec<&str>, Vec<&SymbolType>) {
-        (
-            vec![self.res.as_str(), self.val.as_str()],
-            vec![&self.type_1, &self.type_2],
-        )
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use super::*;
+
+    #[test]
+    fn float_operands_compare_by_bits() {
+        assert_ne!(Operand::ConstFloat(0.0), Operand::ConstFloat(-0.0));
+        assert_eq!(Operand::ConstFloat(f32::NAN), Operand::ConstFloat(f32::NAN));
+        assert_eq!(Operand::ConstFloat(1.5), Operand::ConstFloat(1.5));
+        assert_ne!(Operand::ConstFloat(1.0), Operand::ConstInt(1));
+        let set: HashSet<Operand> = [0.0, -0.0, f32::NAN, f32::NAN].into_iter().map(Operand::ConstFloat).collect();
+        assert_eq!(set.len(), 3);
+        assert!(set.contains(&Operand::ConstFloat(f32::NAN)));
     }
 }
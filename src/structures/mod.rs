@@ -3,5 +3,5 @@ pub mod riscv_struct;
 pub mod writetext_trait;
 pub mod riscv_regs;
 pub mod symbol;
-pub mod rewrite_fundef;
+pub mod def_use;
 pub mod scopes;
\ No newline at end of file
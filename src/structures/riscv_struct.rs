@@ -111,6 +111,7 @@ pub struct AsmBlock {
     pub weight: usize,
     // 循环嵌套权重
     pub depth: usize,    // 循环嵌套深度
+    pub tail_call: bool,    // 以调用结束且直接返回调用结果，拆除栈帧后改为尾调用
 }
 
 impl AsmBlock {
@@ -122,6 +123,7 @@ impl AsmBlock {
             pre_instr_cnt,
             weight: 10_usize.pow(depth as u32),
             depth,
+            tail_call: false,
         }
     }
 }
@@ -188,6 +190,7 @@ pub enum AsmInstructionType {
     Jump,
     Ret,
     Call,
+    Tail,
 }
 
 /// 表示具体的汇编指令。
@@ -237,6 +240,8 @@ pub enum AsmInstruction {
     //ret指令，返回值可无
     Ret(),
     Call(String, String, Vec<String>, Vec<SymbolWidth>),
+    //尾调用，拆除栈帧后直接跳转到被调用函数，由其返回到调用者的调用者
+    Tail(String),
 }
 
 /// 二元指令结构体，用于存储两个操作数的指令。
@@ -379,6 +384,7 @@ impl AsmInstruction {
             AsmInstruction::Jump(_) => AsmInstructionType::Jump,
             AsmInstruction::Ret() => AsmInstructionType::Ret,
             AsmInstruction::Call(_, _, _, _) => AsmInstructionType::Call,
+            AsmInstruction::Tail(_) => AsmInstructionType::Tail,
         }
     }
 }
@@ -143,7 +143,7 @@ pub struct Labels {
     pub basis_block: HashMap<String, i32>,  // 基本块标号计数，只允许特定标号
 }
 
-const BLOCK_LABELS: [&str; 14] = [
+const BLOCK_LABELS: [&str; 16] = [
     "_L",
     "ret_then",
     "or_false",
@@ -158,6 +158,8 @@ const BLOCK_LABELS: [&str; 14] = [
     "while_entry",
     "while_body",
     "while_end",
+    "inline",
+    "unroll",
 ];
 
 impl Labels {
@@ -151,4 +151,33 @@ pub fn num_is_zero(ty: &SymbolType, val: &str) -> bool {
         },
         _ => panic!("{:?} TODO", ty),
     }
-}
\ No newline at end of file
+}
+
+/// sylib提供的库函数，依次为函数名、返回值类型和参数类型
+pub fn sylib_functions() -> Vec<(&'static str, SymbolType, Vec<SymbolType>)> {
+    let ty_void = SymbolType::new(SymbolWidth::Void, false);
+    let ty_i32 = SymbolType::new(SymbolWidth::I32, false);
+    let ty_float = SymbolType::new(SymbolWidth::Float, false);
+    let ty_i32_ptr = SymbolType::new(
+        SymbolWidth::Arr{tar: Box::new(ty_i32.clone()), dims: vec!(-1)},
+        false
+    );
+    let ty_float_ptr = SymbolType::new(
+        SymbolWidth::Arr{tar: Box::new(ty_float.clone()), dims: vec!(-1)},
+        false
+    );
+    vec!(
+        ("getint", ty_i32.clone(), vec!()),
+        ("getch", ty_i32.clone(), vec!()),
+        ("getarray", ty_i32.clone(), vec!(ty_i32_ptr.clone())),
+        ("getfloat", ty_float.clone(), vec!()),
+        ("getfarray", ty_i32.clone(), vec!(ty_float_ptr.clone())),
+        ("putint", ty_void.clone(), vec!(ty_i32.clone())),
+        ("putch", ty_void.clone(), vec!(ty_i32.clone())),
+        ("putarray", ty_void.clone(), vec!(ty_i32.clone(), ty_i32_ptr)),
+        ("putfloat", ty_void.clone(), vec!(ty_float)),
+        ("putfarray", ty_void.clone(), vec!(ty_i32, ty_float_ptr)),
+        ("starttime", ty_void.clone(), vec!()),
+        ("stoptime", ty_void, vec!()),
+    )
+}
@@ -21,11 +21,10 @@ pub fn is_float_immediate(s: &str) -> bool {
     is_hex(s) && s.len() == 18
 }
 
-//检查立即数是否为2的幂
+//检查立即数是否为2的幂，0、负数与超出i32范围的数都不是
 pub fn is_poweroftwo(s: &str) -> Option<i32> {
-    let num = s.parse::<i32>().unwrap();
-    match num & (num - 1) {
-        0 => Some(f32::log2(num as f32) as i32),
+    match s.parse::<i32>() {
+        Ok(num) if num > 0 && num & (num - 1) == 0 => Some(num.trailing_zeros() as i32),
         _ => None
     }
 }
@@ -40,6 +39,11 @@ pub fn is_temp_opr(s: &str) -> bool {
     s.contains("%temp.") && is_decimal(&s[6..])
 }
 
+// 检查标号是否为虚拟寄存器，包括LLVM IR的临时标号和汇编的临时标号
+pub fn is_virtual_reg(s: &str) -> bool {
+    is_num_label(s) || is_temp_opr(s)
+}
+
 // 检查立即数是否在十二位整型数的表示范围内-2048到+2047
 pub fn inside_imm_range(s: &str) -> bool {
     // 先检查是否为isize
@@ -51,4 +55,20 @@ pub fn inside_imm_range(s: &str) -> bool {
 /// 检查ty1和ty2是否都是整型数
 pub fn both_is_int(ty1: &SymbolType, ty2: &SymbolType) -> bool {
     return ty1.width != SymbolWidth::Float && ty2.width != SymbolWidth::Float;
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn power_of_two() {
+        assert_eq!(is_poweroftwo("1"), Some(0));
+        assert_eq!(is_poweroftwo("8"), Some(3));
+        assert_eq!(is_poweroftwo("1073741824"), Some(30));
+        assert_eq!(is_poweroftwo("6"), None);
+        assert_eq!(is_poweroftwo("0"), None);
+        assert_eq!(is_poweroftwo("-8"), None);
+        assert_eq!(is_poweroftwo("-2147483648"), None);
+        assert_eq!(is_poweroftwo("2147483648"), None);
+    }
+}
@@ -0,0 +1,321 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use compiler::{OptLevel, Session};
+
+/// 一次运行的结果
+struct Output {
+    exit_code: i32,
+    stdout: Vec<u8>,
+    /// 后端能统计时为执行的指令数与估算的周期数
+    counts: Option<(u64, u64)>,
+}
+
+/// 执行编译结果的方式
+trait Backend: Send + Sync {
+    fn name(&self) -> &str;
+    /// 用session编译source，以input为标准输入运行
+    fn run(&self, session: &Session, name: &str, source: &str, input: &[u8]) -> Result<Output, String>;
+}
+
+/// 解释执行优化后的llvm
+struct Interpreter;
+
+impl Backend for Interpreter {
+    fn name(&self) -> &str {
+        "interpreter"
+    }
+
+    fn run(&self, session: &Session, _name: &str, source: &str, input: &[u8]) -> Result<Output, String> {
+        let llvm = session.compile_to_llvm(source)
+            .map_err(|diagnostics| format!("编译失败: {:?}", diagnostics))?;
+        let result = llvm.interpret(input).map_err(|err| format!("运行错误: {}", err))?;
+        Ok(Output { exit_code: result.exit_code, stdout: result.stdout, counts: None })
+    }
+}
+
+/// 在进程内的RISC-V模拟器上执行汇编
+struct Simulator;
+
+impl Backend for Simulator {
+    fn name(&self) -> &str {
+        "simulator"
+    }
+
+    fn run(&self, session: &Session, _name: &str, source: &str, input: &[u8]) -> Result<Output, String> {
+        let asm = session.compile_to_asm(source)
+            .map_err(|diagnostics| format!("编译失败: {:?}", diagnostics))?;
+        let result = asm.simulate(input).map_err(|err| format!("运行错误: {}", err))?;
+        Ok(Output {
+            exit_code: result.exit_code,
+            stdout: result.stdout,
+            counts: Some((result.instructions, result.cycles)),
+        })
+    }
+}
+
+/// 用交叉编译工具链汇编、链接sylib，再交给qemu-riscv64执行
+struct Qemu {
+    cc: String,
+    qemu: String,
+    work_dir: PathBuf,
+    sylib: PathBuf,
+}
+
+impl Qemu {
+    /// 工具链与qemu都可用时编译好sylib，否则返回None；可用RISCV_CC与QEMU环境变量指定程序
+    fn detect(opt_level: OptLevel) -> Option<Self> {
+        let cc = std::env::var("RISCV_CC").unwrap_or_else(|_| "riscv64-linux-gnu-gcc".to_string());
+        let qemu = std::env::var("QEMU").unwrap_or_else(|_| "qemu-riscv64".to_string());
+        let available = |program: &str| Command::new(program)
+            .arg("--version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .map_or(false, |status| status.success());
+        if !available(&cc) || !available(&qemu) {
+            return None;
+        }
+        let work_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("functional_{:?}", opt_level));
+        fs::create_dir_all(&work_dir).unwrap();
+        let sylib = work_dir.join("sylib.o");
+        let status = Command::new(&cc)
+            .arg("-c")
+            .arg(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/libsysy/sylib.c"))
+            .arg("-o")
+            .arg(&sylib)
+            .status()
+            .ok()?;
+        status.success().then_some(Qemu { cc, qemu, work_dir, sylib })
+    }
+}
+
+impl Backend for Qemu {
+    fn name(&self) -> &str {
+        "qemu"
+    }
+
+    fn run(&self, session: &Session, name: &str, source: &str, input: &[u8]) -> Result<Output, String> {
+        let asm = session.compile_to_asm(source)
+            .map_err(|diagnostics| format!("编译失败: {:?}", diagnostics))?;
+        let asm_path = self.work_dir.join(format!("{}.s", name));
+        let elf_path = self.work_dir.join(name);
+        let mut text = vec![];
+        session.emit(&asm, &mut text);
+        fs::write(&asm_path, text).unwrap();
+        // 静态链接，qemu不需要交叉编译的动态库
+        let link = Command::new(&self.cc)
+            .arg("-static")
+            .arg(&asm_path)
+            .arg(&self.sylib)
+            .arg("-o")
+            .arg(&elf_path)
+            .output()
+            .map_err(|err| format!("无法运行{}: {}", self.cc, err))?;
+        if !link.status.success() {
+            return Err(format!("汇编或链接失败:\n{}", String::from_utf8_lossy(&link.stderr)));
+        }
+        let mut child = Command::new(&self.qemu)
+            .arg(&elf_path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|err| format!("无法运行{}: {}", self.qemu, err))?;
+        // 输入较大时子进程可能先写满输出，因此另起线程写入标准输入
+        let mut stdin = child.stdin.take().unwrap();
+        let input = input.to_vec();
+        let writer = thread::spawn(move || stdin.write_all(&input));
+        let output = child.wait_with_output().map_err(|err| format!("运行错误: {}", err))?;
+        // 程序不读完输入就退出时写入会失败，不影响结果
+        let _ = writer.join();
+        let exit_code = output.status.code().ok_or_else(|| "程序被信号终止".to_string())?;
+        Ok(Output { exit_code, stdout: output.stdout, counts: None })
+    }
+}
+
+/// 功能测例
+const FUNCTIONAL: &str = "functional";
+/// 优化器边界情况的回归测例
+const REGRESSION: &str = "regression";
+
+/// tests/<suite>下全部的.sy，按文件名排序
+fn test_cases(suite: &str) -> Vec<PathBuf> {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join(suite);
+    let mut cases: Vec<PathBuf> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().map_or(false, |ext| ext == "sy"))
+        .collect();
+    cases.sort();
+    cases
+}
+
+/// 最多列出的不同行数
+const MAX_DIFF_LINES: usize = 10;
+
+/// 逐行比较，列出不同的行，-为期望的输出，+为实际的输出
+fn diff(expected: &str, actual: &str) -> String {
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+    let mut res = vec![];
+    for i in 0..expected.len().max(actual.len()) {
+        let (exp, act) = (expected.get(i), actual.get(i));
+        if exp == act {
+            continue;
+        }
+        if res.len() == MAX_DIFF_LINES {
+            res.push("  ...".to_string());
+            break;
+        }
+        let mut line = format!("  第{}行:", i + 1);
+        if let Some(exp) = exp {
+            line += &format!("\n    -{}", exp);
+        }
+        if let Some(act) = act {
+            line += &format!("\n    +{}", act);
+        }
+        res.push(line);
+    }
+    res.join("\n")
+}
+
+/// 编译并运行一个用例，与.out比较：最后一行为main的返回值，其余为标准输出
+fn run_case(backend: &dyn Backend, session: &Session, case: &Path) -> Result<Output, String> {
+    let name = case.file_stem().unwrap().to_string_lossy();
+    let source = fs::read_to_string(case).unwrap();
+    let input = fs::read(case.with_extension("in")).unwrap_or_default();
+    let expected = fs::read_to_string(case.with_extension("out")).unwrap();
+
+    let output = backend.run(session, &name, &source, &input)?;
+
+    let expected = expected.trim_end_matches('\n');
+    let (expected_stdout, expected_code) = expected.rsplit_once('\n').unwrap_or(("", expected));
+    // 进程的退出码只保留低8位
+    let exit_code = (output.exit_code & 0xff).to_string();
+    if exit_code != expected_code.trim() {
+        return Err(format!("返回值为{}，应为{}", exit_code, expected_code));
+    }
+    // 68_brainfk会输出\r\n，而.out中只有\n
+    let stdout = String::from_utf8_lossy(&output.stdout).replace("\r\n", "\n");
+    let stdout = stdout.trim_end_matches('\n');
+    let expected_stdout = expected_stdout.trim_end_matches('\n');
+    if stdout != expected_stdout {
+        return Err(format!("输出不符:\n{}", diff(expected_stdout, stdout)));
+    }
+    Ok(output)
+}
+
+/// 86_long_code2中有上千项相加的表达式，编译时递归很深，测试线程默认的2MB栈不够
+const STACK_SIZE: usize = 64 << 20;
+
+/// 运行tests/<suite>下的全部用例并打印汇总，有用例失败时测试失败
+fn run_suite(backend: impl Backend + 'static, suite: &'static str, opt_level: OptLevel) {
+    let (summary, failures) = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(move || {
+            let session = Session::new(opt_level);
+            let cases = test_cases(suite);
+            let mut failures = vec![];
+            let (mut instructions, mut cycles, mut counted) = (0, 0, false);
+            for case in cases.iter() {
+                let name = case.file_stem().unwrap().to_string_lossy().to_string();
+                match run_case(&backend, &session, case) {
+                    Ok(output) => {
+                        if let Some((case_instructions, case_cycles)) = output.counts {
+                            instructions += case_instructions;
+                            cycles += case_cycles;
+                            counted = true;
+                        }
+                    },
+                    Err(msg) => failures.push(format!("{}: {}", name, msg)),
+                }
+            }
+            let mut summary = format!(
+                "{} {} {:?}: 通过{}个，失败{}个",
+                backend.name(), suite, opt_level, cases.len() - failures.len(), failures.len()
+            );
+            // 用cargo test -- --nocapture查看，可用于比较优化前后的性能
+            if counted {
+                summary += &format!("，共执行{}条指令，约{}个周期", instructions, cycles);
+            }
+            (summary, failures)
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+    for failure in failures.iter() {
+        println!("{}", failure);
+    }
+    println!("{}", summary);
+    assert!(failures.is_empty(), "{}", summary);
+}
+
+/// 没有交叉编译工具链或qemu时跳过
+fn run_qemu_suite(opt_level: OptLevel) {
+    match Qemu::detect(opt_level) {
+        Some(qemu) => run_suite(qemu, FUNCTIONAL, opt_level),
+        None => println!("未找到riscv64-linux-gnu-gcc或qemu-riscv64，跳过"),
+    }
+}
+
+#[test]
+fn interpreter_o0() {
+    run_suite(Interpreter, FUNCTIONAL, OptLevel::O0);
+}
+
+#[test]
+fn interpreter_o1() {
+    run_suite(Interpreter, FUNCTIONAL, OptLevel::O1);
+}
+
+#[test]
+fn interpreter_o2() {
+    run_suite(Interpreter, FUNCTIONAL, OptLevel::O2);
+}
+
+#[test]
+fn simulator_o0() {
+    run_suite(Simulator, FUNCTIONAL, OptLevel::O0);
+}
+
+#[test]
+fn simulator_o1() {
+    run_suite(Simulator, FUNCTIONAL, OptLevel::O1);
+}
+
+#[test]
+fn simulator_o2() {
+    run_suite(Simulator, FUNCTIONAL, OptLevel::O2);
+}
+
+#[test]
+fn qemu_o0() {
+    run_qemu_suite(OptLevel::O0);
+}
+
+#[test]
+fn qemu_o1() {
+    run_qemu_suite(OptLevel::O1);
+}
+
+#[test]
+fn qemu_o2() {
+    run_qemu_suite(OptLevel::O2);
+}
+
+#[test]
+fn regression_interpreter() {
+    for opt_level in [OptLevel::O0, OptLevel::O1, OptLevel::O2] {
+        run_suite(Interpreter, REGRESSION, opt_level);
+    }
+}
+
+#[test]
+fn regression_simulator() {
+    for opt_level in [OptLevel::O0, OptLevel::O1, OptLevel::O2] {
+        run_suite(Simulator, REGRESSION, opt_level);
+    }
+}